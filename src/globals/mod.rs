@@ -3,7 +3,8 @@ use bimap::BiMap;
 use scoped_tls::scoped_thread_local;
 use std::cell::RefCell;
 use std::fmt::{Debug, Formatter};
-use crate::solidlang::defs::{FunctionDef, StructDef};
+use crate::solidlang::defs::{EnumDef, FunctionDef, InterfaceDef, StructDef};
+use crate::solidlang::span::SourceMap;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct Symbol {
@@ -58,7 +59,10 @@ impl StringInterner {
 pub struct SessionGlobals {
     pub string_interner: RefCell<StringInterner>,
     pub struct_def_pool: RefCell<Pool<StructDef>>,
-    pub function_def_pool: RefCell<Pool<FunctionDef>>
+    pub enum_def_pool: RefCell<Pool<EnumDef>>,
+    pub function_def_pool: RefCell<Pool<FunctionDef>>,
+    pub interface_def_pool: RefCell<Pool<InterfaceDef>>,
+    pub source_map: RefCell<SourceMap>
 }
 
 scoped_thread_local!(static SESSION_GLOBALS: SessionGlobals);
@@ -68,7 +72,10 @@ impl SessionGlobals {
         Self {
             string_interner: RefCell::new(StringInterner::new()),
             struct_def_pool: RefCell::new(Pool::new()),
-            function_def_pool: RefCell::new(Pool::new())
+            enum_def_pool: RefCell::new(Pool::new()),
+            function_def_pool: RefCell::new(Pool::new()),
+            interface_def_pool: RefCell::new(Pool::new()),
+            source_map: RefCell::new(SourceMap::default())
         }
     }
 
@@ -96,6 +103,14 @@ impl SessionGlobals {
         SESSION_GLOBALS.with(|sg| function(&mut sg.struct_def_pool.borrow_mut()))
     }
 
+    pub fn with_enum_def_pool<T>(function: impl FnOnce(&Pool<EnumDef>) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&sg.enum_def_pool.borrow()))
+    }
+
+    pub fn with_enum_def_pool_mut<T>(function: impl FnOnce(&mut Pool<EnumDef>) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&mut sg.enum_def_pool.borrow_mut()))
+    }
+
     pub fn with_function_def_pool<T>(function: impl FnOnce(&Pool<FunctionDef>) -> T) -> T {
         SESSION_GLOBALS.with(|sg| function(&sg.function_def_pool.borrow()))
     }
@@ -103,4 +118,20 @@ impl SessionGlobals {
     pub fn with_function_def_pool_mut<T>(function: impl FnOnce(&mut Pool<FunctionDef>) -> T) -> T {
         SESSION_GLOBALS.with(|sg| function(&mut sg.function_def_pool.borrow_mut()))
     }
+
+    pub fn with_interface_def_pool<T>(function: impl FnOnce(&Pool<InterfaceDef>) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&sg.interface_def_pool.borrow()))
+    }
+
+    pub fn with_interface_def_pool_mut<T>(function: impl FnOnce(&mut Pool<InterfaceDef>) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&mut sg.interface_def_pool.borrow_mut()))
+    }
+
+    pub fn with_source_map<T>(function: impl FnOnce(&SourceMap) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&sg.source_map.borrow()))
+    }
+
+    pub fn with_source_map_mut<T>(function: impl FnOnce(&mut SourceMap) -> T) -> T {
+        SESSION_GLOBALS.with(|sg| function(&mut sg.source_map.borrow_mut()))
+    }
 }
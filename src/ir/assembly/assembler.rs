@@ -1,15 +1,31 @@
 use crate::globals::{SessionGlobals, Symbol};
 use crate::ir::comp::{
-    IRComp, IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompConstant, IRCompFunctionCall,
-    IRCompKind, IRCompUnaryOperation, IRCompUnaryOperationKind,
+    IRComp, IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompConstant, IRCompConversion,
+    IRCompConversionKind, IRCompFunctionCall, IRCompKind, IRCompUnaryOperation,
+    IRCompUnaryOperationKind, IRDebugLocation,
+};
+use crate::ir::{
+    IRInlineHint, IRItem, IRItemExternDecl, IRItemFunctionDef, IRItemKind, IRModule, IRType,
+    IRValue, IRVisibility,
 };
-use crate::ir::{IRItem, IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue};
 use std::collections::HashMap;
 use std::iter::Peekable;
 use std::str::Chars;
 
 const EOF_CHAR: char = '\0';
 
+/// Types of typed constants, with their size and signedness
+const CONSTANT_TYPES: [(&str, usize, bool); 8] = [
+    ("u8", 1, false),
+    ("i8", 1, true),
+    ("u16", 2, false),
+    ("i16", 2, true),
+    ("u32", 4, false),
+    ("i32", 4, true),
+    ("u64", 8, false),
+    ("i64", 8, true),
+];
+
 struct IRAsssmblyLexerCursor<'a> {
     initial_len: usize,
     lines_consumed: usize,
@@ -62,6 +78,10 @@ impl<'a> IRAsssmblyLexerCursor<'a> {
         }
 
         while self.nth(0) == ';' {
+            // Comments starting with @ are source locations
+            if self.nth(1) == '@' || (self.nth(1) == ' ' && self.nth(2) == '@') {
+                break;
+            }
             loop {
                 let c = self.bump();
                 if c == '\n' || c == EOF_CHAR {
@@ -99,6 +119,15 @@ impl<'a> IRAsssmblyLexerCursor<'a> {
             }
             '(' => IRAssemblyTokenKind::LParen,
             ')' => IRAssemblyTokenKind::RParen,
+            '#' => IRAssemblyTokenKind::Hash,
+            '[' => IRAssemblyTokenKind::LSBracket,
+            ']' => IRAssemblyTokenKind::RSBracket,
+            ';' => {
+                while self.nth(0) != '\n' && self.nth(0) != EOF_CHAR {
+                    self.bump();
+                }
+                IRAssemblyTokenKind::Location
+            }
 
             c if c.is_ascii_digit() => {
                 while self.nth(0).is_ascii_digit() {
@@ -132,6 +161,11 @@ enum IRAssemblyTokenKind {
     Assign,
     LParen,
     RParen,
+    Hash,
+    LSBracket,
+    RSBracket,
+    /// `; @file:line:column function`
+    Location,
 
     UnexpectedCharacter,
 
@@ -280,13 +314,75 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             })
     }
 
+    /// Parses a possibly negative integer into the little endian bytes of the type
+    fn parse_typed_integer(
+        &mut self,
+        name: &str,
+        size: usize,
+        signed: bool,
+    ) -> IRAssemblerResult<Vec<u8>> {
+        let token = self.token_stream.next().unwrap();
+        self.expected.clear();
+        // Negative integers are lexed as words
+        let value = match token.kind {
+            IRAssemblyTokenKind::Integer | IRAssemblyTokenKind::Word => {
+                self.get_token_string(&token).parse::<i128>().ok()
+            }
+            _ => None,
+        };
+
+        let bits = size as u32 * 8;
+        let (min, max) = if signed {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        };
+        match value {
+            Some(value) if (min..=max).contains(&value) => Ok(value.to_le_bytes()[..size].to_vec()),
+            _ => Err(IRAssemblerError {
+                message: format!("Expected integer to fit into {}", name),
+                start: token.start,
+                line: token.line,
+            }),
+        }
+    }
+
     fn parse_ir_type(&mut self) -> IRAssemblerResult<IRType> {
         self.expect_kind(IRAssemblyTokenKind::LParen)?;
         let size = self.parse_integer_u64()?;
         let align = self.parse_integer_u64()?;
+        let float = self.check_keyword("float");
+        if float {
+            self.advance_token();
+        }
         self.expect_kind(IRAssemblyTokenKind::RParen)?;
 
-        return Ok(IRType { size, align });
+        return Ok(IRType { size, align, float });
+    }
+
+    fn parse_location(&mut self) -> IRAssemblerResult<IRDebugLocation> {
+        let token = self.expect_kind(IRAssemblyTokenKind::Location)?;
+        let annotation = self.get_token_string(&token);
+        let annotation = annotation.trim_start_matches([';', ' ', '@']).trim_end();
+
+        // Paths can hold spaces and colons, the function name and the numbers cannot
+        let location = annotation
+            .rsplit_once(' ')
+            .and_then(|(position, function)| {
+                let (position, column) = position.rsplit_once(':')?;
+                let (file, line) = position.rsplit_once(':')?;
+                Some(IRDebugLocation {
+                    file: SessionGlobals::with_interner_mut(|i| i.intern(file)),
+                    line: line.parse().ok()?,
+                    column: column.parse().ok()?,
+                    function: SessionGlobals::with_interner_mut(|i| i.intern(function)),
+                })
+            });
+        location.ok_or_else(|| IRAssemblerError {
+            message: "Expected a location like \"; @file:line:column function\"".to_owned(),
+            start: token.start,
+            line: token.line,
+        })
     }
 
     fn get_symbol(&self, token: &IRAssemblyToken) -> Symbol {
@@ -335,6 +431,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::FunctionCall(IRCompFunctionCall { name, args }),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("binop") {
@@ -373,6 +470,40 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                 operation_kind = Some(IRCompBinaryOperationKind::LesserEqual)
             } else if self.check_keyword(">=") {
                 operation_kind = Some(IRCompBinaryOperationKind::GreaterEqual)
+            } else if self.check_keyword("sdiv") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedDiv)
+            } else if self.check_keyword("smod") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedMod)
+            } else if self.check_keyword("sshr") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedRShift)
+            } else if self.check_keyword("scmp>") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedGreater)
+            } else if self.check_keyword("scmp<") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedLesser)
+            } else if self.check_keyword("scmp>=") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedGreaterEqual)
+            } else if self.check_keyword("scmp<=") {
+                operation_kind = Some(IRCompBinaryOperationKind::SignedLesserEqual)
+            } else if self.check_keyword("fadd") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatPlus)
+            } else if self.check_keyword("fsub") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatMinus)
+            } else if self.check_keyword("fmul") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatMul)
+            } else if self.check_keyword("fdiv") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatDiv)
+            } else if self.check_keyword("fcmp==") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatEqual)
+            } else if self.check_keyword("fcmp!=") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatNotEqual)
+            } else if self.check_keyword("fcmp<") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatLesser)
+            } else if self.check_keyword("fcmp>") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatGreater)
+            } else if self.check_keyword("fcmp<=") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatLesserEqual)
+            } else if self.check_keyword("fcmp>=") {
+                operation_kind = Some(IRCompBinaryOperationKind::FloatGreaterEqual)
             }
             self.advance_token();
 
@@ -387,6 +518,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                         right_operand,
                     }),
                     id,
+                    location: None,
                 });
             } else {
                 return Err(self.error_unexpected());
@@ -403,7 +535,10 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                 operation_kind = Some(IRCompUnaryOperationKind::BitNot)
             } else if self.check_keyword("signedneg") {
                 operation_kind = Some(IRCompUnaryOperationKind::SignedNegation)
+            } else if self.check_keyword("fneg") {
+                operation_kind = Some(IRCompUnaryOperationKind::FloatNegation)
             }
+            self.advance_token();
 
             let operand = self.parse_ir_value()?;
 
@@ -414,24 +549,74 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                         operand,
                     }),
                     id,
+                    location: None,
                 });
             } else {
                 return Err(self.error_unexpected());
             }
         }
+        if self.check_keyword("convert") {
+            // Conversion between integer and float sizes
+            self.advance_token();
+
+            let kind = if self.check_keyword("utof") {
+                IRCompConversionKind::UnsignedToFloat
+            } else if self.check_keyword("stof") {
+                IRCompConversionKind::SignedToFloat
+            } else if self.check_keyword("ftou") {
+                IRCompConversionKind::FloatToUnsigned
+            } else if self.check_keyword("ftos") {
+                IRCompConversionKind::FloatToSigned
+            } else if self.check_keyword("ftof") {
+                IRCompConversionKind::FloatToFloat
+            } else if self.check_keyword("trunc") {
+                IRCompConversionKind::Truncate
+            } else if self.check_keyword("zext") {
+                IRCompConversionKind::ZeroExtend
+            } else if self.check_keyword("sext") {
+                IRCompConversionKind::SignExtend
+            } else {
+                return Err(self.error_unexpected());
+            };
+            self.advance_token();
+
+            let size = self.parse_integer_u64()?;
+            let operand = self.parse_ir_value()?;
+
+            return Ok(IRComp {
+                kind: IRCompKind::Conversion(IRCompConversion {
+                    kind,
+                    operand,
+                    size,
+                }),
+                id,
+                location: None,
+            });
+        }
         if self.check_keyword("const") {
             // Constant
             self.advance_token();
 
-            let len = self.parse_integer_u64()?;
-            let mut bytes = vec![];
-            for _ in 0..len {
-                bytes.push(self.parse_integer_u8()?);
-            }
+            // Either a typed integer like `const i32 -5` or the raw bytes like `const 2 1 0`
+            let typed = CONSTANT_TYPES
+                .iter()
+                .find(|(name, _, _)| self.check_keyword(name));
+            let bytes = if let Some(&(name, size, signed)) = typed {
+                self.advance_token();
+                self.parse_typed_integer(name, size, signed)?
+            } else {
+                let len = self.parse_integer_u64()?;
+                let mut bytes = vec![];
+                for _ in 0..len {
+                    bytes.push(self.parse_integer_u8()?);
+                }
+                bytes
+            };
 
             return Ok(IRComp {
                 kind: IRCompKind::Constant(IRCompConstant { bytes }),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("alloc") {
@@ -442,6 +627,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::Alloc(ir_type),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("store") {
@@ -455,6 +641,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::Store(ir_type, location, value),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("load") {
@@ -467,6 +654,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::Load(ir_type, location),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("offsetstore") {
@@ -481,6 +669,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::OffsetStore(ir_type, location, value, offset),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("offsetload") {
@@ -494,6 +683,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::OffsetLoad(ir_type, location, offset),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("return") {
@@ -505,6 +695,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::Return(value),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("if") {
@@ -517,6 +708,7 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::If(value, self.get_symbol(&location)),
                 id,
+                location: None,
             });
         }
         if self.check_keyword("jmp") {
@@ -528,16 +720,84 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
             return Ok(IRComp {
                 kind: IRCompKind::Jmp(self.get_symbol(&location)),
                 id,
+                location: None,
             });
         }
 
         Err(self.error_unexpected())
     }
 
+    /// Parses the attributes in front of an item, returns whether there was any
+    fn parse_attributes(
+        &mut self,
+        inline_hint: &mut IRInlineHint,
+        visibility: &mut IRVisibility,
+    ) -> IRAssemblerResult<bool> {
+        let mut any = false;
+
+        while self.check_kind(IRAssemblyTokenKind::Hash) {
+            self.advance_token();
+            self.expect_kind(IRAssemblyTokenKind::LSBracket)?;
+            if self.check_keyword("inline") {
+                *inline_hint = IRInlineHint::Always;
+            } else if self.check_keyword("noinline") {
+                *inline_hint = IRInlineHint::Never;
+            } else if self.check_keyword("internal") {
+                *visibility = IRVisibility::Internal;
+            } else {
+                return Err(self.error_unexpected());
+            }
+            self.advance_token();
+            self.expect_kind(IRAssemblyTokenKind::RSBracket)?;
+            any = true;
+        }
+
+        Ok(any)
+    }
+
     fn parse_ir_module(&mut self) -> IRAssemblerResult<IRModule> {
         let mut items = vec![];
 
         loop {
+            let mut inline_hint = IRInlineHint::Auto;
+            let mut visibility = IRVisibility::Exported;
+            let has_attributes = self.parse_attributes(&mut inline_hint, &mut visibility)?;
+
+            if self.check_keyword("extern") {
+                // Extern declaration
+                let token = self.token_stream.next().unwrap();
+                if has_attributes {
+                    return Err(IRAssemblerError {
+                        message: "Extern declarations cannot have attributes".to_owned(),
+                        start: token.start,
+                        line: token.line,
+                    });
+                }
+
+                let name = self.expect_kind(IRAssemblyTokenKind::Word)?;
+                let name = self.get_symbol(&name);
+
+                self.expect_kind(IRAssemblyTokenKind::Colon)?;
+
+                let mut params = vec![];
+                while !self.check_kind(IRAssemblyTokenKind::Arrow) {
+                    params.push(self.parse_ir_type()?);
+                }
+                self.advance_token();
+
+                let return_type = self.parse_ir_type()?;
+
+                items.push(IRItem {
+                    kind: IRItemKind::ExternDecl(IRItemExternDecl {
+                        name,
+                        params,
+                        return_type,
+                    }),
+                });
+
+                continue;
+            }
+
             if self.check_keyword("fn") {
                 // Function definition
                 self.advance_token();
@@ -558,9 +818,10 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
 
                 let return_type = self.parse_ir_type()?;
 
-                // Computations and labels
+                // Computations and labels, a location follows the first comp it applies to
                 let mut comps = vec![];
                 let mut label_defs = HashMap::new();
+                let mut location = None;
                 while !self.check_keyword("endfn") {
                     if self.check_kind(IRAssemblyTokenKind::Colon) {
                         self.advance_token();
@@ -569,7 +830,12 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                         let current_index = comps.len() as u64;
                         label_defs.insert(label, current_index);
                     } else {
-                        comps.push(self.parse_ir_comp()?);
+                        let mut comp = self.parse_ir_comp()?;
+                        if self.check_kind(IRAssemblyTokenKind::Location) {
+                            location = Some(self.parse_location()?);
+                        }
+                        comp.location = location.clone();
+                        comps.push(comp);
                     }
                 }
                 self.advance_token();
@@ -577,6 +843,8 @@ impl<'a, T: Iterator<Item = IRAssemblyToken>> IRAssembler<'a, T> {
                 items.push(IRItem {
                     kind: IRItemKind::FunctionDef(IRItemFunctionDef {
                         name: SessionGlobals::with_interner_mut(|i| i.intern(name)),
+                        visibility,
+                        inline_hint,
                         params,
                         return_type,
                         comps,
@@ -1,6 +1,8 @@
 use crate::globals::{SessionGlobals, Symbol};
-use crate::ir::comp::{IRComp, IRCompBinaryOperationKind, IRCompKind, IRCompUnaryOperationKind};
-use crate::ir::{IRItem, IRItemKind, IRModule, IRType, IRValue};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperationKind, IRCompConversionKind, IRCompKind, IRCompUnaryOperationKind,
+};
+use crate::ir::{IRInlineHint, IRItem, IRItemKind, IRModule, IRType, IRValue, IRVisibility};
 
 pub mod assembler;
 
@@ -9,7 +11,10 @@ pub fn assembly_for_ir_item(ir_item: &IRItem) -> String {
 
     let mut result = "".to_owned();
 
-    let dump_ir_type = |ir_type: &IRType| format!("({} {})", ir_type.size, ir_type.align);
+    let dump_ir_type = |ir_type: &IRType| {
+        let float = if ir_type.float { " float" } else { "" };
+        format!("({} {}{})", ir_type.size, ir_type.align, float)
+    };
 
     let dump_symbol =
         |symbol: &Symbol| SessionGlobals::with_interner(|interner| interner.get(symbol).unwrap());
@@ -62,6 +67,23 @@ pub fn assembly_for_ir_item(ir_item: &IRItem) -> String {
                     IRCompBinaryOperationKind::Lesser => "<",
                     IRCompBinaryOperationKind::GreaterEqual => ">=",
                     IRCompBinaryOperationKind::LesserEqual => "<=",
+                    IRCompBinaryOperationKind::SignedDiv => "sdiv",
+                    IRCompBinaryOperationKind::SignedMod => "smod",
+                    IRCompBinaryOperationKind::SignedRShift => "sshr",
+                    IRCompBinaryOperationKind::SignedGreater => "scmp>",
+                    IRCompBinaryOperationKind::SignedLesser => "scmp<",
+                    IRCompBinaryOperationKind::SignedGreaterEqual => "scmp>=",
+                    IRCompBinaryOperationKind::SignedLesserEqual => "scmp<=",
+                    IRCompBinaryOperationKind::FloatPlus => "fadd",
+                    IRCompBinaryOperationKind::FloatMinus => "fsub",
+                    IRCompBinaryOperationKind::FloatMul => "fmul",
+                    IRCompBinaryOperationKind::FloatDiv => "fdiv",
+                    IRCompBinaryOperationKind::FloatEqual => "fcmp==",
+                    IRCompBinaryOperationKind::FloatNotEqual => "fcmp!=",
+                    IRCompBinaryOperationKind::FloatGreater => "fcmp>",
+                    IRCompBinaryOperationKind::FloatLesser => "fcmp<",
+                    IRCompBinaryOperationKind::FloatGreaterEqual => "fcmp>=",
+                    IRCompBinaryOperationKind::FloatLesserEqual => "fcmp<=",
                 };
                 *result += " ";
                 *result += &dump_ir_value(&operation.left_operand);
@@ -74,10 +96,28 @@ pub fn assembly_for_ir_item(ir_item: &IRItem) -> String {
                     IRCompUnaryOperationKind::BoolNot => "boolnot",
                     IRCompUnaryOperationKind::BitNot => "bitnot",
                     IRCompUnaryOperationKind::SignedNegation => "signedneg",
+                    IRCompUnaryOperationKind::FloatNegation => "fneg",
                 };
                 *result += " ";
                 *result += &dump_ir_value(&operation.operand);
             }
+            IRCompKind::Conversion(conversion) => {
+                *result += "convert ";
+                *result += match &conversion.kind {
+                    IRCompConversionKind::UnsignedToFloat => "utof",
+                    IRCompConversionKind::SignedToFloat => "stof",
+                    IRCompConversionKind::FloatToUnsigned => "ftou",
+                    IRCompConversionKind::FloatToSigned => "ftos",
+                    IRCompConversionKind::FloatToFloat => "ftof",
+                    IRCompConversionKind::Truncate => "trunc",
+                    IRCompConversionKind::ZeroExtend => "zext",
+                    IRCompConversionKind::SignExtend => "sext",
+                };
+                *result += " ";
+                *result += &conversion.size.to_string();
+                *result += " ";
+                *result += &dump_ir_value(&conversion.operand);
+            }
             IRCompKind::Constant(constant) => {
                 *result += "const ";
                 *result += &constant.bytes.len().to_string();
@@ -130,17 +170,25 @@ pub fn assembly_for_ir_item(ir_item: &IRItem) -> String {
                 *result += "if ";
                 *result += &dump_ir_value(cond);
                 *result += " ";
-                *result += &dump_symbol(location);
+                *result += dump_symbol(location);
             }
             IRCompKind::Jmp(location) => {
                 *result += "jmp ";
-                *result += &dump_symbol(location);
+                *result += dump_symbol(location);
             }
         }
     };
 
     match &ir_item.kind {
         IRItemKind::FunctionDef(function_def) => {
+            if function_def.visibility == IRVisibility::Internal {
+                result += "#[internal]\n";
+            }
+            match function_def.inline_hint {
+                IRInlineHint::Auto => {}
+                IRInlineHint::Always => result += "#[inline]\n",
+                IRInlineHint::Never => result += "#[noinline]\n",
+            }
             result += "fn ";
             result += dump_symbol(&function_def.name);
             result += ": ";
@@ -154,21 +202,54 @@ pub fn assembly_for_ir_item(ir_item: &IRItem) -> String {
             result += "-> ";
             result += &dump_ir_type(&function_def.return_type);
 
+            // Source locations are shown as `; @file:line:column function` annotations when they
+            // change, the assembler gives them to the comps up to the next one
+            let mut position = None;
             for i in 0..function_def.comps.len() {
                 // FIXME this could be optimized
                 for label_def in &function_def.label_defs {
                     if i == *label_def.1 as usize {
                         result += "\n    ";
                         result += ":";
-                        result += &dump_symbol(label_def.0);
+                        result += dump_symbol(label_def.0);
                     }
                 }
                 let comp = &function_def.comps[i];
                 result += "\n    ";
                 dump_ir_comp(comp, &mut result);
+                if let Some(location) = &comp.location {
+                    let comp_position = format!(
+                        "{} {}",
+                        location.position(),
+                        dump_symbol(&location.function)
+                    );
+                    if position.as_ref() != Some(&comp_position) {
+                        result += " ; @";
+                        result += &comp_position;
+                        position = Some(comp_position);
+                    }
+                }
+            }
+            for label_def in &function_def.label_defs {
+                if function_def.comps.len() == *label_def.1 as usize {
+                    result += "\n    ";
+                    result += ":";
+                    result += dump_symbol(label_def.0);
+                }
             }
             result += "\nendfn";
         }
+        IRItemKind::ExternDecl(extern_decl) => {
+            result += "extern ";
+            result += dump_symbol(&extern_decl.name);
+            result += ": ";
+            for param in &extern_decl.params {
+                result += &dump_ir_type(param);
+                result += " ";
+            }
+            result += "-> ";
+            result += &dump_ir_type(&extern_decl.return_type);
+        }
     }
 
     result
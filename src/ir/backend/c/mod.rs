@@ -0,0 +1,794 @@
+use crate::globals::Symbol;
+use crate::ir::backend::{
+    infer_value_types, sanitize_symbol, symbol_str, IRBackendError, IRBackendResult, IRSignatures,
+};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperationKind, IRCompConversionKind, IRCompKind, IRCompUnaryOperationKind,
+};
+use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue, IRVisibility};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Name given to the IR `main` function, so a C `main` can wrap it
+const MAIN_WRAPPER_TARGET: &str = "solidc_main";
+
+fn scalar_c_type(size: u64) -> Option<&'static str> {
+    match size {
+        1 => Some("uint8_t"),
+        2 => Some("uint16_t"),
+        4 => Some("uint32_t"),
+        8 => Some("uint64_t"),
+        _ => None,
+    }
+}
+
+fn signed_c_type(size: u64) -> Option<&'static str> {
+    match size {
+        1 => Some("int8_t"),
+        2 => Some("int16_t"),
+        4 => Some("int32_t"),
+        8 => Some("int64_t"),
+        _ => None,
+    }
+}
+
+/// Suffix of the prelude functions reinterpreting the bits of a float of the given size
+fn float_suffix(size: u64) -> Option<&'static str> {
+    match size {
+        4 => Some("f32"),
+        8 => Some("f64"),
+        _ => None,
+    }
+}
+
+/// Suffix of the prelude functions for values of the given type, if they are floats
+fn float_suffix_of(ir_type: &IRType) -> Option<&'static str> {
+    if ir_type.float {
+        float_suffix(ir_type.size)
+    } else {
+        None
+    }
+}
+
+/// Functions reinterpreting the bits held by unsigned integers as floats and back
+const FLOAT_PRELUDE: &str = "\
+static inline float solidc_f32(uint32_t bits) { float f; memcpy(&f, &bits, 4); return f; }
+static inline uint32_t solidc_bits_f32(float f) { uint32_t bits; memcpy(&bits, &f, 4); return bits; }
+static inline double solidc_f64(uint64_t bits) { double f; memcpy(&f, &bits, 8); return f; }
+static inline uint64_t solidc_bits_f64(double f) { uint64_t bits; memcpy(&bits, &f, 8); return bits; }
+";
+
+/// C type used for values of the given size
+///
+/// Sizes without a matching integer type are byte arrays wrapped in a struct, so they can be
+/// assigned, passed and returned by value.
+fn c_type(size: u64) -> String {
+    match scalar_c_type(size) {
+        Some(c_type) => c_type.to_owned(),
+        None if size == 0 => "void".to_owned(),
+        None => format!("solidc_bytes{}", size),
+    }
+}
+
+/// C type of the params and results of external functions, which take floats as floats
+fn extern_c_type(ir_type: &IRType) -> String {
+    match float_suffix_of(ir_type) {
+        Some("f32") => "float".to_owned(),
+        Some(_) => "double".to_owned(),
+        None => c_type(ir_type.size),
+    }
+}
+
+/// C library function taking or yielding pointers, which are cast from and to integers at calls
+struct CLibraryFunction {
+    prototype: &'static str,
+    pointer_params: &'static [bool],
+    pointer_result: bool,
+}
+
+/// Functions of the C library that can't be declared with integer types without clashing with
+/// the headers
+fn c_library_function(name: &str) -> Option<CLibraryFunction> {
+    let (prototype, pointer_params, pointer_result): (_, &[_], _) = match name {
+        "malloc" => ("void *malloc(size_t);", &[false], true),
+        "calloc" => ("void *calloc(size_t, size_t);", &[false, false], true),
+        "realloc" => ("void *realloc(void *, size_t);", &[true, false], true),
+        "free" => ("void free(void *);", &[true], false),
+        _ => return None,
+    };
+    Some(CLibraryFunction {
+        prototype,
+        pointer_params,
+        pointer_result,
+    })
+}
+
+fn function_name(name: Symbol) -> String {
+    if symbol_str(name) == "main" {
+        MAIN_WRAPPER_TARGET.to_owned()
+    } else {
+        sanitize_symbol(name)
+    }
+}
+
+fn value_name(value: &IRValue) -> String {
+    format!("v_{}", sanitize_symbol(value.id))
+}
+
+fn label_name(label: Symbol) -> String {
+    format!("l_{}", sanitize_symbol(label))
+}
+
+fn pointer(location: &IRValue, offset: u64) -> String {
+    if offset == 0 {
+        format!("(uint8_t *)(uintptr_t){}", value_name(location))
+    } else {
+        format!(
+            "(uint8_t *)(uintptr_t){} + {}",
+            value_name(location),
+            offset
+        )
+    }
+}
+
+fn prototype(function_def: &IRItemFunctionDef) -> String {
+    let params: Vec<_> = function_def
+        .params
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, ir_type))| ir_type.size > 0)
+        .map(|(index, (id, ir_type))| match id {
+            Some(id) => format!(
+                "{} {}",
+                c_type(ir_type.size),
+                value_name(&IRValue { id: *id })
+            ),
+            None => format!("{} unused_{}", c_type(ir_type.size), index),
+        })
+        .collect();
+
+    format!(
+        "{}{} {}({})",
+        match function_def.visibility {
+            IRVisibility::Exported => "",
+            IRVisibility::Internal => "static ",
+        },
+        c_type(function_def.return_type.size),
+        function_name(function_def.name),
+        if params.is_empty() {
+            "void".to_owned()
+        } else {
+            params.join(", ")
+        }
+    )
+}
+
+struct FunctionEmitter<'a> {
+    function_def: &'a IRItemFunctionDef,
+    types: HashMap<Symbol, IRType>,
+    signatures: &'a IRSignatures,
+    output: String,
+}
+
+impl<'a> FunctionEmitter<'a> {
+    fn line(&mut self, line: &str) {
+        self.output += "    ";
+        self.output += line;
+        self.output += "\n";
+    }
+
+    fn size_of(&self, value: &IRValue) -> u64 {
+        self.types[&value.id].size
+    }
+
+    fn unsupported_size(&self, size: u64) -> IRBackendError {
+        IRBackendError {
+            message: format!(
+                "Arithmetic on values of {} bytes is not supported (in function {})",
+                size,
+                symbol_str(self.function_def.name)
+            ),
+        }
+    }
+
+    fn assign(&self, comp: &IRComp, expression: &str) -> String {
+        match comp.id {
+            Some(id) if self.types[&id].size > 0 => {
+                format!("{} = {};", value_name(&IRValue { id }), expression)
+            }
+            _ => format!("{};", expression),
+        }
+    }
+
+    fn emit_comp(&mut self, index: usize, comp: &IRComp) -> IRBackendResult<()> {
+        match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => {
+                // Floats are converted from and to their bits around calls to external functions,
+                // and pointers of the C library functions from and to integers
+                let signature = self.signatures.externs.get(&function_call.name);
+                let library_function =
+                    if self.signatures.functions.contains_key(&function_call.name) {
+                        None
+                    } else {
+                        c_library_function(symbol_str(function_call.name))
+                    };
+                let args: Vec<_> = function_call
+                    .args
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, arg)| self.size_of(arg) > 0)
+                    .map(|(index, arg)| {
+                        let pointer = library_function.as_ref().is_some_and(|function| {
+                            function.pointer_params.get(index) == Some(&true)
+                        });
+                        match signature
+                            .and_then(|(params, _)| params.get(index))
+                            .and_then(float_suffix_of)
+                        {
+                            _ if pointer => format!("(void *)(uintptr_t){}", value_name(arg)),
+                            Some(suffix) => format!("solidc_{}({})", suffix, value_name(arg)),
+                            None => value_name(arg),
+                        }
+                    })
+                    .collect();
+                let mut call =
+                    format!("{}({})", function_name(function_call.name), args.join(", "));
+                if library_function.is_some_and(|function| function.pointer_result) {
+                    call = format!("(uint64_t)(uintptr_t){}", call);
+                } else if let Some(suffix) =
+                    signature.and_then(|(_, return_type)| float_suffix_of(return_type))
+                {
+                    call = format!("solidc_bits_{}({})", suffix, call);
+                }
+                let line = self.assign(comp, &call);
+                self.line(&line);
+            }
+            IRCompKind::BinaryOperation(operation) if operation.kind.is_float() => {
+                let size = self.size_of(&operation.left_operand);
+                let suffix = float_suffix(size).ok_or_else(|| self.unsupported_size(size))?;
+                let operator = match operation.kind {
+                    IRCompBinaryOperationKind::FloatPlus => "+",
+                    IRCompBinaryOperationKind::FloatMinus => "-",
+                    IRCompBinaryOperationKind::FloatMul => "*",
+                    IRCompBinaryOperationKind::FloatDiv => "/",
+                    IRCompBinaryOperationKind::FloatEqual => "==",
+                    IRCompBinaryOperationKind::FloatNotEqual => "!=",
+                    IRCompBinaryOperationKind::FloatGreater => ">",
+                    IRCompBinaryOperationKind::FloatLesser => "<",
+                    IRCompBinaryOperationKind::FloatGreaterEqual => ">=",
+                    IRCompBinaryOperationKind::FloatLesserEqual => "<=",
+                    _ => unreachable!(),
+                };
+                let operation_expression = format!(
+                    "solidc_{}({}) {} solidc_{}({})",
+                    suffix,
+                    value_name(&operation.left_operand),
+                    operator,
+                    suffix,
+                    value_name(&operation.right_operand)
+                );
+                let expression = if operation.kind.is_comparison() {
+                    format!("(uint8_t)({})", operation_expression)
+                } else {
+                    format!("solidc_bits_{}({})", suffix, operation_expression)
+                };
+                let line = self.assign(comp, &expression);
+                self.line(&line);
+            }
+            IRCompKind::BinaryOperation(operation) => {
+                let size = self.size_of(&operation.left_operand);
+                let c_type = scalar_c_type(size).ok_or_else(|| self.unsupported_size(size))?;
+                let operator = match operation.kind {
+                    IRCompBinaryOperationKind::Plus => "+",
+                    IRCompBinaryOperationKind::Minus => "-",
+                    IRCompBinaryOperationKind::Mul => "*",
+                    IRCompBinaryOperationKind::Div => "/",
+                    IRCompBinaryOperationKind::Mod => "%",
+                    IRCompBinaryOperationKind::BitAnd => "&",
+                    IRCompBinaryOperationKind::BitOr => "|",
+                    IRCompBinaryOperationKind::BitRShift => ">>",
+                    IRCompBinaryOperationKind::BitLShift => "<<",
+                    IRCompBinaryOperationKind::Equal => "==",
+                    IRCompBinaryOperationKind::NotEqual => "!=",
+                    IRCompBinaryOperationKind::Greater => ">",
+                    IRCompBinaryOperationKind::Lesser => "<",
+                    IRCompBinaryOperationKind::GreaterEqual => ">=",
+                    IRCompBinaryOperationKind::LesserEqual => "<=",
+                    IRCompBinaryOperationKind::SignedDiv => "/",
+                    IRCompBinaryOperationKind::SignedMod => "%",
+                    IRCompBinaryOperationKind::SignedRShift => ">>",
+                    IRCompBinaryOperationKind::SignedGreater => ">",
+                    IRCompBinaryOperationKind::SignedLesser => "<",
+                    IRCompBinaryOperationKind::SignedGreaterEqual => ">=",
+                    IRCompBinaryOperationKind::SignedLesserEqual => "<=",
+                    _ => unreachable!(),
+                };
+                let result_type = if operation.kind.is_comparison() {
+                    "uint8_t"
+                } else {
+                    c_type
+                };
+                // Small operands are computed as uint32_t, so integer promotion never turns them
+                // into signed ints (which could overflow), signed operands are sign extended
+                let arithmetic_type = if operation.kind.is_signed() {
+                    signed_c_type(size).unwrap()
+                } else if size < 4 {
+                    "uint32_t"
+                } else {
+                    c_type
+                };
+                let expression = format!(
+                    "({})(({}){} {} ({}){})",
+                    result_type,
+                    arithmetic_type,
+                    value_name(&operation.left_operand),
+                    operator,
+                    arithmetic_type,
+                    value_name(&operation.right_operand)
+                );
+                let line = self.assign(comp, &expression);
+                self.line(&line);
+            }
+            IRCompKind::UnaryOperation(operation) => {
+                let size = self.size_of(&operation.operand);
+                let c_type = scalar_c_type(size).ok_or_else(|| self.unsupported_size(size))?;
+                let operand = value_name(&operation.operand);
+                let expression = match operation.kind {
+                    IRCompUnaryOperationKind::BoolNot => format!("(uint8_t)({} == 0)", operand),
+                    IRCompUnaryOperationKind::BitNot => {
+                        format!("({})~(uint64_t){}", c_type, operand)
+                    }
+                    IRCompUnaryOperationKind::SignedNegation => {
+                        format!("({})(UINT64_C(0) - {})", c_type, operand)
+                    }
+                    IRCompUnaryOperationKind::FloatNegation => {
+                        let suffix =
+                            float_suffix(size).ok_or_else(|| self.unsupported_size(size))?;
+                        format!("solidc_bits_{}(-solidc_{}({}))", suffix, suffix, operand)
+                    }
+                };
+                let line = self.assign(comp, &expression);
+                self.line(&line);
+            }
+            IRCompKind::Conversion(conversion) => {
+                let from = self.size_of(&conversion.operand);
+                let to = conversion.size;
+                let operand = value_name(&conversion.operand);
+                let expression = match conversion.kind {
+                    IRCompConversionKind::UnsignedToFloat | IRCompConversionKind::SignedToFloat => {
+                        let integer_type = match conversion.kind {
+                            IRCompConversionKind::SignedToFloat => signed_c_type(from),
+                            _ => scalar_c_type(from),
+                        }
+                        .ok_or_else(|| self.unsupported_size(from))?;
+                        let suffix = float_suffix(to).ok_or_else(|| self.unsupported_size(to))?;
+                        let float_type = if to == 4 { "float" } else { "double" };
+                        format!(
+                            "solidc_bits_{}(({})({}){})",
+                            suffix, float_type, integer_type, operand
+                        )
+                    }
+                    IRCompConversionKind::FloatToUnsigned | IRCompConversionKind::FloatToSigned => {
+                        let suffix =
+                            float_suffix(from).ok_or_else(|| self.unsupported_size(from))?;
+                        let unsigned_type =
+                            scalar_c_type(to).ok_or_else(|| self.unsupported_size(to))?;
+                        let integer_type = match conversion.kind {
+                            IRCompConversionKind::FloatToSigned => signed_c_type(to).unwrap(),
+                            _ => unsigned_type,
+                        };
+                        format!(
+                            "({})({})solidc_{}({})",
+                            unsigned_type, integer_type, suffix, operand
+                        )
+                    }
+                    IRCompConversionKind::FloatToFloat => {
+                        let from_suffix =
+                            float_suffix(from).ok_or_else(|| self.unsupported_size(from))?;
+                        let to_suffix =
+                            float_suffix(to).ok_or_else(|| self.unsupported_size(to))?;
+                        let float_type = if to == 4 { "float" } else { "double" };
+                        format!(
+                            "solidc_bits_{}(({})solidc_{}({}))",
+                            to_suffix, float_type, from_suffix, operand
+                        )
+                    }
+                    IRCompConversionKind::Truncate | IRCompConversionKind::ZeroExtend => {
+                        let c_type = scalar_c_type(to).ok_or_else(|| self.unsupported_size(to))?;
+                        format!("({}){}", c_type, operand)
+                    }
+                    IRCompConversionKind::SignExtend => {
+                        let signed_type =
+                            signed_c_type(from).ok_or_else(|| self.unsupported_size(from))?;
+                        let c_type = scalar_c_type(to).ok_or_else(|| self.unsupported_size(to))?;
+                        format!("({})({}){}", c_type, signed_type, operand)
+                    }
+                };
+                let line = self.assign(comp, &expression);
+                self.line(&line);
+            }
+            IRCompKind::Constant(constant) => {
+                if comp.id.is_some() {
+                    let size = constant.bytes.len() as u64;
+                    let expression = if scalar_c_type(size).is_some() {
+                        let mut qword = [0u8; 8];
+                        qword[..constant.bytes.len()].copy_from_slice(&constant.bytes);
+                        format!("({})UINT64_C({})", c_type(size), u64::from_le_bytes(qword))
+                    } else if size == 0 {
+                        return Ok(());
+                    } else {
+                        let bytes: Vec<_> =
+                            constant.bytes.iter().map(|byte| byte.to_string()).collect();
+                        format!("({}){{{{{}}}}}", c_type(size), bytes.join(", "))
+                    };
+                    let line = self.assign(comp, &expression);
+                    self.line(&line);
+                }
+            }
+            IRCompKind::Alloc(_) => {
+                // Memory yielded by alloc is zeroed, like in the interpreter
+                self.line(&format!(
+                    "memset(alloc_{}, 0, sizeof(alloc_{}));",
+                    index, index
+                ));
+                let expression = format!("(uint64_t)(uintptr_t)alloc_{}", index);
+                let line = self.assign(comp, &expression);
+                self.line(&line);
+            }
+            IRCompKind::Store(ir_type, location, value) => {
+                self.emit_store(ir_type, location, value, 0);
+            }
+            IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                self.emit_store(ir_type, location, value, *offset);
+            }
+            IRCompKind::Load(ir_type, location) => {
+                self.emit_load(comp, ir_type, location, 0);
+            }
+            IRCompKind::OffsetLoad(ir_type, location, offset) => {
+                self.emit_load(comp, ir_type, location, *offset);
+            }
+            IRCompKind::Return(value) => {
+                if self.function_def.return_type.size == 0 {
+                    self.line("return;");
+                } else {
+                    self.line(&format!("return {};", value_name(value)));
+                }
+            }
+            IRCompKind::If(value, label) => {
+                self.line(&format!(
+                    "if ({}) goto {};",
+                    value_name(value),
+                    label_name(*label)
+                ));
+            }
+            IRCompKind::Jmp(label) => {
+                self.line(&format!("goto {};", label_name(*label)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_store(&mut self, ir_type: &IRType, location: &IRValue, value: &IRValue, offset: u64) {
+        if ir_type.size > 0 {
+            self.line(&format!(
+                "memcpy({}, &{}, {});",
+                pointer(location, offset),
+                value_name(value),
+                ir_type.size
+            ));
+        }
+    }
+
+    fn emit_load(&mut self, comp: &IRComp, ir_type: &IRType, location: &IRValue, offset: u64) {
+        if let Some(id) = comp.id {
+            if ir_type.size > 0 {
+                self.line(&format!(
+                    "memcpy(&{}, {}, {});",
+                    value_name(&IRValue { id }),
+                    pointer(location, offset),
+                    ir_type.size
+                ));
+            }
+        }
+    }
+
+    fn emit(mut self) -> IRBackendResult<String> {
+        self.output += &prototype(self.function_def);
+        self.output += " {\n";
+
+        // Every value is declared upfront, so gotos never jump over a declaration
+        let params: HashSet<_> = self
+            .function_def
+            .params
+            .iter()
+            .filter_map(|p| p.0)
+            .collect();
+        let mut declarations: Vec<_> = self
+            .types
+            .iter()
+            .filter(|(id, ir_type)| !params.contains(id) && ir_type.size > 0)
+            .map(|(id, ir_type)| {
+                format!(
+                    "{} {};",
+                    c_type(ir_type.size),
+                    value_name(&IRValue { id: *id })
+                )
+            })
+            .collect();
+        declarations.sort();
+        for declaration in declarations {
+            self.line(&declaration);
+        }
+        let function_def = self.function_def;
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            if let IRCompKind::Alloc(ir_type) = &comp.kind {
+                self.line(&format!(
+                    "_Alignas({}) uint8_t alloc_{}[{}];",
+                    ir_type.align.max(1),
+                    index,
+                    ir_type.size.max(1)
+                ));
+            }
+        }
+
+        let mut labels: Vec<_> = function_def.label_defs.iter().collect();
+        labels.sort_by_key(|(label, index)| (**index, symbol_str(**label)));
+        let mut labels = labels.into_iter().peekable();
+
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            while let Some((label, _)) = labels.next_if(|(_, i)| **i as usize == index) {
+                self.output += &format!("{}:;\n", label_name(*label));
+            }
+            self.emit_comp(index, comp)?;
+        }
+        for (label, _) in labels {
+            self.output += &format!("{}:;\n", label_name(*label));
+        }
+
+        // Falling off the end of a function returns nothing meaningful
+        if function_def.return_type.size > 0 {
+            let return_type = c_type(function_def.return_type.size);
+            self.line(&format!(
+                "{{ {} undefined = {{0}}; return undefined; }}",
+                return_type
+            ));
+        }
+        self.output += "}\n";
+
+        Ok(self.output)
+    }
+}
+
+/// Emits a single C11 translation unit for a module
+///
+/// Every value becomes a local variable of an unsigned integer type (or a struct wrapping a byte
+/// array for the other sizes), `Alloc` comps become local arrays and labels become `goto`
+/// targets. Internal functions are `static`. Calls to functions that are neither defined nor
+/// declared in the module are declared as external functions returning a `uint64_t`, except for
+/// the heap functions of the C library, which keep their prototypes. If the module has a `main`
+/// function, a C `main` calling it is emitted as well.
+pub fn c_source_for_ir_module(module: &IRModule) -> IRBackendResult<String> {
+    let signatures = IRSignatures::for_module(module);
+    let mut function_defs = vec![];
+    let mut extern_decls = vec![];
+    let mut types = vec![];
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                types.push(infer_value_types(function_def, &signatures)?);
+                function_defs.push(function_def);
+            }
+            IRItemKind::ExternDecl(extern_decl) => extern_decls.push(extern_decl),
+        }
+    }
+
+    let mut result = "#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n".to_owned();
+    result += FLOAT_PRELUDE;
+    result += "\n";
+
+    // Byte array types
+    let mut sizes = BTreeSet::new();
+    for (function_def, types) in function_defs.iter().zip(types.iter()) {
+        sizes.extend(types.values().map(|ir_type| ir_type.size));
+        sizes.insert(function_def.return_type.size);
+    }
+    for extern_decl in &extern_decls {
+        sizes.extend(extern_decl.params.iter().map(|ir_type| ir_type.size));
+        sizes.insert(extern_decl.return_type.size);
+    }
+    for size in sizes {
+        if size > 0 && scalar_c_type(size).is_none() {
+            result += &format!(
+                "typedef struct {{ uint8_t bytes[{}]; }} solidc_bytes{};\n",
+                size, size
+            );
+        }
+    }
+    result += "\n";
+
+    // Prototypes
+    for function_def in &function_defs {
+        result += &prototype(function_def);
+        result += ";\n";
+    }
+    for extern_decl in &extern_decls {
+        if let Some(function) = c_library_function(symbol_str(extern_decl.name)) {
+            result += function.prototype;
+            result += "\n";
+            continue;
+        }
+        let params: Vec<_> = extern_decl
+            .params
+            .iter()
+            .filter(|ir_type| ir_type.size > 0)
+            .map(extern_c_type)
+            .collect();
+        result += &format!(
+            "extern {} {}({});\n",
+            extern_c_type(&extern_decl.return_type),
+            sanitize_symbol(extern_decl.name),
+            if params.is_empty() {
+                "void".to_owned()
+            } else {
+                params.join(", ")
+            }
+        );
+    }
+    let mut externs = BTreeSet::new();
+    for function_def in &function_defs {
+        for comp in &function_def.comps {
+            if let IRCompKind::FunctionCall(function_call) = &comp.kind {
+                if !signatures.functions.contains_key(&function_call.name)
+                    && !signatures.externs.contains_key(&function_call.name)
+                {
+                    externs.insert(sanitize_symbol(function_call.name));
+                }
+            }
+        }
+    }
+    for name in externs {
+        match c_library_function(&name) {
+            Some(function) => result += function.prototype,
+            None => result += &format!("extern uint64_t {}();", name),
+        }
+        result += "\n";
+    }
+
+    for (function_def, types) in function_defs.iter().zip(types) {
+        result += "\n";
+        let emitter = FunctionEmitter {
+            function_def,
+            types,
+            signatures: &signatures,
+            output: String::new(),
+        };
+        result += &emitter.emit()?;
+    }
+
+    // Entry point
+    let main = function_defs
+        .iter()
+        .find(|function_def| symbol_str(function_def.name) == "main");
+    if let Some(main) = main {
+        let args = match main.params.len() {
+            0 => "",
+            2 => "(uint32_t)argc, (uint64_t)(uintptr_t)argv",
+            _ => {
+                return Err(IRBackendError {
+                    message: "The main function must take either no params or two params"
+                        .to_owned(),
+                })
+            }
+        };
+        result += "\nint main(int argc, char **argv) {\n";
+        result += "    (void)argc;\n    (void)argv;\n";
+        if scalar_c_type(main.return_type.size).is_some() {
+            result += &format!("    return (int){}({});\n", MAIN_WRAPPER_TARGET, args);
+        } else {
+            result += &format!("    {}({});\n    return 0;\n", MAIN_WRAPPER_TARGET, args);
+        }
+        result += "}\n";
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::SessionGlobals;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+
+    const MODULE: &str = "\
+extern sqrt: (8 8 float) -> (8 8 float)
+
+fn sum_to: %n := (8 8) -> (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %one := const 8 1 0 0 0 0 0 0 0
+    %total := alloc (16 8)
+    store (8 8) %total %zero
+    :loop
+    %done := binop == %n %zero
+    if %done end
+    %t := load (8 8) %total
+    %t := binop + %t %n
+    store (8 8) %total %t
+    offsetstore (8 8) %total %n 8
+    %n := binop - %n %one
+    jmp loop
+    :end
+    %result := load (8 8) %total
+    return %result
+endfn
+
+fn main: -> (8 8)
+    %n := const 8 4 0 0 0 0 0 0 0
+    %sum := call sum_to 1 %n
+    %f := convert utof 8 %sum
+    %root := call sqrt 1 %f
+    %half := const 8 0 0 0 0 0 0 224 63
+    %root := binop fmul %root %half
+    %r := convert ftou 8 %root
+    return %r
+endfn
+";
+
+    fn c_source(src: &str) -> String {
+        c_source_for_ir_module(&assemble_ir_module(src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn allocs_and_labels_become_arrays_and_gotos() {
+        SessionGlobals::create(|| {
+            let source = c_source(MODULE);
+            assert!(source.contains("    _Alignas(8) uint8_t alloc_2[16];\n"));
+            assert!(source.contains("    memset(alloc_2, 0, sizeof(alloc_2));\n"));
+            assert!(source.contains("    v_total = (uint64_t)(uintptr_t)alloc_2;\n"));
+            assert!(source.contains("    memcpy((uint8_t *)(uintptr_t)v_total + 8, &v_n, 8);\n"));
+            assert!(source.contains("l_loop:;\n"));
+            assert!(source.contains("    if (v_done) goto l_end;\n"));
+            assert!(source.contains("    goto l_loop;\nl_end:;\n"));
+        });
+    }
+
+    #[test]
+    fn heap_functions_keep_their_c_library_prototypes() {
+        let src = "\
+extern malloc: (8 8) -> (8 8)
+extern free: (8 8) -> (0 1)
+
+fn main: -> (8 8)
+    %size := const 8 16 0 0 0 0 0 0 0
+    %ptr := call malloc 1 %size
+    %moved := call realloc 2 %ptr %size
+    call free 1 %moved
+    return %size
+endfn
+";
+        SessionGlobals::create(|| {
+            let source = c_source(src);
+            assert!(source.starts_with("#include <stddef.h>\n"));
+            assert!(source.contains("\nvoid *malloc(size_t);\nvoid free(void *);\n"));
+            assert!(source.contains("\nvoid *realloc(void *, size_t);\n"));
+            assert!(source.contains("    v_ptr = (uint64_t)(uintptr_t)malloc(v_size);\n"));
+            assert!(source.contains(
+                "    v_moved = (uint64_t)(uintptr_t)realloc((void *)(uintptr_t)v_ptr, v_size);\n"
+            ));
+            assert!(source.contains("    free((void *)(uintptr_t)v_moved);\n"));
+        });
+    }
+
+    #[test]
+    fn float_operations_and_externs_use_c_floats() {
+        SessionGlobals::create(|| {
+            let source = c_source(MODULE);
+            assert!(source.contains("extern double sqrt(double);\n"));
+            assert!(source.contains("    v_f = solidc_bits_f64((double)(uint64_t)v_sum);\n"));
+            assert!(source.contains("    v_root = solidc_bits_f64(sqrt(solidc_f64(v_f)));\n"));
+            assert!(source.contains(
+                "    v_root = solidc_bits_f64(solidc_f64(v_root) * solidc_f64(v_half));\n"
+            ));
+            assert!(source.contains("    v_r = (uint64_t)(uint64_t)solidc_f64(v_root);\n"));
+        });
+    }
+}
@@ -0,0 +1,926 @@
+use crate::globals::Symbol;
+use crate::ir::backend::{
+    infer_value_types, sanitize_symbol, symbol_str, IRBackendError, IRBackendResult, IRSignatures,
+    EXTERN_RETURN_IR_TYPE,
+};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperationKind, IRCompConversionKind, IRCompKind, IRCompUnaryOperationKind,
+};
+use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue, IRVisibility};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+fn is_scalar(size: u64) -> bool {
+    matches!(size, 1 | 2 | 4 | 8)
+}
+
+/// LLVM type used for values of the given size
+///
+/// Sizes without a matching integer type are byte arrays.
+fn llvm_type(size: u64) -> String {
+    if is_scalar(size) {
+        format!("i{}", size * 8)
+    } else if size == 0 {
+        "void".to_owned()
+    } else {
+        format!("[{} x i8]", size)
+    }
+}
+
+/// LLVM type of the floats of the given size, they are held by integers of the same size
+fn float_type(size: u64) -> Option<&'static str> {
+    match size {
+        4 => Some("float"),
+        8 => Some("double"),
+        _ => None,
+    }
+}
+
+/// LLVM type of the params and results of external functions, which take floats as floats
+fn extern_llvm_type(ir_type: &IRType) -> String {
+    match float_type(ir_type.size) {
+        Some(float_type) if ir_type.float => float_type.to_owned(),
+        _ => llvm_type(ir_type.size),
+    }
+}
+
+fn function_name(name: Symbol) -> String {
+    format!("@{}", sanitize_symbol(name))
+}
+
+/// Name of the stack slot holding a value
+fn slot_name(id: Symbol) -> String {
+    format!("%v.{}", sanitize_symbol(id))
+}
+
+fn label_name(label: Symbol) -> String {
+    format!("l.{}", sanitize_symbol(label))
+}
+
+fn constant_literal(bytes: &[u8]) -> String {
+    if is_scalar(bytes.len() as u64) {
+        // Printed as a signed number, which LLVM accepts for every width
+        let mut qword = [0u8; 8];
+        qword[..bytes.len()].copy_from_slice(bytes);
+        let shift = 64 - bytes.len() * 8;
+        ((i64::from_le_bytes(qword) << shift) >> shift).to_string()
+    } else {
+        let mut literal = "c\"".to_owned();
+        for byte in bytes {
+            literal += &format!("\\{:02X}", byte);
+        }
+        literal += "\"";
+        literal
+    }
+}
+
+struct FunctionEmitter<'a> {
+    function_def: &'a IRItemFunctionDef,
+    types: HashMap<Symbol, IRType>,
+    signatures: &'a IRSignatures,
+    /// Parameter types of the functions called but not defined in the module
+    externs: &'a mut BTreeMap<String, Vec<String>>,
+    /// Declarations of the intrinsics used by the module
+    intrinsics: &'a mut BTreeSet<String>,
+    output: String,
+    next_temporary: usize,
+    next_block: usize,
+    /// Whether the current basic block still lacks a terminator
+    open: bool,
+}
+
+impl<'a> FunctionEmitter<'a> {
+    fn line(&mut self, line: &str) {
+        self.output += "  ";
+        self.output += line;
+        self.output += "\n";
+    }
+
+    fn temporary(&mut self) -> String {
+        self.next_temporary += 1;
+        format!("%t{}", self.next_temporary)
+    }
+
+    fn error(&self, message: &str) -> IRBackendError {
+        IRBackendError {
+            message: format!(
+                "{} (in function {})",
+                message,
+                symbol_str(self.function_def.name)
+            ),
+        }
+    }
+
+    fn size_of(&self, value: &IRValue) -> u64 {
+        self.types[&value.id].size
+    }
+
+    fn start_block(&mut self, name: &str) {
+        if self.open {
+            self.line(&format!("br label %{}", name));
+        }
+        self.output += &format!("{}:\n", name);
+        self.open = true;
+    }
+
+    /// Starts a block for code following a terminator that no label makes reachable
+    fn ensure_open(&mut self) {
+        if !self.open {
+            self.next_block += 1;
+            let name = format!("dead{}", self.next_block);
+            self.start_block(&name);
+        }
+    }
+
+    fn load_value(&mut self, value: &IRValue) -> String {
+        let temporary = self.temporary();
+        let line = format!(
+            "{} = load {}, ptr {}",
+            temporary,
+            llvm_type(self.size_of(value)),
+            slot_name(value.id)
+        );
+        self.line(&line);
+        temporary
+    }
+
+    /// Loads a value holding a float and reinterprets its bits
+    fn load_float(&mut self, value: &IRValue) -> IRBackendResult<String> {
+        let size = self.size_of(value);
+        let float_type = float_type(size).ok_or_else(|| {
+            self.error(&format!(
+                "Float operations on values of {} bytes are not supported",
+                size
+            ))
+        })?;
+        let operand = self.load_value(value);
+        let float = self.temporary();
+        self.line(&format!(
+            "{} = bitcast {} {} to {}",
+            float,
+            llvm_type(size),
+            operand,
+            float_type
+        ));
+        Ok(float)
+    }
+
+    /// Writes the bits of a float to the slot of the id of a comp
+    fn define_float(&mut self, comp: &IRComp, float: String, size: u64) -> IRBackendResult<()> {
+        let operand = self.temporary();
+        self.line(&format!(
+            "{} = bitcast {} {} to {}",
+            operand,
+            float_type(size).unwrap(),
+            float,
+            llvm_type(size)
+        ));
+        self.define(comp, operand, size)
+    }
+
+    fn emit_conversion(
+        &mut self,
+        comp: &IRComp,
+        kind: &IRCompConversionKind,
+        operand: &IRValue,
+        to: u64,
+    ) -> IRBackendResult<()> {
+        let from = self.size_of(operand);
+        let unsupported = |emitter: &Self| {
+            emitter.error(&format!(
+                "Cannot convert a value of {} bytes to {} bytes",
+                from, to
+            ))
+        };
+        let result = self.temporary();
+        match kind {
+            IRCompConversionKind::UnsignedToFloat | IRCompConversionKind::SignedToFloat => {
+                let float_type = float_type(to).ok_or_else(|| unsupported(self))?;
+                if !is_scalar(from) {
+                    return Err(unsupported(self));
+                }
+                let instruction = match kind {
+                    IRCompConversionKind::SignedToFloat => "sitofp",
+                    _ => "uitofp",
+                };
+                let integer = self.load_value(operand);
+                self.line(&format!(
+                    "{} = {} {} {} to {}",
+                    result,
+                    instruction,
+                    llvm_type(from),
+                    integer,
+                    float_type
+                ));
+                self.define_float(comp, result, to)
+            }
+            IRCompConversionKind::FloatToUnsigned | IRCompConversionKind::FloatToSigned => {
+                if !is_scalar(to) {
+                    return Err(unsupported(self));
+                }
+                let float = self.load_float(operand)?;
+                // The saturating intrinsics match the interpreter for floats out of range
+                let intrinsic = format!(
+                    "llvm.{}.sat.{}.{}",
+                    match kind {
+                        IRCompConversionKind::FloatToSigned => "fptosi",
+                        _ => "fptoui",
+                    },
+                    llvm_type(to),
+                    if from == 4 { "f32" } else { "f64" }
+                );
+                let float_type = float_type(from).unwrap();
+                self.intrinsics.insert(format!(
+                    "declare {} @{}({})",
+                    llvm_type(to),
+                    intrinsic,
+                    float_type
+                ));
+                self.line(&format!(
+                    "{} = call {} @{}({} {})",
+                    result,
+                    llvm_type(to),
+                    intrinsic,
+                    float_type,
+                    float
+                ));
+                self.define(comp, result, to)
+            }
+            IRCompConversionKind::FloatToFloat => {
+                let to_type = float_type(to).ok_or_else(|| unsupported(self))?;
+                let float = self.load_float(operand)?;
+                let instruction = match from.cmp(&to) {
+                    std::cmp::Ordering::Less => "fpext",
+                    std::cmp::Ordering::Greater => "fptrunc",
+                    std::cmp::Ordering::Equal => "bitcast",
+                };
+                self.line(&format!(
+                    "{} = {} {} {} to {}",
+                    result,
+                    instruction,
+                    float_type(from).unwrap(),
+                    float,
+                    to_type
+                ));
+                self.define_float(comp, result, to)
+            }
+            IRCompConversionKind::Truncate
+            | IRCompConversionKind::ZeroExtend
+            | IRCompConversionKind::SignExtend => {
+                if !is_scalar(from) || !is_scalar(to) {
+                    return Err(unsupported(self));
+                }
+                let instruction = match (kind, from.cmp(&to)) {
+                    (_, std::cmp::Ordering::Equal) => "bitcast",
+                    (IRCompConversionKind::Truncate, std::cmp::Ordering::Greater) => "trunc",
+                    (IRCompConversionKind::ZeroExtend, std::cmp::Ordering::Less) => "zext",
+                    (IRCompConversionKind::SignExtend, std::cmp::Ordering::Less) => "sext",
+                    _ => return Err(unsupported(self)),
+                };
+                let integer = self.load_value(operand);
+                self.line(&format!(
+                    "{} = {} {} {} to {}",
+                    result,
+                    instruction,
+                    llvm_type(from),
+                    integer,
+                    llvm_type(to)
+                ));
+                self.define(comp, result, to)
+            }
+        }
+    }
+
+    /// Turns an operand of one size into an operand of another size
+    fn convert(&mut self, operand: String, from: u64, to: u64) -> IRBackendResult<String> {
+        if from == to {
+            return Ok(operand);
+        }
+        if !is_scalar(from) || !is_scalar(to) {
+            return Err(self.error(&format!(
+                "Cannot convert a value of {} bytes to {} bytes",
+                from, to
+            )));
+        }
+        let temporary = self.temporary();
+        let instruction = if from < to { "zext" } else { "trunc" };
+        self.line(&format!(
+            "{} = {} {} {} to {}",
+            temporary,
+            instruction,
+            llvm_type(from),
+            operand,
+            llvm_type(to)
+        ));
+        Ok(temporary)
+    }
+
+    /// Writes the result of a comp to the slot of its id
+    fn define(&mut self, comp: &IRComp, operand: String, size: u64) -> IRBackendResult<()> {
+        if let Some(id) = comp.id {
+            let slot_size = self.types[&id].size;
+            if slot_size > 0 {
+                let operand = self.convert(operand, size, slot_size)?;
+                self.line(&format!(
+                    "store {} {}, ptr {}",
+                    llvm_type(slot_size),
+                    operand,
+                    slot_name(id)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn address(&mut self, location: &IRValue, offset: u64) -> String {
+        let location = self.load_value(location);
+        let pointer = self.temporary();
+        self.line(&format!("{} = inttoptr i64 {} to ptr", pointer, location));
+        if offset == 0 {
+            pointer
+        } else {
+            let offset_pointer = self.temporary();
+            self.line(&format!(
+                "{} = getelementptr i8, ptr {}, i64 {}",
+                offset_pointer, pointer, offset
+            ));
+            offset_pointer
+        }
+    }
+
+    fn emit_store(
+        &mut self,
+        ir_type: &IRType,
+        location: &IRValue,
+        value: &IRValue,
+        offset: u64,
+    ) -> IRBackendResult<()> {
+        if ir_type.size == 0 {
+            return Ok(());
+        }
+        let address = self.address(location, offset);
+        let value_size = self.size_of(value);
+        if value_size == ir_type.size || (is_scalar(value_size) && is_scalar(ir_type.size)) {
+            let operand = self.load_value(value);
+            let operand = self.convert(operand, value_size, ir_type.size)?;
+            self.line(&format!(
+                "store {} {}, ptr {}, align {}",
+                llvm_type(ir_type.size),
+                operand,
+                address,
+                ir_type.align.max(1)
+            ));
+        } else {
+            self.line(&format!(
+                "call void @llvm.memcpy.p0.p0.i64(ptr {}, ptr {}, i64 {}, i1 false)",
+                address,
+                slot_name(value.id),
+                ir_type.size.min(value_size)
+            ));
+        }
+        Ok(())
+    }
+
+    fn emit_load(
+        &mut self,
+        comp: &IRComp,
+        ir_type: &IRType,
+        location: &IRValue,
+        offset: u64,
+    ) -> IRBackendResult<()> {
+        if ir_type.size == 0 || comp.id.is_none() {
+            return Ok(());
+        }
+        let address = self.address(location, offset);
+        let operand = self.temporary();
+        self.line(&format!(
+            "{} = load {}, ptr {}, align {}",
+            operand,
+            llvm_type(ir_type.size),
+            address,
+            ir_type.align.max(1)
+        ));
+        self.define(comp, operand, ir_type.size)
+    }
+
+    fn emit_comp(&mut self, index: usize, comp: &IRComp) -> IRBackendResult<()> {
+        self.ensure_open();
+        match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => {
+                // Floats are reinterpreted from and to their bits around calls to external functions
+                let signature = self.signatures.externs.get(&function_call.name);
+                let mut args = vec![];
+                for (index, arg) in function_call.args.iter().enumerate() {
+                    let size = self.size_of(arg);
+                    if size > 0 {
+                        match signature.and_then(|(params, _)| params.get(index)) {
+                            Some(param) if param.float && float_type(size).is_some() => {
+                                let operand = self.load_float(arg)?;
+                                args.push((extern_llvm_type(param), operand));
+                            }
+                            _ => {
+                                let operand = self.load_value(arg);
+                                args.push((llvm_type(size), operand));
+                            }
+                        }
+                    }
+                }
+                let return_type = self.signatures.return_type(function_call.name);
+                let float_result = signature.is_some()
+                    && return_type.float
+                    && float_type(return_type.size).is_some();
+                if signature.is_none()
+                    && !self.signatures.functions.contains_key(&function_call.name)
+                {
+                    let name = sanitize_symbol(function_call.name);
+                    let param_types: Vec<_> = args.iter().map(|arg| arg.0.clone()).collect();
+                    match self.externs.get(&name) {
+                        Some(previous) if *previous != param_types => {
+                            return Err(self.error(&format!(
+                                "External function {} is called with different argument types",
+                                name
+                            )))
+                        }
+                        _ => {
+                            self.externs.insert(name, param_types);
+                        }
+                    }
+                }
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|(llvm_type, operand)| format!("{} {}", llvm_type, operand))
+                    .collect();
+                let call = format!(
+                    "call {} {}({})",
+                    if float_result {
+                        extern_llvm_type(&return_type)
+                    } else {
+                        llvm_type(return_type.size)
+                    },
+                    function_name(function_call.name),
+                    args.join(", ")
+                );
+                if return_type.size == 0 {
+                    self.line(&call);
+                } else if float_result {
+                    let result = self.temporary();
+                    self.line(&format!("{} = {}", result, call));
+                    self.define_float(comp, result, return_type.size)?;
+                } else {
+                    let result = self.temporary();
+                    self.line(&format!("{} = {}", result, call));
+                    self.define(comp, result, return_type.size)?;
+                }
+            }
+            IRCompKind::BinaryOperation(operation) if operation.kind.is_float() => {
+                let size = self.size_of(&operation.left_operand);
+                let left = self.load_float(&operation.left_operand)?;
+                let right = self.load_float(&operation.right_operand)?;
+                let instruction = match operation.kind {
+                    IRCompBinaryOperationKind::FloatPlus => "fadd",
+                    IRCompBinaryOperationKind::FloatMinus => "fsub",
+                    IRCompBinaryOperationKind::FloatMul => "fmul",
+                    IRCompBinaryOperationKind::FloatDiv => "fdiv",
+                    // Ordered comparisons are false for NaN, unordered ones true
+                    IRCompBinaryOperationKind::FloatEqual => "fcmp oeq",
+                    IRCompBinaryOperationKind::FloatNotEqual => "fcmp une",
+                    IRCompBinaryOperationKind::FloatGreater => "fcmp ogt",
+                    IRCompBinaryOperationKind::FloatLesser => "fcmp olt",
+                    IRCompBinaryOperationKind::FloatGreaterEqual => "fcmp oge",
+                    IRCompBinaryOperationKind::FloatLesserEqual => "fcmp ole",
+                    _ => unreachable!(),
+                };
+                let result = self.temporary();
+                self.line(&format!(
+                    "{} = {} {} {}, {}",
+                    result,
+                    instruction,
+                    float_type(size).unwrap(),
+                    left,
+                    right
+                ));
+                if operation.kind.is_comparison() {
+                    let extended = self.temporary();
+                    self.line(&format!("{} = zext i1 {} to i8", extended, result));
+                    self.define(comp, extended, 1)?;
+                } else {
+                    self.define_float(comp, result, size)?;
+                }
+            }
+            IRCompKind::BinaryOperation(operation) => {
+                let size = self.size_of(&operation.left_operand);
+                if !is_scalar(size) {
+                    return Err(self.error(&format!(
+                        "Arithmetic on values of {} bytes is not supported",
+                        size
+                    )));
+                }
+                let left = self.load_value(&operation.left_operand);
+                let right = self.load_value(&operation.right_operand);
+                let right_size = self.size_of(&operation.right_operand);
+                let right = self.convert(right, right_size, size)?;
+                let (instruction, comparison) = match operation.kind {
+                    IRCompBinaryOperationKind::Plus => ("add", false),
+                    IRCompBinaryOperationKind::Minus => ("sub", false),
+                    IRCompBinaryOperationKind::Mul => ("mul", false),
+                    IRCompBinaryOperationKind::Div => ("udiv", false),
+                    IRCompBinaryOperationKind::Mod => ("urem", false),
+                    IRCompBinaryOperationKind::BitAnd => ("and", false),
+                    IRCompBinaryOperationKind::BitOr => ("or", false),
+                    IRCompBinaryOperationKind::BitRShift => ("lshr", false),
+                    IRCompBinaryOperationKind::BitLShift => ("shl", false),
+                    IRCompBinaryOperationKind::Equal => ("icmp eq", true),
+                    IRCompBinaryOperationKind::NotEqual => ("icmp ne", true),
+                    IRCompBinaryOperationKind::Greater => ("icmp ugt", true),
+                    IRCompBinaryOperationKind::Lesser => ("icmp ult", true),
+                    IRCompBinaryOperationKind::GreaterEqual => ("icmp uge", true),
+                    IRCompBinaryOperationKind::LesserEqual => ("icmp ule", true),
+                    IRCompBinaryOperationKind::SignedDiv => ("sdiv", false),
+                    IRCompBinaryOperationKind::SignedMod => ("srem", false),
+                    IRCompBinaryOperationKind::SignedRShift => ("ashr", false),
+                    IRCompBinaryOperationKind::SignedGreater => ("icmp sgt", true),
+                    IRCompBinaryOperationKind::SignedLesser => ("icmp slt", true),
+                    IRCompBinaryOperationKind::SignedGreaterEqual => ("icmp sge", true),
+                    IRCompBinaryOperationKind::SignedLesserEqual => ("icmp sle", true),
+                    _ => unreachable!(),
+                };
+                let result = self.temporary();
+                self.line(&format!(
+                    "{} = {} {} {}, {}",
+                    result,
+                    instruction,
+                    llvm_type(size),
+                    left,
+                    right
+                ));
+                if comparison {
+                    let extended = self.temporary();
+                    self.line(&format!("{} = zext i1 {} to i8", extended, result));
+                    self.define(comp, extended, 1)?;
+                } else {
+                    self.define(comp, result, size)?;
+                }
+            }
+            IRCompKind::UnaryOperation(operation)
+                if matches!(operation.kind, IRCompUnaryOperationKind::FloatNegation) =>
+            {
+                let size = self.size_of(&operation.operand);
+                let operand = self.load_float(&operation.operand)?;
+                let result = self.temporary();
+                self.line(&format!(
+                    "{} = fneg {} {}",
+                    result,
+                    float_type(size).unwrap(),
+                    operand
+                ));
+                self.define_float(comp, result, size)?;
+            }
+            IRCompKind::UnaryOperation(operation) => {
+                let size = self.size_of(&operation.operand);
+                if !is_scalar(size) {
+                    return Err(self.error(&format!(
+                        "Arithmetic on values of {} bytes is not supported",
+                        size
+                    )));
+                }
+                let llvm_type = llvm_type(size);
+                let operand = self.load_value(&operation.operand);
+                let result = self.temporary();
+                match operation.kind {
+                    IRCompUnaryOperationKind::BoolNot => {
+                        let comparison = self.temporary();
+                        self.line(&format!(
+                            "{} = icmp eq {} {}, 0",
+                            comparison, llvm_type, operand
+                        ));
+                        self.line(&format!(
+                            "{} = zext i1 {} to {}",
+                            result, comparison, llvm_type
+                        ));
+                    }
+                    IRCompUnaryOperationKind::BitNot => {
+                        self.line(&format!("{} = xor {} {}, -1", result, llvm_type, operand));
+                    }
+                    IRCompUnaryOperationKind::SignedNegation => {
+                        self.line(&format!("{} = sub {} 0, {}", result, llvm_type, operand));
+                    }
+                    IRCompUnaryOperationKind::FloatNegation => unreachable!(),
+                }
+                self.define(comp, result, size)?;
+            }
+            IRCompKind::Conversion(conversion) => {
+                self.emit_conversion(comp, &conversion.kind, &conversion.operand, conversion.size)?;
+            }
+            IRCompKind::Constant(constant) => {
+                let size = constant.bytes.len() as u64;
+                if size > 0 {
+                    self.define(comp, constant_literal(&constant.bytes), size)?;
+                }
+            }
+            IRCompKind::Alloc(ir_type) => {
+                // Memory yielded by alloc is zeroed, like in the interpreter
+                self.line(&format!(
+                    "call void @llvm.memset.p0.i64(ptr align {} %alloc{}, i8 0, i64 {}, i1 false)",
+                    ir_type.align.max(1),
+                    index,
+                    ir_type.size.max(1)
+                ));
+                let pointer = self.temporary();
+                self.line(&format!(
+                    "{} = ptrtoint ptr %alloc{} to i64",
+                    pointer, index
+                ));
+                self.define(comp, pointer, 8)?;
+            }
+            IRCompKind::Store(ir_type, location, value) => {
+                self.emit_store(ir_type, location, value, 0)?;
+            }
+            IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                self.emit_store(ir_type, location, value, *offset)?;
+            }
+            IRCompKind::Load(ir_type, location) => {
+                self.emit_load(comp, ir_type, location, 0)?;
+            }
+            IRCompKind::OffsetLoad(ir_type, location, offset) => {
+                self.emit_load(comp, ir_type, location, *offset)?;
+            }
+            IRCompKind::Return(value) => {
+                let return_size = self.function_def.return_type.size;
+                if return_size == 0 {
+                    self.line("ret void");
+                } else {
+                    let operand = self.load_value(value);
+                    let size = self.size_of(value);
+                    let operand = self.convert(operand, size, return_size)?;
+                    self.line(&format!("ret {} {}", llvm_type(return_size), operand));
+                }
+                self.open = false;
+            }
+            IRCompKind::If(value, label) => {
+                let size = self.size_of(value);
+                if !is_scalar(size) {
+                    return Err(self.error(&format!("Cannot branch on a value of {} bytes", size)));
+                }
+                let operand = self.load_value(value);
+                let condition = self.temporary();
+                self.line(&format!(
+                    "{} = icmp ne {} {}, 0",
+                    condition,
+                    llvm_type(size),
+                    operand
+                ));
+                self.next_block += 1;
+                let fallthrough = format!("fallthrough{}", self.next_block);
+                self.line(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    condition,
+                    label_name(*label),
+                    fallthrough
+                ));
+                self.open = false;
+                self.start_block(&fallthrough);
+            }
+            IRCompKind::Jmp(label) => {
+                self.line(&format!("br label %{}", label_name(*label)));
+                self.open = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit(mut self) -> IRBackendResult<String> {
+        let function_def = self.function_def;
+        let params: Vec<_> = function_def
+            .params
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, ir_type))| ir_type.size > 0)
+            .map(|(index, (id, ir_type))| match id {
+                Some(id) => format!("{} %p.{}", llvm_type(ir_type.size), sanitize_symbol(*id)),
+                None => format!("{} %unused{}", llvm_type(ir_type.size), index),
+            })
+            .collect();
+        self.output += &format!(
+            "define {}{} {}({}) {{\n",
+            match function_def.visibility {
+                IRVisibility::Exported => "",
+                IRVisibility::Internal => "internal ",
+            },
+            llvm_type(function_def.return_type.size),
+            function_name(function_def.name),
+            params.join(", ")
+        );
+        self.output += "entry:\n";
+
+        // Every value lives in a stack slot, so values can be redefined and used across blocks
+        // without building SSA form here (mem2reg takes care of that)
+        let mut slots: Vec<_> = self
+            .types
+            .iter()
+            .filter(|(_, ir_type)| ir_type.size > 0)
+            .map(|(id, ir_type)| (slot_name(*id), ir_type.clone()))
+            .collect();
+        slots.sort_by(|a, b| a.0.cmp(&b.0));
+        for (slot, ir_type) in slots {
+            self.line(&format!("{} = alloca {}", slot, llvm_type(ir_type.size)));
+        }
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            if let IRCompKind::Alloc(ir_type) = &comp.kind {
+                self.line(&format!(
+                    "%alloc{} = alloca [{} x i8], align {}",
+                    index,
+                    ir_type.size.max(1),
+                    ir_type.align.max(1)
+                ));
+            }
+        }
+        for (id, ir_type) in &function_def.params {
+            if let Some(id) = id {
+                if ir_type.size > 0 {
+                    self.line(&format!(
+                        "store {} %p.{}, ptr {}",
+                        llvm_type(ir_type.size),
+                        sanitize_symbol(*id),
+                        slot_name(*id)
+                    ));
+                }
+            }
+        }
+        self.open = true;
+
+        let mut labels: Vec<_> = function_def.label_defs.iter().collect();
+        labels.sort_by_key(|(label, index)| (**index, symbol_str(**label)));
+        let mut labels = labels.into_iter().peekable();
+
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            while let Some((label, _)) = labels.next_if(|(_, i)| **i as usize == index) {
+                self.start_block(&label_name(*label));
+            }
+            self.emit_comp(index, comp)?;
+        }
+        for (label, _) in labels {
+            self.start_block(&label_name(*label));
+        }
+
+        // Falling off the end of a function returns nothing meaningful
+        if self.open {
+            if function_def.return_type.size == 0 {
+                self.line("ret void");
+            } else {
+                let line = format!(
+                    "ret {} zeroinitializer",
+                    llvm_type(function_def.return_type.size)
+                );
+                self.line(&line);
+            }
+        }
+        self.output += "}\n";
+
+        Ok(self.output)
+    }
+}
+
+/// Emits a textual LLVM IR module for a module
+///
+/// Values of 1, 2, 4 or 8 bytes become integers and other sizes become byte arrays. Every value
+/// is kept in an `alloca` slot, `Alloc` comps become `alloca`s in the entry block, and labels
+/// become basic blocks (`If` branches to its label or to a synthesized fallthrough block).
+/// Internal functions get internal linkage. Functions that are called but neither defined nor
+/// declared in the module are declared as external functions returning an `i64`, with parameter
+/// types taken from their call sites. Float params and results of declared externs are passed as
+/// `float` or `double`.
+pub fn llvm_ir_for_ir_module(module: &IRModule) -> IRBackendResult<String> {
+    let signatures = IRSignatures::for_module(module);
+    let mut externs = BTreeMap::new();
+    let mut intrinsics = BTreeSet::new();
+    for (name, (params, _)) in &signatures.externs {
+        let param_types = params
+            .iter()
+            .filter(|ir_type| ir_type.size > 0)
+            .map(extern_llvm_type)
+            .collect();
+        externs.insert(sanitize_symbol(*name), param_types);
+    }
+    let mut functions = String::new();
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                let emitter = FunctionEmitter {
+                    function_def,
+                    types: infer_value_types(function_def, &signatures)?,
+                    signatures: &signatures,
+                    externs: &mut externs,
+                    intrinsics: &mut intrinsics,
+                    output: String::new(),
+                    next_temporary: 0,
+                    next_block: 0,
+                    open: false,
+                };
+                functions += "\n";
+                functions += &emitter.emit()?;
+            }
+            IRItemKind::ExternDecl(_) => {}
+        }
+    }
+
+    let mut result = "; ModuleID = 'solidc'\nsource_filename = \"solidc\"\n\n".to_owned();
+    result += "declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)\n";
+    result += "declare void @llvm.memset.p0.i64(ptr, i8, i64, i1)\n";
+    for intrinsic in intrinsics {
+        result += &intrinsic;
+        result += "\n";
+    }
+    let return_types: HashMap<_, _> = signatures
+        .externs
+        .iter()
+        .map(|(name, (_, return_type))| (sanitize_symbol(*name), return_type))
+        .collect();
+    for (name, param_types) in externs {
+        let return_type = return_types
+            .get(&name)
+            .copied()
+            .unwrap_or(&EXTERN_RETURN_IR_TYPE);
+        result += &format!(
+            "declare {} @{}({})\n",
+            extern_llvm_type(return_type),
+            name,
+            param_types.join(", ")
+        );
+    }
+    result += &functions;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::SessionGlobals;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+
+    const MODULE: &str = "\
+extern sqrt: (8 8 float) -> (8 8 float)
+
+fn sum_to: %n := (8 8) -> (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %one := const 8 1 0 0 0 0 0 0 0
+    %total := alloc (16 8)
+    store (8 8) %total %zero
+    :loop
+    %done := binop == %n %zero
+    if %done end
+    %t := load (8 8) %total
+    %t := binop + %t %n
+    store (8 8) %total %t
+    offsetstore (8 8) %total %n 8
+    %n := binop - %n %one
+    jmp loop
+    :end
+    %result := load (8 8) %total
+    return %result
+endfn
+
+fn main: -> (8 8)
+    %n := const 8 4 0 0 0 0 0 0 0
+    %sum := call sum_to 1 %n
+    %f := convert utof 8 %sum
+    %root := call sqrt 1 %f
+    %half := const 8 0 0 0 0 0 0 224 63
+    %root := binop fmul %root %half
+    %r := convert ftou 8 %root
+    return %r
+endfn
+";
+
+    fn llvm_ir(src: &str) -> String {
+        llvm_ir_for_ir_module(&assemble_ir_module(src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn allocs_and_labels_become_allocas_and_blocks() {
+        SessionGlobals::create(|| {
+            let ir = llvm_ir(MODULE);
+            assert!(ir.contains("entry:\n  %v.done = alloca i8\n"));
+            assert!(ir.contains("  %alloc2 = alloca [16 x i8], align 8\n"));
+            assert!(ir.contains(
+                "  call void @llvm.memset.p0.i64(ptr align 8 %alloc2, i8 0, i64 16, i1 false)\n"
+            ));
+            assert!(ir.contains("  br label %l.loop\nl.loop:\n"));
+            assert!(ir.contains("  br i1 %t10, label %l.end, label %fallthrough1\nfallthrough1:\n"));
+            assert!(ir.contains("  %t22 = getelementptr i8, ptr %t21, i64 8\n"));
+        });
+    }
+
+    #[test]
+    fn float_operations_and_externs_use_llvm_floats() {
+        SessionGlobals::create(|| {
+            let ir = llvm_ir(MODULE);
+            assert!(ir.contains("declare double @sqrt(double)\n"));
+            assert!(ir.contains("  %t3 = uitofp i64 %t4 to double\n"));
+            assert!(ir.contains("  %t8 = call double @sqrt(double %t7)\n"));
+            assert!(ir.contains("  %t14 = fmul double %t11, %t13\n"));
+            assert!(ir.contains("declare i64 @llvm.fptoui.sat.i64.f64(double)\n"));
+            assert!(ir.contains("  %t16 = call i64 @llvm.fptoui.sat.i64.f64(double %t18)\n"));
+        });
+    }
+}
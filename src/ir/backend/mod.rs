@@ -0,0 +1,216 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::IRCompKind;
+use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue};
+use std::collections::HashMap;
+
+pub mod c;
+pub mod llvm;
+pub mod wat;
+pub mod x86_64;
+
+#[derive(Debug)]
+pub struct IRBackendError {
+    pub message: String,
+}
+
+pub type IRBackendResult<T> = Result<T, IRBackendError>;
+
+/// Type of the pointers yielded by `Alloc`
+pub const POINTER_IR_TYPE: IRType = IRType {
+    size: 8,
+    align: 8,
+    float: false,
+};
+
+/// Return type assumed for functions that are neither defined nor declared in the module
+pub const EXTERN_RETURN_IR_TYPE: IRType = IRType {
+    size: 8,
+    align: 8,
+    float: false,
+};
+
+/// Signatures of the functions defined and declared in a module
+pub struct IRSignatures {
+    pub functions: HashMap<Symbol, (Vec<IRType>, IRType)>,
+    pub externs: HashMap<Symbol, (Vec<IRType>, IRType)>,
+}
+
+impl IRSignatures {
+    pub fn for_module(module: &IRModule) -> Self {
+        let mut functions = HashMap::new();
+        let mut externs = HashMap::new();
+        for item in &module.items {
+            match &item.kind {
+                IRItemKind::FunctionDef(function_def) => {
+                    functions.insert(
+                        function_def.name,
+                        (
+                            function_def.params.iter().map(|p| p.1.clone()).collect(),
+                            function_def.return_type.clone(),
+                        ),
+                    );
+                }
+                IRItemKind::ExternDecl(extern_decl) => {
+                    externs.insert(
+                        extern_decl.name,
+                        (extern_decl.params.clone(), extern_decl.return_type.clone()),
+                    );
+                }
+            }
+        }
+        Self { functions, externs }
+    }
+
+    pub fn return_type(&self, name: Symbol) -> IRType {
+        self.functions
+            .get(&name)
+            .or_else(|| self.externs.get(&name))
+            .map(|signature| signature.1.clone())
+            .unwrap_or(EXTERN_RETURN_IR_TYPE)
+    }
+
+    /// Types of the params, None for functions that are neither defined nor declared
+    pub fn param_types(&self, name: Symbol) -> Option<&[IRType]> {
+        self.functions
+            .get(&name)
+            .or_else(|| self.externs.get(&name))
+            .map(|signature| signature.0.as_slice())
+    }
+}
+
+fn constant_ir_type(len: u64) -> IRType {
+    IRType {
+        size: len,
+        align: if len.is_power_of_two() && len <= 8 {
+            len
+        } else {
+            1
+        },
+        float: false,
+    }
+}
+
+/// Computes the type of every value of a function
+///
+/// The IR itself only stores types on memory operations, so the type of each value is derived
+/// from the comp defining it. Values can be used before the comp defining them (through jumps),
+/// so this iterates until every value has a type.
+pub fn infer_value_types(
+    function_def: &IRItemFunctionDef,
+    signatures: &IRSignatures,
+) -> IRBackendResult<HashMap<Symbol, IRType>> {
+    let mut types = HashMap::new();
+    for (id, ir_type) in &function_def.params {
+        if let Some(id) = id {
+            types.insert(*id, ir_type.clone());
+        }
+    }
+
+    loop {
+        let mut changed = false;
+        for comp in &function_def.comps {
+            let id = match comp.id {
+                Some(id) if !types.contains_key(&id) => id,
+                _ => continue,
+            };
+            let ir_type = match &comp.kind {
+                IRCompKind::FunctionCall(function_call) => {
+                    Some(signatures.return_type(function_call.name))
+                }
+                IRCompKind::BinaryOperation(operation) if operation.kind.is_comparison() => {
+                    Some(IRType {
+                        size: 1,
+                        align: 1,
+                        float: false,
+                    })
+                }
+                IRCompKind::BinaryOperation(operation) => {
+                    types.get(&operation.left_operand.id).cloned()
+                }
+                IRCompKind::UnaryOperation(operation) => types.get(&operation.operand.id).cloned(),
+                IRCompKind::Conversion(conversion) => Some(IRType {
+                    size: conversion.size,
+                    align: conversion.size,
+                    float: conversion.kind.is_to_float(),
+                }),
+                IRCompKind::Constant(constant) => {
+                    Some(constant_ir_type(constant.bytes.len() as u64))
+                }
+                IRCompKind::Alloc(_) => Some(POINTER_IR_TYPE),
+                IRCompKind::Load(ir_type, _) | IRCompKind::OffsetLoad(ir_type, _, _) => {
+                    Some(ir_type.clone())
+                }
+                IRCompKind::Store(..)
+                | IRCompKind::OffsetStore(..)
+                | IRCompKind::Return(_)
+                | IRCompKind::If(..)
+                | IRCompKind::Jmp(_) => Some(IRType {
+                    size: 0,
+                    align: 1,
+                    float: false,
+                }),
+            };
+            if let Some(ir_type) = ir_type {
+                types.insert(id, ir_type);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    for comp in &function_def.comps {
+        for value in used_values(&comp.kind) {
+            if !types.contains_key(&value.id) {
+                return Err(IRBackendError {
+                    message: format!(
+                        "Could not infer the type of %{} in function {}",
+                        symbol_str(value.id),
+                        symbol_str(function_def.name)
+                    ),
+                });
+            }
+        }
+    }
+
+    Ok(types)
+}
+
+/// Returns the values read by a comp
+pub fn used_values(kind: &IRCompKind) -> Vec<&IRValue> {
+    match kind {
+        IRCompKind::FunctionCall(function_call) => function_call.args.iter().collect(),
+        IRCompKind::BinaryOperation(operation) => {
+            vec![&operation.left_operand, &operation.right_operand]
+        }
+        IRCompKind::UnaryOperation(operation) => vec![&operation.operand],
+        IRCompKind::Conversion(conversion) => vec![&conversion.operand],
+        IRCompKind::Constant(_) | IRCompKind::Alloc(_) | IRCompKind::Jmp(_) => vec![],
+        IRCompKind::Store(_, location, value) | IRCompKind::OffsetStore(_, location, value, _) => {
+            vec![location, value]
+        }
+        IRCompKind::Load(_, location) | IRCompKind::OffsetLoad(_, location, _) => vec![location],
+        IRCompKind::Return(value) | IRCompKind::If(value, _) => vec![value],
+    }
+}
+
+pub fn symbol_str(symbol: Symbol) -> &'static str {
+    SessionGlobals::with_interner(|i| i.get(&symbol).unwrap())
+}
+
+/// Turns a symbol into a name made only of ASCII alphanumerics and underscores
+///
+/// Every other character is replaced by its hex code surrounded with underscores.
+pub fn sanitize_symbol(symbol: Symbol) -> String {
+    let mut result = String::new();
+    for c in symbol_str(symbol).chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            result.push(c);
+        } else {
+            result += &format!("_{:x}_", c as u32);
+        }
+    }
+    result
+}
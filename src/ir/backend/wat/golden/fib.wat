@@ -0,0 +1,132 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (func $fib (export "fib") (param $p0 i32) (result i32)
+    (local $v_a i32)
+    (local $v_b i32)
+    (local $v_c i32)
+    (local $v_comparison i32)
+    (local $v_constant_0 i32)
+    (local $v_constant_1 i32)
+    (local $v_i i32)
+    (local $v_iplusplus i32)
+    (local $v_iterations i32)
+    (local $v_iterator i64)
+    (local $v_result i32)
+    (local $v_sequence_numbers i64)
+    (local $frame i32)
+    (local $copy_source i32)
+    (local $next_block i32)
+    global.get $__stack_pointer
+    i32.const 16
+    i32.sub
+    local.tee $frame
+    global.set $__stack_pointer
+    local.get $p0
+    local.set $v_iterations
+    i32.const 1
+    local.set $v_constant_1
+    i32.const 0
+    local.set $v_constant_0
+    local.get $frame
+    i32.const 0
+    i32.add
+    local.tee $copy_source
+    i32.const 0
+    i32.const 2
+    memory.fill
+    local.get $copy_source
+    i64.extend_i32_u
+    local.set $v_sequence_numbers
+    local.get $frame
+    i32.const 2
+    i32.add
+    local.tee $copy_source
+    i32.const 0
+    i32.const 1
+    memory.fill
+    local.get $copy_source
+    i64.extend_i32_u
+    local.set $v_iterator
+    local.get $v_sequence_numbers
+    i32.wrap_i64
+    i32.const 1
+    i32.add
+    local.get $v_constant_1
+    i32.store8
+    local.get $v_sequence_numbers
+    i32.wrap_i64
+    local.get $v_constant_0
+    i32.store8
+    local.get $v_iterator
+    i32.wrap_i64
+    local.get $v_constant_0
+    i32.store8
+    block $b3
+      loop $l1
+        local.get $v_iterator
+        i32.wrap_i64
+        i32.load8_u
+        local.set $v_i
+        local.get $v_i
+        local.get $v_iterations
+        i32.ge_u
+        i32.const 255
+        i32.and
+        local.set $v_comparison
+        local.get $v_comparison
+        br_if $b3
+        local.get $v_sequence_numbers
+        i32.wrap_i64
+        i32.load8_u
+        local.set $v_a
+        local.get $v_sequence_numbers
+        i32.wrap_i64
+        i32.load8_u offset=1
+        local.set $v_b
+        local.get $v_a
+        local.get $v_b
+        i32.add
+        i32.const 255
+        i32.and
+        local.set $v_c
+        local.get $v_sequence_numbers
+        i32.wrap_i64
+        local.get $v_b
+        i32.store8
+        local.get $v_sequence_numbers
+        i32.wrap_i64
+        i32.const 1
+        i32.add
+        local.get $v_c
+        i32.store8
+        local.get $v_i
+        local.get $v_constant_1
+        i32.add
+        i32.const 255
+        i32.and
+        local.set $v_iplusplus
+        local.get $v_iterator
+        i32.wrap_i64
+        local.get $v_iplusplus
+        i32.store8
+        br $l1
+      end
+    end
+    local.get $v_sequence_numbers
+    i32.wrap_i64
+    i32.load8_u offset=1
+    local.set $v_result
+    local.get $v_result
+    local.get $frame
+    i32.const 16
+    i32.add
+    global.set $__stack_pointer
+    return
+    local.get $frame
+    i32.const 16
+    i32.add
+    global.set $__stack_pointer
+    i32.const 0
+  )
+)
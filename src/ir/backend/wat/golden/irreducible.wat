@@ -0,0 +1,76 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (func $count (export "count") (param $p0 i32) (result i32)
+    (local $v_done i32)
+    (local $v_n i32)
+    (local $v_one i32)
+    (local $v_skip i32)
+    (local $v_ten i32)
+    (local $frame i32)
+    (local $copy_source i32)
+    (local $next_block i32)
+    global.get $__stack_pointer
+    local.set $frame
+    local.get $p0
+    local.set $v_skip
+    loop $dispatch
+      block $d5
+        block $d4
+          block $d3
+            block $d2
+              block $d1
+                block $d0
+                  local.get $next_block
+                  br_table $d0 $d1 $d2 $d3 $d4 $d5
+                end
+                i32.const 0
+                local.set $v_n
+                i32.const 1
+                local.set $v_one
+                i32.const 10
+                local.set $v_ten
+                local.get $v_skip
+                if
+                  i32.const 2
+                  local.set $next_block
+                  br $dispatch
+                end
+              end
+              local.get $v_n
+              local.get $v_one
+              i32.add
+              i32.const 255
+              i32.and
+              local.set $v_n
+            end
+            local.get $v_n
+            local.get $v_one
+            i32.add
+            i32.const 255
+            i32.and
+            local.set $v_n
+            local.get $v_n
+            local.get $v_ten
+            i32.ge_u
+            i32.const 255
+            i32.and
+            local.set $v_done
+            local.get $v_done
+            if
+              i32.const 4
+              local.set $next_block
+              br $dispatch
+            end
+          end
+          i32.const 1
+          local.set $next_block
+          br $dispatch
+        end
+        local.get $v_n
+        return
+      end
+    end
+    i32.const 0
+  )
+)
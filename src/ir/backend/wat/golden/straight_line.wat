@@ -0,0 +1,33 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (func $add_scaled (export "add_scaled") (param $p0 i32) (param $p1 i32) (result i32)
+    (local $v_a i32)
+    (local $v_b i32)
+    (local $v_scaled i32)
+    (local $v_sum i32)
+    (local $v_two i32)
+    (local $frame i32)
+    (local $copy_source i32)
+    (local $next_block i32)
+    global.get $__stack_pointer
+    local.set $frame
+    local.get $p0
+    local.set $v_a
+    local.get $p1
+    local.set $v_b
+    i32.const 2
+    local.set $v_two
+    local.get $v_b
+    local.get $v_two
+    i32.mul
+    local.set $v_scaled
+    local.get $v_a
+    local.get $v_scaled
+    i32.add
+    local.set $v_sum
+    local.get $v_sum
+    return
+    i32.const 0
+  )
+)
@@ -0,0 +1,1197 @@
+use crate::globals::Symbol;
+use crate::ir::backend::{
+    infer_value_types, sanitize_symbol, symbol_str, IRBackendError, IRBackendResult, IRSignatures,
+    EXTERN_RETURN_IR_TYPE,
+};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperationKind, IRCompConversionKind, IRCompKind, IRCompUnaryOperationKind,
+};
+use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue, IRVisibility};
+use std::collections::{BTreeMap, HashMap};
+
+/// Size of the linear memory, in 64KiB pages
+const MEMORY_PAGES: u64 = 16;
+
+/// The shadow stack starts at the end of the linear memory and grows downwards
+const STACK_TOP: u64 = MEMORY_PAGES * 65536;
+
+/// How a value is represented in wasm
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Repr {
+    /// Zero sized values have no representation
+    Nothing,
+    /// Values of 1, 2 or 4 bytes, zero extended
+    I32,
+    /// Values of 8 bytes (including pointers)
+    I64,
+    /// Every other size lives in the shadow stack and is represented by its address
+    Memory,
+}
+
+fn repr(ir_type: &IRType) -> Repr {
+    match ir_type.size {
+        0 => Repr::Nothing,
+        1 | 2 | 4 => Repr::I32,
+        8 => Repr::I64,
+        _ => Repr::Memory,
+    }
+}
+
+fn wasm_type(repr: Repr) -> Option<&'static str> {
+    match repr {
+        Repr::Nothing => None,
+        Repr::I32 | Repr::Memory => Some("i32"),
+        Repr::I64 => Some("i64"),
+    }
+}
+
+/// Wasm type of the floats of the given size and of the integers holding their bits
+fn float_types(size: u64) -> Option<(&'static str, &'static str)> {
+    match size {
+        4 => Some(("f32", "i32")),
+        8 => Some(("f64", "i64")),
+        _ => None,
+    }
+}
+
+/// Float and integer wasm types of a float param or result of an external function
+fn extern_float_types(ir_type: &IRType) -> Option<(&'static str, &'static str)> {
+    float_types(ir_type.size).filter(|_| ir_type.float)
+}
+
+/// Wasm type of the params and results of external functions, which take floats as floats
+fn extern_wasm_type(ir_type: &IRType) -> Option<&'static str> {
+    match extern_float_types(ir_type) {
+        Some((float_type, _)) => Some(float_type),
+        None => wasm_type(repr(ir_type)),
+    }
+}
+
+fn round_up(value: u64, to: u64) -> u64 {
+    value.div_ceil(to) * to
+}
+
+fn function_name(name: Symbol) -> String {
+    format!("${}", sanitize_symbol(name))
+}
+
+fn value_local(id: Symbol) -> String {
+    format!("$v_{}", sanitize_symbol(id))
+}
+
+/// A basic block of a function: a range of comps only entered through its first comp
+#[derive(Debug)]
+struct BasicBlock {
+    start: usize,
+    end: usize,
+    /// Block reached when the last comp of the block doesn't jump
+    fallthrough: Option<usize>,
+    /// Blocks reached through jumps
+    jumps: Vec<usize>,
+}
+
+fn basic_blocks(function_def: &IRItemFunctionDef) -> Vec<BasicBlock> {
+    let comps = &function_def.comps;
+    let mut leaders = vec![0];
+    leaders.extend(
+        function_def
+            .label_defs
+            .values()
+            .map(|index| *index as usize),
+    );
+    for (index, comp) in comps.iter().enumerate() {
+        if matches!(
+            comp.kind,
+            IRCompKind::If(..) | IRCompKind::Jmp(_) | IRCompKind::Return(_)
+        ) {
+            leaders.push(index + 1);
+        }
+    }
+    leaders.retain(|leader| *leader < comps.len());
+    leaders.sort_unstable();
+    leaders.dedup();
+
+    let block_of = |index: usize| leaders.iter().position(|leader| *leader == index);
+    let mut label_blocks = HashMap::new();
+    for (label, index) in &function_def.label_defs {
+        // Labels placed after the last comp target a virtual block past the end
+        let block = block_of(*index as usize).unwrap_or(leaders.len());
+        label_blocks.insert(*label, block);
+    }
+
+    let mut blocks = vec![];
+    for (block_index, start) in leaders.iter().enumerate() {
+        let end = leaders.get(block_index + 1).copied().unwrap_or(comps.len());
+        let next = if block_index + 1 < leaders.len() {
+            Some(block_index + 1)
+        } else {
+            None
+        };
+        let (fallthrough, jumps) = match &comps[end - 1].kind {
+            IRCompKind::If(_, label) => (next, vec![label_blocks[label]]),
+            IRCompKind::Jmp(label) => (None, vec![label_blocks[label]]),
+            IRCompKind::Return(_) => (None, vec![]),
+            _ => (next, vec![]),
+        };
+        blocks.push(BasicBlock {
+            start: *start,
+            end,
+            fallthrough,
+            jumps,
+        });
+    }
+
+    blocks
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ScopeKind {
+    /// `block` scope, branching to it jumps to its end
+    Block,
+    /// `loop` scope, branching to it jumps to its start
+    Loop,
+}
+
+/// A wasm scope covering the basic blocks `start..end`, targeted by jumps to `target`
+#[derive(Debug, Copy, Clone)]
+struct Scope {
+    kind: ScopeKind,
+    start: usize,
+    end: usize,
+    target: usize,
+}
+
+/// Places wasm `block` and `loop` scopes so every jump between basic blocks becomes a `br`
+///
+/// This is a simple stackifier working on the original order of the blocks: every backward
+/// jump becomes a `loop` spanning from its target to the last block jumping back to it, and
+/// every forward jump becomes a `block` ending right before its target. Block starts are moved
+/// backwards until all scopes are properly nested. Returns `None` if the control flow cannot be
+/// expressed that way (irreducible control flow, or overlapping loops).
+fn stackify(blocks: &[BasicBlock]) -> Option<Vec<Scope>> {
+    let mut loops: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut forward: BTreeMap<usize, usize> = BTreeMap::new();
+    for (index, block) in blocks.iter().enumerate() {
+        for target in &block.jumps {
+            if *target <= index {
+                let end = loops.entry(*target).or_insert(index + 1);
+                *end = (*end).max(index + 1);
+            } else if *target != index + 1 || block.fallthrough.is_some() {
+                let start = forward.entry(*target).or_insert(index);
+                *start = (*start).min(index);
+            }
+        }
+    }
+
+    let loops: Vec<_> = loops
+        .into_iter()
+        .map(|(target, end)| Scope {
+            kind: ScopeKind::Loop,
+            start: target,
+            end,
+            target,
+        })
+        .collect();
+
+    for a in &loops {
+        for b in &loops {
+            if a.start < b.start && b.start < a.end && a.end < b.end {
+                return None;
+            }
+        }
+    }
+
+    let mut scopes: Vec<_> = forward
+        .into_iter()
+        .map(|(target, start)| Scope {
+            kind: ScopeKind::Block,
+            start,
+            end: target,
+            target,
+        })
+        .collect();
+
+    // Jumping into a loop from outside of it (other than to its header) is irreducible
+    for scope in &scopes {
+        for l in &loops {
+            if scope.start < l.start && l.start < scope.end && scope.end < l.end {
+                return None;
+            }
+        }
+    }
+
+    loop {
+        let mut changed = false;
+        for i in 0..scopes.len() {
+            let mut start = scopes[i].start;
+            let end = scopes[i].end;
+            for other in loops.iter().chain(scopes.iter()) {
+                // A scope starting inside of another one has to end inside of it too, otherwise
+                // it is extended to contain it
+                if other.start < start && start < other.end && other.end < end {
+                    start = other.start;
+                }
+            }
+            if start != scopes[i].start {
+                scopes[i].start = start;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    scopes.extend(loops);
+    for a in &scopes {
+        for b in &scopes {
+            if a.start < b.start && b.start < a.end && a.end < b.end {
+                return None;
+            }
+        }
+    }
+
+    Some(scopes)
+}
+
+struct FunctionEmitter<'a> {
+    function_def: &'a IRItemFunctionDef,
+    signatures: &'a IRSignatures,
+    types: HashMap<Symbol, IRType>,
+    /// Offsets (relative to the frame pointer) of the shadow stack memory of each value
+    /// represented in memory
+    memory_slots: Vec<(Symbol, u64)>,
+    /// Offsets (relative to the frame pointer) of the memory reserved by each `Alloc` comp
+    allocs: HashMap<usize, u64>,
+    frame_size: u64,
+    output: String,
+    depth: usize,
+}
+
+impl<'a> FunctionEmitter<'a> {
+    fn new(
+        function_def: &'a IRItemFunctionDef,
+        signatures: &'a IRSignatures,
+    ) -> IRBackendResult<Self> {
+        let types = infer_value_types(function_def, signatures)?;
+        let mut emitter = Self {
+            function_def,
+            signatures,
+            types,
+            memory_slots: vec![],
+            allocs: HashMap::new(),
+            frame_size: 0,
+            output: String::new(),
+            depth: 2,
+        };
+
+        let mut memory_values: Vec<_> = emitter
+            .types
+            .iter()
+            .filter(|(_, ir_type)| repr(ir_type) == Repr::Memory)
+            .map(|(id, ir_type)| (*id, ir_type.size))
+            .collect();
+        // Keep the layout deterministic
+        memory_values.sort_by_key(|(id, _)| symbol_str(*id));
+        for (id, size) in memory_values {
+            let offset = emitter.reserve(size, 8);
+            emitter.memory_slots.push((id, offset));
+        }
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            if let IRCompKind::Alloc(ir_type) = &comp.kind {
+                let offset = emitter.reserve(ir_type.size, ir_type.align);
+                emitter.allocs.insert(index, offset);
+            }
+        }
+        emitter.frame_size = round_up(emitter.frame_size, 16);
+
+        Ok(emitter)
+    }
+
+    fn reserve(&mut self, size: u64, align: u64) -> u64 {
+        let offset = round_up(self.frame_size, align.clamp(1, 16));
+        self.frame_size = offset + size;
+        offset
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.output += "  ";
+        }
+        self.output += line;
+        self.output += "\n";
+    }
+
+    fn repr_of(&self, value: &IRValue) -> Repr {
+        repr(&self.types[&value.id])
+    }
+
+    fn get(&mut self, value: &IRValue) {
+        if self.repr_of(value) != Repr::Nothing {
+            self.line(&format!("local.get {}", value_local(value.id)));
+        }
+    }
+
+    fn set(&mut self, id: Option<Symbol>, repr: Repr) {
+        match (id, repr) {
+            (_, Repr::Nothing) => {}
+            (Some(id), _) if self.types[&id].size > 0 => {
+                self.line(&format!("local.set {}", value_local(id)))
+            }
+            _ => self.line("drop"),
+        }
+    }
+
+    /// Pushes the address stored in a pointer value
+    fn address(&mut self, location: &IRValue) {
+        self.get(location);
+        if self.repr_of(location) == Repr::I64 {
+            self.line("i32.wrap_i64");
+        }
+    }
+
+    fn unsupported(&self, message: &str) -> IRBackendError {
+        IRBackendError {
+            message: format!(
+                "{} (in function {})",
+                message,
+                symbol_str(self.function_def.name)
+            ),
+        }
+    }
+
+    /// Pushes the float held by a value
+    fn get_float(&mut self, value: &IRValue) -> IRBackendResult<(&'static str, &'static str)> {
+        let size = self.types[&value.id].size;
+        let (float_type, integer_type) = float_types(size).ok_or_else(|| {
+            self.unsupported(&format!(
+                "Float operations on values of {} bytes are not supported",
+                size
+            ))
+        })?;
+        self.get(value);
+        self.line(&format!("{}.reinterpret_{}", float_type, integer_type));
+        Ok((float_type, integer_type))
+    }
+
+    fn emit_conversion(
+        &mut self,
+        id: Option<Symbol>,
+        kind: &IRCompConversionKind,
+        operand: &IRValue,
+        to: u64,
+    ) -> IRBackendResult<()> {
+        let from = self.types[&operand.id].size;
+        let unsupported = |emitter: &Self| {
+            emitter.unsupported(&format!(
+                "Cannot convert a value of {} bytes to {} bytes",
+                from, to
+            ))
+        };
+        match kind {
+            IRCompConversionKind::UnsignedToFloat | IRCompConversionKind::SignedToFloat => {
+                let (float_type, integer_type) =
+                    float_types(to).ok_or_else(|| unsupported(self))?;
+                let signed = matches!(kind, IRCompConversionKind::SignedToFloat);
+                let operand_type = match self.repr_of(operand) {
+                    Repr::I32 => "i32",
+                    Repr::I64 => "i64",
+                    _ => return Err(unsupported(self)),
+                };
+                self.get(operand);
+                // Values of 1 and 2 bytes are zero extended
+                match (signed, from) {
+                    (true, 1) => self.line("i32.extend8_s"),
+                    (true, 2) => self.line("i32.extend16_s"),
+                    _ => {}
+                }
+                self.line(&format!(
+                    "{}.convert_{}_{}",
+                    float_type,
+                    operand_type,
+                    if signed { "s" } else { "u" }
+                ));
+                self.line(&format!("{}.reinterpret_{}", integer_type, float_type));
+                self.set(
+                    id,
+                    repr(&IRType {
+                        size: to,
+                        align: to,
+                        float: true,
+                    }),
+                );
+            }
+            IRCompConversionKind::FloatToUnsigned | IRCompConversionKind::FloatToSigned => {
+                let result_repr = repr(&IRType {
+                    size: to,
+                    align: to,
+                    float: false,
+                });
+                let result_type = match result_repr {
+                    Repr::I32 if to != 0 => "i32",
+                    Repr::I64 => "i64",
+                    _ => return Err(unsupported(self)),
+                };
+                let (float_type, _) = self.get_float(operand)?;
+                // Saturating, like the interpreter
+                self.line(&format!(
+                    "{}.trunc_sat_{}_{}",
+                    result_type,
+                    float_type,
+                    match kind {
+                        IRCompConversionKind::FloatToSigned => "s",
+                        _ => "u",
+                    }
+                ));
+                self.truncate(to);
+                self.set(id, result_repr);
+            }
+            IRCompConversionKind::FloatToFloat => {
+                let (to_type, integer_type) = float_types(to).ok_or_else(|| unsupported(self))?;
+                let (from_type, _) = self.get_float(operand)?;
+                match (from_type, to_type) {
+                    ("f32", "f64") => self.line("f64.promote_f32"),
+                    ("f64", "f32") => self.line("f32.demote_f64"),
+                    _ => {}
+                }
+                self.line(&format!("{}.reinterpret_{}", integer_type, to_type));
+                self.set(
+                    id,
+                    repr(&IRType {
+                        size: to,
+                        align: to,
+                        float: true,
+                    }),
+                );
+            }
+            IRCompConversionKind::Truncate
+            | IRCompConversionKind::ZeroExtend
+            | IRCompConversionKind::SignExtend => {
+                let signed = matches!(kind, IRCompConversionKind::SignExtend);
+                let from_repr = self.repr_of(operand);
+                let result_repr = repr(&IRType {
+                    size: to,
+                    align: to,
+                    float: false,
+                });
+                self.get(operand);
+                match (from_repr, result_repr) {
+                    (Repr::I32, Repr::I32) | (Repr::I32, Repr::I64) => {
+                        match (signed, from) {
+                            (true, 1) => self.line("i32.extend8_s"),
+                            (true, 2) => self.line("i32.extend16_s"),
+                            _ => {}
+                        }
+                        if result_repr == Repr::I64 {
+                            let sign = if signed { "s" } else { "u" };
+                            self.line(&format!("i64.extend_i32_{}", sign));
+                        } else {
+                            self.truncate(to);
+                        }
+                    }
+                    (Repr::I64, Repr::I32) => {
+                        self.line("i32.wrap_i64");
+                        self.truncate(to);
+                    }
+                    (Repr::I64, Repr::I64) => {}
+                    _ => return Err(unsupported(self)),
+                }
+                self.set(id, result_repr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Masks the i32 on the stack to the given size
+    fn truncate(&mut self, size: u64) {
+        let mask = match size {
+            1 => 0xff,
+            2 => 0xffff,
+            _ => return,
+        };
+        self.line(&format!("i32.const {}", mask));
+        self.line("i32.and");
+    }
+
+    fn emit_comp(&mut self, index: usize, comp: &IRComp) -> IRBackendResult<()> {
+        match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => {
+                // Floats are reinterpreted from and to their bits around calls to external functions
+                let signature = self.signatures.externs.get(&function_call.name);
+                for (index, arg) in function_call.args.iter().enumerate() {
+                    self.get(arg);
+                    let param = signature.and_then(|(params, _)| params.get(index));
+                    if let Some((float_type, integer_type)) = param.and_then(extern_float_types) {
+                        self.line(&format!("{}.reinterpret_{}", float_type, integer_type));
+                    }
+                }
+                self.line(&format!("call {}", function_name(function_call.name)));
+                let return_type = self.signatures.return_type(function_call.name);
+                if let Some((float_type, integer_type)) =
+                    signature.and_then(|_| extern_float_types(&return_type))
+                {
+                    self.line(&format!("{}.reinterpret_{}", integer_type, float_type));
+                }
+                match (comp.id, repr(&return_type)) {
+                    (Some(id), Repr::Memory) => {
+                        // The callee frame is released, so the result is copied right away
+                        let slot = format!("local.get {}", value_local(id));
+                        self.line("local.set $copy_source");
+                        self.line(&slot);
+                        self.line("local.get $copy_source");
+                        self.line(&format!("i32.const {}", return_type.size));
+                        self.line("memory.copy");
+                    }
+                    (id, repr) => self.set(id, repr),
+                }
+            }
+            IRCompKind::BinaryOperation(operation) if operation.kind.is_float() => {
+                let (float_type, integer_type) = self.get_float(&operation.left_operand)?;
+                self.get_float(&operation.right_operand)?;
+                let instruction = match operation.kind {
+                    IRCompBinaryOperationKind::FloatPlus => "add",
+                    IRCompBinaryOperationKind::FloatMinus => "sub",
+                    IRCompBinaryOperationKind::FloatMul => "mul",
+                    IRCompBinaryOperationKind::FloatDiv => "div",
+                    IRCompBinaryOperationKind::FloatEqual => "eq",
+                    IRCompBinaryOperationKind::FloatNotEqual => "ne",
+                    IRCompBinaryOperationKind::FloatGreater => "gt",
+                    IRCompBinaryOperationKind::FloatLesser => "lt",
+                    IRCompBinaryOperationKind::FloatGreaterEqual => "ge",
+                    IRCompBinaryOperationKind::FloatLesserEqual => "le",
+                    _ => unreachable!(),
+                };
+                self.line(&format!("{}.{}", float_type, instruction));
+                if operation.kind.is_comparison() {
+                    self.set(comp.id, Repr::I32);
+                } else {
+                    self.line(&format!("{}.reinterpret_{}", integer_type, float_type));
+                    self.set(comp.id, self.repr_of(&operation.left_operand));
+                }
+            }
+            IRCompKind::BinaryOperation(operation) => {
+                let size = self.types[&operation.left_operand.id].size;
+                let (prefix, operand_repr) = match repr(&self.types[&operation.left_operand.id]) {
+                    Repr::I32 => ("i32", Repr::I32),
+                    Repr::I64 => ("i64", Repr::I64),
+                    _ => {
+                        return Err(self.unsupported(&format!(
+                            "Arithmetic on values of {} bytes is not supported",
+                            size
+                        )))
+                    }
+                };
+                // Small values are kept zero extended, signed operations need them sign extended
+                let sign_extend = match (operation.kind.is_signed(), size) {
+                    (true, 1) => Some("i32.extend8_s"),
+                    (true, 2) => Some("i32.extend16_s"),
+                    _ => None,
+                };
+                self.get(&operation.left_operand);
+                if let Some(sign_extend) = sign_extend {
+                    self.line(sign_extend);
+                }
+                self.get(&operation.right_operand);
+                if let (Some(sign_extend), false) = (
+                    sign_extend,
+                    matches!(operation.kind, IRCompBinaryOperationKind::SignedRShift),
+                ) {
+                    self.line(sign_extend);
+                }
+                // Shifts can have operands of different sizes
+                match (operand_repr, self.repr_of(&operation.right_operand)) {
+                    (Repr::I32, Repr::I64) => self.line("i32.wrap_i64"),
+                    (Repr::I64, Repr::I32) => self.line("i64.extend_i32_u"),
+                    _ => {}
+                }
+                let (instruction, result_size) = match operation.kind {
+                    IRCompBinaryOperationKind::Plus => ("add", size),
+                    IRCompBinaryOperationKind::Minus => ("sub", size),
+                    IRCompBinaryOperationKind::Mul => ("mul", size),
+                    IRCompBinaryOperationKind::Div => ("div_u", size),
+                    IRCompBinaryOperationKind::Mod => ("rem_u", size),
+                    IRCompBinaryOperationKind::BitAnd => ("and", size),
+                    IRCompBinaryOperationKind::BitOr => ("or", size),
+                    IRCompBinaryOperationKind::BitRShift => ("shr_u", size),
+                    IRCompBinaryOperationKind::BitLShift => ("shl", size),
+                    IRCompBinaryOperationKind::Equal => ("eq", 1),
+                    IRCompBinaryOperationKind::NotEqual => ("ne", 1),
+                    IRCompBinaryOperationKind::Greater => ("gt_u", 1),
+                    IRCompBinaryOperationKind::Lesser => ("lt_u", 1),
+                    IRCompBinaryOperationKind::GreaterEqual => ("ge_u", 1),
+                    IRCompBinaryOperationKind::LesserEqual => ("le_u", 1),
+                    IRCompBinaryOperationKind::SignedDiv => ("div_s", size),
+                    IRCompBinaryOperationKind::SignedMod => ("rem_s", size),
+                    IRCompBinaryOperationKind::SignedRShift => ("shr_s", size),
+                    IRCompBinaryOperationKind::SignedGreater => ("gt_s", 1),
+                    IRCompBinaryOperationKind::SignedLesser => ("lt_s", 1),
+                    IRCompBinaryOperationKind::SignedGreaterEqual => ("ge_s", 1),
+                    IRCompBinaryOperationKind::SignedLesserEqual => ("le_s", 1),
+                    _ => unreachable!(),
+                };
+                self.line(&format!("{}.{}", prefix, instruction));
+                self.truncate(result_size);
+                let result_repr = if result_size == 1 {
+                    Repr::I32
+                } else {
+                    operand_repr
+                };
+                self.set(comp.id, result_repr);
+            }
+            IRCompKind::UnaryOperation(operation) => {
+                let size = self.types[&operation.operand.id].size;
+                let prefix = match self.repr_of(&operation.operand) {
+                    Repr::I32 => "i32",
+                    Repr::I64 => "i64",
+                    _ => {
+                        return Err(self.unsupported(&format!(
+                            "Arithmetic on values of {} bytes is not supported",
+                            size
+                        )))
+                    }
+                };
+                match operation.kind {
+                    IRCompUnaryOperationKind::BoolNot => {
+                        self.get(&operation.operand);
+                        self.line(&format!("{}.eqz", prefix));
+                        self.set(comp.id, Repr::I32);
+                    }
+                    IRCompUnaryOperationKind::BitNot => {
+                        self.get(&operation.operand);
+                        self.line(&format!("{}.const -1", prefix));
+                        self.line(&format!("{}.xor", prefix));
+                        self.truncate(size);
+                        self.set(comp.id, self.repr_of(&operation.operand));
+                    }
+                    IRCompUnaryOperationKind::SignedNegation => {
+                        self.line(&format!("{}.const 0", prefix));
+                        self.get(&operation.operand);
+                        self.line(&format!("{}.sub", prefix));
+                        self.truncate(size);
+                        self.set(comp.id, self.repr_of(&operation.operand));
+                    }
+                    IRCompUnaryOperationKind::FloatNegation => {
+                        let (float_type, integer_type) = self.get_float(&operation.operand)?;
+                        self.line(&format!("{}.neg", float_type));
+                        self.line(&format!("{}.reinterpret_{}", integer_type, float_type));
+                        self.set(comp.id, self.repr_of(&operation.operand));
+                    }
+                }
+            }
+            IRCompKind::Conversion(conversion) => {
+                self.emit_conversion(
+                    comp.id,
+                    &conversion.kind,
+                    &conversion.operand,
+                    conversion.size,
+                )?;
+            }
+            IRCompKind::Constant(constant) => {
+                let id = match comp.id {
+                    Some(id) => id,
+                    None => return Ok(()),
+                };
+                let mut qword = [0u8; 8];
+                match repr(&self.types[&id]) {
+                    Repr::Nothing => {}
+                    Repr::I32 => {
+                        qword[..constant.bytes.len()].copy_from_slice(&constant.bytes);
+                        let value = u64::from_le_bytes(qword) as u32 as i32;
+                        self.line(&format!("i32.const {}", value));
+                        self.set(Some(id), Repr::I32);
+                    }
+                    Repr::I64 => {
+                        qword.copy_from_slice(&constant.bytes);
+                        self.line(&format!("i64.const {}", i64::from_le_bytes(qword)));
+                        self.set(Some(id), Repr::I64);
+                    }
+                    Repr::Memory => {
+                        for (offset, byte) in constant.bytes.iter().enumerate() {
+                            self.line(&format!("local.get {}", value_local(id)));
+                            self.line(&format!("i32.const {}", byte));
+                            self.line(&format!("i32.store8 offset={}", offset));
+                        }
+                    }
+                }
+            }
+            IRCompKind::Alloc(ir_type) => {
+                // Memory yielded by alloc is zeroed, like in the interpreter
+                let offset = self.allocs[&index];
+                self.line("local.get $frame");
+                self.line(&format!("i32.const {}", offset));
+                self.line("i32.add");
+                self.line("local.tee $copy_source");
+                self.line("i32.const 0");
+                self.line(&format!("i32.const {}", ir_type.size));
+                self.line("memory.fill");
+                self.line("local.get $copy_source");
+                self.line("i64.extend_i32_u");
+                self.set(comp.id, Repr::I64);
+            }
+            IRCompKind::Store(ir_type, location, value) => {
+                self.emit_store(ir_type, location, value, 0)?;
+            }
+            IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                self.emit_store(ir_type, location, value, *offset)?;
+            }
+            IRCompKind::Load(ir_type, location) => {
+                self.emit_load(comp, ir_type, location, 0);
+            }
+            IRCompKind::OffsetLoad(ir_type, location, offset) => {
+                self.emit_load(comp, ir_type, location, *offset);
+            }
+            IRCompKind::Return(value) => {
+                if repr(&self.function_def.return_type) != Repr::Nothing {
+                    self.get(value);
+                }
+                self.emit_release_frame();
+                self.line("return");
+            }
+            IRCompKind::If(value, _) => {
+                self.get(value);
+                if self.repr_of(value) == Repr::I64 {
+                    self.line("i32.wrap_i64");
+                }
+                // The branch itself is emitted by the caller
+            }
+            IRCompKind::Jmp(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn emit_store(
+        &mut self,
+        ir_type: &IRType,
+        location: &IRValue,
+        value: &IRValue,
+        offset: u64,
+    ) -> IRBackendResult<()> {
+        let value_repr = self.repr_of(value);
+        self.address(location);
+        if offset > 0 {
+            self.line(&format!("i32.const {}", offset));
+            self.line("i32.add");
+        }
+        match (ir_type.size, value_repr) {
+            (0, _) => self.line("drop"),
+            (_, Repr::Memory) => {
+                self.get(value);
+                self.line(&format!("i32.const {}", ir_type.size));
+                self.line("memory.copy");
+            }
+            (1 | 2 | 4, Repr::I32 | Repr::I64) => {
+                self.get(value);
+                if value_repr == Repr::I64 {
+                    self.line("i32.wrap_i64");
+                }
+                let instruction = match ir_type.size {
+                    1 => "i32.store8",
+                    2 => "i32.store16",
+                    _ => "i32.store",
+                };
+                self.line(instruction);
+            }
+            (8, Repr::I32 | Repr::I64) => {
+                self.get(value);
+                if value_repr == Repr::I32 {
+                    self.line("i64.extend_i32_u");
+                }
+                self.line("i64.store");
+            }
+            (size, _) => {
+                return Err(self.unsupported(&format!(
+                    "Storing {} bytes from a value of {} bytes is not supported",
+                    size, self.types[&value.id].size
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_load(&mut self, comp: &IRComp, ir_type: &IRType, location: &IRValue, offset: u64) {
+        let id = match comp.id {
+            Some(id) => id,
+            None => return,
+        };
+        match repr(ir_type) {
+            Repr::Nothing => {}
+            Repr::Memory => {
+                self.line(&format!("local.get {}", value_local(id)));
+                self.address(location);
+                if offset > 0 {
+                    self.line(&format!("i32.const {}", offset));
+                    self.line("i32.add");
+                }
+                self.line(&format!("i32.const {}", ir_type.size));
+                self.line("memory.copy");
+            }
+            repr => {
+                self.address(location);
+                let instruction = match ir_type.size {
+                    1 => "i32.load8_u",
+                    2 => "i32.load16_u",
+                    4 => "i32.load",
+                    _ => "i64.load",
+                };
+                if offset > 0 {
+                    self.line(&format!("{} offset={}", instruction, offset));
+                } else {
+                    self.line(instruction);
+                }
+                self.set(Some(id), repr);
+            }
+        }
+    }
+
+    fn emit_release_frame(&mut self) {
+        if self.frame_size > 0 {
+            self.line("local.get $frame");
+            self.line(&format!("i32.const {}", self.frame_size));
+            self.line("i32.add");
+            self.line("global.set $__stack_pointer");
+        }
+    }
+
+    /// Emits the comps of a basic block, except for its final jump
+    fn emit_block_body(&mut self, block: &BasicBlock) -> IRBackendResult<()> {
+        let function_def = self.function_def;
+        for index in block.start..block.end {
+            self.emit_comp(index, &function_def.comps[index])?;
+        }
+        Ok(())
+    }
+
+    fn emit_structured(&mut self, blocks: &[BasicBlock], scopes: &[Scope]) -> IRBackendResult<()> {
+        let mut open: Vec<Scope> = vec![];
+        for (index, block) in blocks.iter().enumerate() {
+            // Close the scopes ending here
+            while let Some(scope) = open.last() {
+                if scope.end > index {
+                    break;
+                }
+                open.pop();
+                self.depth -= 1;
+                self.line("end");
+            }
+
+            // Open the scopes starting here, outermost first
+            let mut starting: Vec<_> = scopes.iter().filter(|s| s.start == index).collect();
+            starting.sort_by_key(|s| (std::cmp::Reverse(s.end), s.kind == ScopeKind::Loop));
+            for scope in starting {
+                self.line(&match scope.kind {
+                    ScopeKind::Block => format!("block $b{}", scope.target),
+                    ScopeKind::Loop => format!("loop $l{}", scope.target),
+                });
+                self.depth += 1;
+                open.push(*scope);
+            }
+
+            self.emit_block_body(block)?;
+
+            let branch = |target: usize| {
+                if target <= index {
+                    format!("$l{}", target)
+                } else {
+                    format!("$b{}", target)
+                }
+            };
+            match &self.function_def.comps[block.end - 1].kind {
+                IRCompKind::If(..) => {
+                    let target = branch(block.jumps[0]);
+                    self.line(&format!("br_if {}", target));
+                }
+                IRCompKind::Jmp(_) => {
+                    let target = block.jumps[0];
+                    if target != index + 1 {
+                        self.line(&format!("br {}", branch(target)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        while open.pop().is_some() {
+            self.depth -= 1;
+            self.line("end");
+        }
+
+        Ok(())
+    }
+
+    /// Fallback for control flow the stackifier can't handle: a loop dispatching on the index of
+    /// the next basic block
+    fn emit_dispatch(&mut self, blocks: &[BasicBlock]) -> IRBackendResult<()> {
+        self.line("loop $dispatch");
+        self.depth += 1;
+        for index in (0..=blocks.len()).rev() {
+            self.line(&format!("block $d{}", index));
+            self.depth += 1;
+        }
+        self.line("local.get $next_block");
+        let targets: Vec<_> = (0..=blocks.len()).map(|i| format!("$d{}", i)).collect();
+        self.line(&format!("br_table {}", targets.join(" ")));
+
+        for block in blocks {
+            self.depth -= 1;
+            self.line("end");
+            self.emit_block_body(block)?;
+            let jump = |emitter: &mut Self, target: usize| {
+                emitter.line(&format!("i32.const {}", target));
+                emitter.line("local.set $next_block");
+                emitter.line("br $dispatch");
+            };
+            match &self.function_def.comps[block.end - 1].kind {
+                IRCompKind::If(..) => {
+                    self.line("if");
+                    self.depth += 1;
+                    jump(self, block.jumps[0]);
+                    self.depth -= 1;
+                    self.line("end");
+                }
+                IRCompKind::Jmp(_) => jump(self, block.jumps[0]),
+                _ => {}
+            }
+        }
+        self.depth -= 1;
+        self.line("end");
+        self.depth -= 1;
+        self.line("end");
+
+        Ok(())
+    }
+
+    fn emit(mut self) -> IRBackendResult<String> {
+        let function_def = self.function_def;
+        let mut header = format!("  (func {}", function_name(function_def.name));
+        if function_def.visibility == IRVisibility::Exported {
+            header += &format!(" (export \"{}\")", symbol_str(function_def.name));
+        }
+        for (index, (_, ir_type)) in function_def.params.iter().enumerate() {
+            if let Some(wasm_type) = wasm_type(repr(ir_type)) {
+                header += &format!(" (param $p{} {})", index, wasm_type);
+            }
+        }
+        if let Some(wasm_type) = wasm_type(repr(&function_def.return_type)) {
+            header += &format!(" (result {})", wasm_type);
+        }
+        self.output += &header;
+        self.output += "\n";
+
+        let mut locals: Vec<_> = self
+            .types
+            .iter()
+            .filter_map(|(id, ir_type)| wasm_type(repr(ir_type)).map(|t| (value_local(*id), t)))
+            .collect();
+        locals.sort();
+        for (name, wasm_type) in locals {
+            self.line(&format!("(local {} {})", name, wasm_type));
+        }
+        self.line("(local $frame i32)");
+        self.line("(local $copy_source i32)");
+        self.line("(local $next_block i32)");
+
+        // Frame setup
+        self.line("global.get $__stack_pointer");
+        if self.frame_size > 0 {
+            self.line(&format!("i32.const {}", self.frame_size));
+            self.line("i32.sub");
+            self.line("local.tee $frame");
+            self.line("global.set $__stack_pointer");
+        } else {
+            self.line("local.set $frame");
+        }
+        for (id, offset) in self.memory_slots.clone() {
+            self.line("local.get $frame");
+            self.line(&format!("i32.const {}", offset));
+            self.line("i32.add");
+            self.line(&format!("local.set {}", value_local(id)));
+        }
+
+        // Params are copied into their values (params in memory are passed by address)
+        for (index, (id, ir_type)) in function_def.params.iter().enumerate() {
+            let id = match id {
+                Some(id) => *id,
+                None => continue,
+            };
+            match repr(ir_type) {
+                Repr::Nothing => {}
+                Repr::Memory => {
+                    self.line(&format!("local.get {}", value_local(id)));
+                    self.line(&format!("local.get $p{}", index));
+                    self.line(&format!("i32.const {}", ir_type.size));
+                    self.line("memory.copy");
+                }
+                _ => {
+                    self.line(&format!("local.get $p{}", index));
+                    self.line(&format!("local.set {}", value_local(id)));
+                }
+            }
+        }
+
+        let blocks = basic_blocks(function_def);
+        match stackify(&blocks) {
+            Some(scopes) => self.emit_structured(&blocks, &scopes)?,
+            None => self.emit_dispatch(&blocks)?,
+        }
+
+        // Falling off the end of a function returns nothing meaningful
+        self.emit_release_frame();
+        match wasm_type(repr(&function_def.return_type)) {
+            Some("i64") => self.line("i64.const 0"),
+            Some(_) => self.line("i32.const 0"),
+            None => {}
+        }
+        self.output += "  )\n";
+
+        Ok(self.output)
+    }
+}
+
+/// Emits a WebAssembly text module for an IR module
+///
+/// Values are mapped to wasm locals (`i32` for values of up to 4 bytes, `i64` for values of 8
+/// bytes) and every other value lives in a shadow stack in linear memory, as well as the memory
+/// yielded by `Alloc` comps. The label based control flow is turned into structured control flow
+/// by a stackifier, falling back to a dispatch loop when that fails. Calls to functions that are
+/// not defined in the module become imports from the `env` module, and only exported functions
+/// are exported. The output only depends on the module, so it can be compared against golden
+/// files.
+pub fn wat_for_ir_module(module: &IRModule) -> IRBackendResult<String> {
+    let signatures = IRSignatures::for_module(module);
+    let mut result = "(module\n".to_owned();
+
+    // Imports, typed after their declaration or after the first call to them
+    let mut imports: BTreeMap<String, String> = BTreeMap::new();
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                let types = infer_value_types(function_def, &signatures)?;
+                for comp in &function_def.comps {
+                    let function_call = match &comp.kind {
+                        IRCompKind::FunctionCall(function_call)
+                            if !signatures.functions.contains_key(&function_call.name)
+                                && !signatures.externs.contains_key(&function_call.name) =>
+                        {
+                            function_call
+                        }
+                        _ => continue,
+                    };
+                    let name = symbol_str(function_call.name).to_owned();
+                    let mut import = format!(
+                        "  (import \"env\" \"{}\" (func {}",
+                        name,
+                        function_name(function_call.name)
+                    );
+                    for arg in &function_call.args {
+                        if let Some(wasm_type) = wasm_type(repr(&types[&arg.id])) {
+                            import += &format!(" (param {})", wasm_type);
+                        }
+                    }
+                    if let Some(wasm_type) = wasm_type(repr(&EXTERN_RETURN_IR_TYPE)) {
+                        import += &format!(" (result {})", wasm_type);
+                    }
+                    import += "))\n";
+                    imports.entry(name).or_insert(import);
+                }
+            }
+            IRItemKind::ExternDecl(extern_decl) => {
+                let name = symbol_str(extern_decl.name).to_owned();
+                let mut import = format!(
+                    "  (import \"env\" \"{}\" (func {}",
+                    name,
+                    function_name(extern_decl.name)
+                );
+                for param in &extern_decl.params {
+                    if let Some(wasm_type) = extern_wasm_type(param) {
+                        import += &format!(" (param {})", wasm_type);
+                    }
+                }
+                if let Some(wasm_type) = extern_wasm_type(&extern_decl.return_type) {
+                    import += &format!(" (result {})", wasm_type);
+                }
+                import += "))\n";
+                imports.insert(name, import);
+            }
+        }
+    }
+    for import in imports.values() {
+        result += import;
+    }
+
+    result += &format!("  (memory (export \"memory\") {})\n", MEMORY_PAGES);
+    result += &format!(
+        "  (global $__stack_pointer (mut i32) (i32.const {}))\n",
+        STACK_TOP
+    );
+
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                result += &FunctionEmitter::new(function_def, &signatures)?.emit()?;
+            }
+            IRItemKind::ExternDecl(_) => {}
+        }
+    }
+
+    result += ")\n";
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::SessionGlobals;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+
+    fn assert_golden(src: &str, golden: &str) {
+        SessionGlobals::create(|| {
+            let wat = wat_for_ir_module(&assemble_ir_module(src).unwrap()).unwrap();
+            assert_eq!(wat, golden);
+        });
+    }
+
+    #[test]
+    fn straight_line_function() {
+        let src = "\
+fn add_scaled: %a := (4 4) %b := (4 4) -> (4 4)
+    %two := const 4 2 0 0 0
+    %scaled := binop * %b %two
+    %sum := binop + %a %scaled
+    return %sum
+endfn
+";
+        assert_golden(src, include_str!("golden/straight_line.wat"));
+    }
+
+    #[test]
+    fn loop_becomes_structured_control_flow() {
+        assert_golden(
+            include_str!("../../../../ir_examples/fib.solidir"),
+            include_str!("golden/fib.wat"),
+        );
+    }
+
+    #[test]
+    fn irreducible_control_flow_falls_back_to_a_dispatch_loop() {
+        // The loop over first and second can be entered at both labels
+        let src = "\
+fn count: %skip := (1 1) -> (1 1)
+    %n := const 1 0
+    %one := const 1 1
+    %ten := const 1 10
+    if %skip second
+    :first
+    %n := binop + %n %one
+    :second
+    %n := binop + %n %one
+    %done := binop >= %n %ten
+    if %done end
+    jmp first
+    :end
+    return %n
+endfn
+";
+        assert_golden(src, include_str!("golden/irreducible.wat"));
+    }
+}
@@ -0,0 +1,879 @@
+use crate::globals::Symbol;
+use crate::ir::backend::{
+    infer_value_types, sanitize_symbol, symbol_str, IRBackendError, IRBackendResult, IRSignatures,
+};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperationKind, IRCompConversionKind, IRCompKind, IRCompUnaryOperationKind,
+};
+use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue, IRVisibility};
+use std::collections::HashMap;
+
+const ARG_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+const SSE_ARG_REGISTERS: usize = 8;
+
+/// How a value is passed to or returned from a function, following the System V ABI
+///
+/// Floats fall into the SSE class, every other value into the INTEGER class (including aggregates
+/// holding floats, which the IR can't tell apart).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PassingClass {
+    /// Nothing to pass (zero sized values)
+    Nothing,
+    /// Passed in a single vector register
+    Sse,
+    /// Passed in a single general purpose register
+    OneRegister,
+    /// Passed in two general purpose registers
+    TwoRegisters,
+    /// Passed on the stack (or through a hidden pointer for return values)
+    Memory,
+}
+
+fn passing_class(ir_type: &IRType) -> PassingClass {
+    match ir_type.size {
+        0 => PassingClass::Nothing,
+        4 | 8 if ir_type.float => PassingClass::Sse,
+        1..=8 => PassingClass::OneRegister,
+        9..=16 => PassingClass::TwoRegisters,
+        _ => PassingClass::Memory,
+    }
+}
+
+fn round_up(value: u64, to: u64) -> u64 {
+    value.div_ceil(to) * to
+}
+
+fn size_suffix(size: u64) -> Option<(&'static str, &'static str)> {
+    match size {
+        1 => Some(("b", "%r11b")),
+        2 => Some(("w", "%r11w")),
+        4 => Some(("l", "%r11d")),
+        8 => Some(("q", "%r11")),
+        _ => None,
+    }
+}
+
+/// Suffix of the SSE instructions operating on floats of the given size
+fn float_suffix(size: u64) -> Option<&'static str> {
+    match size {
+        4 => Some("ss"),
+        8 => Some("sd"),
+        _ => None,
+    }
+}
+
+/// Instruction sign-extending the low `size` bytes of %rax into the whole register
+fn sign_extend_rax(size: u64) -> Option<&'static str> {
+    match size {
+        1 => Some("movsbq %al, %rax"),
+        2 => Some("movswq %ax, %rax"),
+        4 => Some("movslq %eax, %rax"),
+        _ => None,
+    }
+}
+
+/// Instruction zero-extending the low `size` bytes of %rax into the whole register
+fn zero_extend_rax(size: u64) -> Option<&'static str> {
+    match size {
+        1 => Some("movzbl %al, %eax"),
+        2 => Some("movzwl %ax, %eax"),
+        4 => Some("movl %eax, %eax"),
+        _ => None,
+    }
+}
+
+struct FunctionEmitter<'a> {
+    function_def: &'a IRItemFunctionDef,
+    signatures: &'a IRSignatures,
+    types: HashMap<Symbol, IRType>,
+    /// Offsets (relative to %rbp) of the slots holding each value
+    slots: HashMap<Symbol, i64>,
+    /// Offsets (relative to %rbp) of the memory reserved by each `Alloc` comp
+    allocs: HashMap<usize, i64>,
+    /// Offset of the slot holding the hidden return pointer
+    sret_slot: Option<i64>,
+    frame_size: u64,
+    output: String,
+}
+
+impl<'a> FunctionEmitter<'a> {
+    fn new(
+        function_def: &'a IRItemFunctionDef,
+        signatures: &'a IRSignatures,
+    ) -> IRBackendResult<Self> {
+        let types = infer_value_types(function_def, signatures)?;
+        let mut emitter = Self {
+            function_def,
+            signatures,
+            types,
+            slots: HashMap::new(),
+            allocs: HashMap::new(),
+            sret_slot: None,
+            frame_size: 0,
+            output: String::new(),
+        };
+        emitter.layout_frame();
+        Ok(emitter)
+    }
+
+    fn reserve(&mut self, size: u64, align: u64) -> i64 {
+        let align = align.clamp(8, 16);
+        self.frame_size = round_up(self.frame_size + size.max(8), align);
+        -(self.frame_size as i64)
+    }
+
+    fn layout_frame(&mut self) {
+        if passing_class(&self.function_def.return_type) == PassingClass::Memory {
+            self.sret_slot = Some(self.reserve(8, 8));
+        }
+
+        // Params passed in memory stay where the caller put them
+        let mut registers_used = self.sret_slot.is_some() as usize;
+        let mut sse_registers_used = 0;
+        let mut stack_offset = 16;
+        for (id, ir_type) in &self.function_def.params {
+            let in_memory = match passing_class(ir_type) {
+                PassingClass::Nothing => false,
+                PassingClass::Sse if sse_registers_used < SSE_ARG_REGISTERS => {
+                    sse_registers_used += 1;
+                    false
+                }
+                PassingClass::OneRegister if registers_used < ARG_REGISTERS.len() => {
+                    registers_used += 1;
+                    false
+                }
+                PassingClass::TwoRegisters if registers_used + 1 < ARG_REGISTERS.len() => {
+                    registers_used += 2;
+                    false
+                }
+                _ => true,
+            };
+            if in_memory {
+                if let Some(id) = id {
+                    self.slots.insert(*id, stack_offset);
+                }
+                stack_offset += round_up(ir_type.size, 8) as i64;
+            }
+        }
+
+        let mut ids: Vec<_> = self
+            .types
+            .iter()
+            .filter(|(id, _)| !self.slots.contains_key(id))
+            .map(|(id, ir_type)| (*id, ir_type.clone()))
+            .collect();
+        // Keep the layout deterministic
+        ids.sort_by_key(|(id, _)| symbol_str(*id));
+        for (id, ir_type) in ids {
+            let slot = self.reserve(round_up(ir_type.size, 8), 8);
+            self.slots.insert(id, slot);
+        }
+
+        for (index, comp) in self.function_def.comps.iter().enumerate() {
+            if let IRCompKind::Alloc(ir_type) = &comp.kind {
+                let offset = self.reserve(ir_type.size, ir_type.align);
+                self.allocs.insert(index, offset);
+            }
+        }
+
+        self.frame_size = round_up(self.frame_size, 16);
+    }
+
+    fn line(&mut self, line: &str) {
+        self.output += "    ";
+        self.output += line;
+        self.output += "\n";
+    }
+
+    fn slot(&self, value: &IRValue) -> String {
+        format!("{}(%rbp)", self.slots[&value.id])
+    }
+
+    fn slot_with_offset(&self, value: &IRValue, offset: u64) -> String {
+        format!("{}(%rbp)", self.slots[&value.id] + offset as i64)
+    }
+
+    fn size_of(&self, value: &IRValue) -> u64 {
+        self.types[&value.id].size
+    }
+
+    fn label(&self, label: Symbol) -> String {
+        format!(
+            ".L{}_{}",
+            sanitize_symbol(self.function_def.name),
+            sanitize_symbol(label)
+        )
+    }
+
+    /// Copies `size` bytes between two memory operands, clobbering %r11, %rsi, %rdi and %rcx
+    fn copy(&mut self, source: &str, destination: &str, size: u64) {
+        if size == 0 {
+            return;
+        }
+        if let Some((suffix, register)) = size_suffix(size) {
+            self.line(&format!("mov{} {}, {}", suffix, source, register));
+            self.line(&format!("mov{} {}, {}", suffix, register, destination));
+        } else {
+            self.line(&format!("leaq {}, %rsi", source));
+            self.line(&format!("leaq {}, %rdi", destination));
+            self.line(&format!("movq ${}, %rcx", size));
+            self.line("rep movsb");
+        }
+    }
+
+    /// Writes %rax into the slot of a value of at most 8 bytes, keeping the unused bytes zeroed
+    fn store_rax(&mut self, id: Symbol, size: u64) {
+        if let Some(zero_extend) = zero_extend_rax(size) {
+            self.line(zero_extend);
+        } else if size != 8 {
+            self.line(&format!("movq ${}, %r11", (1u128 << (size * 8)) - 1));
+            self.line("andq %r11, %rax");
+        }
+        let slot = self.slot(&IRValue { id });
+        self.line(&format!("movq %rax, {}", slot));
+    }
+
+    /// Copies memory into the slot of a value, zeroing the padding of the slot
+    fn load_into_slot(&mut self, source: &str, id: Symbol, size: u64) {
+        let value = IRValue { id };
+        for chunk in 0..round_up(size, 8) / 8 {
+            let destination = self.slot_with_offset(&value, chunk * 8);
+            self.line(&format!("movq $0, {}", destination));
+        }
+        let destination = self.slot(&value);
+        self.copy(source, &destination, size);
+    }
+
+    fn emit_prologue(&mut self) {
+        let name = sanitize_symbol(self.function_def.name);
+        if self.function_def.visibility == IRVisibility::Exported {
+            self.output += &format!("    .globl {}\n", name);
+        }
+        self.output += &format!("    .type {}, @function\n", name);
+        self.output += &format!("{}:\n", name);
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if self.frame_size > 0 {
+            self.line(&format!("subq ${}, %rsp", self.frame_size));
+        }
+
+        let mut registers = ARG_REGISTERS.iter();
+        if let Some(sret_slot) = self.sret_slot {
+            let register = registers.next().unwrap();
+            self.line(&format!("movq {}, {}(%rbp)", register, sret_slot));
+        }
+        let mut registers_left = ARG_REGISTERS.len() - self.sret_slot.is_some() as usize;
+        let mut sse_registers = 0..SSE_ARG_REGISTERS;
+        let function_def = self.function_def;
+        for (id, ir_type) in &function_def.params {
+            if passing_class(ir_type) == PassingClass::Sse {
+                if let (Some(register), Some(id)) = (sse_registers.next(), id) {
+                    self.line(&format!("movq %xmm{}, %rax", register));
+                    self.store_rax(*id, ir_type.size);
+                }
+                continue;
+            }
+            let registers_needed = match passing_class(ir_type) {
+                PassingClass::OneRegister if registers_left >= 1 => 1,
+                PassingClass::TwoRegisters if registers_left >= 2 => 2,
+                _ => continue,
+            };
+            registers_left -= registers_needed;
+            let registers: Vec<_> = registers.by_ref().take(registers_needed).collect();
+            let id = match id {
+                Some(id) => *id,
+                None => continue,
+            };
+            if registers_needed == 1 {
+                // Only the bytes of the value are meaningful, the rest of the register is undefined
+                self.line(&format!("movq {}, %rax", registers[0]));
+                self.store_rax(id, ir_type.size);
+            } else {
+                for (chunk, register) in registers.iter().enumerate() {
+                    let slot = self.slot_with_offset(&IRValue { id }, chunk as u64 * 8);
+                    self.line(&format!("movq {}, {}", register, slot));
+                }
+            }
+        }
+    }
+
+    fn emit_epilogue(&mut self) {
+        self.line("leave");
+        self.line("ret");
+    }
+
+    fn emit_call(&mut self, comp: &IRComp, name: Symbol, args: &[IRValue]) -> IRBackendResult<()> {
+        let return_type = self.signatures.return_type(name);
+        let return_class = passing_class(&return_type);
+
+        let mut registers_left =
+            ARG_REGISTERS.len() - (return_class == PassingClass::Memory) as usize;
+        let mut register_args = vec![];
+        let mut sse_args = vec![];
+        let mut memory_args = vec![];
+        for (index, arg) in args.iter().enumerate() {
+            let size = self.size_of(arg);
+            // The declared params give the class, as constants passed as floats are not typed
+            let ir_type = match self.signatures.param_types(name) {
+                Some(param_types) if index < param_types.len() => param_types[index].clone(),
+                _ => self.types[&arg.id].clone(),
+            };
+            match passing_class(&ir_type) {
+                PassingClass::Nothing => {}
+                PassingClass::Sse if sse_args.len() < SSE_ARG_REGISTERS => {
+                    sse_args.push(self.slot(arg));
+                }
+                PassingClass::OneRegister if registers_left >= 1 => {
+                    registers_left -= 1;
+                    register_args.push(self.slot(arg));
+                }
+                PassingClass::TwoRegisters if registers_left >= 2 => {
+                    registers_left -= 2;
+                    register_args.push(self.slot(arg));
+                    register_args.push(self.slot_with_offset(arg, 8));
+                }
+                _ => memory_args.push((arg.clone(), size)),
+            }
+        }
+
+        let memory_args_size: u64 = memory_args.iter().map(|(_, size)| round_up(*size, 8)).sum();
+        let padding = round_up(memory_args_size, 16) - memory_args_size;
+        if padding > 0 {
+            self.line(&format!("subq ${}, %rsp", padding));
+        }
+        for (arg, size) in memory_args.iter().rev() {
+            self.line(&format!("subq ${}, %rsp", round_up(*size, 8)));
+            let source = self.slot(arg);
+            self.copy(&source, "(%rsp)", *size);
+        }
+
+        let mut registers = ARG_REGISTERS.iter();
+        if return_class == PassingClass::Memory {
+            let register = registers.next().unwrap();
+            match comp.id {
+                Some(id) => {
+                    let slot = self.slot(&IRValue { id });
+                    self.line(&format!("leaq {}, {}", slot, register));
+                }
+                None => {
+                    return Err(IRBackendError {
+                        message: format!(
+                            "The result of the call to {} must be named (in function {})",
+                            symbol_str(name),
+                            symbol_str(self.function_def.name)
+                        ),
+                    });
+                }
+            }
+        }
+        for (source, register) in register_args.iter().zip(registers) {
+            self.line(&format!("movq {}, {}", source, register));
+        }
+        for (register, source) in sse_args.iter().enumerate() {
+            self.line(&format!("movq {}, %xmm{}", source, register));
+        }
+        // Variadic functions expect the number of vector registers used in %al
+        if sse_args.is_empty() {
+            self.line("xorl %eax, %eax");
+        } else {
+            self.line(&format!("movl ${}, %eax", sse_args.len()));
+        }
+        self.line(&format!("call {}", sanitize_symbol(name)));
+        if memory_args_size + padding > 0 {
+            self.line(&format!("addq ${}, %rsp", memory_args_size + padding));
+        }
+
+        if let Some(id) = comp.id {
+            match return_class {
+                PassingClass::Nothing | PassingClass::Memory => {}
+                PassingClass::Sse => {
+                    self.line("movq %xmm0, %rax");
+                    self.store_rax(id, return_type.size);
+                }
+                PassingClass::OneRegister => self.store_rax(id, return_type.size),
+                PassingClass::TwoRegisters => {
+                    let value = IRValue { id };
+                    let low = self.slot(&value);
+                    let high = self.slot_with_offset(&value, 8);
+                    self.line(&format!("movq %rax, {}", low));
+                    self.line(&format!("movq %rdx, {}", high));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_comp(&mut self, index: usize, comp: &IRComp) -> IRBackendResult<()> {
+        match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => {
+                self.emit_call(comp, function_call.name, &function_call.args)?;
+            }
+            IRCompKind::BinaryOperation(operation) if operation.kind.is_float() => {
+                let size = self.size_of(&operation.left_operand);
+                let suffix = float_suffix(size).ok_or_else(|| self.unsupported_size(size))?;
+                let left = self.slot(&operation.left_operand);
+                let right = self.slot(&operation.right_operand);
+                self.line(&format!("movq {}, %xmm0", left));
+                self.line(&format!("movq {}, %xmm1", right));
+                let result_size = match operation.kind {
+                    IRCompBinaryOperationKind::FloatPlus => {
+                        self.line(&format!("add{} %xmm1, %xmm0", suffix));
+                        size
+                    }
+                    IRCompBinaryOperationKind::FloatMinus => {
+                        self.line(&format!("sub{} %xmm1, %xmm0", suffix));
+                        size
+                    }
+                    IRCompBinaryOperationKind::FloatMul => {
+                        self.line(&format!("mul{} %xmm1, %xmm0", suffix));
+                        size
+                    }
+                    IRCompBinaryOperationKind::FloatDiv => {
+                        self.line(&format!("div{} %xmm1, %xmm0", suffix));
+                        size
+                    }
+                    _ => {
+                        // Unordered results (NaN operands) set ZF, PF and CF, so `a` and `ae`
+                        // are false for them, equality also checks PF
+                        let (operands, condition) = match operation.kind {
+                            IRCompBinaryOperationKind::FloatGreater => ("%xmm1, %xmm0", "a"),
+                            IRCompBinaryOperationKind::FloatGreaterEqual => ("%xmm1, %xmm0", "ae"),
+                            IRCompBinaryOperationKind::FloatLesser => ("%xmm0, %xmm1", "a"),
+                            IRCompBinaryOperationKind::FloatLesserEqual => ("%xmm0, %xmm1", "ae"),
+                            _ => ("%xmm1, %xmm0", "e"),
+                        };
+                        self.line(&format!("ucomi{} {}", suffix, operands));
+                        match operation.kind {
+                            IRCompBinaryOperationKind::FloatEqual => {
+                                self.line("sete %al");
+                                self.line("setnp %cl");
+                                self.line("andb %cl, %al");
+                            }
+                            IRCompBinaryOperationKind::FloatNotEqual => {
+                                self.line("setne %al");
+                                self.line("setp %cl");
+                                self.line("orb %cl, %al");
+                            }
+                            _ => self.line(&format!("set{} %al", condition)),
+                        }
+                        1
+                    }
+                };
+                if result_size != 1 {
+                    self.line("movq %xmm0, %rax");
+                }
+                if let Some(id) = comp.id {
+                    self.store_rax(id, result_size);
+                }
+            }
+            IRCompKind::BinaryOperation(operation) => {
+                let size = self.size_of(&operation.left_operand);
+                if size_suffix(size).is_none() {
+                    return Err(self.unsupported_size(size));
+                }
+                let left = self.slot(&operation.left_operand);
+                let right = self.slot(&operation.right_operand);
+                if let (true, Some(sign_extend)) =
+                    (operation.kind.is_signed(), sign_extend_rax(size))
+                {
+                    self.line(&format!("movq {}, %rax", right));
+                    self.line(sign_extend);
+                    self.line("movq %rax, %rcx");
+                    self.line(&format!("movq {}, %rax", left));
+                    self.line(sign_extend);
+                } else {
+                    self.line(&format!("movq {}, %rax", left));
+                    self.line(&format!("movq {}, %rcx", right));
+                }
+                let comparison = |condition: &str| format!("set{} %al", condition);
+                let result_size = match operation.kind {
+                    IRCompBinaryOperationKind::Plus => {
+                        self.line("addq %rcx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::Minus => {
+                        self.line("subq %rcx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::Mul => {
+                        self.line("imulq %rcx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::Div => {
+                        self.line("xorl %edx, %edx");
+                        self.line("divq %rcx");
+                        size
+                    }
+                    IRCompBinaryOperationKind::Mod => {
+                        self.line("xorl %edx, %edx");
+                        self.line("divq %rcx");
+                        self.line("movq %rdx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::BitAnd => {
+                        self.line("andq %rcx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::BitOr => {
+                        self.line("orq %rcx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::BitRShift => {
+                        self.line("shrq %cl, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::BitLShift => {
+                        self.line("shlq %cl, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::SignedDiv => {
+                        self.line("cqto");
+                        self.line("idivq %rcx");
+                        size
+                    }
+                    IRCompBinaryOperationKind::SignedMod => {
+                        self.line("cqto");
+                        self.line("idivq %rcx");
+                        self.line("movq %rdx, %rax");
+                        size
+                    }
+                    IRCompBinaryOperationKind::SignedRShift => {
+                        self.line("sarq %cl, %rax");
+                        size
+                    }
+                    _ => {
+                        let condition = match operation.kind {
+                            IRCompBinaryOperationKind::Equal => "e",
+                            IRCompBinaryOperationKind::NotEqual => "ne",
+                            IRCompBinaryOperationKind::Greater => "a",
+                            IRCompBinaryOperationKind::Lesser => "b",
+                            IRCompBinaryOperationKind::GreaterEqual => "ae",
+                            IRCompBinaryOperationKind::SignedGreater => "g",
+                            IRCompBinaryOperationKind::SignedLesser => "l",
+                            IRCompBinaryOperationKind::SignedGreaterEqual => "ge",
+                            IRCompBinaryOperationKind::SignedLesserEqual => "le",
+                            _ => "be",
+                        };
+                        self.line("cmpq %rcx, %rax");
+                        self.line(&comparison(condition));
+                        1
+                    }
+                };
+                if let Some(id) = comp.id {
+                    self.store_rax(id, result_size);
+                }
+            }
+            IRCompKind::UnaryOperation(operation) => {
+                let size = self.size_of(&operation.operand);
+                if size_suffix(size).is_none() {
+                    return Err(self.unsupported_size(size));
+                }
+                let operand = self.slot(&operation.operand);
+                self.line(&format!("movq {}, %rax", operand));
+                match operation.kind {
+                    IRCompUnaryOperationKind::BoolNot => {
+                        self.line("testq %rax, %rax");
+                        self.line("sete %al");
+                    }
+                    IRCompUnaryOperationKind::BitNot => self.line("notq %rax"),
+                    IRCompUnaryOperationKind::SignedNegation => self.line("negq %rax"),
+                    IRCompUnaryOperationKind::FloatNegation => match size {
+                        4 => self.line("btcl $31, %eax"),
+                        8 => self.line("btcq $63, %rax"),
+                        _ => return Err(self.unsupported_size(size)),
+                    },
+                }
+                if let Some(id) = comp.id {
+                    let result_size = match operation.kind {
+                        IRCompUnaryOperationKind::BoolNot => 1,
+                        _ => size,
+                    };
+                    self.store_rax(id, result_size);
+                }
+            }
+            IRCompKind::Conversion(conversion) => {
+                self.emit_conversion(comp, &conversion.kind, &conversion.operand, conversion.size)?;
+            }
+            IRCompKind::Constant(constant) => {
+                if let Some(id) = comp.id {
+                    let value = IRValue { id };
+                    for (chunk, bytes) in constant.bytes.chunks(8).enumerate() {
+                        let mut qword = [0u8; 8];
+                        qword[..bytes.len()].copy_from_slice(bytes);
+                        let destination = self.slot_with_offset(&value, chunk as u64 * 8);
+                        self.line(&format!("movabsq ${}, %rax", u64::from_le_bytes(qword)));
+                        self.line(&format!("movq %rax, {}", destination));
+                    }
+                }
+            }
+            IRCompKind::Alloc(ir_type) => {
+                // Memory yielded by alloc is zeroed, like in the interpreter
+                let offset = self.allocs[&index];
+                if ir_type.size > 0 {
+                    self.line(&format!("leaq {}(%rbp), %rdi", offset));
+                    self.line("xorl %eax, %eax");
+                    self.line(&format!("movq ${}, %rcx", ir_type.size));
+                    self.line("rep stosb");
+                }
+                if let Some(id) = comp.id {
+                    self.line(&format!("leaq {}(%rbp), %rax", offset));
+                    self.store_rax(id, 8);
+                }
+            }
+            IRCompKind::Store(ir_type, location, value) => {
+                self.emit_store(ir_type, location, value, 0);
+            }
+            IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                self.emit_store(ir_type, location, value, *offset);
+            }
+            IRCompKind::Load(ir_type, location) => {
+                self.emit_load(comp, ir_type, location, 0);
+            }
+            IRCompKind::OffsetLoad(ir_type, location, offset) => {
+                self.emit_load(comp, ir_type, location, *offset);
+            }
+            IRCompKind::Return(value) => {
+                let return_type = self.function_def.return_type.clone();
+                match passing_class(&return_type) {
+                    PassingClass::Nothing => {}
+                    PassingClass::Sse => {
+                        let source = self.slot(value);
+                        self.line(&format!("movq {}, %xmm0", source));
+                    }
+                    PassingClass::OneRegister => {
+                        let source = self.slot(value);
+                        self.line(&format!("movq {}, %rax", source));
+                    }
+                    PassingClass::TwoRegisters => {
+                        let low = self.slot(value);
+                        let high = self.slot_with_offset(value, 8);
+                        self.line(&format!("movq {}, %rax", low));
+                        self.line(&format!("movq {}, %rdx", high));
+                    }
+                    PassingClass::Memory => {
+                        let sret_slot = self.sret_slot.unwrap();
+                        let source = self.slot(value);
+                        self.line(&format!("movq {}(%rbp), %rax", sret_slot));
+                        self.copy(&source, "(%rax)", return_type.size);
+                        self.line(&format!("movq {}(%rbp), %rax", sret_slot));
+                    }
+                }
+                self.emit_epilogue();
+            }
+            IRCompKind::If(value, label) => {
+                let source = self.slot(value);
+                self.line(&format!("cmpb $0, {}", source));
+                let label = self.label(*label);
+                self.line(&format!("jne {}", label));
+            }
+            IRCompKind::Jmp(label) => {
+                let label = self.label(*label);
+                self.line(&format!("jmp {}", label));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Converts between integers and floats, floats out of the range of an integer give an
+    /// unspecified value (the "integer indefinite" value of SSE)
+    fn emit_conversion(
+        &mut self,
+        comp: &IRComp,
+        kind: &IRCompConversionKind,
+        operand: &IRValue,
+        to: u64,
+    ) -> IRBackendResult<()> {
+        let from = self.size_of(operand);
+        let source = self.slot(operand);
+        match kind {
+            IRCompConversionKind::UnsignedToFloat | IRCompConversionKind::SignedToFloat => {
+                let suffix = float_suffix(to).ok_or_else(|| self.unsupported_size(to))?;
+                if size_suffix(from).is_none() {
+                    return Err(self.unsupported_size(from));
+                }
+                self.line(&format!("movq {}, %rax", source));
+                if matches!(kind, IRCompConversionKind::SignedToFloat) {
+                    if let Some(sign_extend) = sign_extend_rax(from) {
+                        self.line(sign_extend);
+                    }
+                }
+                if matches!(kind, IRCompConversionKind::UnsignedToFloat) && from == 8 {
+                    // cvtsi2s* only converts signed integers, so values with the top bit set are
+                    // halved (keeping the low bit for rounding) and doubled back
+                    self.line("testq %rax, %rax");
+                    self.line("js 1f");
+                    self.line(&format!("cvtsi2{}q %rax, %xmm0", suffix));
+                    self.line("jmp 2f");
+                    self.output += "1:\n";
+                    self.line("movq %rax, %rcx");
+                    self.line("shrq %rcx");
+                    self.line("andl $1, %eax");
+                    self.line("orq %rax, %rcx");
+                    self.line(&format!("cvtsi2{}q %rcx, %xmm0", suffix));
+                    self.line(&format!("add{} %xmm0, %xmm0", suffix));
+                    self.output += "2:\n";
+                } else {
+                    // Smaller unsigned values are zero extended in their slot
+                    self.line(&format!("cvtsi2{}q %rax, %xmm0", suffix));
+                }
+                self.line("movq %xmm0, %rax");
+            }
+            IRCompConversionKind::FloatToUnsigned | IRCompConversionKind::FloatToSigned => {
+                let suffix = float_suffix(from).ok_or_else(|| self.unsupported_size(from))?;
+                if size_suffix(to).is_none() {
+                    return Err(self.unsupported_size(to));
+                }
+                self.line(&format!("movq {}, %xmm0", source));
+                if matches!(kind, IRCompConversionKind::FloatToUnsigned) && to == 8 {
+                    // cvtts*2si only yields signed integers, so floats from 2^63 are offset by it
+                    if from == 4 {
+                        self.line("cvtss2sd %xmm0, %xmm0");
+                    }
+                    let two_pow_63 = ((1u64 << 63) as f64).to_bits();
+                    self.line(&format!("movabsq ${}, %rax", two_pow_63));
+                    self.line("movq %rax, %xmm1");
+                    self.line("ucomisd %xmm1, %xmm0");
+                    self.line("jae 1f");
+                    self.line("cvttsd2siq %xmm0, %rax");
+                    self.line("jmp 2f");
+                    self.output += "1:\n";
+                    self.line("subsd %xmm1, %xmm0");
+                    self.line("cvttsd2siq %xmm0, %rax");
+                    self.line("btcq $63, %rax");
+                    self.output += "2:\n";
+                } else {
+                    self.line(&format!("cvtt{}2siq %xmm0, %rax", suffix));
+                }
+            }
+            IRCompConversionKind::FloatToFloat => {
+                let from_suffix = float_suffix(from).ok_or_else(|| self.unsupported_size(from))?;
+                let to_suffix = float_suffix(to).ok_or_else(|| self.unsupported_size(to))?;
+                self.line(&format!("movq {}, %xmm0", source));
+                if from != to {
+                    self.line(&format!("cvt{}2{} %xmm0, %xmm0", from_suffix, to_suffix));
+                }
+                self.line("movq %xmm0, %rax");
+            }
+            IRCompConversionKind::Truncate | IRCompConversionKind::ZeroExtend => {
+                // Slots are zero extended, and storing drops the bytes above the result
+                self.line(&format!("movq {}, %rax", source));
+            }
+            IRCompConversionKind::SignExtend => {
+                self.line(&format!("movq {}, %rax", source));
+                if let Some(sign_extend) = sign_extend_rax(from) {
+                    self.line(sign_extend);
+                }
+            }
+        }
+        if let Some(id) = comp.id {
+            self.store_rax(id, to);
+        }
+        Ok(())
+    }
+
+    fn emit_store(&mut self, ir_type: &IRType, location: &IRValue, value: &IRValue, offset: u64) {
+        let location = self.slot(location);
+        let source = self.slot(value);
+        self.line(&format!("movq {}, %rax", location));
+        self.copy(&source, &format!("{}(%rax)", offset), ir_type.size);
+    }
+
+    fn emit_load(&mut self, comp: &IRComp, ir_type: &IRType, location: &IRValue, offset: u64) {
+        if let Some(id) = comp.id {
+            let location = self.slot(location);
+            self.line(&format!("movq {}, %rax", location));
+            self.load_into_slot(&format!("{}(%rax)", offset), id, ir_type.size);
+        }
+    }
+
+    fn unsupported_size(&self, size: u64) -> IRBackendError {
+        IRBackendError {
+            message: format!(
+                "Arithmetic on values of {} bytes is not supported (in function {})",
+                size,
+                symbol_str(self.function_def.name)
+            ),
+        }
+    }
+
+    fn emit(mut self) -> IRBackendResult<String> {
+        self.emit_prologue();
+
+        let mut labels: Vec<_> = self.function_def.label_defs.iter().collect();
+        labels.sort_by_key(|(label, index)| (**index, symbol_str(**label)));
+        let mut labels = labels.into_iter().peekable();
+
+        let function_def = self.function_def;
+        for (index, comp) in function_def.comps.iter().enumerate() {
+            while let Some((label, _)) = labels.next_if(|(_, i)| **i as usize == index) {
+                self.output += &format!("{}:\n", self.label(*label));
+            }
+            self.emit_comp(index, comp)?;
+        }
+        for (label, _) in labels {
+            self.output += &format!("{}:\n", self.label(*label));
+        }
+
+        // Falling off the end of a function returns nothing
+        self.emit_epilogue();
+        let name = sanitize_symbol(self.function_def.name);
+        self.output += &format!("    .size {}, .-{}\n", name, name);
+
+        Ok(self.output)
+    }
+}
+
+/// Lowers a module to x86-64 GNU assembler text (AT&T syntax) following the System V ABI
+///
+/// Exported functions become global symbols. Calls to functions that are not defined in the
+/// module are emitted as calls to external symbols (returning a 64 bit value unless they are
+/// declared), so the output can be linked against the C library (`cc out.s -o out`).
+pub fn assembly_for_ir_module(module: &IRModule) -> IRBackendResult<String> {
+    let signatures = IRSignatures::for_module(module);
+    let mut result = "    .text\n".to_owned();
+
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                result += "\n";
+                result += &FunctionEmitter::new(function_def, &signatures)?.emit()?;
+            }
+            IRItemKind::ExternDecl(_) => {}
+        }
+    }
+
+    result += "\n    .section .note.GNU-stack,\"\",@progbits\n";
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::SessionGlobals;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+
+    #[test]
+    fn floats_are_passed_in_vector_registers() {
+        let src = "\
+extern pow: (8 8 float) (8 8 float) -> (8 8 float)
+
+fn main: -> (8 8)
+    %n := const 8 3 0 0 0 0 0 0 0
+    %base := const 8 0 0 0 0 0 0 0 64
+    %exponent := convert utof 8 %n
+    %result := call pow 2 %base %exponent
+    return %result
+endfn
+";
+        SessionGlobals::create(|| {
+            let assembly = assembly_for_ir_module(&assemble_ir_module(src).unwrap()).unwrap();
+            let call = assembly.find("call pow").unwrap();
+            let before = &assembly[..call];
+            assert!(before.contains(", %xmm0\n") && before.contains(", %xmm1\n"));
+            assert!(before.ends_with("movl $2, %eax\n    "));
+            assert!(assembly[call..].contains("movq %xmm0, %rax"));
+        });
+    }
+}
@@ -0,0 +1,893 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{
+    IRComp, IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompConstant, IRCompConversion,
+    IRCompConversionKind, IRCompFunctionCall, IRCompKind, IRCompUnaryOperation,
+    IRCompUnaryOperationKind, IRDebugLocation,
+};
+use crate::ir::{
+    IRInlineHint, IRItem, IRItemExternDecl, IRItemFunctionDef, IRItemKind, IRModule, IRType,
+    IRValue, IRVisibility,
+};
+use std::collections::HashMap;
+
+pub const IR_BINARY_MAGIC: &[u8; 8] = b"SOLIDIR\0";
+pub const IR_BINARY_VERSION: u16 = 1;
+
+/// Kinds of the sections of a binary module
+///
+/// The values are part of the format and must never change.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum IRBinarySectionKind {
+    Strings = 1,
+    /// Items in the order of the module, each prefixed by its kind
+    Items = 2,
+    /// Reserved, the IR has no globals yet
+    Globals = 3,
+}
+
+impl IRBinarySectionKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::Strings),
+            2 => Some(Self::Items),
+            3 => Some(Self::Globals),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IRBinaryError {
+    pub message: String,
+    /// Offset in the input at which the error was found
+    pub offset: usize,
+}
+
+pub type IRBinaryResult<T> = Result<T, IRBinaryError>;
+
+/// Returns whether the bytes start like a binary module
+pub fn is_binary_ir_module(bytes: &[u8]) -> bool {
+    bytes.starts_with(IR_BINARY_MAGIC)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn write_uleb(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            break;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+fn binary_operation_code(kind: &IRCompBinaryOperationKind) -> u8 {
+    match kind {
+        IRCompBinaryOperationKind::Plus => 0,
+        IRCompBinaryOperationKind::Minus => 1,
+        IRCompBinaryOperationKind::Mul => 2,
+        IRCompBinaryOperationKind::Div => 3,
+        IRCompBinaryOperationKind::Mod => 4,
+        IRCompBinaryOperationKind::BitAnd => 5,
+        IRCompBinaryOperationKind::BitOr => 6,
+        IRCompBinaryOperationKind::BitRShift => 7,
+        IRCompBinaryOperationKind::BitLShift => 8,
+        IRCompBinaryOperationKind::Equal => 9,
+        IRCompBinaryOperationKind::NotEqual => 10,
+        IRCompBinaryOperationKind::Greater => 11,
+        IRCompBinaryOperationKind::Lesser => 12,
+        IRCompBinaryOperationKind::GreaterEqual => 13,
+        IRCompBinaryOperationKind::LesserEqual => 14,
+        IRCompBinaryOperationKind::FloatPlus => 15,
+        IRCompBinaryOperationKind::FloatMinus => 16,
+        IRCompBinaryOperationKind::FloatMul => 17,
+        IRCompBinaryOperationKind::FloatDiv => 18,
+        IRCompBinaryOperationKind::FloatEqual => 19,
+        IRCompBinaryOperationKind::FloatNotEqual => 20,
+        IRCompBinaryOperationKind::FloatGreater => 21,
+        IRCompBinaryOperationKind::FloatLesser => 22,
+        IRCompBinaryOperationKind::FloatGreaterEqual => 23,
+        IRCompBinaryOperationKind::FloatLesserEqual => 24,
+        IRCompBinaryOperationKind::SignedDiv => 25,
+        IRCompBinaryOperationKind::SignedMod => 26,
+        IRCompBinaryOperationKind::SignedRShift => 27,
+        IRCompBinaryOperationKind::SignedGreater => 28,
+        IRCompBinaryOperationKind::SignedLesser => 29,
+        IRCompBinaryOperationKind::SignedGreaterEqual => 30,
+        IRCompBinaryOperationKind::SignedLesserEqual => 31,
+    }
+}
+
+fn binary_operation_kind(code: u8) -> Option<IRCompBinaryOperationKind> {
+    Some(match code {
+        0 => IRCompBinaryOperationKind::Plus,
+        1 => IRCompBinaryOperationKind::Minus,
+        2 => IRCompBinaryOperationKind::Mul,
+        3 => IRCompBinaryOperationKind::Div,
+        4 => IRCompBinaryOperationKind::Mod,
+        5 => IRCompBinaryOperationKind::BitAnd,
+        6 => IRCompBinaryOperationKind::BitOr,
+        7 => IRCompBinaryOperationKind::BitRShift,
+        8 => IRCompBinaryOperationKind::BitLShift,
+        9 => IRCompBinaryOperationKind::Equal,
+        10 => IRCompBinaryOperationKind::NotEqual,
+        11 => IRCompBinaryOperationKind::Greater,
+        12 => IRCompBinaryOperationKind::Lesser,
+        13 => IRCompBinaryOperationKind::GreaterEqual,
+        14 => IRCompBinaryOperationKind::LesserEqual,
+        15 => IRCompBinaryOperationKind::FloatPlus,
+        16 => IRCompBinaryOperationKind::FloatMinus,
+        17 => IRCompBinaryOperationKind::FloatMul,
+        18 => IRCompBinaryOperationKind::FloatDiv,
+        19 => IRCompBinaryOperationKind::FloatEqual,
+        20 => IRCompBinaryOperationKind::FloatNotEqual,
+        21 => IRCompBinaryOperationKind::FloatGreater,
+        22 => IRCompBinaryOperationKind::FloatLesser,
+        23 => IRCompBinaryOperationKind::FloatGreaterEqual,
+        24 => IRCompBinaryOperationKind::FloatLesserEqual,
+        25 => IRCompBinaryOperationKind::SignedDiv,
+        26 => IRCompBinaryOperationKind::SignedMod,
+        27 => IRCompBinaryOperationKind::SignedRShift,
+        28 => IRCompBinaryOperationKind::SignedGreater,
+        29 => IRCompBinaryOperationKind::SignedLesser,
+        30 => IRCompBinaryOperationKind::SignedGreaterEqual,
+        31 => IRCompBinaryOperationKind::SignedLesserEqual,
+        _ => return None,
+    })
+}
+
+fn unary_operation_code(kind: &IRCompUnaryOperationKind) -> u8 {
+    match kind {
+        IRCompUnaryOperationKind::BoolNot => 0,
+        IRCompUnaryOperationKind::BitNot => 1,
+        IRCompUnaryOperationKind::SignedNegation => 2,
+        IRCompUnaryOperationKind::FloatNegation => 3,
+    }
+}
+
+fn unary_operation_kind(code: u8) -> Option<IRCompUnaryOperationKind> {
+    Some(match code {
+        0 => IRCompUnaryOperationKind::BoolNot,
+        1 => IRCompUnaryOperationKind::BitNot,
+        2 => IRCompUnaryOperationKind::SignedNegation,
+        3 => IRCompUnaryOperationKind::FloatNegation,
+        _ => return None,
+    })
+}
+
+fn conversion_code(kind: &IRCompConversionKind) -> u8 {
+    match kind {
+        IRCompConversionKind::UnsignedToFloat => 0,
+        IRCompConversionKind::SignedToFloat => 1,
+        IRCompConversionKind::FloatToUnsigned => 2,
+        IRCompConversionKind::FloatToSigned => 3,
+        IRCompConversionKind::FloatToFloat => 4,
+        IRCompConversionKind::Truncate => 5,
+        IRCompConversionKind::ZeroExtend => 6,
+        IRCompConversionKind::SignExtend => 7,
+    }
+}
+
+fn conversion_kind(code: u8) -> Option<IRCompConversionKind> {
+    Some(match code {
+        0 => IRCompConversionKind::UnsignedToFloat,
+        1 => IRCompConversionKind::SignedToFloat,
+        2 => IRCompConversionKind::FloatToUnsigned,
+        3 => IRCompConversionKind::FloatToSigned,
+        4 => IRCompConversionKind::FloatToFloat,
+        5 => IRCompConversionKind::Truncate,
+        6 => IRCompConversionKind::ZeroExtend,
+        7 => IRCompConversionKind::SignExtend,
+        _ => return None,
+    })
+}
+
+const ITEM_FUNCTION_DEF: u8 = 0;
+const ITEM_EXTERN_DECL: u8 = 1;
+
+const COMP_FUNCTION_CALL: u8 = 0;
+const COMP_BINARY_OPERATION: u8 = 1;
+const COMP_UNARY_OPERATION: u8 = 2;
+const COMP_CONSTANT: u8 = 3;
+const COMP_ALLOC: u8 = 4;
+const COMP_STORE: u8 = 5;
+const COMP_LOAD: u8 = 6;
+const COMP_OFFSET_STORE: u8 = 7;
+const COMP_OFFSET_LOAD: u8 = 8;
+const COMP_RETURN: u8 = 9;
+const COMP_IF: u8 = 10;
+const COMP_JMP: u8 = 11;
+const COMP_CONVERSION: u8 = 12;
+
+struct IRBinaryWriter {
+    strings: Vec<Symbol>,
+    string_indices: HashMap<Symbol, u64>,
+}
+
+impl IRBinaryWriter {
+    fn string_index(&mut self, symbol: Symbol) -> u64 {
+        if let Some(index) = self.string_indices.get(&symbol) {
+            return *index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(symbol);
+        self.string_indices.insert(symbol, index);
+        index
+    }
+
+    fn write_symbol(&mut self, output: &mut Vec<u8>, symbol: Symbol) {
+        let index = self.string_index(symbol);
+        write_uleb(output, index);
+    }
+
+    /// Writes 0 for `None`, and the string index plus one otherwise
+    fn write_optional_symbol(&mut self, output: &mut Vec<u8>, symbol: Option<Symbol>) {
+        match symbol {
+            Some(symbol) => {
+                let index = self.string_index(symbol);
+                write_uleb(output, index + 1);
+            }
+            None => write_uleb(output, 0),
+        }
+    }
+
+    fn write_ir_type(&mut self, output: &mut Vec<u8>, ir_type: &IRType) {
+        write_uleb(output, ir_type.size);
+        write_uleb(output, ir_type.align);
+        output.push(ir_type.float as u8);
+    }
+
+    fn write_comp(&mut self, output: &mut Vec<u8>, comp: &IRComp) {
+        self.write_optional_symbol(output, comp.id);
+        match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => {
+                output.push(COMP_FUNCTION_CALL);
+                self.write_symbol(output, function_call.name);
+                write_uleb(output, function_call.args.len() as u64);
+                for arg in &function_call.args {
+                    self.write_symbol(output, arg.id);
+                }
+            }
+            IRCompKind::BinaryOperation(operation) => {
+                output.push(COMP_BINARY_OPERATION);
+                output.push(binary_operation_code(&operation.kind));
+                self.write_symbol(output, operation.left_operand.id);
+                self.write_symbol(output, operation.right_operand.id);
+            }
+            IRCompKind::UnaryOperation(operation) => {
+                output.push(COMP_UNARY_OPERATION);
+                output.push(unary_operation_code(&operation.kind));
+                self.write_symbol(output, operation.operand.id);
+            }
+            IRCompKind::Conversion(conversion) => {
+                output.push(COMP_CONVERSION);
+                output.push(conversion_code(&conversion.kind));
+                write_uleb(output, conversion.size);
+                self.write_symbol(output, conversion.operand.id);
+            }
+            IRCompKind::Constant(constant) => {
+                output.push(COMP_CONSTANT);
+                write_uleb(output, constant.bytes.len() as u64);
+                output.extend_from_slice(&constant.bytes);
+            }
+            IRCompKind::Alloc(ir_type) => {
+                output.push(COMP_ALLOC);
+                self.write_ir_type(output, ir_type);
+            }
+            IRCompKind::Store(ir_type, location, value) => {
+                output.push(COMP_STORE);
+                self.write_ir_type(output, ir_type);
+                self.write_symbol(output, location.id);
+                self.write_symbol(output, value.id);
+            }
+            IRCompKind::Load(ir_type, location) => {
+                output.push(COMP_LOAD);
+                self.write_ir_type(output, ir_type);
+                self.write_symbol(output, location.id);
+            }
+            IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                output.push(COMP_OFFSET_STORE);
+                self.write_ir_type(output, ir_type);
+                self.write_symbol(output, location.id);
+                self.write_symbol(output, value.id);
+                write_uleb(output, *offset);
+            }
+            IRCompKind::OffsetLoad(ir_type, location, offset) => {
+                output.push(COMP_OFFSET_LOAD);
+                self.write_ir_type(output, ir_type);
+                self.write_symbol(output, location.id);
+                write_uleb(output, *offset);
+            }
+            IRCompKind::Return(value) => {
+                output.push(COMP_RETURN);
+                self.write_symbol(output, value.id);
+            }
+            IRCompKind::If(value, label) => {
+                output.push(COMP_IF);
+                self.write_symbol(output, value.id);
+                self.write_symbol(output, *label);
+            }
+            IRCompKind::Jmp(label) => {
+                output.push(COMP_JMP);
+                self.write_symbol(output, *label);
+            }
+        }
+        self.write_location(output, comp.location.as_ref());
+    }
+
+    /// Writes the file as an optional symbol, followed by the rest of the location if there is one
+    fn write_location(&mut self, output: &mut Vec<u8>, location: Option<&IRDebugLocation>) {
+        self.write_optional_symbol(output, location.map(|location| location.file));
+        if let Some(location) = location {
+            write_uleb(output, location.line);
+            write_uleb(output, location.column);
+            self.write_symbol(output, location.function);
+        }
+    }
+
+    fn write_function_def(&mut self, output: &mut Vec<u8>, function_def: &IRItemFunctionDef) {
+        self.write_symbol(output, function_def.name);
+        output.push(match function_def.visibility {
+            IRVisibility::Exported => 0,
+            IRVisibility::Internal => 1,
+        });
+        output.push(match function_def.inline_hint {
+            IRInlineHint::Auto => 0,
+            IRInlineHint::Always => 1,
+            IRInlineHint::Never => 2,
+        });
+        self.write_ir_type(output, &function_def.return_type);
+
+        write_uleb(output, function_def.params.len() as u64);
+        for (id, ir_type) in &function_def.params {
+            self.write_optional_symbol(output, *id);
+            self.write_ir_type(output, ir_type);
+        }
+
+        // Labels are sorted so the same module always gives the same bytes
+        let mut labels: Vec<_> = function_def
+            .label_defs
+            .iter()
+            .map(|(label, index)| {
+                (
+                    *index,
+                    SessionGlobals::with_interner(|i| i.get(label).unwrap()),
+                    *label,
+                )
+            })
+            .collect();
+        labels.sort_by_key(|(index, name, _)| (*index, *name));
+        write_uleb(output, labels.len() as u64);
+        for (index, _, label) in labels {
+            self.write_symbol(output, label);
+            write_uleb(output, index);
+        }
+
+        write_uleb(output, function_def.comps.len() as u64);
+        for comp in &function_def.comps {
+            self.write_comp(output, comp);
+        }
+    }
+
+    fn write_extern_decl(&mut self, output: &mut Vec<u8>, extern_decl: &IRItemExternDecl) {
+        self.write_symbol(output, extern_decl.name);
+        write_uleb(output, extern_decl.params.len() as u64);
+        for param in &extern_decl.params {
+            self.write_ir_type(output, param);
+        }
+        self.write_ir_type(output, &extern_decl.return_type);
+    }
+}
+
+fn push_section(output: &mut Vec<u8>, kind: IRBinarySectionKind, payload: &[u8]) {
+    output.push(kind as u8);
+    output.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    output.extend_from_slice(payload);
+}
+
+/// Serializes a module to the binary format
+///
+/// A binary module is laid out as follows (every fixed-size integer is little-endian):
+///
+/// - the magic `SOLIDIR\0`
+/// - the format version (`u16`) and reserved flags (`u16`, always 0)
+/// - the number of sections (`u32`)
+/// - the sections, each made of a kind (`u8`), a payload length (`u32`) and the payload
+/// - a CRC-32 of everything before it (`u32`)
+///
+/// Inside payloads, integers are unsigned LEB128 and symbols are indices into the string table.
+/// The string table section must come before any section using symbols.
+pub fn write_ir_module(module: &IRModule) -> Vec<u8> {
+    let mut writer = IRBinaryWriter {
+        strings: vec![],
+        string_indices: HashMap::new(),
+    };
+
+    let mut items = vec![];
+    write_uleb(&mut items, module.items.len() as u64);
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                items.push(ITEM_FUNCTION_DEF);
+                writer.write_function_def(&mut items, function_def);
+            }
+            IRItemKind::ExternDecl(extern_decl) => {
+                items.push(ITEM_EXTERN_DECL);
+                writer.write_extern_decl(&mut items, extern_decl);
+            }
+        }
+    }
+
+    let mut strings = vec![];
+    write_uleb(&mut strings, writer.strings.len() as u64);
+    for symbol in &writer.strings {
+        SessionGlobals::with_interner(|i| {
+            let string = i.get(symbol).unwrap();
+            write_uleb(&mut strings, string.len() as u64);
+            strings.extend_from_slice(string.as_bytes());
+        });
+    }
+
+    let mut output = vec![];
+    output.extend_from_slice(IR_BINARY_MAGIC);
+    output.extend_from_slice(&IR_BINARY_VERSION.to_le_bytes());
+    output.extend_from_slice(&0u16.to_le_bytes());
+    output.extend_from_slice(&2u32.to_le_bytes());
+    push_section(&mut output, IRBinarySectionKind::Strings, &strings);
+    push_section(&mut output, IRBinarySectionKind::Items, &items);
+    let checksum = crc32(&output);
+    output.extend_from_slice(&checksum.to_le_bytes());
+
+    output
+}
+
+struct IRBinaryReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: Vec<Symbol>,
+}
+
+impl<'a> IRBinaryReader<'a> {
+    fn error(&self, message: &str) -> IRBinaryError {
+        IRBinaryError {
+            message: message.to_owned(),
+            offset: self.position,
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> IRBinaryResult<&'a [u8]> {
+        if self.bytes.len() - self.position < len {
+            return Err(self.error("Unexpected end of input"));
+        }
+        let bytes = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> IRBinaryResult<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> IRBinaryResult<u16> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> IRBinaryResult<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_uleb(&mut self) -> IRBinaryResult<u64> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift >= 64 || (shift == 63 && byte & 0x7E != 0) {
+                return Err(self.error("Integer is too large"));
+            }
+            result |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads a count, checking it against the remaining input so corrupted counts fail early
+    fn read_count(&mut self) -> IRBinaryResult<usize> {
+        let count = self.read_uleb()?;
+        if count > (self.bytes.len() - self.position) as u64 {
+            return Err(self.error("Count is larger than the remaining input"));
+        }
+        Ok(count as usize)
+    }
+
+    fn read_symbol(&mut self) -> IRBinaryResult<Symbol> {
+        let index = self.read_uleb()?;
+        self.strings
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| self.error("String index is out of bounds"))
+    }
+
+    fn read_optional_symbol(&mut self) -> IRBinaryResult<Option<Symbol>> {
+        let index = self.read_uleb()?;
+        if index == 0 {
+            return Ok(None);
+        }
+        self.strings
+            .get(index as usize - 1)
+            .copied()
+            .map(Some)
+            .ok_or_else(|| self.error("String index is out of bounds"))
+    }
+
+    fn read_ir_value(&mut self) -> IRBinaryResult<IRValue> {
+        Ok(IRValue {
+            id: self.read_symbol()?,
+        })
+    }
+
+    fn read_ir_type(&mut self) -> IRBinaryResult<IRType> {
+        Ok(IRType {
+            size: self.read_uleb()?,
+            align: self.read_uleb()?,
+            float: self.read_u8()? != 0,
+        })
+    }
+
+    fn read_strings(&mut self) -> IRBinaryResult<()> {
+        let count = self.read_count()?;
+        for _ in 0..count {
+            let len = self.read_count()?;
+            let bytes = self.read_bytes(len)?;
+            let string =
+                std::str::from_utf8(bytes).map_err(|_| self.error("String is not valid UTF-8"))?;
+            let symbol = SessionGlobals::with_interner_mut(|i| i.intern(string));
+            self.strings.push(symbol);
+        }
+        Ok(())
+    }
+
+    fn read_comp(&mut self) -> IRBinaryResult<IRComp> {
+        let id = self.read_optional_symbol()?;
+        let kind = match self.read_u8()? {
+            COMP_FUNCTION_CALL => {
+                let name = self.read_symbol()?;
+                let count = self.read_count()?;
+                let mut args = Vec::with_capacity(count);
+                for _ in 0..count {
+                    args.push(self.read_ir_value()?);
+                }
+                IRCompKind::FunctionCall(IRCompFunctionCall { name, args })
+            }
+            COMP_BINARY_OPERATION => {
+                let kind = binary_operation_kind(self.read_u8()?)
+                    .ok_or_else(|| self.error("Unknown binary operation"))?;
+                IRCompKind::BinaryOperation(IRCompBinaryOperation {
+                    kind,
+                    left_operand: self.read_ir_value()?,
+                    right_operand: self.read_ir_value()?,
+                })
+            }
+            COMP_UNARY_OPERATION => {
+                let kind = unary_operation_kind(self.read_u8()?)
+                    .ok_or_else(|| self.error("Unknown unary operation"))?;
+                IRCompKind::UnaryOperation(IRCompUnaryOperation {
+                    kind,
+                    operand: self.read_ir_value()?,
+                })
+            }
+            COMP_CONVERSION => {
+                let kind = conversion_kind(self.read_u8()?)
+                    .ok_or_else(|| self.error("Unknown conversion"))?;
+                IRCompKind::Conversion(IRCompConversion {
+                    kind,
+                    size: self.read_uleb()?,
+                    operand: self.read_ir_value()?,
+                })
+            }
+            COMP_CONSTANT => {
+                let len = self.read_count()?;
+                IRCompKind::Constant(IRCompConstant {
+                    bytes: self.read_bytes(len)?.to_vec(),
+                })
+            }
+            COMP_ALLOC => IRCompKind::Alloc(self.read_ir_type()?),
+            COMP_STORE => IRCompKind::Store(
+                self.read_ir_type()?,
+                self.read_ir_value()?,
+                self.read_ir_value()?,
+            ),
+            COMP_LOAD => IRCompKind::Load(self.read_ir_type()?, self.read_ir_value()?),
+            COMP_OFFSET_STORE => IRCompKind::OffsetStore(
+                self.read_ir_type()?,
+                self.read_ir_value()?,
+                self.read_ir_value()?,
+                self.read_uleb()?,
+            ),
+            COMP_OFFSET_LOAD => IRCompKind::OffsetLoad(
+                self.read_ir_type()?,
+                self.read_ir_value()?,
+                self.read_uleb()?,
+            ),
+            COMP_RETURN => IRCompKind::Return(self.read_ir_value()?),
+            COMP_IF => IRCompKind::If(self.read_ir_value()?, self.read_symbol()?),
+            COMP_JMP => IRCompKind::Jmp(self.read_symbol()?),
+            _ => return Err(self.error("Unknown comp kind")),
+        };
+        let location = self.read_location()?;
+        Ok(IRComp { kind, id, location })
+    }
+
+    fn read_location(&mut self) -> IRBinaryResult<Option<IRDebugLocation>> {
+        let file = match self.read_optional_symbol()? {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        Ok(Some(IRDebugLocation {
+            file,
+            line: self.read_uleb()?,
+            column: self.read_uleb()?,
+            function: self.read_symbol()?,
+        }))
+    }
+
+    fn read_function_def(&mut self) -> IRBinaryResult<IRItemFunctionDef> {
+        let name = self.read_symbol()?;
+        let visibility = match self.read_u8()? {
+            0 => IRVisibility::Exported,
+            1 => IRVisibility::Internal,
+            _ => return Err(self.error("Unknown visibility")),
+        };
+        let inline_hint = match self.read_u8()? {
+            0 => IRInlineHint::Auto,
+            1 => IRInlineHint::Always,
+            2 => IRInlineHint::Never,
+            _ => return Err(self.error("Unknown inline hint")),
+        };
+        let return_type = self.read_ir_type()?;
+
+        let count = self.read_count()?;
+        let mut params = Vec::with_capacity(count);
+        for _ in 0..count {
+            params.push((self.read_optional_symbol()?, self.read_ir_type()?));
+        }
+
+        let count = self.read_count()?;
+        let mut label_defs = HashMap::new();
+        for _ in 0..count {
+            let label = self.read_symbol()?;
+            if label_defs.insert(label, self.read_uleb()?).is_some() {
+                return Err(self.error("Label is defined twice"));
+            }
+        }
+
+        let count = self.read_count()?;
+        let mut comps = Vec::with_capacity(count);
+        for _ in 0..count {
+            comps.push(self.read_comp()?);
+        }
+        if label_defs.values().any(|index| *index > comps.len() as u64) {
+            return Err(self.error("Label is defined past the end of its function"));
+        }
+
+        Ok(IRItemFunctionDef {
+            name,
+            visibility,
+            inline_hint,
+            return_type,
+            params,
+            comps,
+            label_defs,
+        })
+    }
+
+    fn read_extern_decl(&mut self) -> IRBinaryResult<IRItemExternDecl> {
+        let name = self.read_symbol()?;
+        let count = self.read_count()?;
+        let mut params = Vec::with_capacity(count);
+        for _ in 0..count {
+            params.push(self.read_ir_type()?);
+        }
+
+        Ok(IRItemExternDecl {
+            name,
+            params,
+            return_type: self.read_ir_type()?,
+        })
+    }
+}
+
+/// Deserializes a module from the binary format
+///
+/// The checksum is verified before anything else, so corrupted input is reported as such.
+pub fn read_ir_module(bytes: &[u8]) -> IRBinaryResult<IRModule> {
+    let header_len = IR_BINARY_MAGIC.len() + 8;
+    if !is_binary_ir_module(bytes) {
+        return Err(IRBinaryError {
+            message: "Input is not a binary IR module".to_owned(),
+            offset: 0,
+        });
+    }
+    if bytes.len() < header_len + 4 {
+        return Err(IRBinaryError {
+            message: "Unexpected end of input".to_owned(),
+            offset: bytes.len(),
+        });
+    }
+    let (content, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32(content) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(IRBinaryError {
+            message: "Checksum mismatch".to_owned(),
+            offset: content.len(),
+        });
+    }
+
+    let mut reader = IRBinaryReader {
+        bytes: content,
+        position: IR_BINARY_MAGIC.len(),
+        strings: vec![],
+    };
+    let version = reader.read_u16()?;
+    if version != IR_BINARY_VERSION {
+        return Err(reader.error(&format!(
+            "Unsupported version {} (expected {})",
+            version, IR_BINARY_VERSION
+        )));
+    }
+    if reader.read_u16()? != 0 {
+        return Err(reader.error("Unknown flags"));
+    }
+
+    let mut items = vec![];
+    let section_count = reader.read_u32()?;
+    for _ in 0..section_count {
+        let kind_offset = reader.position;
+        let kind = reader.read_u8()?;
+        let len = reader.read_u32()? as usize;
+        let end = reader.position.saturating_add(len);
+        if end > content.len() {
+            return Err(reader.error("Section goes past the end of input"));
+        }
+
+        match IRBinarySectionKind::from_u8(kind) {
+            Some(IRBinarySectionKind::Strings) => reader.read_strings()?,
+            Some(IRBinarySectionKind::Items) => {
+                let count = reader.read_count()?;
+                for _ in 0..count {
+                    let kind = match reader.read_u8()? {
+                        ITEM_FUNCTION_DEF => IRItemKind::FunctionDef(reader.read_function_def()?),
+                        ITEM_EXTERN_DECL => IRItemKind::ExternDecl(reader.read_extern_decl()?),
+                        _ => return Err(reader.error("Unknown item kind")),
+                    };
+                    items.push(IRItem { kind });
+                }
+            }
+            Some(IRBinarySectionKind::Globals) => {
+                return Err(IRBinaryError {
+                    message: "Globals sections are not supported yet".to_owned(),
+                    offset: kind_offset,
+                })
+            }
+            None => {
+                return Err(IRBinaryError {
+                    message: format!("Unknown section kind {}", kind),
+                    offset: kind_offset,
+                })
+            }
+        }
+
+        if reader.position != end {
+            return Err(reader.error("Section length does not match its content"));
+        }
+    }
+    if reader.position != content.len() {
+        return Err(reader.error("Unexpected data after the last section"));
+    }
+
+    Ok(IRModule { items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+    use crate::ir::assembly::assembly_for_ir_modules;
+
+    /// Written like `assembly_for_ir_modules` dumps it, with the externs around the functions
+    const MODULE: &str = "\
+extern puts: (8 8) -> (4 4)
+
+#[internal]
+#[inline]
+fn half: %x := (8 8) %scale := (8 8 float) -> (8 8)
+    %f := convert utof 8 %x
+    %h := binop fdiv %f %scale
+    %r := convert ftou 8 %h
+    %low := convert trunc 4 %r
+    %r := convert sext 8 %low
+    return %r
+endfn
+
+#[noinline]
+fn main: -> (8 8)
+    %n := const 8 10 0 0 0 0 0 0 0
+    %slot := alloc (16 8)
+    offsetstore (8 8) %slot %n 8
+    :loop
+    %v := offsetload (8 8) %slot 8
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %done := binop == %v %zero
+    if %done end
+    %two := const 8 0 0 0 0 0 0 0 64
+    %v := call half 2 %v %two
+    %v := unop bitnot %v
+    %v := unop bitnot %v
+    store (8 8) %slot %v
+    jmp loop
+    :end
+    %p := load (8 8) %slot
+    call puts 1 %p
+    return %zero
+endfn
+
+extern exit: (4 4) -> (0 1)
+
+";
+
+    #[test]
+    fn round_trip_keeps_the_module() {
+        SessionGlobals::create(|| {
+            let bytes = write_ir_module(&assemble_ir_module(MODULE).unwrap());
+            let module = read_ir_module(&bytes).unwrap();
+            assert_eq!(assembly_for_ir_modules(&module), MODULE);
+            assert_eq!(write_ir_module(&module), bytes);
+        });
+    }
+
+    #[test]
+    fn round_trip_keeps_the_locations() {
+        let src = "\
+fn main: -> (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0 ; @src/main.solid:2:5 main
+    %slot := alloc (8 8)
+    store (8 8) %slot %zero
+    %v := load (8 8) %slot ; @src/main.solid:3:5 main
+    return %v ; @src/main.solid:1:18 main
+endfn
+
+";
+        SessionGlobals::create(|| {
+            let bytes = write_ir_module(&assemble_ir_module(src).unwrap());
+            let module = read_ir_module(&bytes).unwrap();
+            assert_eq!(assembly_for_ir_modules(&module), src);
+        });
+    }
+
+    #[test]
+    fn corrupted_module_is_rejected() {
+        SessionGlobals::create(|| {
+            let mut bytes = write_ir_module(&assemble_ir_module(MODULE).unwrap());
+            let middle = bytes.len() / 2;
+            bytes[middle] ^= 1;
+            let error = read_ir_module(&bytes).err().unwrap();
+            assert_eq!(error.message, "Checksum mismatch");
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use crate::globals::Symbol;
+use crate::globals::{SessionGlobals, Symbol};
 use crate::ir::{IRType, IRValue};
 
 #[derive(Debug, Clone)]
@@ -26,6 +26,87 @@ pub enum IRCompBinaryOperationKind {
     Lesser,
     GreaterEqual,
     LesserEqual,
+
+    /// Operations on two's complement integers, the shift is arithmetic
+    SignedDiv,
+    SignedMod,
+    SignedRShift,
+
+    /// Comparisons of two's complement integers
+    SignedGreater,
+    SignedLesser,
+    SignedGreaterEqual,
+    SignedLesserEqual,
+
+    /// Operations on IEEE 754 floats of 4 or 8 bytes
+    FloatPlus,
+    FloatMinus,
+    FloatMul,
+    FloatDiv,
+
+    /// Comparisons of IEEE 754 floats, false when an operand is NaN (except for FloatNotEqual)
+    FloatEqual,
+    FloatNotEqual,
+    FloatGreater,
+    FloatLesser,
+    FloatGreaterEqual,
+    FloatLesserEqual,
+}
+
+impl IRCompBinaryOperationKind {
+    /// Whether the operands are floats
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Self::FloatPlus
+                | Self::FloatMinus
+                | Self::FloatMul
+                | Self::FloatDiv
+                | Self::FloatEqual
+                | Self::FloatNotEqual
+                | Self::FloatGreater
+                | Self::FloatLesser
+                | Self::FloatGreaterEqual
+                | Self::FloatLesserEqual
+        )
+    }
+
+    /// Whether the operands are two's complement integers
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Self::SignedDiv
+                | Self::SignedMod
+                | Self::SignedRShift
+                | Self::SignedGreater
+                | Self::SignedLesser
+                | Self::SignedGreaterEqual
+                | Self::SignedLesserEqual
+        )
+    }
+
+    /// Whether the operation yields a bool (a single byte) rather than a value of its operands type
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Equal
+                | Self::NotEqual
+                | Self::Greater
+                | Self::Lesser
+                | Self::GreaterEqual
+                | Self::LesserEqual
+                | Self::SignedGreater
+                | Self::SignedLesser
+                | Self::SignedGreaterEqual
+                | Self::SignedLesserEqual
+                | Self::FloatEqual
+                | Self::FloatNotEqual
+                | Self::FloatGreater
+                | Self::FloatLesser
+                | Self::FloatGreaterEqual
+                | Self::FloatLesserEqual
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +121,7 @@ pub enum IRCompUnaryOperationKind {
     BoolNot,
     BitNot,
     SignedNegation,
+    FloatNegation,
 }
 
 #[derive(Debug, Clone)]
@@ -48,6 +130,41 @@ pub struct IRCompUnaryOperation {
     pub operand: IRValue,
 }
 
+#[derive(Debug, Clone)]
+pub enum IRCompConversionKind {
+    UnsignedToFloat,
+    SignedToFloat,
+    /// Rounds towards zero, floats out of the range of the integer give an unspecified value
+    FloatToUnsigned,
+    /// Rounds towards zero, floats out of the range of the integer give an unspecified value
+    FloatToSigned,
+    FloatToFloat,
+    /// Keeps the low bytes of a wider integer
+    Truncate,
+    /// Widens an unsigned integer
+    ZeroExtend,
+    /// Widens a two's complement integer
+    SignExtend,
+}
+
+impl IRCompConversionKind {
+    /// Whether the result is a float
+    pub fn is_to_float(&self) -> bool {
+        matches!(
+            self,
+            Self::UnsignedToFloat | Self::SignedToFloat | Self::FloatToFloat
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IRCompConversion {
+    pub kind: IRCompConversionKind,
+    pub operand: IRValue,
+    /// Size of the result, floats are 4 or 8 bytes
+    pub size: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct IRCompConstant {
     pub bytes: Vec<u8>,
@@ -61,6 +178,8 @@ pub enum IRCompKind {
     BinaryOperation(IRCompBinaryOperation),
     /// Yields the result of the operation
     UnaryOperation(IRCompUnaryOperation),
+    /// Yields the operand converted to another integer or float size
+    Conversion(IRCompConversion),
     /// Yields the constant
     Constant(IRCompConstant),
     /// Yields a pointer to a new location on the stack
@@ -81,8 +200,29 @@ pub enum IRCompKind {
     Jmp(Symbol),
 }
 
+/// Source code a comp was lowered from
+#[derive(Debug, Clone)]
+pub struct IRDebugLocation {
+    /// Path of the source file
+    pub file: Symbol,
+    /// Both start at 1
+    pub line: u64,
+    pub column: u64,
+    /// Path of the source function, which the IR name of the function may mangle
+    pub function: Symbol,
+}
+
+impl IRDebugLocation {
+    /// `file:line:column`
+    pub fn position(&self) -> String {
+        let file = SessionGlobals::with_interner(|i| i.get(&self.file).unwrap().to_owned());
+        format!("{}:{}:{}", file, self.line, self.column)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IRComp {
     pub kind: IRCompKind,
     pub id: Option<Symbol>,
+    pub location: Option<IRDebugLocation>,
 }
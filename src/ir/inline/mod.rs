@@ -0,0 +1,713 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{IRComp, IRCompKind};
+use crate::ir::{IRInlineHint, IRItemFunctionDef, IRItemKind, IRModule, IRType, IRValue};
+use std::collections::{HashMap, HashSet};
+
+/// Functions with at most this many comps are inlined when they have no inline hint
+pub const DEFAULT_INLINE_SIZE_THRESHOLD: usize = 16;
+
+/// Copy of the parts of a callee needed to inline it
+struct InlineCandidate {
+    name: Symbol,
+    inline_hint: IRInlineHint,
+    params: Vec<(Option<Symbol>, IRType)>,
+    return_type: IRType,
+    comps: Vec<IRComp>,
+    label_defs: HashMap<Symbol, u64>,
+}
+
+impl InlineCandidate {
+    fn from_function_def(function_def: &IRItemFunctionDef) -> Self {
+        Self {
+            name: function_def.name,
+            inline_hint: function_def.inline_hint,
+            params: function_def.params.clone(),
+            return_type: function_def.return_type.clone(),
+            comps: function_def.comps.clone(),
+            label_defs: function_def.label_defs.clone(),
+        }
+    }
+
+    /// Index of the first comp that may be executed more than once per call
+    fn straight_line_prefix_len(&self) -> usize {
+        let first_label = self
+            .label_defs
+            .values()
+            .map(|index| *index as usize)
+            .min()
+            .unwrap_or(self.comps.len());
+        let first_jump = self
+            .comps
+            .iter()
+            .position(|comp| matches!(comp.kind, IRCompKind::If(..) | IRCompKind::Jmp(_)))
+            .unwrap_or(self.comps.len());
+
+        first_label.min(first_jump)
+    }
+}
+
+fn function_defs(module: &IRModule) -> HashMap<Symbol, &IRItemFunctionDef> {
+    module
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            IRItemKind::FunctionDef(function_def) => Some((function_def.name, function_def)),
+            IRItemKind::ExternDecl(_) => None,
+        })
+        .collect()
+}
+
+fn callees(function_def: &IRItemFunctionDef) -> impl Iterator<Item = Symbol> + '_ {
+    function_def
+        .comps
+        .iter()
+        .filter_map(|comp| match &comp.kind {
+            IRCompKind::FunctionCall(function_call) => Some(function_call.name),
+            _ => None,
+        })
+}
+
+/// Returns the functions that can (directly or indirectly) call themselves
+fn recursive_functions(functions: &HashMap<Symbol, &IRItemFunctionDef>) -> HashSet<Symbol> {
+    let mut result = HashSet::new();
+
+    for name in functions.keys() {
+        let mut stack = vec![*name];
+        let mut visited = HashSet::new();
+        while let Some(current) = stack.pop() {
+            if let Some(function_def) = functions.get(&current) {
+                for callee in callees(function_def) {
+                    if callee == *name {
+                        result.insert(*name);
+                    }
+                    if visited.insert(callee) {
+                        stack.push(callee);
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Orders the functions so that callees come before their callers (ignoring cycles)
+fn post_order(module: &IRModule, functions: &HashMap<Symbol, &IRItemFunctionDef>) -> Vec<Symbol> {
+    fn visit(
+        name: Symbol,
+        functions: &HashMap<Symbol, &IRItemFunctionDef>,
+        visited: &mut HashSet<Symbol>,
+        result: &mut Vec<Symbol>,
+    ) {
+        if !visited.insert(name) {
+            return;
+        }
+        if let Some(function_def) = functions.get(&name) {
+            for callee in callees(function_def) {
+                visit(callee, functions, visited, result);
+            }
+            result.push(name);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut result = vec![];
+    for item in &module.items {
+        match &item.kind {
+            IRItemKind::FunctionDef(function_def) => {
+                visit(function_def.name, functions, &mut visited, &mut result)
+            }
+            IRItemKind::ExternDecl(_) => {}
+        }
+    }
+    result
+}
+
+struct Inliner {
+    size_threshold: usize,
+    recursive: HashSet<Symbol>,
+    candidates: HashMap<Symbol, InlineCandidate>,
+    inlined_count: u64,
+}
+
+impl Inliner {
+    fn should_inline(&self, caller: Symbol, callee: Symbol, args_len: usize) -> bool {
+        if caller == callee || self.recursive.contains(&callee) {
+            return false;
+        }
+
+        let candidate = match self.candidates.get(&callee) {
+            Some(candidate) => candidate,
+            None => return false,
+        };
+
+        if candidate.params.len() != args_len {
+            return false;
+        }
+
+        match candidate.inline_hint {
+            IRInlineHint::Auto => candidate.comps.len() <= self.size_threshold,
+            IRInlineHint::Always => true,
+            IRInlineHint::Never => false,
+        }
+    }
+
+    fn fresh_symbol(&self, callee: Symbol, name: &str) -> Symbol {
+        SessionGlobals::with_interner_mut(|i| {
+            let name = format!(
+                "inl{}_{}_{}",
+                self.inlined_count,
+                i.get(&callee).unwrap(),
+                name
+            );
+            i.intern(&name)
+        })
+    }
+
+    fn fresh_symbol_for(&self, callee: Symbol, symbol: Symbol) -> Symbol {
+        let name = SessionGlobals::with_interner(|i| i.get(&symbol).unwrap());
+        self.fresh_symbol(callee, name)
+    }
+
+    fn inline_into(&mut self, function_def: &mut IRItemFunctionDef) {
+        let old_comps = std::mem::take(&mut function_def.comps);
+        let old_len = old_comps.len();
+
+        let mut hoisted = vec![];
+        let mut comps = vec![];
+        // Maps old comp indices (including the end of the function) to new comp indices
+        let mut new_indices = Vec::with_capacity(old_len + 1);
+        let mut label_defs = HashMap::new();
+
+        for comp in old_comps {
+            new_indices.push(comps.len());
+
+            let function_call = match &comp.kind {
+                IRCompKind::FunctionCall(function_call) => function_call,
+                _ => {
+                    comps.push(comp);
+                    continue;
+                }
+            };
+
+            if !self.should_inline(
+                function_def.name,
+                function_call.name,
+                function_call.args.len(),
+            ) {
+                comps.push(comp);
+                continue;
+            }
+
+            let candidate = &self.candidates[&function_call.name];
+            let callee = candidate.name;
+
+            // Renaming of values and labels
+            let mut values: HashMap<Symbol, Symbol> = HashMap::new();
+            // Values can be redefined, so the params are bound to copies of the args rather
+            // than to the args themselves
+            for (index, ((param, ir_type), arg)) in candidate
+                .params
+                .iter()
+                .zip(function_call.args.iter())
+                .enumerate()
+            {
+                if let Some(param) = param {
+                    let slot = self.fresh_symbol(callee, &format!("arg{}", index));
+                    let copy = self.fresh_symbol_for(callee, *param);
+                    hoisted.push(IRComp {
+                        kind: IRCompKind::Alloc(ir_type.clone()),
+                        id: Some(slot),
+                        location: comp.location.clone(),
+                    });
+                    comps.push(IRComp {
+                        kind: IRCompKind::Store(ir_type.clone(), IRValue { id: slot }, arg.clone()),
+                        id: None,
+                        location: comp.location.clone(),
+                    });
+                    comps.push(IRComp {
+                        kind: IRCompKind::Load(ir_type.clone(), IRValue { id: slot }),
+                        id: Some(copy),
+                        location: comp.location.clone(),
+                    });
+                    values.insert(*param, copy);
+                }
+            }
+            let mut labels: HashMap<Symbol, Symbol> = HashMap::new();
+            for label in candidate.label_defs.keys() {
+                labels.insert(*label, self.fresh_symbol_for(callee, *label));
+            }
+
+            // A single trailing return of a value defined exactly once can be wired directly
+            // to the result of the call, everything else goes through a stack slot
+            let returns = candidate
+                .comps
+                .iter()
+                .filter(|c| matches!(c.kind, IRCompKind::Return(_)))
+                .count();
+            let direct_result = match (comp.id, candidate.comps.last()) {
+                (
+                    Some(result),
+                    Some(IRComp {
+                        kind: IRCompKind::Return(value),
+                        ..
+                    }),
+                ) if returns == 1
+                    && candidate
+                        .comps
+                        .iter()
+                        .filter(|c| c.id == Some(value.id))
+                        .count()
+                        == 1
+                    && !values.contains_key(&value.id)
+                    && !function_call.args.iter().any(|arg| arg.id == result) =>
+                {
+                    values.insert(value.id, result);
+                    true
+                }
+                (
+                    None,
+                    Some(IRComp {
+                        kind: IRCompKind::Return(_),
+                        ..
+                    }),
+                ) if returns == 1 => true,
+                _ => false,
+            };
+
+            let mut rename_value = |inliner: &Inliner, value: &IRValue| -> IRValue {
+                let id = *values
+                    .entry(value.id)
+                    .or_insert_with(|| inliner.fresh_symbol_for(callee, value.id));
+                IRValue { id }
+            };
+
+            let end_label = self.fresh_symbol(callee, "end");
+            let result_slot = match comp.id {
+                Some(_) if !direct_result => {
+                    let slot = self.fresh_symbol(callee, "ret");
+                    hoisted.push(IRComp {
+                        kind: IRCompKind::Alloc(candidate.return_type.clone()),
+                        id: Some(slot),
+                        location: comp.location.clone(),
+                    });
+                    Some(IRValue { id: slot })
+                }
+                _ => None,
+            };
+
+            let prefix_len = candidate.straight_line_prefix_len();
+            let base = comps.len();
+            let mut callee_indices = Vec::with_capacity(candidate.comps.len() + 1);
+            for (index, callee_comp) in candidate.comps.iter().enumerate() {
+                callee_indices.push(comps.len() - base);
+
+                let id = callee_comp
+                    .id
+                    .map(|id| rename_value(self, &IRValue { id }).id);
+                let kind = match &callee_comp.kind {
+                    IRCompKind::FunctionCall(call) => {
+                        let mut call = call.clone();
+                        call.args = call.args.iter().map(|a| rename_value(self, a)).collect();
+                        IRCompKind::FunctionCall(call)
+                    }
+                    IRCompKind::BinaryOperation(operation) => {
+                        let mut operation = operation.clone();
+                        operation.left_operand = rename_value(self, &operation.left_operand);
+                        operation.right_operand = rename_value(self, &operation.right_operand);
+                        IRCompKind::BinaryOperation(operation)
+                    }
+                    IRCompKind::UnaryOperation(operation) => {
+                        let mut operation = operation.clone();
+                        operation.operand = rename_value(self, &operation.operand);
+                        IRCompKind::UnaryOperation(operation)
+                    }
+                    IRCompKind::Conversion(conversion) => {
+                        let mut conversion = conversion.clone();
+                        conversion.operand = rename_value(self, &conversion.operand);
+                        IRCompKind::Conversion(conversion)
+                    }
+                    IRCompKind::Constant(constant) => IRCompKind::Constant(constant.clone()),
+                    IRCompKind::Alloc(ir_type) => {
+                        if index < prefix_len {
+                            hoisted.push(IRComp {
+                                kind: IRCompKind::Alloc(ir_type.clone()),
+                                id,
+                                location: callee_comp.location.clone(),
+                            });
+                            continue;
+                        }
+                        IRCompKind::Alloc(ir_type.clone())
+                    }
+                    IRCompKind::Store(ir_type, location, value) => IRCompKind::Store(
+                        ir_type.clone(),
+                        rename_value(self, location),
+                        rename_value(self, value),
+                    ),
+                    IRCompKind::Load(ir_type, location) => {
+                        IRCompKind::Load(ir_type.clone(), rename_value(self, location))
+                    }
+                    IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                        IRCompKind::OffsetStore(
+                            ir_type.clone(),
+                            rename_value(self, location),
+                            rename_value(self, value),
+                            *offset,
+                        )
+                    }
+                    IRCompKind::OffsetLoad(ir_type, location, offset) => IRCompKind::OffsetLoad(
+                        ir_type.clone(),
+                        rename_value(self, location),
+                        *offset,
+                    ),
+                    IRCompKind::Return(value) => {
+                        if direct_result {
+                            continue;
+                        }
+                        if let Some(result_slot) = &result_slot {
+                            comps.push(IRComp {
+                                kind: IRCompKind::Store(
+                                    candidate.return_type.clone(),
+                                    result_slot.clone(),
+                                    rename_value(self, value),
+                                ),
+                                id: None,
+                                location: callee_comp.location.clone(),
+                            });
+                        }
+                        IRCompKind::Jmp(end_label)
+                    }
+                    IRCompKind::If(value, label) => {
+                        IRCompKind::If(rename_value(self, value), labels[label])
+                    }
+                    IRCompKind::Jmp(label) => IRCompKind::Jmp(labels[label]),
+                };
+
+                comps.push(IRComp {
+                    kind,
+                    id,
+                    location: callee_comp.location.clone(),
+                });
+            }
+            callee_indices.push(comps.len() - base);
+
+            for (label, index) in &candidate.label_defs {
+                label_defs.insert(
+                    labels[label],
+                    (base + callee_indices[*index as usize]) as u64,
+                );
+            }
+
+            if !direct_result {
+                label_defs.insert(end_label, comps.len() as u64);
+                if let (Some(id), Some(result_slot)) = (comp.id, result_slot) {
+                    comps.push(IRComp {
+                        kind: IRCompKind::Load(candidate.return_type.clone(), result_slot),
+                        id: Some(id),
+                        location: comp.location.clone(),
+                    });
+                }
+            }
+
+            self.inlined_count += 1;
+        }
+        new_indices.push(comps.len());
+
+        for (label, index) in &function_def.label_defs {
+            label_defs.insert(*label, new_indices[*index as usize] as u64);
+        }
+
+        // Hoisted allocations are placed at the start of the function, before every label
+        let hoisted_len = hoisted.len() as u64;
+        for index in label_defs.values_mut() {
+            *index += hoisted_len;
+        }
+        hoisted.extend(comps);
+
+        function_def.comps = hoisted;
+        function_def.label_defs = label_defs;
+    }
+}
+
+/// Inlines calls to small, non-recursive functions of the module into their callers
+///
+/// Functions marked `#[inline]` are inlined regardless of their size and functions marked
+/// `#[noinline]` are never inlined. Callees are processed before their callers, so calls
+/// inside of inlined functions are inlined as well.
+pub fn inline_ir_module(module: &mut IRModule, size_threshold: usize) {
+    let (order, recursive) = {
+        let functions = function_defs(module);
+        (
+            post_order(module, &functions),
+            recursive_functions(&functions),
+        )
+    };
+
+    let mut inliner = Inliner {
+        size_threshold,
+        recursive,
+        candidates: HashMap::new(),
+        inlined_count: 0,
+    };
+
+    for name in order {
+        for item in &mut module.items {
+            match &mut item.kind {
+                IRItemKind::FunctionDef(function_def) if function_def.name == name => {
+                    inliner.inline_into(function_def);
+                    inliner
+                        .candidates
+                        .insert(name, InlineCandidate::from_function_def(function_def));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+    use crate::ir::interpreter::IRInterpreter;
+    use std::cell::{Cell, RefCell};
+
+    /// Inlines the module and runs its `main`, which must not call anything anymore
+    fn inline_and_run(src: &str) -> u64 {
+        let result = Cell::new(0);
+        SessionGlobals::create(|| {
+            let mut module = assemble_ir_module(src).unwrap();
+            inline_ir_module(&mut module, DEFAULT_INLINE_SIZE_THRESHOLD);
+
+            let main = SessionGlobals::with_interner_mut(|i| i.intern("main"));
+            for item in &module.items {
+                if let IRItemKind::FunctionDef(function_def) = &item.kind {
+                    if function_def.name == main {
+                        assert_eq!(callees(function_def).count(), 0);
+                    }
+                }
+            }
+
+            let mut interpreter = IRInterpreter::new();
+            interpreter.load_module(module);
+            let value = unsafe { interpreter.call_function(main, &[]) }.unwrap();
+            result.set(value.into_u64());
+        });
+        result.get()
+    }
+
+    /// Inlines the module and returns the names of the functions its `main` still calls, with
+    /// the number of comps of `main`
+    fn inline_main(src: &str) -> (Vec<String>, usize) {
+        let result = RefCell::new((vec![], 0));
+        SessionGlobals::create(|| {
+            let mut module = assemble_ir_module(src).unwrap();
+            inline_ir_module(&mut module, DEFAULT_INLINE_SIZE_THRESHOLD);
+
+            let main = SessionGlobals::with_interner_mut(|i| i.intern("main"));
+            for item in &module.items {
+                if let IRItemKind::FunctionDef(function_def) = &item.kind {
+                    if function_def.name == main {
+                        let calls = callees(function_def)
+                            .map(|callee| {
+                                SessionGlobals::with_interner(|i| i.get(&callee).unwrap())
+                                    .to_owned()
+                            })
+                            .collect();
+                        *result.borrow_mut() = (calls, function_def.comps.len());
+                    }
+                }
+            }
+        });
+        result.into_inner()
+    }
+
+    /// Function returning 1 with one more comp than the default threshold allows
+    fn large_function(attributes: &str) -> String {
+        format!(
+            "{}fn large: -> (8 8)\n{}    return %x\nendfn\n",
+            attributes,
+            "    %x := const 8 1 0 0 0 0 0 0 0\n".repeat(DEFAULT_INLINE_SIZE_THRESHOLD)
+        )
+    }
+
+    #[test]
+    fn param_reassignment_does_not_leak_into_the_caller() {
+        let src = "
+fn inc: %a := (8 8) -> (8 8)
+    %one := const 8 1 0 0 0 0 0 0 0
+    %a := binop + %a %one
+    return %a
+endfn
+
+fn main: -> (8 8)
+    %x := const 8 5 0 0 0 0 0 0 0
+    %y := call inc 1 %x
+    %sum := binop + %x %y
+    return %sum
+endfn
+";
+        assert_eq!(inline_and_run(src), 11);
+    }
+
+    #[test]
+    fn result_named_like_an_arg() {
+        let src = "
+fn f: %a := (8 8) %p := (8 8) -> (8 8)
+    %r := const 8 1 0 0 0 0 0 0 0
+    %s := binop + %a %a
+    store (8 8) %p %s
+    return %r
+endfn
+
+fn main: -> (8 8)
+    %p := alloc (8 8)
+    %x := const 8 20 0 0 0 0 0 0 0
+    %x := call f 2 %x %p
+    %v := load (8 8) %p
+    %sum := binop + %v %x
+    return %sum
+endfn
+";
+        assert_eq!(inline_and_run(src), 41);
+    }
+
+    #[test]
+    fn callee_with_a_loop() {
+        let src = "
+fn sum_to: %n := (8 8) -> (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %one := const 8 1 0 0 0 0 0 0 0
+    %total := alloc (8 8)
+    store (8 8) %total %zero
+    :loop
+    %done := binop == %n %zero
+    if %done end
+    %t := load (8 8) %total
+    %t := binop + %t %n
+    store (8 8) %total %t
+    %n := binop - %n %one
+    jmp loop
+    :end
+    %result := load (8 8) %total
+    return %result
+endfn
+
+fn main: -> (8 8)
+    %n := const 8 4 0 0 0 0 0 0 0
+    %a := call sum_to 1 %n
+    %b := call sum_to 1 %n
+    %sum := binop + %a %b
+    %sum := binop + %sum %n
+    return %sum
+endfn
+";
+        assert_eq!(inline_and_run(src), 24);
+    }
+
+    #[test]
+    fn inline_hint_overrides_the_size_threshold() {
+        let src = large_function("#[inline]\n")
+            + "
+fn main: -> (8 8)
+    %x := call large 0
+    return %x
+endfn
+";
+        assert_eq!(inline_main(&src).0, Vec::<String>::new());
+        assert_eq!(inline_and_run(&src), 1);
+    }
+
+    #[test]
+    fn callees_above_the_size_threshold_are_left_alone() {
+        let src = large_function("")
+            + "
+fn main: -> (8 8)
+    %x := call large 0
+    return %x
+endfn
+";
+        assert_eq!(inline_main(&src), (vec!["large".to_owned()], 2));
+    }
+
+    #[test]
+    fn noinline_hint_is_respected() {
+        let src = "
+#[noinline]
+fn one: -> (8 8)
+    %x := const 8 1 0 0 0 0 0 0 0
+    return %x
+endfn
+
+fn main: -> (8 8)
+    %x := call one 0
+    return %x
+endfn
+";
+        assert_eq!(inline_main(src), (vec!["one".to_owned()], 2));
+    }
+
+    #[test]
+    fn recursive_callees_are_skipped() {
+        let src = "
+#[inline]
+fn count_down: %n := (8 8) -> (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %done := binop == %n %zero
+    if %done end
+    %one := const 8 1 0 0 0 0 0 0 0
+    %n := binop - %n %one
+    %n := call count_down 1 %n
+    :end
+    return %n
+endfn
+
+fn main: -> (8 8)
+    %n := const 8 3 0 0 0 0 0 0 0
+    %x := call count_down 1 %n
+    return %x
+endfn
+";
+        assert_eq!(inline_main(src), (vec!["count_down".to_owned()], 3));
+    }
+
+    #[test]
+    fn mutually_recursive_callees_are_skipped() {
+        let src = "
+#[inline]
+fn is_even: %n := (8 8) -> (1 1)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %done := binop == %n %zero
+    if %done end
+    %one := const 8 1 0 0 0 0 0 0 0
+    %n := binop - %n %one
+    %r := call is_odd 1 %n
+    return %r
+    :end
+    %yes := const 1 1
+    return %yes
+endfn
+
+#[inline]
+fn is_odd: %n := (8 8) -> (1 1)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %done := binop == %n %zero
+    if %done end
+    %one := const 8 1 0 0 0 0 0 0 0
+    %n := binop - %n %one
+    %r := call is_even 1 %n
+    return %r
+    :end
+    %no := const 1 0
+    return %no
+endfn
+
+fn main: -> (1 1)
+    %n := const 8 4 0 0 0 0 0 0 0
+    %x := call is_even 1 %n
+    return %x
+endfn
+";
+        assert_eq!(inline_main(src), (vec!["is_even".to_owned()], 3));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::IRDebugLocation;
+use std::collections::BTreeMap;
+
+/// Comp that allocated a heap block
+#[derive(Debug, Clone)]
+pub struct IRInterpreterSite {
+    pub function: Symbol,
+    /// Index of the comp in the function
+    pub comp: usize,
+    pub location: Option<IRDebugLocation>,
+}
+
+impl IRInterpreterSite {
+    pub fn describe(&self) -> String {
+        let comp = format!(
+            "{}#{}",
+            SessionGlobals::with_interner(|i| i.get(&self.function).unwrap().to_owned()),
+            self.comp
+        );
+        match &self.location {
+            Some(location) => format!("{} ({})", comp, location.position()),
+            None => comp,
+        }
+    }
+}
+
+/// Bytes past the end of each block, so that accesses overflowing it still land in it
+const REDZONE_SIZE: u64 = 64;
+
+struct IRInterpreterHeapBlock {
+    /// The bytes of the block followed by its redzone
+    bytes: Box<[u8]>,
+    size: u64,
+    site: IRInterpreterSite,
+    freed: bool,
+}
+
+/// Memory yielded by `malloc` and `realloc`, zeroed like the memory yielded by `Alloc`
+///
+/// Freed blocks are kept (and never reused) so that later uses of them are caught.
+pub(super) struct IRInterpreterHeap {
+    /// Blocks by the address of their first byte
+    blocks: BTreeMap<u64, IRInterpreterHeapBlock>,
+}
+
+impl IRInterpreterHeap {
+    pub fn new() -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    pub fn malloc(&mut self, size: u64, site: IRInterpreterSite) -> u64 {
+        // The redzone also gives blocks of 0 bytes an address of their own
+        let bytes = vec![0; (size + REDZONE_SIZE) as usize].into_boxed_slice();
+        let ptr = bytes.as_ptr() as u64;
+        self.blocks.insert(
+            ptr,
+            IRInterpreterHeapBlock {
+                bytes,
+                size,
+                site,
+                freed: false,
+            },
+        );
+        ptr
+    }
+
+    /// Frees the block starting at the pointer, freeing null does nothing
+    pub fn free(&mut self, ptr: u64) -> Result<(), String> {
+        if ptr == 0 {
+            return Ok(());
+        }
+
+        match self.blocks.get_mut(&ptr) {
+            Some(block) if block.freed => Err(format!(
+                "double free of 0x{:x}, allocated at {}",
+                ptr,
+                block.site.describe()
+            )),
+            Some(block) => {
+                block.freed = true;
+                Ok(())
+            }
+            None => Err(format!("free of 0x{:x}, which malloc did not yield", ptr)),
+        }
+    }
+
+    /// Moves the block to a new one of the size, null pointers get a new block
+    pub fn realloc(&mut self, ptr: u64, size: u64, site: IRInterpreterSite) -> Result<u64, String> {
+        if ptr == 0 {
+            return Ok(self.malloc(size, site));
+        }
+
+        let len = match self.blocks.get(&ptr) {
+            Some(block) if !block.freed => block.size.min(size) as usize,
+            // Fails like freeing it would
+            _ => return Err(self.free(ptr).unwrap_err()),
+        };
+        let new_ptr = self.malloc(size, site);
+        let bytes = self.blocks[&ptr].bytes[..len].to_vec();
+        self.blocks.get_mut(&new_ptr).unwrap().bytes[..len].copy_from_slice(&bytes);
+        self.free(ptr)?;
+        Ok(new_ptr)
+    }
+
+    /// Checks that the bytes accessed are not part of a freed block, and do not run past the end
+    /// of a block into its redzone
+    pub fn check_access(&self, ptr: u64, size: u64) -> Result<(), String> {
+        let end = ptr.saturating_add(size.max(1));
+        let overlapping = self
+            .blocks
+            .range(..end)
+            .rev()
+            .take_while(|(start, block)| *start + block.bytes.len() as u64 > ptr);
+        for (start, block) in overlapping {
+            if block.freed {
+                return Err(format!(
+                    "use after free of 0x{:x}, allocated at {}",
+                    start,
+                    block.site.describe()
+                ));
+            }
+            if ptr < *start || ptr + size > *start + block.size {
+                return Err(format!(
+                    "out of bounds access of {} bytes at 0x{:x}, outside of the {} bytes of 0x{:x} allocated at {}",
+                    size,
+                    ptr,
+                    block.size,
+                    start,
+                    block.site.describe()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks that were never freed, with their sizes
+    pub fn leaks(&self) -> Vec<(u64, IRInterpreterSite)> {
+        self.blocks
+            .values()
+            .filter(|block| !block.freed)
+            .map(|block| (block.size, block.site.clone()))
+            .collect()
+    }
+}
@@ -1,9 +1,70 @@
-use crate::globals::Symbol;
-use crate::ir::comp::{IRCompBinaryOperationKind, IRCompKind, IRCompUnaryOperationKind};
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{
+    IRCompBinaryOperationKind, IRCompConversion, IRCompConversionKind, IRCompKind,
+    IRCompUnaryOperationKind, IRDebugLocation,
+};
+use crate::ir::interpreter::heap::{IRInterpreterHeap, IRInterpreterSite};
 use crate::ir::{IRItemFunctionDef, IRItemKind, IRModule};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::ops::Rem;
 
+pub mod heap;
+
+/// Function call that was running when a trap happened
+#[derive(Debug, Clone)]
+pub struct IRInterpreterFrame {
+    /// IR name of the function
+    pub function: Symbol,
+    /// Location of the comp that was running, if the function was lowered with them
+    pub location: Option<IRDebugLocation>,
+}
+
+impl Display for IRInterpreterFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "{} ({})",
+                SessionGlobals::with_interner(|i| i.get(&location.function).unwrap().to_owned()),
+                location.position()
+            ),
+            None => write!(
+                f,
+                "{}",
+                SessionGlobals::with_interner(|i| i.get(&self.function).unwrap().to_owned())
+            ),
+        }
+    }
+}
+
+/// A fault stopping the interpreted program, like a bad use of the heap
+#[derive(Debug)]
+pub struct IRInterpreterTrap {
+    pub message: String,
+    /// Calls that were running, innermost first
+    pub frames: Vec<IRInterpreterFrame>,
+}
+
+impl Display for IRInterpreterTrap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trap: {}", self.message)?;
+        for frame in &self.frames {
+            write!(f, "\n    at {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+pub type IRInterpreterResult<T> = Result<T, IRInterpreterTrap>;
+
+fn trap<T>(message: String) -> IRInterpreterResult<T> {
+    Err(IRInterpreterTrap {
+        message,
+        frames: vec![],
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct IRInterpreterValue {
     pub bytes: Vec<u8>,
@@ -165,15 +226,126 @@ impl IRInterpreterValue {
     }
 }
 
+impl IRInterpreterValue {
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_u32(value.to_bits())
+    }
+
+    pub fn from_f64(value: f64) -> Self {
+        Self::from_u64(value.to_bits())
+    }
+
+    pub fn into_f32(&self) -> f32 {
+        f32::from_bits(self.into_u32())
+    }
+
+    pub fn into_f64(&self) -> f64 {
+        f64::from_bits(self.into_u64())
+    }
+
+    /// Zero extends an integer of 1, 2, 4 or 8 bytes
+    fn to_unsigned(&self) -> Option<u64> {
+        match self.bytes.len() {
+            1 => Some(self.into_u8() as u64),
+            2 => Some(self.into_u16() as u64),
+            4 => Some(self.into_u32() as u64),
+            8 => Some(self.into_u64()),
+            _ => None,
+        }
+    }
+
+    /// Sign extends an integer of 1, 2, 4 or 8 bytes
+    fn to_signed(&self) -> Option<i64> {
+        match self.bytes.len() {
+            1 => Some(self.into_i8() as i64),
+            2 => Some(self.into_i16() as i64),
+            4 => Some(self.into_i32() as i64),
+            8 => Some(self.into_i64()),
+            _ => None,
+        }
+    }
+
+    /// Widens a float of 4 or 8 bytes, which is exact
+    fn to_float(&self) -> Option<f64> {
+        match self.bytes.len() {
+            4 => Some(self.into_f32() as f64),
+            8 => Some(self.into_f64()),
+            _ => None,
+        }
+    }
+}
+
+/// Performs a conversion, yields void when the sizes are not valid for it
+///
+/// Floats out of the range of the integer they are converted to saturate, and NaN becomes 0.
+fn convert(conversion: &IRCompConversion, operand: &IRInterpreterValue) -> IRInterpreterValue {
+    let from_float = |value: f64| match conversion.size {
+        4 => IRInterpreterValue::from_f32(value as f32),
+        8 => IRInterpreterValue::from_f64(value),
+        _ => IRInterpreterValue::void(),
+    };
+
+    match conversion.kind {
+        IRCompConversionKind::UnsignedToFloat => match operand.to_unsigned() {
+            // Converted straight from the integer, so it is rounded only once
+            Some(value) if conversion.size == 4 => IRInterpreterValue::from_f32(value as f32),
+            Some(value) => from_float(value as f64),
+            None => IRInterpreterValue::void(),
+        },
+        IRCompConversionKind::SignedToFloat => match operand.to_signed() {
+            Some(value) if conversion.size == 4 => IRInterpreterValue::from_f32(value as f32),
+            Some(value) => from_float(value as f64),
+            None => IRInterpreterValue::void(),
+        },
+        IRCompConversionKind::FloatToUnsigned => match (operand.to_float(), conversion.size) {
+            (Some(value), 1) => IRInterpreterValue::from_u8(value as u8),
+            (Some(value), 2) => IRInterpreterValue::from_u16(value as u16),
+            (Some(value), 4) => IRInterpreterValue::from_u32(value as u32),
+            (Some(value), 8) => IRInterpreterValue::from_u64(value as u64),
+            _ => IRInterpreterValue::void(),
+        },
+        IRCompConversionKind::FloatToSigned => match (operand.to_float(), conversion.size) {
+            (Some(value), 1) => IRInterpreterValue::from_i8(value as i8),
+            (Some(value), 2) => IRInterpreterValue::from_i16(value as i16),
+            (Some(value), 4) => IRInterpreterValue::from_i32(value as i32),
+            (Some(value), 8) => IRInterpreterValue::from_i64(value as i64),
+            _ => IRInterpreterValue::void(),
+        },
+        IRCompConversionKind::FloatToFloat => match operand.to_float() {
+            Some(value) => from_float(value),
+            None => IRInterpreterValue::void(),
+        },
+        IRCompConversionKind::Truncate
+        | IRCompConversionKind::ZeroExtend
+        | IRCompConversionKind::SignExtend => {
+            let value = match conversion.kind {
+                IRCompConversionKind::SignExtend => operand.to_signed().map(|value| value as u64),
+                _ => operand.to_unsigned(),
+            };
+            match (value, conversion.size) {
+                (Some(value), 1 | 2 | 4 | 8) => IRInterpreterValue {
+                    bytes: value.to_le_bytes()[..conversion.size as usize].to_vec(),
+                },
+                _ => IRInterpreterValue::void(),
+            }
+        }
+    }
+}
+
 struct IRInterpreterStack {
     values: Vec<IRInterpreterValue>,
     frames: Vec<usize>,
 }
 
+/// Interprets IR functions
+///
+/// Calls to `malloc`, `free` and `realloc` that no loaded function defines are served by the
+/// heap of the interpreter, which traps on bad frees and uses of freed memory. The blocks still
+/// allocated are reported when the interpreter is dropped.
 pub struct IRInterpreter {
     functions: HashMap<Symbol, IRItemFunctionDef>,
     stack: IRInterpreterStack,
-    label_defs: HashMap<Symbol, u64>,
+    heap: IRInterpreterHeap,
 }
 
 impl IRInterpreter {
@@ -184,7 +356,7 @@ impl IRInterpreter {
                 values: vec![],
                 frames: vec![],
             },
-            label_defs: HashMap::new(),
+            heap: IRInterpreterHeap::new(),
         }
     }
 
@@ -192,19 +364,103 @@ impl IRInterpreter {
         for item in module.items {
             match item.kind {
                 IRItemKind::FunctionDef(function_def) => {
-                    self.label_defs.extend(function_def.label_defs.iter());
+                    // Modules sharing symbols have to go through ir::link first
+                    if self.functions.contains_key(&function_def.name) {
+                        panic!(
+                            "Function {} is already loaded",
+                            SessionGlobals::with_interner(|i| i.get(&function_def.name).unwrap())
+                        );
+                    }
                     self.functions.insert(function_def.name, function_def);
                 }
+                IRItemKind::ExternDecl(_) => {}
             }
         }
     }
 
+    /// Blocks of the heap that were never freed, with their sizes and allocation sites
+    pub fn leaks(&self) -> Vec<(u64, IRInterpreterSite)> {
+        self.heap.leaks()
+    }
+
+    /// Serves a call to a heap function, yields None for other functions
+    fn call_heap_function(
+        &mut self,
+        name: &str,
+        args: &[IRInterpreterValue],
+        site: IRInterpreterSite,
+    ) -> Option<IRInterpreterResult<IRInterpreterValue>> {
+        let result = match (name, args) {
+            ("malloc", [size]) => Ok(IRInterpreterValue::from_u64(
+                self.heap.malloc(size.into_u64(), site),
+            )),
+            ("free", [ptr]) => self
+                .heap
+                .free(ptr.into_u64())
+                .map(|_| IRInterpreterValue::void()),
+            ("realloc", [ptr, size]) => self
+                .heap
+                .realloc(ptr.into_u64(), size.into_u64(), site)
+                .map(IRInterpreterValue::from_u64),
+            _ => return None,
+        };
+        Some(result.or_else(trap))
+    }
+
+    /// Checks that the location is not freed heap memory
+    fn check_access(&self, location: &IRInterpreterValue, size: u64) -> IRInterpreterResult<()> {
+        self.heap
+            .check_access(location.into_u64(), size)
+            .or_else(trap)
+    }
+
+    /// Calls the function, traps carry a frame for each call they unwind
     pub unsafe fn call_function(
         &mut self,
         function_name: Symbol,
         args: &[IRInterpreterValue],
-    ) -> IRInterpreterValue {
-        let function_def = &self.functions[&function_name];
+    ) -> IRInterpreterResult<IRInterpreterValue> {
+        let mut current_comp = 0;
+        let depth = self.stack.frames.len();
+        self.run_function(function_name, args, &mut current_comp)
+            .map_err(|mut trap| {
+                // The frame of the function is left on the stack by traps
+                while self.stack.frames.len() > depth {
+                    self.pop_frame();
+                }
+                if let Some(function_def) = self.functions.get(&function_name) {
+                    trap.frames.push(IRInterpreterFrame {
+                        function: function_name,
+                        location: function_def.comps[current_comp].location.clone(),
+                    });
+                }
+                trap
+            })
+    }
+
+    /// Pops the frame of the running function and the values it pushed
+    fn pop_frame(&mut self) {
+        for _ in 0..self.stack.frames.pop().unwrap() {
+            self.stack.values.pop();
+        }
+    }
+
+    /// Runs the function, keeping the index of the running comp in current_comp
+    unsafe fn run_function(
+        &mut self,
+        function_name: Symbol,
+        args: &[IRInterpreterValue],
+        current_comp: &mut usize,
+    ) -> IRInterpreterResult<IRInterpreterValue> {
+        let function_def = match self.functions.get(&function_name) {
+            Some(function_def) => function_def,
+            None => {
+                return trap(format!(
+                    "call to undefined function {}",
+                    SessionGlobals::with_interner(|i| i.get(&function_name).unwrap().to_owned())
+                ))
+            }
+        };
         let comps_len = function_def.comps.len();
         let mut values = HashMap::new();
 
@@ -218,11 +474,12 @@ impl IRInterpreter {
 
         // TODO : Take a reference instead of cloning
         let comps = function_def.comps.clone();
-        let mut current_comp = 0;
-        while current_comp < comps_len {
+        // Labels are only unique inside of their function
+        let label_defs = function_def.label_defs.clone();
+        while *current_comp < comps_len {
             let mut performed_jump = false;
             let mut target_comp = 0;
-            let comp = &comps[current_comp];
+            let comp = &comps[*current_comp];
 
             let value = match &comp.kind {
                 IRCompKind::FunctionCall(function_call) => {
@@ -233,7 +490,21 @@ impl IRInterpreter {
                         .map(|irv| values[&irv.id].clone())
                         .collect::<Vec<_>>();
 
-                    self.call_function(name, &args)
+                    if self.functions.contains_key(&name) {
+                        self.call_function(name, &args)?
+                    } else {
+                        let site = IRInterpreterSite {
+                            function: function_name,
+                            comp: *current_comp,
+                            location: comp.location.clone(),
+                        };
+                        let builtin =
+                            SessionGlobals::with_interner(|i| i.get(&name).unwrap().to_owned());
+                        match self.call_heap_function(&builtin, &args, site) {
+                            Some(result) => result?,
+                            None => self.call_function(name, &args)?,
+                        }
+                    }
                 }
                 IRCompKind::BinaryOperation(operation) => {
                     let left_operand = &values[&operation.left_operand.id];
@@ -241,6 +512,17 @@ impl IRInterpreter {
 
                     let size = right_operand.bytes.len();
 
+                    let divides = matches!(
+                        operation.kind,
+                        IRCompBinaryOperationKind::Div
+                            | IRCompBinaryOperationKind::Mod
+                            | IRCompBinaryOperationKind::SignedDiv
+                            | IRCompBinaryOperationKind::SignedMod
+                    );
+                    if divides && right_operand.bytes.iter().all(|byte| *byte == 0) {
+                        return trap("division by zero".to_owned());
+                    }
+
                     if size == left_operand.bytes.len() {
                         match size {
                             1 => match operation.kind {
@@ -297,6 +579,47 @@ impl IRInterpreter {
                                         (left_operand.into_u8() <= right_operand.into_u8()) as u8,
                                     )
                                 }
+                                IRCompBinaryOperationKind::SignedDiv => {
+                                    IRInterpreterValue::from_i8(
+                                        left_operand
+                                            .into_i8()
+                                            .wrapping_div(right_operand.into_i8()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedMod => {
+                                    IRInterpreterValue::from_i8(
+                                        left_operand
+                                            .into_i8()
+                                            .wrapping_rem(right_operand.into_i8()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedRShift => {
+                                    IRInterpreterValue::from_i8(
+                                        left_operand.into_i8() >> right_operand.into_u8(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i8() > right_operand.into_i8()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i8() < right_operand.into_i8()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i8() >= right_operand.into_i8()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i8() <= right_operand.into_i8()) as u8,
+                                    )
+                                }
+
+                                _ => IRInterpreterValue::void(),
                             },
 
                             2 => match operation.kind {
@@ -361,6 +684,47 @@ impl IRInterpreter {
                                         (left_operand.into_u16() <= right_operand.into_u16()) as u8,
                                     )
                                 }
+                                IRCompBinaryOperationKind::SignedDiv => {
+                                    IRInterpreterValue::from_i16(
+                                        left_operand
+                                            .into_i16()
+                                            .wrapping_div(right_operand.into_i16()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedMod => {
+                                    IRInterpreterValue::from_i16(
+                                        left_operand
+                                            .into_i16()
+                                            .wrapping_rem(right_operand.into_i16()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedRShift => {
+                                    IRInterpreterValue::from_i16(
+                                        left_operand.into_i16() >> right_operand.into_u16(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i16() > right_operand.into_i16()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i16() < right_operand.into_i16()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i16() >= right_operand.into_i16()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i16() <= right_operand.into_i16()) as u8,
+                                    )
+                                }
+
+                                _ => IRInterpreterValue::void(),
                             },
 
                             4 => match operation.kind {
@@ -425,6 +789,96 @@ impl IRInterpreter {
                                         (left_operand.into_u32() <= right_operand.into_u32()) as u8,
                                     )
                                 }
+                                IRCompBinaryOperationKind::SignedDiv => {
+                                    IRInterpreterValue::from_i32(
+                                        left_operand
+                                            .into_i32()
+                                            .wrapping_div(right_operand.into_i32()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedMod => {
+                                    IRInterpreterValue::from_i32(
+                                        left_operand
+                                            .into_i32()
+                                            .wrapping_rem(right_operand.into_i32()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedRShift => {
+                                    IRInterpreterValue::from_i32(
+                                        left_operand.into_i32() >> right_operand.into_u32(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i32() > right_operand.into_i32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i32() < right_operand.into_i32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i32() >= right_operand.into_i32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i32() <= right_operand.into_i32()) as u8,
+                                    )
+                                }
+
+                                IRCompBinaryOperationKind::FloatPlus => {
+                                    IRInterpreterValue::from_f32(
+                                        left_operand.into_f32() + right_operand.into_f32(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatMinus => {
+                                    IRInterpreterValue::from_f32(
+                                        left_operand.into_f32() - right_operand.into_f32(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatMul => {
+                                    IRInterpreterValue::from_f32(
+                                        left_operand.into_f32() * right_operand.into_f32(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatDiv => {
+                                    IRInterpreterValue::from_f32(
+                                        left_operand.into_f32() / right_operand.into_f32(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() == right_operand.into_f32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatNotEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() != right_operand.into_f32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() > right_operand.into_f32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() < right_operand.into_f32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() >= right_operand.into_f32()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f32() <= right_operand.into_f32()) as u8,
+                                    )
+                                }
                             },
 
                             8 => match operation.kind {
@@ -489,6 +943,96 @@ impl IRInterpreter {
                                         (left_operand.into_u64() <= right_operand.into_u64()) as u8,
                                     )
                                 }
+                                IRCompBinaryOperationKind::SignedDiv => {
+                                    IRInterpreterValue::from_i64(
+                                        left_operand
+                                            .into_i64()
+                                            .wrapping_div(right_operand.into_i64()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedMod => {
+                                    IRInterpreterValue::from_i64(
+                                        left_operand
+                                            .into_i64()
+                                            .wrapping_rem(right_operand.into_i64()),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedRShift => {
+                                    IRInterpreterValue::from_i64(
+                                        left_operand.into_i64() >> right_operand.into_u64(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i64() > right_operand.into_i64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i64() < right_operand.into_i64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i64() >= right_operand.into_i64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::SignedLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_i64() <= right_operand.into_i64()) as u8,
+                                    )
+                                }
+
+                                IRCompBinaryOperationKind::FloatPlus => {
+                                    IRInterpreterValue::from_f64(
+                                        left_operand.into_f64() + right_operand.into_f64(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatMinus => {
+                                    IRInterpreterValue::from_f64(
+                                        left_operand.into_f64() - right_operand.into_f64(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatMul => {
+                                    IRInterpreterValue::from_f64(
+                                        left_operand.into_f64() * right_operand.into_f64(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatDiv => {
+                                    IRInterpreterValue::from_f64(
+                                        left_operand.into_f64() / right_operand.into_f64(),
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() == right_operand.into_f64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatNotEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() != right_operand.into_f64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatGreater => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() > right_operand.into_f64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatLesser => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() < right_operand.into_f64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatGreaterEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() >= right_operand.into_f64()) as u8,
+                                    )
+                                }
+                                IRCompBinaryOperationKind::FloatLesserEqual => {
+                                    IRInterpreterValue::from_u8(
+                                        (left_operand.into_f64() <= right_operand.into_f64()) as u8,
+                                    )
+                                }
                             },
 
                             _ => IRInterpreterValue::void(),
@@ -517,6 +1061,7 @@ impl IRInterpreter {
                             IRCompUnaryOperationKind::SignedNegation => {
                                 IRInterpreterValue::from_i8(-operand.into_i8())
                             }
+                            IRCompUnaryOperationKind::FloatNegation => IRInterpreterValue::void(),
                         },
 
                         2 => match operation.kind {
@@ -527,6 +1072,7 @@ impl IRInterpreter {
                             IRCompUnaryOperationKind::SignedNegation => {
                                 IRInterpreterValue::from_i16(-operand.into_i16())
                             }
+                            IRCompUnaryOperationKind::FloatNegation => IRInterpreterValue::void(),
                         },
 
                         4 => match operation.kind {
@@ -537,6 +1083,9 @@ impl IRInterpreter {
                             IRCompUnaryOperationKind::SignedNegation => {
                                 IRInterpreterValue::from_i32(-operand.into_i32())
                             }
+                            IRCompUnaryOperationKind::FloatNegation => {
+                                IRInterpreterValue::from_f32(-operand.into_f32())
+                            }
                         },
 
                         8 => match operation.kind {
@@ -547,11 +1096,17 @@ impl IRInterpreter {
                             IRCompUnaryOperationKind::SignedNegation => {
                                 IRInterpreterValue::from_i64(-operand.into_i64())
                             }
+                            IRCompUnaryOperationKind::FloatNegation => {
+                                IRInterpreterValue::from_f64(-operand.into_f64())
+                            }
                         },
 
                         _ => IRInterpreterValue::void(),
                     }
                 }
+                IRCompKind::Conversion(conversion) => {
+                    convert(conversion, &values[&conversion.operand.id])
+                }
                 IRCompKind::Constant(constant) => IRInterpreterValue {
                     bytes: constant.bytes.clone(),
                 },
@@ -565,6 +1120,7 @@ impl IRInterpreter {
                     IRInterpreterValue::from_u64(ptr)
                 }
                 IRCompKind::Store(ir_type, location, value) => {
+                    self.check_access(&values[&location.id], ir_type.size)?;
                     let value = &values[&value.id];
                     let ptr = values[&location.id].into_u64() as *mut u8;
                     let slice = std::slice::from_raw_parts_mut(ptr, ir_type.size as usize);
@@ -575,6 +1131,7 @@ impl IRInterpreter {
                     IRInterpreterValue::void()
                 }
                 IRCompKind::Load(ir_type, location) => {
+                    self.check_access(&values[&location.id], ir_type.size)?;
                     let ptr = values[&location.id].into_u64() as *const u8;
                     let slice = std::slice::from_raw_parts(ptr, ir_type.size as usize);
 
@@ -583,8 +1140,11 @@ impl IRInterpreter {
                     }
                 }
                 IRCompKind::OffsetStore(ir_type, location, value, offset) => {
+                    let location =
+                        IRInterpreterValue::from_u64(values[&location.id].into_u64() + *offset);
+                    self.check_access(&location, ir_type.size)?;
                     let value = &values[&value.id];
-                    let ptr = (values[&location.id].into_u64() + *offset) as *mut u8;
+                    let ptr = location.into_u64() as *mut u8;
                     let slice = std::slice::from_raw_parts_mut(ptr, ir_type.size as usize);
                     for i in 0..slice.len() {
                         slice[i] = value.bytes[i];
@@ -593,7 +1153,10 @@ impl IRInterpreter {
                     IRInterpreterValue::void()
                 }
                 IRCompKind::OffsetLoad(ir_type, location, offset) => {
-                    let ptr = (values[&location.id].into_u64() + *offset) as *const u8;
+                    let location =
+                        IRInterpreterValue::from_u64(values[&location.id].into_u64() + *offset);
+                    self.check_access(&location, ir_type.size)?;
+                    let ptr = location.into_u64() as *const u8;
                     let slice = std::slice::from_raw_parts(ptr, ir_type.size as usize);
 
                     IRInterpreterValue {
@@ -603,24 +1166,22 @@ impl IRInterpreter {
                 IRCompKind::Return(value) => {
                     let value = &values[&value.id];
 
-                    for _ in 0..self.stack.frames.pop().unwrap() {
-                        self.stack.values.pop();
-                    }
+                    self.pop_frame();
 
-                    return value.clone();
+                    return Ok(value.clone());
                 }
                 IRCompKind::If(value, location) => {
                     let value = &values[&value.id];
                     if value.into_u8() != 0 {
                         performed_jump = true;
-                        target_comp = self.label_defs[location] as usize;
+                        target_comp = label_defs[location] as usize;
                     }
 
                     IRInterpreterValue::void()
                 }
                 IRCompKind::Jmp(location) => {
                     performed_jump = true;
-                    target_comp = self.label_defs[location] as usize;
+                    target_comp = label_defs[location] as usize;
 
                     IRInterpreterValue::void()
                 }
@@ -631,16 +1192,63 @@ impl IRInterpreter {
             }
 
             if !performed_jump {
-                current_comp += 1;
+                *current_comp += 1;
             } else {
-                current_comp = target_comp;
+                *current_comp = target_comp;
             }
         }
 
-        for _ in 0..self.stack.frames.pop().unwrap() {
-            self.stack.values.pop();
+        self.pop_frame();
+
+        Ok(IRInterpreterValue::void())
+    }
+}
+
+impl Drop for IRInterpreter {
+    fn drop(&mut self) {
+        let leaks = self.heap.leaks();
+        if leaks.is_empty() {
+            return;
         }
 
-        IRInterpreterValue::void()
+        let total: u64 = leaks.iter().map(|(size, _)| size).sum();
+        eprintln!("{} bytes leaked in {} blocks:", total, leaks.len());
+        for (size, site) in leaks {
+            eprintln!("    {} bytes allocated at {}", size, site.describe());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+
+    #[test]
+    fn traps_unwind_the_stack() {
+        let src = "\
+fn divide: %a := (8 8) -> (8 8)
+    %slot := alloc (8 8)
+    %zero := const 8 0 0 0 0 0 0 0 0
+    %result := binop / %a %zero
+    return %result
+endfn
+
+fn main: -> (8 8)
+    %slot := alloc (8 8)
+    %a := const 8 5 0 0 0 0 0 0 0
+    %result := call divide 1 %a
+    return %result
+endfn
+";
+        SessionGlobals::create(|| {
+            let mut interpreter = IRInterpreter::new();
+            interpreter.load_module(assemble_ir_module(src).unwrap());
+            let main = SessionGlobals::with_interner_mut(|i| i.intern("main"));
+            let trap = unsafe { interpreter.call_function(main, &[]) }.unwrap_err();
+            assert_eq!(trap.frames.len(), 2);
+            assert!(interpreter.stack.frames.is_empty());
+            assert!(interpreter.stack.values.is_empty());
+        });
     }
 }
@@ -0,0 +1,516 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::IRCompKind;
+use crate::ir::{IRItem, IRItemExternDecl, IRItemKind, IRModule, IRType, IRVisibility};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub enum IRLinkError {
+    /// A function is exported (or defined twice) by the given modules
+    DuplicateSymbol {
+        name: Symbol,
+        first_origin: String,
+        second_origin: String,
+    },
+    /// A function is used by the given module but no module exports it
+    UnresolvedSymbol { name: Symbol, origin: String },
+    /// A declaration of a function does not match its definition (or another declaration)
+    SignatureMismatch {
+        name: Symbol,
+        first_origin: String,
+        second_origin: String,
+    },
+}
+
+impl IRLinkError {
+    pub fn message(&self) -> String {
+        let name = |name: &Symbol| SessionGlobals::with_interner(|i| i.get(name).unwrap());
+        match self {
+            IRLinkError::DuplicateSymbol {
+                name: symbol,
+                first_origin,
+                second_origin,
+            } => format!(
+                "Function {} is defined in both {} and {}",
+                name(symbol),
+                first_origin,
+                second_origin
+            ),
+            IRLinkError::UnresolvedSymbol {
+                name: symbol,
+                origin,
+            } => format!(
+                "Function {} is used in {} but never defined",
+                name(symbol),
+                origin
+            ),
+            IRLinkError::SignatureMismatch {
+                name: symbol,
+                first_origin,
+                second_origin,
+            } => format!(
+                "Function {} has different signatures in {} and {}",
+                name(symbol),
+                first_origin,
+                second_origin
+            ),
+        }
+    }
+}
+
+type Signature = (Vec<IRType>, IRType);
+
+fn same_signature(a: &Signature, b: &Signature) -> bool {
+    let same_type = |a: &IRType, b: &IRType| a.size == b.size && a.align == b.align;
+    a.0.len() == b.0.len()
+        && a.0.iter().zip(b.0.iter()).all(|(a, b)| same_type(a, b))
+        && same_type(&a.1, &b.1)
+}
+
+/// What a module defines and uses
+struct ModuleSymbols {
+    defined: HashMap<Symbol, (IRVisibility, Signature)>,
+    declared: HashMap<Symbol, Signature>,
+    /// Functions called without being defined or declared in the module
+    called: Vec<Symbol>,
+}
+
+impl ModuleSymbols {
+    fn for_module(module: &IRModule, origin: &str, errors: &mut Vec<IRLinkError>) -> ModuleSymbols {
+        let mut defined = HashMap::new();
+        let mut declared: HashMap<Symbol, Signature> = HashMap::new();
+        for item in &module.items {
+            match &item.kind {
+                IRItemKind::FunctionDef(function_def) => {
+                    let signature = (
+                        function_def.params.iter().map(|p| p.1.clone()).collect(),
+                        function_def.return_type.clone(),
+                    );
+                    if defined
+                        .insert(function_def.name, (function_def.visibility, signature))
+                        .is_some()
+                    {
+                        errors.push(IRLinkError::DuplicateSymbol {
+                            name: function_def.name,
+                            first_origin: origin.to_owned(),
+                            second_origin: origin.to_owned(),
+                        });
+                    }
+                }
+                IRItemKind::ExternDecl(extern_decl) => {
+                    let signature = (extern_decl.params.clone(), extern_decl.return_type.clone());
+                    match declared.get(&extern_decl.name) {
+                        Some(previous) if !same_signature(previous, &signature) => {
+                            errors.push(IRLinkError::SignatureMismatch {
+                                name: extern_decl.name,
+                                first_origin: origin.to_owned(),
+                                second_origin: origin.to_owned(),
+                            });
+                        }
+                        _ => {
+                            declared.insert(extern_decl.name, signature);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut called = vec![];
+        let mut seen = HashSet::new();
+        for item in &module.items {
+            if let IRItemKind::FunctionDef(function_def) = &item.kind {
+                for comp in &function_def.comps {
+                    if let IRCompKind::FunctionCall(function_call) = &comp.kind {
+                        let name = function_call.name;
+                        if !defined.contains_key(&name)
+                            && !declared.contains_key(&name)
+                            && seen.insert(name)
+                        {
+                            called.push(name);
+                        }
+                    }
+                }
+            }
+        }
+
+        ModuleSymbols {
+            defined,
+            declared,
+            called,
+        }
+    }
+
+    /// Functions the module expects another module to define
+    fn imports(&self) -> impl Iterator<Item = (Symbol, Option<&Signature>)> {
+        let mut declared: Vec<_> = self
+            .declared
+            .iter()
+            .filter(|(name, _)| !self.defined.contains_key(name))
+            .map(|(name, signature)| (*name, Some(signature)))
+            .collect();
+        declared.sort_by_key(|(name, _)| SessionGlobals::with_interner(|i| i.get(name).unwrap()));
+        declared
+            .into_iter()
+            .chain(self.called.iter().map(|name| (*name, None)))
+    }
+}
+
+/// Merges separately built modules into one
+///
+/// Modules are added with a name describing where they come from (usually a path), which is
+/// used in errors. Exported functions are visible to every module, while internal functions are
+/// only visible inside their own module and get renamed when their name is used anywhere else.
+/// Calls to functions that are not defined in a module (declared with `extern` or not) are
+/// resolved against the functions exported by the other modules.
+#[derive(Default)]
+pub struct IRLinker {
+    modules: Vec<(String, IRModule)>,
+    /// Keep functions that no module defines as extern declarations instead of failing, so they
+    /// can be provided by the platform (the C library for instance)
+    pub allow_unresolved: bool,
+}
+
+impl IRLinker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_module(&mut self, origin: &str, module: IRModule) {
+        self.modules.push((origin.to_owned(), module));
+    }
+
+    pub fn link(self) -> Result<IRModule, Vec<IRLinkError>> {
+        let mut errors = vec![];
+        let symbols: Vec<_> = self
+            .modules
+            .iter()
+            .map(|(origin, module)| ModuleSymbols::for_module(module, origin, &mut errors))
+            .collect();
+
+        // Exported functions
+        let mut exported: HashMap<Symbol, (usize, &Signature)> = HashMap::new();
+        for (index, module_symbols) in symbols.iter().enumerate() {
+            let mut names: Vec<_> = module_symbols
+                .defined
+                .iter()
+                .filter(|(_, (visibility, _))| *visibility == IRVisibility::Exported)
+                .collect();
+            names.sort_by_key(|(name, _)| SessionGlobals::with_interner(|i| i.get(name).unwrap()));
+            for (name, (_, signature)) in names {
+                match exported.get(name) {
+                    Some((first, _)) => errors.push(IRLinkError::DuplicateSymbol {
+                        name: *name,
+                        first_origin: self.modules[*first].0.clone(),
+                        second_origin: self.modules[index].0.clone(),
+                    }),
+                    None => {
+                        exported.insert(*name, (index, signature));
+                    }
+                }
+            }
+        }
+
+        // Imports
+        let mut unresolved: Vec<Symbol> = vec![];
+        let mut unresolved_signatures: HashMap<Symbol, (usize, Option<&Signature>)> =
+            HashMap::new();
+        for (index, module_symbols) in symbols.iter().enumerate() {
+            for (name, signature) in module_symbols.imports() {
+                if let Some((definition, definition_signature)) = exported.get(&name) {
+                    if let Some(signature) = signature {
+                        if !same_signature(signature, definition_signature) {
+                            errors.push(IRLinkError::SignatureMismatch {
+                                name,
+                                first_origin: self.modules[*definition].0.clone(),
+                                second_origin: self.modules[index].0.clone(),
+                            });
+                        }
+                    }
+                    continue;
+                }
+
+                if !self.allow_unresolved {
+                    errors.push(IRLinkError::UnresolvedSymbol {
+                        name,
+                        origin: self.modules[index].0.clone(),
+                    });
+                    continue;
+                }
+                match unresolved_signatures.get_mut(&name) {
+                    None => {
+                        unresolved.push(name);
+                        unresolved_signatures.insert(name, (index, signature));
+                    }
+                    Some((first, first_signature)) => match (&first_signature, signature) {
+                        (Some(first_signature), Some(signature))
+                            if !same_signature(first_signature, signature) =>
+                        {
+                            errors.push(IRLinkError::SignatureMismatch {
+                                name,
+                                first_origin: self.modules[*first].0.clone(),
+                                second_origin: self.modules[index].0.clone(),
+                            });
+                        }
+                        (None, Some(_)) => {
+                            *first = index;
+                            *first_signature = signature;
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+
+        // Declarations also check locally defined functions
+        for (index, module_symbols) in symbols.iter().enumerate() {
+            for (name, signature) in &module_symbols.declared {
+                if let Some((_, definition_signature)) = module_symbols.defined.get(name) {
+                    if !same_signature(signature, definition_signature) {
+                        errors.push(IRLinkError::SignatureMismatch {
+                            name: *name,
+                            first_origin: self.modules[index].0.clone(),
+                            second_origin: self.modules[index].0.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Every name visible across modules, internal functions must not collide with them
+        let mut taken: HashSet<Symbol> = exported.keys().copied().collect();
+        taken.extend(unresolved.iter().copied());
+        for module_symbols in &symbols {
+            taken.extend(module_symbols.imports().map(|(name, _)| name));
+        }
+        let mut internal_counts: HashMap<Symbol, usize> = HashMap::new();
+        for module_symbols in &symbols {
+            for (name, (visibility, _)) in &module_symbols.defined {
+                if *visibility == IRVisibility::Internal {
+                    *internal_counts.entry(*name).or_insert(0) += 1;
+                }
+            }
+        }
+
+        // Renamed internal functions must not collide with any function either
+        let mut reserved = taken.clone();
+        for module_symbols in &symbols {
+            reserved.extend(module_symbols.defined.keys().copied());
+        }
+
+        let mut items = vec![];
+        for name in &unresolved {
+            // Functions only ever called without a declaration stay implicit
+            if let (_, Some((params, return_type))) = unresolved_signatures[name] {
+                items.push(IRItem {
+                    kind: IRItemKind::ExternDecl(IRItemExternDecl {
+                        name: *name,
+                        params: params.clone(),
+                        return_type: return_type.clone(),
+                    }),
+                });
+            }
+        }
+
+        for (index, (_, module)) in self.modules.into_iter().enumerate() {
+            // Internal functions sharing a name with anything visible outside their module
+            let mut renames = HashMap::new();
+            let mut internals: Vec<_> = symbols[index]
+                .defined
+                .iter()
+                .filter(|(_, (visibility, _))| *visibility == IRVisibility::Internal)
+                .map(|(name, _)| *name)
+                .collect();
+            internals.sort_by_key(|name| SessionGlobals::with_interner(|i| i.get(name).unwrap()));
+            for name in internals {
+                if !taken.contains(&name) && internal_counts[&name] == 1 {
+                    continue;
+                }
+                let base = SessionGlobals::with_interner(|i| i.get(&name).unwrap().to_owned());
+                let mut attempt = 0;
+                let new_name = loop {
+                    let candidate = if attempt == 0 {
+                        format!("{}_{}", base, index)
+                    } else {
+                        format!("{}_{}_{}", base, index, attempt)
+                    };
+                    let candidate = SessionGlobals::with_interner_mut(|i| i.intern(&candidate));
+                    if !reserved.contains(&candidate) {
+                        break candidate;
+                    }
+                    attempt += 1;
+                };
+                reserved.insert(new_name);
+                renames.insert(name, new_name);
+            }
+
+            for mut item in module.items {
+                match &mut item.kind {
+                    IRItemKind::FunctionDef(function_def) => {
+                        if let Some(new_name) = renames.get(&function_def.name) {
+                            function_def.name = *new_name;
+                        }
+                        for comp in &mut function_def.comps {
+                            if let IRCompKind::FunctionCall(function_call) = &mut comp.kind {
+                                if let Some(new_name) = renames.get(&function_call.name) {
+                                    function_call.name = *new_name;
+                                }
+                            }
+                        }
+                        items.push(item);
+                    }
+                    // Declarations are either resolved or merged above
+                    IRItemKind::ExternDecl(_) => {}
+                }
+            }
+        }
+
+        Ok(IRModule { items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::assembly::assembler::assemble_ir_module;
+    use crate::ir::interpreter::IRInterpreter;
+
+    fn link(modules: &[(&str, &str)]) -> Result<IRModule, Vec<IRLinkError>> {
+        let mut linker = IRLinker::new();
+        for (origin, src) in modules {
+            linker.add_module(origin, assemble_ir_module(src).unwrap());
+        }
+        linker.link()
+    }
+
+    fn function_names(module: &IRModule) -> Vec<&'static str> {
+        module
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                IRItemKind::FunctionDef(function_def) => Some(function_def.name),
+                IRItemKind::ExternDecl(_) => None,
+            })
+            .map(|name| SessionGlobals::with_interner(|i| i.get(&name).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn internal_functions_are_renamed_apart() {
+        let first = "
+#[internal]
+fn helper: -> (8 8)
+    %one := const 8 1 0 0 0 0 0 0 0
+    return %one
+endfn
+
+fn first: -> (8 8)
+    %x := call helper 0
+    return %x
+endfn
+";
+        let second = "
+#[internal]
+fn helper: -> (8 8)
+    %ten := const 8 10 0 0 0 0 0 0 0
+    return %ten
+endfn
+
+extern first: -> (8 8)
+
+fn main: -> (8 8)
+    %a := call first 0
+    %b := call helper 0
+    %sum := binop + %a %b
+    return %sum
+endfn
+";
+        SessionGlobals::create(|| {
+            let module = link(&[("first", first), ("second", second)]).unwrap();
+            assert_eq!(
+                function_names(&module),
+                ["helper_0", "first", "helper_1", "main"]
+            );
+
+            let mut interpreter = IRInterpreter::new();
+            interpreter.load_module(module);
+            let main = SessionGlobals::with_interner_mut(|i| i.intern("main"));
+            let value = unsafe { interpreter.call_function(main, &[]) }.unwrap();
+            assert_eq!(value.into_u64(), 11);
+        });
+    }
+
+    #[test]
+    fn internal_function_named_like_an_export_is_renamed() {
+        let first = "
+#[internal]
+fn shared: -> (8 8)
+    %one := const 8 1 0 0 0 0 0 0 0
+    return %one
+endfn
+
+fn main: -> (8 8)
+    %a := call shared 0
+    %b := call other 0
+    %sum := binop + %a %b
+    return %sum
+endfn
+";
+        let second = "
+fn shared: -> (8 8)
+    %ten := const 8 10 0 0 0 0 0 0 0
+    return %ten
+endfn
+
+fn other: -> (8 8)
+    %x := call shared 0
+    return %x
+endfn
+";
+        SessionGlobals::create(|| {
+            let module = link(&[("first", first), ("second", second)]).unwrap();
+            assert_eq!(
+                function_names(&module),
+                ["shared_0", "main", "shared", "other"]
+            );
+        });
+    }
+
+    #[test]
+    fn duplicate_exports_are_reported() {
+        let src = "
+fn main: -> (8 8)
+    %one := const 8 1 0 0 0 0 0 0 0
+    return %one
+endfn
+";
+        SessionGlobals::create(|| {
+            let errors = link(&[("first", src), ("second", src)]).err().unwrap();
+            let messages: Vec<_> = errors.iter().map(|error| error.message()).collect();
+            assert_eq!(
+                messages,
+                ["Function main is defined in both first and second"]
+            );
+        });
+    }
+
+    #[test]
+    fn unresolved_functions_are_reported() {
+        let src = "
+fn main: -> (8 8)
+    %x := call missing 0
+    return %x
+endfn
+";
+        SessionGlobals::create(|| {
+            let errors = link(&[("first", src)]).err().unwrap();
+            let messages: Vec<_> = errors.iter().map(|error| error.message()).collect();
+            assert_eq!(
+                messages,
+                ["Function missing is used in first but never defined"]
+            );
+        });
+    }
+}
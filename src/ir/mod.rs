@@ -3,13 +3,19 @@ use crate::ir::comp::IRComp;
 use std::collections::HashMap;
 
 pub mod assembly;
+pub mod backend;
+pub mod binary;
 pub mod comp;
+pub mod inline;
 pub mod interpreter;
+pub mod link;
 
 #[derive(Debug, Clone)]
 pub struct IRType {
     pub size: u64,
     pub align: u64,
+    /// Holds an IEEE 754 float of 4 or 8 bytes, which decides how it is passed to functions
+    pub float: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -17,16 +23,44 @@ pub struct IRValue {
     pub id: Symbol,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IRInlineHint {
+    /// Let the inliner decide based on the size of the function
+    Auto,
+    /// Always inline the function, unless it is recursive
+    Always,
+    /// Never inline the function
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IRVisibility {
+    /// Visible to the other modules it is linked with
+    Exported,
+    /// Only visible inside its own module
+    Internal,
+}
+
 pub struct IRItemFunctionDef {
     pub name: Symbol,
+    pub visibility: IRVisibility,
+    pub inline_hint: IRInlineHint,
     pub return_type: IRType,
     pub params: Vec<(Option<Symbol>, IRType)>,
     pub comps: Vec<IRComp>,
     pub label_defs: HashMap<Symbol, u64>,
 }
 
+/// Declaration of a function defined in another module
+pub struct IRItemExternDecl {
+    pub name: Symbol,
+    pub params: Vec<IRType>,
+    pub return_type: IRType,
+}
+
 pub enum IRItemKind {
     FunctionDef(IRItemFunctionDef),
+    ExternDecl(IRItemExternDecl),
 }
 
 pub struct IRItem {
@@ -1,65 +1,153 @@
 use solidc::globals::SessionGlobals;
-use solidc::solidlang::lexer::lex;
+use solidc::ir::assembly::assembler::assemble_ir_module;
+use solidc::ir::assembly::assembly_for_ir_modules;
+use solidc::ir::backend::c::c_source_for_ir_module;
+use solidc::ir::backend::llvm::llvm_ir_for_ir_module;
+use solidc::ir::backend::wat::wat_for_ir_module;
+use solidc::ir::backend::x86_64::assembly_for_ir_module;
+use solidc::ir::binary::{is_binary_ir_module, read_ir_module, write_ir_module};
+use solidc::ir::link::IRLinker;
+use solidc::ir::IRModule;
+use solidc::solidlang::loader::{load_module, LoaderErrorKind, SOURCE_FILE_EXTENSION};
 use solidc::solidlang::lowerer::Lowerer;
-use solidc::solidlang::parser::Parser;
+use solidc::solidlang::parser::ParserErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::process::exit;
 
-fn main() {
-    SessionGlobals::create(|| {
-        /*
-        let src = "\
-        fn fib: %iterations := (1 1) -> (1 1)
-            %constant_1 := const 1 1
-            %constant_0 := const 1 0
-
-            %sequence_numbers := alloc (2 1)
-            %iterator := alloc (1 1)
+const USAGE: &str = "\
+usage: solidc --emit=<kind> [-o <output>] <input>...
 
-            offsetstore (1 1) %sequence_numbers %constant_1 1
-            offsetstore (1 1) %sequence_numbers %constant_0 0
-            store (1 1) %iterator %constant_0
+Inputs are SolidLang sources (.solid), IR assembly or binary IR modules, several inputs are
+linked together.
 
-            ; While condition
-            :while_condition
+kinds:
+    ir      IR assembly
+    bin     binary IR module
+    asm     x86-64 GNU assembly
+    c       C11 source
+    llvm    LLVM IR
+    wat     WebAssembly text";
 
-            %i := load (1 1) %iterator
-            %comparison := binop >= %i %iterations
-            if %comparison while_end
+fn fail(message: &str) -> ! {
+    eprintln!("solidc: {}", message);
+    exit(1)
+}
 
-            ; While body
+fn compile_source(input: &str) -> IRModule {
+    let module = load_module(Path::new(input)).unwrap_or_else(|error| {
+        let file = error.path.clone();
+        let path = file.display();
+        match error.kind {
+            LoaderErrorKind::Io(error) => fail(&format!("cannot read {}: {}", path, error)),
+            LoaderErrorKind::Parser(error) => match error.kind {
+                ParserErrorKind::UnexpectedToken { expected, got } => fail(&format!(
+                    "{}: unexpected token {:?}, expected one of {:?}",
+                    path, got, expected
+                )),
+                ParserErrorKind::InvalidAssignmentTarget { span } => {
+                    fail(&SessionGlobals::with_source_map(|source_map| {
+                        source_map.report(&span, "cannot assign to this expression")
+                    }))
+                }
+                ParserErrorKind::ChainedAssignment { span } => {
+                    fail(&SessionGlobals::with_source_map(|source_map| {
+                        source_map.report(&span, "assignments cannot be chained")
+                    }))
+                }
+                ParserErrorKind::InvalidLiteral { span } => {
+                    fail(&SessionGlobals::with_source_map(|source_map| {
+                        source_map.report(&span, "invalid number literal")
+                    }))
+                }
+            },
+            LoaderErrorKind::Cycle(cycle) => {
+                let cycle: Vec<_> = cycle.iter().map(|path| path.display().to_string()).collect();
+                fail(&format!("{}: modules load each other: {}", path, cycle.join(" -> ")))
+            }
+        }
+    });
+    Lowerer::new().process_module(module)
+}
 
-            %a := offsetload (1 1) %sequence_numbers 0
-            %b := offsetload (1 1) %sequence_numbers 1
-            %c := binop + %a %b
-            offsetstore (1 1) %sequence_numbers %b 0
-            offsetstore (1 1) %sequence_numbers %c 1
+fn read_input(input: &str) -> IRModule {
+    if Path::new(input).extension().and_then(|e| e.to_str()) == Some(SOURCE_FILE_EXTENSION) {
+        return compile_source(input);
+    }
 
-            %iplusplus := binop + %i %constant_1
-            store (1 1) %iterator %iplusplus
+    let src = std::fs::read(input)
+        .unwrap_or_else(|error| fail(&format!("cannot read {}: {}", input, error)));
+    if is_binary_ir_module(&src) {
+        read_ir_module(&src)
+            .unwrap_or_else(|error| fail(&format!("{}@{}: {}", input, error.offset, error.message)))
+    } else {
+        let src = std::str::from_utf8(&src)
+            .unwrap_or_else(|_| fail(&format!("{} is not valid UTF-8", input)));
+        assemble_ir_module(src).unwrap_or_else(|error| {
+            fail(&format!("{}:{}: {}", input, error.line + 1, error.message))
+        })
+    }
+}
 
-            jmp while_condition
+fn main() {
+    let mut emit = None;
+    let mut output = None;
+    let mut inputs = vec![];
 
-            ; While end
-            :while_end
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(kind) = arg.strip_prefix("--emit=") {
+            emit = Some(kind.to_owned());
+        } else if arg == "-o" {
+            output = Some(args.next().unwrap_or_else(|| fail("-o expects a path")));
+        } else if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            return;
+        } else if !arg.starts_with('-') {
+            inputs.push(arg);
+        } else {
+            fail(&format!("unexpected argument {}\n{}", arg, USAGE));
+        }
+    }
 
-            %result := offsetload (1 1) %sequence_numbers 1
-            return %result
-        endfn
-        ";
-        let module = assemble_ir_module(src).unwrap();
-        let mut interpreter = IRInterpreter::new();
-        interpreter.load_module(module);
-        let function_name = SessionGlobals::with_interner_mut(|i| i.intern("fib"));
-        let returned = unsafe { interpreter.call_function(function_name, &[IRInterpreterValue::from_u8(10)]) };
-        println!("{}", returned.into_u8());
-        */
+    let emit = match emit {
+        Some(emit) if !inputs.is_empty() => emit,
+        _ => fail(USAGE),
+    };
 
-        let src = "
-fn main(argc: i32, argv: **u8) -> i32 { 0 }
-    ";
+    SessionGlobals::create(|| {
+        let mut modules: Vec<_> = inputs.iter().map(|input| read_input(input)).collect();
+        let module = if modules.len() == 1 {
+            modules.pop().unwrap()
+        } else {
+            // Functions no input defines are left to the platform (the C library for instance)
+            let mut linker = IRLinker::new();
+            linker.allow_unresolved = true;
+            for (input, module) in inputs.iter().zip(modules) {
+                linker.add_module(input, module);
+            }
+            linker.link().unwrap_or_else(|errors| {
+                let messages: Vec<_> = errors.iter().map(|error| error.message()).collect();
+                fail(&messages.join("\nsolidc: "))
+            })
+        };
+        let result = match emit.as_str() {
+            "ir" => Ok(assembly_for_ir_modules(&module).into_bytes()),
+            "bin" => Ok(write_ir_module(&module)),
+            "asm" => assembly_for_ir_module(&module).map(String::into_bytes),
+            "c" => c_source_for_ir_module(&module).map(String::into_bytes),
+            "llvm" => llvm_ir_for_ir_module(&module).map(String::into_bytes),
+            "wat" => wat_for_ir_module(&module).map(String::into_bytes),
+            _ => fail(&format!("unknown emit kind {}\n{}", emit, USAGE)),
+        };
+        let result = result.unwrap_or_else(|error| fail(&error.message));
 
-        let mut parser = Parser::new(lex(src), src);
-        let module = parser.parse_module().unwrap();
-        let lowerer = Lowerer::new();
-        lowerer.process_module(module);
+        match &output {
+            Some(output) => std::fs::write(output, result)
+                .unwrap_or_else(|error| fail(&format!("cannot write {}: {}", output, error))),
+            None => std::io::stdout()
+                .write_all(&result)
+                .unwrap_or_else(|error| fail(&format!("cannot write output: {}", error))),
+        }
     });
 }
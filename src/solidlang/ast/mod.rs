@@ -13,6 +13,11 @@ pub enum ASTItemKind {
     FunctionDef(ASTFunctionDef),
     StructDef(ASTStructDef),
     Template(ASTTemplate),
+    ModuleDef(ASTModuleDef),
+    Use(ASTUse),
+    Impl(ASTImpl),
+    EnumDef(ASTEnumDef),
+    InterfaceDef(ASTInterfaceDef),
 }
 
 #[derive(Debug, Clone)]
@@ -24,7 +29,7 @@ pub struct ASTItem {
 pub struct ASTFunctionDef {
     pub name: Symbol,
     pub return_type: Option<ASTType>,
-    pub params: Vec<ASTNameAndType>,
+    pub params: Vec<ASTParam>,
     pub statement_block: ASTStatementBlock,
 
     pub span: Span,
@@ -38,9 +43,80 @@ pub struct ASTStructDef {
     pub span: Span,
 }
 
+#[derive(Debug, Clone)]
+pub struct ASTEnumDef {
+    pub name: Symbol,
+    pub variants: Vec<ASTEnumVariant>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTEnumVariant {
+    pub name: Symbol,
+    /// Types of the values the variant holds, empty for `Name`
+    pub payload: Vec<ASTType>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTInterfaceDef {
+    pub name: Symbol,
+    pub functions: Vec<ASTFunctionSignature>,
+
+    pub span: Span,
+}
+
+/// Function declared without a body, like `fn less(self: Self, other: Self) -> bool;`
+#[derive(Debug, Clone)]
+pub struct ASTFunctionSignature {
+    pub name: Symbol,
+    pub return_type: Option<ASTType>,
+    pub params: Vec<ASTParam>,
+
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct ASTTemplate {
-    pub params: Vec<Symbol>,
+    pub params: Vec<ASTTemplateParam>,
+    pub items: Vec<ASTItem>,
+
+    pub span: Span,
+}
+
+/// `T`, `T: Ord + Eq` or `const N: u64`
+#[derive(Debug, Clone)]
+pub struct ASTTemplateParam {
+    pub name: Symbol,
+    /// Paths of the interfaces the args must implement
+    pub bounds: Vec<Vec<Symbol>>,
+    /// Type of the values of a const param, None for type params
+    pub ast_type: Option<ASTType>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTModuleDef {
+    pub name: Symbol,
+    /// None for `mod name;` until the file of the module is loaded
+    pub items: Option<Vec<ASTItem>>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTUse {
+    pub path: Vec<Symbol>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTImpl {
+    pub ast_type: ASTType,
     pub items: Vec<ASTItem>,
 
     pub span: Span,
@@ -53,6 +129,11 @@ pub enum ASTTypeKind {
         generic_args: Vec<ASTType>,
     },
     PointerTo(Box<ASTType>),
+    Tuple(Vec<ASTType>),
+    /// `[T; N]`, the length being a const
+    Array(Box<ASTType>, Box<ASTType>),
+    /// Value given to a const param
+    IntegerLiteral(u64),
 }
 
 #[derive(Debug, Clone)]
@@ -70,14 +151,23 @@ pub struct ASTNameAndType {
     pub span: Span,
 }
 
+#[derive(Debug, Clone)]
+pub struct ASTParam {
+    pub pattern: ASTPattern,
+    pub ast_type: ASTType,
+
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub enum ASTStatementKind {
-    // TODO : Matching local binding
-    LocalBinding(Symbol, Option<ASTType>, Option<ASTExpression>),
+    LocalBinding(ASTPattern, Option<ASTType>, Option<ASTExpression>),
     Expression(ASTExpression),
     Return(ASTExpression),
-    Break,
-    Continue,
+    /// Label of the loop and value it yields
+    Break(Option<Symbol>, Option<ASTExpression>),
+    /// Label of the loop
+    Continue(Option<Symbol>),
     Item(ASTItem),
     Semicolon,
 }
@@ -99,6 +189,15 @@ pub struct ASTStatement {
 #[derive(Debug, Clone)]
 pub enum ASTOperator {
     Assign,
+    PlusAssign,
+    MinusAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitRShiftAssign,
+    BitLShiftAssign,
 
     Plus,
     Minus,
@@ -127,20 +226,33 @@ pub enum ASTOperator {
 #[derive(Debug, Clone)]
 pub enum ASTExpressionKind {
     Ident(Symbol),
-    IntegerLiteral(u64),
+    /// Value and optional type suffix, like `u8` in `10u8`
+    IntegerLiteral(u64, Option<Symbol>),
+    FloatLiteral(f64, Option<Symbol>),
     Boolean(bool),
+    Tuple(Vec<ASTExpression>),
 
     UnaryOperation(ASTOperator, Box<ASTExpression>),
     BinaryOperation(ASTOperator, Box<ASTExpression>, Box<ASTExpression>),
+    /// `expression as Type`
+    Cast(Box<ASTExpression>, ASTType),
+    /// Start, end and whether the end is included
+    Range(Box<ASTExpression>, Box<ASTExpression>, bool),
 
     If(
         Box<ASTExpression>,
         ASTStatementBlock,
         Option<ASTStatementBlock>,
     ),
-    While(Box<ASTExpression>, ASTStatementBlock),
-    Loop(ASTStatementBlock),
-    For(Symbol, Box<ASTExpression>, ASTStatementBlock),
+    // Loops start with their optional label
+    While(Option<Symbol>, Box<ASTExpression>, ASTStatementBlock),
+    Loop(Option<Symbol>, ASTStatementBlock),
+    For(
+        Option<Symbol>,
+        Symbol,
+        Box<ASTExpression>,
+        ASTStatementBlock,
+    ),
     Block(ASTStatementBlock),
 
     TemplateApplication(Box<ASTExpression>, Vec<ASTType>),
@@ -148,7 +260,9 @@ pub enum ASTExpressionKind {
     Index(Box<ASTExpression>, Box<ASTExpression>),
 
     MemberAccess(Box<ASTExpression>, Symbol),
-    StaticAccess(Box<ASTExpression>, Symbol), // TODO : Match
+    StaticAccess(Box<ASTExpression>, Symbol),
+
+    Match(Box<ASTExpression>, Vec<ASTMatchArm>),
 }
 
 #[derive(Debug, Clone)]
@@ -158,7 +272,69 @@ pub struct ASTExpression {
     pub span: Span,
 }
 
+#[derive(Debug, Clone)]
+pub struct ASTMatchArm {
+    pub pattern: ASTPattern,
+    pub expression: ASTExpression,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ASTPatternKind {
+    /// `_`
+    Wildcard,
+    Binding(Symbol),
+    /// Integer or boolean literal, possibly negated
+    Literal(ASTExpression),
+    /// `Enum::Variant` or `Enum::Variant(patterns...)`
+    Variant(Vec<Symbol>, Vec<ASTPattern>),
+    /// `Struct { field, field: pattern, .. }`, the bool telling whether other fields are ignored
+    Struct(Vec<Symbol>, Vec<(Symbol, ASTPattern)>, bool),
+    Tuple(Vec<ASTPattern>),
+    Array(Vec<ASTPattern>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTPattern {
+    pub kind: ASTPatternKind,
+
+    pub span: Span,
+}
+
+impl ASTOperator {
+    pub fn is_assignment(&self) -> bool {
+        matches!(self, ASTOperator::Assign) || self.compound_assignment_operator().is_some()
+    }
+
+    /// Operator applied by a compound assignment like `+=`
+    pub fn compound_assignment_operator(&self) -> Option<ASTOperator> {
+        match self {
+            ASTOperator::PlusAssign => Some(ASTOperator::Plus),
+            ASTOperator::MinusAssign => Some(ASTOperator::Minus),
+            ASTOperator::MulAssign => Some(ASTOperator::Mul),
+            ASTOperator::DivAssign => Some(ASTOperator::Div),
+            ASTOperator::ModAssign => Some(ASTOperator::Mod),
+            ASTOperator::BitAndAssign => Some(ASTOperator::BitAnd),
+            ASTOperator::BitOrAssign => Some(ASTOperator::BitOr),
+            ASTOperator::BitRShiftAssign => Some(ASTOperator::BitRShift),
+            ASTOperator::BitLShiftAssign => Some(ASTOperator::BitLShift),
+            _ => None,
+        }
+    }
+}
+
 impl ASTExpression {
+    /// Whether the expression names a location that can be assigned to
+    pub fn is_place(&self) -> bool {
+        matches!(
+            self.kind,
+            ASTExpressionKind::Ident(_)
+                | ASTExpressionKind::MemberAccess(_, _)
+                | ASTExpressionKind::Index(_, _)
+        )
+    }
+
     pub fn collect_static_access_path(&self) -> (Vec<Symbol>, Option<&ASTExpression>) {
         let mut expression = self;
         let mut path = vec![];
@@ -175,8 +351,25 @@ impl ASTExpression {
                 _ => break Some(expression),
             }
         };
+        path.reverse();
         (path, remainder)
     }
+
+    /// Collects a path like `a::<T>::b::c::<U>`, along with the template args applied to it in order
+    pub fn collect_template_application_path(&self) -> Option<(Vec<Symbol>, Vec<ASTType>)> {
+        let (path, remainder) = self.collect_static_access_path();
+        match remainder.map(|e| &e.kind) {
+            None => Some((path, vec![])),
+            Some(ASTExpressionKind::TemplateApplication(expression, args)) => {
+                let (mut full_path, mut full_args) =
+                    expression.collect_template_application_path()?;
+                full_path.extend(path);
+                full_args.extend(args.iter().cloned());
+                Some((full_path, full_args))
+            }
+            Some(_) => None,
+        }
+    }
 }
 
 impl ASTStatement {
@@ -184,9 +377,10 @@ impl ASTStatement {
         match &self.kind {
             ASTStatementKind::Expression(e) => match e.kind {
                 ASTExpressionKind::If(_, _, _) => false,
-                ASTExpressionKind::While(_, _) => false,
-                ASTExpressionKind::Loop(_) => false,
-                ASTExpressionKind::For(_, _, _) => false,
+                ASTExpressionKind::While(_, _, _) => false,
+                ASTExpressionKind::Loop(_, _) => false,
+                ASTExpressionKind::For(_, _, _, _) => false,
+                ASTExpressionKind::Match(_, _) => false,
                 _ => true,
             },
             ASTStatementKind::Item(_) => false,
@@ -1,5 +1,7 @@
 use crate::globals::Symbol;
 use crate::solidlang::ast::ASTStatementBlock;
+use crate::solidlang::item::SavedScopes;
+use crate::solidlang::pool::PoolRef;
 use crate::solidlang::ty::Ty;
 
 #[derive(Debug)]
@@ -10,14 +12,65 @@ pub struct StructDefField {
 
 #[derive(Debug)]
 pub struct StructDef {
+    /// Full path of the struct, including its module
+    pub path: Vec<Symbol>,
     pub fields: Vec<StructDefField>,
-    pub generic_params: usize
+    pub generic_params: usize,
+    /// What each generic param takes as arg
+    pub generic_kinds: Vec<GenericParamKind>
+}
+
+#[derive(Debug)]
+pub struct EnumDefVariant {
+    pub name: Symbol,
+    pub payload: Vec<Ty>
+}
+
+/// A tagged union, holding a tag followed by the payload of one of its variants
+#[derive(Debug)]
+pub struct EnumDef {
+    /// Full path of the enum, including its module
+    pub path: Vec<Symbol>,
+    pub variants: Vec<EnumDefVariant>,
+    pub generic_params: usize,
+    /// What each generic param takes as arg
+    pub generic_kinds: Vec<GenericParamKind>
+}
+
+/// Arg a generic param takes
+#[derive(Debug, Clone)]
+pub enum GenericParamKind {
+    /// A type implementing the interfaces
+    Type(Vec<PoolRef<InterfaceDef>>),
+    /// A value of the integer type
+    Const(Ty)
 }
 
 pub struct FunctionDef {
+    /// Full path of the function, including its module
+    pub path: Vec<Symbol>,
     pub params: Vec<(Symbol, Ty)>,
     pub generic_params: Vec<Symbol>,
+    /// What each generic param takes as arg
+    pub generic_kinds: Vec<GenericParamKind>,
     pub return_type: Ty,
 
-    pub code: ASTStatementBlock
-}
\ No newline at end of file
+    pub code: ASTStatementBlock,
+    /// Items visible from the body of the function
+    pub scopes: SavedScopes
+}
+/// Function an interface requires, `Self` being the generic param 0 of its types
+#[derive(Debug)]
+pub struct InterfaceDefFunction {
+    pub name: Symbol,
+    pub params: Vec<(Symbol, Ty)>,
+    pub return_type: Ty
+}
+
+/// Methods a type must have to be passed to a generic param bounded by the interface
+#[derive(Debug)]
+pub struct InterfaceDef {
+    /// Full path of the interface, including its module
+    pub path: Vec<Symbol>,
+    pub functions: Vec<InterfaceDefFunction>
+}
@@ -1,8 +1,8 @@
 use crate::globals::{SessionGlobals, Symbol};
 use crate::solidlang::ty::{Ty, TyPrimitive};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::solidlang::pool::PoolRef;
-use crate::solidlang::defs::FunctionDef;
+use crate::solidlang::defs::{FunctionDef, InterfaceDef};
 
 #[derive(Clone)]
 pub struct SavedScopes {
@@ -13,30 +13,182 @@ impl SavedScopes {
     pub fn empty() -> Self {
         Self { scopes: vec![] }
     }
+
+    /// Number of functions registered with exactly this path
+    pub fn count_functions(&self, path: &[Symbol]) -> usize {
+        self.scopes
+            .iter()
+            .filter_map(|scope| scope.functions.get(path))
+            .map(|functions| functions.len())
+            .sum()
+    }
+
+    /// The scopes with another one holding the functions, registered with their full paths
+    pub fn with_functions(&self, functions: &[(Vec<Symbol>, PoolRef<FunctionDef>)]) -> Self {
+        let mut scope = ItemScope::new();
+        for (path, function) in functions {
+            scope.functions.entry(path.clone()).or_default().push(*function);
+        }
+
+        let mut scopes = self.scopes.clone();
+        scopes.push(scope);
+        Self { scopes }
+    }
 }
 
 #[derive(Clone)]
 pub struct ItemScope {
     tys: HashMap<Vec<Symbol>, Ty>,
-    functions: HashMap<Vec<Symbol>, Vec<PoolRef<FunctionDef>>>
+    functions: HashMap<Vec<Symbol>, Vec<PoolRef<FunctionDef>>>,
+    interfaces: HashMap<Vec<Symbol>, PoolRef<InterfaceDef>>
 }
 
 impl ItemScope {
     fn new() -> Self {
         Self {
             tys: HashMap::new(),
-            functions: HashMap::new()
+            functions: HashMap::new(),
+            interfaces: HashMap::new()
         }
     }
 }
 
 pub struct ItemContext {
     scopes: Vec<ItemScope>,
+    /// Path of the module items are currently registered in
+    module_path: Vec<Symbol>,
+    modules: HashSet<Vec<Symbol>>,
+    /// Names brought in by `use`, mapped to the module of the `use` and the path it names
+    aliases: HashMap<Vec<Symbol>, (Vec<Symbol>, Vec<Symbol>)>,
 }
 
 impl ItemContext {
     pub fn new() -> Self {
-        Self { scopes: vec![] }
+        Self {
+            scopes: vec![],
+            module_path: vec![],
+            modules: HashSet::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub fn enter_module(&mut self, name: Symbol) {
+        self.module_path.push(name);
+        self.modules.insert(self.module_path.clone());
+    }
+
+    pub fn exit_module(&mut self) {
+        self.module_path.pop();
+    }
+
+    pub fn swap_module_path(&mut self, with: Vec<Symbol>) -> Vec<Symbol> {
+        std::mem::replace(&mut self.module_path, with)
+    }
+
+    /// Full path of an item named in the current module
+    pub fn item_path(&self, name: Symbol) -> Vec<Symbol> {
+        let mut path = self.module_path.clone();
+        path.push(name);
+        path
+    }
+
+    pub fn register_alias(&mut self, path: &[Symbol]) {
+        let name = *path.last().unwrap();
+        let alias = self.item_path(name);
+        if self.aliases.contains_key(&alias) {
+            panic!("ERROR {} is already imported", path_to_string(&alias));
+        }
+        self.aliases
+            .insert(alias, (self.module_path.clone(), path.to_vec()));
+    }
+
+    /// Checks that every `use` names a type, a function or a module
+    pub fn check_aliases(&self) {
+        for (alias, (module_path, path)) in &self.aliases {
+            let found = self
+                .resolve_from(module_path, path, &mut vec![alias.clone()], &|path| {
+                    if self.find_ty(path).is_some()
+                        || !self.find_functions(path).is_empty()
+                        || self.find_interface(path).is_some()
+                        || self.modules.contains(path)
+                    {
+                        Some(())
+                    } else {
+                        None
+                    }
+                })
+                .is_some();
+            if !found {
+                panic!("ERROR Could not resolve import {}", path_to_string(path));
+            }
+        }
+    }
+
+    /// Resolves a path used in a module
+    ///
+    /// The path is looked up relative to the module, then to each of its parents up to the root.
+    /// When it starts with a name brought in by `use`, that name is replaced by the path it names.
+    fn resolve_from<R>(
+        &self,
+        module_path: &[Symbol],
+        path: &[Symbol],
+        visited_aliases: &mut Vec<Vec<Symbol>>,
+        lookup: &dyn Fn(&[Symbol]) -> Option<R>,
+    ) -> Option<R> {
+        for depth in (0..=module_path.len()).rev() {
+            let mut full_path = module_path[..depth].to_vec();
+            full_path.extend_from_slice(path);
+            if let Some(result) = lookup(&full_path) {
+                return Some(result);
+            }
+
+            for length in (1..=path.len()).rev() {
+                full_path.truncate(depth + length);
+                if let Some((alias_module_path, alias_path)) = self.aliases.get(&full_path) {
+                    if visited_aliases.contains(&full_path) {
+                        let aliases: Vec<_> = visited_aliases
+                            .iter()
+                            .map(|alias| path_to_string(alias))
+                            .collect();
+                        panic!("ERROR Cyclic imports through {}", aliases.join(", "));
+                    }
+                    visited_aliases.push(full_path.clone());
+                    let mut target = alias_path.clone();
+                    target.extend_from_slice(&path[length..]);
+                    let result =
+                        self.resolve_from(alias_module_path, &target, visited_aliases, lookup);
+                    visited_aliases.pop();
+                    if result.is_some() {
+                        return result;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_ty(&self, path: &[Symbol]) -> Option<&Ty> {
+        for i in (0..self.scopes.len()).rev() {
+            if let Some(r) = self.scopes[i].tys.get(path) {
+                return Some(r);
+            }
+        }
+
+        None
+    }
+
+    /// Functions registered with exactly this path
+    pub fn find_functions(&self, path: &[Symbol]) -> Vec<PoolRef<FunctionDef>> {
+        let mut result = vec![];
+
+        for i in (0..self.scopes.len()).rev() {
+            if let Some(r) = self.scopes[i].functions.get(path) {
+                result.extend(r.iter());
+            }
+        }
+
+        result
     }
 
     pub fn start_scope(&mut self) {
@@ -69,14 +221,19 @@ impl ItemContext {
             self.register_ty(&[i.intern("i32")], Ty::from_primitive(TyPrimitive::I32));
             self.register_ty(&[i.intern("u64")], Ty::from_primitive(TyPrimitive::U64));
             self.register_ty(&[i.intern("i64")], Ty::from_primitive(TyPrimitive::I64));
+            self.register_ty(&[i.intern("f32")], Ty::from_primitive(TyPrimitive::F32));
+            self.register_ty(&[i.intern("f64")], Ty::from_primitive(TyPrimitive::F64));
             self.register_ty(&[i.intern("bool")], Ty::from_primitive(TyPrimitive::Bool));
             self.register_ty(&[i.intern("char")], Ty::from_primitive(TyPrimitive::Char));
             self.register_ty(&[i.intern("void")], Ty::from_primitive(TyPrimitive::Void));
         });
     }
 
+    /// Registers a type in the current module
     pub fn register_ty(&mut self, path: &[Symbol], ty: Ty) {
-        if self.resolve_ty(path).is_some() {
+        let mut full_path = self.module_path.clone();
+        full_path.extend_from_slice(path);
+        if self.find_ty(&full_path).is_some() {
             panic!("ERROR Type already defined");
         }
 
@@ -84,42 +241,106 @@ impl ItemContext {
             .last_mut()
             .unwrap()
             .tys
-            .insert(path.to_vec(), ty);
+            .insert(full_path, ty);
     }
 
     pub fn resolve_ty(&self, path: &[Symbol]) -> Option<&Ty> {
-        for i in (0..self.scopes.len()).rev() {
-            if let Some(r) = self.scopes[i].tys.get(path) {
-                return Some(r);
-            }
-        }
-
-        None
+        self.resolve_from(&self.module_path, path, &mut vec![], &|path| self.find_ty(path))
     }
 
+    /// Registers a function in the current module
     pub fn register_function(&mut self, path: &[Symbol], fun: PoolRef<FunctionDef>) {
+        let mut full_path = self.module_path.clone();
+        full_path.extend_from_slice(path);
 
         let functions = &mut self.scopes
             .last_mut()
             .unwrap()
             .functions;
-        if functions.contains_key(path) {
-            functions.get_mut(path).unwrap().push(fun);
-        }
-        else {
-            functions.insert(path.to_vec(), vec![fun]);
+        functions.entry(full_path).or_default().push(fun);
+    }
+
+    fn find_interface(&self, path: &[Symbol]) -> Option<PoolRef<InterfaceDef>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.interfaces.get(path).copied())
+    }
+
+    /// Registers an interface in the current module
+    pub fn register_interface(&mut self, name: Symbol, interface: PoolRef<InterfaceDef>) {
+        let path = self.item_path(name);
+        if self.find_interface(&path).is_some() {
+            panic!("ERROR Interface {} is already defined", path_to_string(&path));
         }
+
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .interfaces
+            .insert(path, interface);
+    }
+
+    pub fn resolve_interface(&self, path: &[Symbol]) -> Option<PoolRef<InterfaceDef>> {
+        self.resolve_from(&self.module_path, path, &mut vec![], &|path| {
+            self.find_interface(path)
+        })
     }
 
     pub fn resolve_function(&self, path: &[Symbol]) -> Vec<PoolRef<FunctionDef>> {
-        let mut result = vec![];
+        self.resolve_from(&self.module_path, path, &mut vec![], &|path| {
+            let functions = self.find_functions(path);
+            if functions.is_empty() {
+                None
+            } else {
+                Some(functions)
+            }
+        })
+        .unwrap_or_default()
+    }
 
-        for i in (0..self.scopes.len()).rev() {
-            if let Some(r) = self.scopes[i].functions.get(path) {
-                result.extend(r.iter());
+    /// Functions with a name close to the last segment of the path, closest first
+    pub fn near_miss_functions(&self, path: &[Symbol]) -> Vec<Vec<Symbol>> {
+        let name = SessionGlobals::with_interner(|i| i.get(path.last().unwrap()).unwrap());
+        let max_distance = (name.len() / 3).max(1);
+
+        let mut candidates = vec![];
+        for scope in &self.scopes {
+            for candidate in scope.functions.keys() {
+                let candidate_name =
+                    SessionGlobals::with_interner(|i| i.get(candidate.last().unwrap()).unwrap());
+                let distance = edit_distance(name, candidate_name);
+                if distance <= max_distance {
+                    candidates.push((distance, path_to_string(candidate), candidate.clone()));
+                }
             }
         }
+        candidates.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        candidates.dedup_by(|a, b| a.1 == b.1);
 
-        result
+        candidates.into_iter().take(5).map(|(_, _, path)| path).collect()
+    }
+}
+
+pub fn path_to_string(path: &[Symbol]) -> String {
+    SessionGlobals::with_interner(|i| {
+        path.iter()
+            .map(|symbol| i.get(symbol).unwrap())
+            .collect::<Vec<_>>()
+            .join("::")
+    })
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
     }
+    previous[b.len()]
 }
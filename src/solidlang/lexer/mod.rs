@@ -32,9 +32,12 @@ impl<'a> LexerCursor<'a> {
 pub enum TokenKind {
     // Identifiers
     Ident,
+    /// Name of a loop, like `'outer`
+    Label,
 
     // Literals
     IntegerLiteral,
+    FloatLiteral,
     BooleanTrue,
     BooleanFalse,
 
@@ -52,13 +55,24 @@ pub enum TokenKind {
     KwReturn,
     KwBreak,
     KwContinue,
+    KwMod,
+    KwUse,
+    KwImpl,
+    KwEnum,
+    KwMatch,
+    KwAs,
+    KwInterface,
+    KwConst,
 
     // Punctuation
     Semicolon,
     Colon,
     ColonColon,
     Arrow,
+    FatArrow,
     Dot,
+    DotDot,
+    DotDotEq,
     Comma,
     LTurbofish,
 
@@ -74,6 +88,15 @@ pub enum TokenKind {
 
     // Assignment & operators
     Assign,
+    PlusAssign,
+    MinusAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitRShiftAssign,
+    BitLShiftAssign,
 
     Plus,
     Minus,
@@ -136,7 +159,19 @@ fn first_token(src: &str) -> ThinToken {
                 TokenKind::Colon
             }
         }
-        '.' => TokenKind::Dot,
+        '.' => {
+            if cursor.nth(0) == '.' {
+                cursor.bump();
+                if cursor.nth(0) == '=' {
+                    cursor.bump();
+                    TokenKind::DotDotEq
+                } else {
+                    TokenKind::DotDot
+                }
+            } else {
+                TokenKind::Dot
+            }
+        }
         ',' => TokenKind::Comma,
 
         '(' => TokenKind::LParen,
@@ -148,7 +183,12 @@ fn first_token(src: &str) -> ThinToken {
         '<' => {
             if cursor.nth(0) == '~' {
                 cursor.bump();
-                TokenKind::BitLShift
+                if cursor.nth(0) == '=' {
+                    cursor.bump();
+                    TokenKind::BitLShiftAssign
+                } else {
+                    TokenKind::BitLShift
+                }
             } else if cursor.nth(0) == '=' {
                 cursor.bump();
                 TokenKind::LesserEqual
@@ -169,27 +209,64 @@ fn first_token(src: &str) -> ThinToken {
             if cursor.nth(0) == '=' {
                 cursor.bump();
                 TokenKind::Equal
+            } else if cursor.nth(0) == '>' {
+                cursor.bump();
+                TokenKind::FatArrow
             } else {
                 TokenKind::Assign
             }
         }
-        '+' => TokenKind::Plus,
+        '+' => {
+            if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::PlusAssign
+            } else {
+                TokenKind::Plus
+            }
+        }
         '-' => {
             if cursor.nth(0) == '>' {
                 cursor.bump();
                 TokenKind::Arrow
+            } else if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::MinusAssign
             } else {
                 TokenKind::Minus
             }
         }
-        '*' => TokenKind::Mul,
-        '/' => TokenKind::Div,
-        '%' => TokenKind::Mod,
+        '*' => {
+            if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::MulAssign
+            } else {
+                TokenKind::Mul
+            }
+        }
+        '/' => {
+            if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::DivAssign
+            } else {
+                TokenKind::Div
+            }
+        }
+        '%' => {
+            if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::ModAssign
+            } else {
+                TokenKind::Mod
+            }
+        }
 
         '&' => {
             if cursor.nth(0) == '&' {
                 cursor.bump();
                 TokenKind::BoolAnd
+            } else if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::BitAndAssign
             } else {
                 TokenKind::BitAnd
             }
@@ -198,6 +275,9 @@ fn first_token(src: &str) -> ThinToken {
             if cursor.nth(0) == '|' {
                 cursor.bump();
                 TokenKind::BoolOr
+            } else if cursor.nth(0) == '=' {
+                cursor.bump();
+                TokenKind::BitOrAssign
             } else {
                 TokenKind::BitOr
             }
@@ -205,7 +285,12 @@ fn first_token(src: &str) -> ThinToken {
         '~' => {
             if cursor.nth(0) == '>' {
                 cursor.bump();
-                TokenKind::BitRShift
+                if cursor.nth(0) == '=' {
+                    cursor.bump();
+                    TokenKind::BitRShiftAssign
+                } else {
+                    TokenKind::BitRShift
+                }
             } else {
                 TokenKind::BitNot
             }
@@ -219,6 +304,13 @@ fn first_token(src: &str) -> ThinToken {
             }
         }
 
+        '\'' if is_valid_ident_char::<true>(cursor.nth(0)) => {
+            while is_valid_ident_char::<false>(cursor.nth(0)) {
+                cursor.bump();
+            }
+            TokenKind::Label
+        }
+
         c if is_valid_ident_char::<true>(c) => {
             while is_valid_ident_char::<false>(cursor.nth(0)) {
                 cursor.bump();
@@ -240,15 +332,65 @@ fn first_token(src: &str) -> ThinToken {
                 "return" => TokenKind::KwReturn,
                 "break" => TokenKind::KwBreak,
                 "continue" => TokenKind::KwContinue,
+                "mod" => TokenKind::KwMod,
+                "use" => TokenKind::KwUse,
+                "impl" => TokenKind::KwImpl,
+                "enum" => TokenKind::KwEnum,
+                "match" => TokenKind::KwMatch,
+                "as" => TokenKind::KwAs,
+                "interface" => TokenKind::KwInterface,
+                "const" => TokenKind::KwConst,
                 _ => TokenKind::Ident,
             }
         }
         EOF_CHAR => TokenKind::EOF,
         c if c.is_ascii_digit() => {
-            while cursor.nth(0).is_ascii_digit() {
+            let is_digit = |c: char| c.is_ascii_digit() || c == '_';
+            let mut float = false;
+            if c == '0' && cursor.nth(0) == 'x' {
+                cursor.bump();
+                while cursor.nth(0).is_ascii_hexdigit() || cursor.nth(0) == '_' {
+                    cursor.bump();
+                }
+            } else if c == '0' && matches!(cursor.nth(0), 'o' | 'b') {
+                // Digits invalid for the radix are reported by the parser
                 cursor.bump();
+                while is_digit(cursor.nth(0)) {
+                    cursor.bump();
+                }
+            } else {
+                while is_digit(cursor.nth(0)) {
+                    cursor.bump();
+                }
+                // A dot only starts a fraction when a digit follows, so `0..5` stays a range
+                if cursor.nth(0) == '.' && cursor.nth(1).is_ascii_digit() {
+                    cursor.bump();
+                    while is_digit(cursor.nth(0)) {
+                        cursor.bump();
+                    }
+                    float = true;
+                }
+                if matches!(cursor.nth(0), 'e' | 'E')
+                    && (cursor.nth(1).is_ascii_digit()
+                        || (matches!(cursor.nth(1), '+' | '-') && cursor.nth(2).is_ascii_digit()))
+                {
+                    cursor.bump();
+                    cursor.bump();
+                    while is_digit(cursor.nth(0)) {
+                        cursor.bump();
+                    }
+                    float = true;
+                }
+            }
+            // Type suffix, like `u8` in `10u8`
+            while is_valid_ident_char::<false>(cursor.nth(0)) {
+                cursor.bump();
+            }
+            if float {
+                TokenKind::FloatLiteral
+            } else {
+                TokenKind::IntegerLiteral
             }
-            TokenKind::IntegerLiteral
         }
         c if c.is_ascii_whitespace() => {
             while cursor.nth(0).is_ascii_whitespace() {
@@ -0,0 +1,94 @@
+use crate::globals::SessionGlobals;
+use crate::solidlang::ast::{ASTItem, ASTItemKind, ASTModule};
+use crate::solidlang::lexer::lex;
+use crate::solidlang::parser::{Parser, ParserError};
+use std::path::{Path, PathBuf};
+
+pub const SOURCE_FILE_EXTENSION: &str = "solid";
+
+#[derive(Debug)]
+pub enum LoaderErrorKind {
+    Io(std::io::Error),
+    Parser(ParserError),
+    /// Files loading each other, the first one being loaded again by the last one
+    Cycle(Vec<PathBuf>),
+}
+
+#[derive(Debug)]
+pub struct LoaderError {
+    pub kind: LoaderErrorKind,
+    /// File in which the error happened
+    pub path: PathBuf,
+}
+
+pub type LoaderResult<T> = Result<T, LoaderError>;
+
+struct Loader {
+    /// Files currently being loaded, outermost first
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    fn load_file(&mut self, path: &Path) -> LoaderResult<ASTModule> {
+        let error = |kind| LoaderError {
+            kind,
+            path: path.to_path_buf(),
+        };
+
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| error(LoaderErrorKind::Io(e)))?;
+        if let Some(position) = self.stack.iter().position(|p| *p == canonical) {
+            let mut cycle = self.stack[position..].to_vec();
+            cycle.push(canonical);
+            return Err(error(LoaderErrorKind::Cycle(cycle)));
+        }
+
+        let src = std::fs::read_to_string(path).map_err(|e| error(LoaderErrorKind::Io(e)))?;
+        let offset = SessionGlobals::with_source_map_mut(|source_map| {
+            source_map.add_file(path.to_path_buf(), src.clone())
+        });
+        let mut parser = Parser::new(lex(&src), &src, offset);
+        let mut module = parser
+            .parse_module()
+            .map_err(|e| error(LoaderErrorKind::Parser(e)))?;
+
+        self.stack.push(canonical);
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        self.load_items(&mut module.items, directory)?;
+        self.stack.pop();
+
+        Ok(module)
+    }
+
+    fn load_items(&mut self, items: &mut [ASTItem], directory: &Path) -> LoaderResult<()> {
+        for item in items {
+            match &mut item.kind {
+                ASTItemKind::ModuleDef(ast_module_def) => match &mut ast_module_def.items {
+                    Some(items) => self.load_items(items, directory)?,
+                    None => {
+                        let name =
+                            SessionGlobals::with_interner(|i| i.get(&ast_module_def.name).unwrap());
+                        let path = directory.join(name).with_extension(SOURCE_FILE_EXTENSION);
+                        ast_module_def.items = Some(self.load_file(&path)?.items);
+                    }
+                },
+                ASTItemKind::Template(ast_template) => {
+                    self.load_items(&mut ast_template.items, directory)?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a source file along with the files of the modules it declares
+///
+/// `mod name;` loads `name.solid` from the directory of the file declaring it (inline modules do
+/// not change the directory), and fails if a file ends up loading itself.
+pub fn load_module(path: &Path) -> LoaderResult<ASTModule> {
+    let mut loader = Loader { stack: vec![] };
+    loader.load_file(path)
+}
@@ -0,0 +1,343 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{IRCompFunctionCall, IRCompKind};
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind};
+use crate::solidlang::defs::{FunctionDef, GenericParamKind};
+use crate::solidlang::item::path_to_string;
+use crate::solidlang::lowerer::codegen::expression::{is_float_literal, is_integer_literal};
+use crate::solidlang::lowerer::codegen::heap::HeapBuiltin;
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::pool::PoolRef;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+/// An argument, lowered before the function it is passed to is picked
+enum Arg {
+    /// Lowered once the type of its param is known
+    IntegerLiteral,
+    FloatLiteral,
+    Value(Value),
+    /// Struct a method is called on, passed by pointer or by value depending on the method
+    Receiver(Place)
+}
+
+/// How well a function matches the args of a call, lower is better
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+struct Rank {
+    /// Generic functions only match through an instantiation
+    generic: bool,
+    /// Args passed to `*void` params
+    pointer_conversions: usize,
+    /// Integer literals passed to params of another type than `i32`, and float literals to another type than `f64`
+    literal_conversions: usize
+}
+
+/// Params of a function matching the args of a call
+struct Candidate {
+    function_def: PoolRef<FunctionDef>,
+    template_args: Vec<Ty>,
+    params: Vec<Ty>,
+    rank: Rank
+}
+
+fn signature(function_def: PoolRef<FunctionDef>) -> String {
+    SessionGlobals::with_function_def_pool(|pool| {
+        let function_def = pool.get(function_def);
+        let names = &function_def.generic_params;
+        let params: Vec<_> = function_def.params.iter().map(|(name, ty)| {
+            let name = SessionGlobals::with_interner(|i| i.get(name).unwrap());
+            format!("{}: {}", name, ty.to_string_with_params(names))
+        }).collect();
+
+        let mut signature = String::new();
+        if !names.is_empty() {
+            let params: Vec<_> = names.iter().zip(&function_def.generic_kinds).map(|(name, kind)| {
+                let name = SessionGlobals::with_interner(|i| i.get(name).unwrap()).to_owned();
+                let bounds = match kind {
+                    GenericParamKind::Type(bounds) if bounds.is_empty() => return name,
+                    GenericParamKind::Type(bounds) => bounds,
+                    GenericParamKind::Const(ty) => return format!("const {}: {}", name, ty)
+                };
+                let bounds: Vec<_> = bounds.iter().map(|bound| {
+                    SessionGlobals::with_interface_def_pool(|pool| path_to_string(&pool.get(*bound).path))
+                }).collect();
+                format!("{}: {}", name, bounds.join(" + "))
+            }).collect();
+            signature += &format!("template<{}> ", params.join(", "));
+        }
+        signature += &format!("{}({})", path_to_string(&function_def.path), params.join(", "));
+        if !function_def.return_type.is_void() {
+            signature += &format!(" -> {}", function_def.return_type.to_string_with_params(names));
+        }
+        signature
+    })
+}
+
+fn args_string(args: &[Arg]) -> String {
+    let args: Vec<_> = args.iter().map(|arg| match arg {
+        Arg::IntegerLiteral => "{integer}".to_owned(),
+        Arg::FloatLiteral => "{float}".to_owned(),
+        Arg::Value(value) => value.ty.to_string(),
+        Arg::Receiver(place) => place.ty.to_string()
+    }).collect();
+    format!("({})", args.join(", "))
+}
+
+/// Binds the generic params left unbound to the types of the args passed to params using them
+///
+/// Literals only bind the params they are passed to directly, to `i32` or `f64`, once the other
+/// args are matched.
+fn infer_template_args(generic_params: &[Symbol], params: &[(Symbol, Ty)], args: &[Arg], bindings: &mut [Option<Ty>]) -> Result<(), String> {
+    // Mismatches are reported once the params are substituted
+    for (arg, (_, param)) in args.iter().zip(params) {
+        if let Arg::Value(value) = arg {
+            param.infer_params(&value.ty, bindings);
+        }
+    }
+    for (arg, (_, param)) in args.iter().zip(params) {
+        if let TyKind::Param(index) = param.kind {
+            match arg {
+                Arg::IntegerLiteral if bindings[index].is_none() => bindings[index] = Some(Ty::from_primitive(TyPrimitive::I32)),
+                Arg::FloatLiteral if bindings[index].is_none() => bindings[index] = Some(Ty::from_primitive(TyPrimitive::F64)),
+                _ => {}
+            }
+        }
+    }
+
+    match bindings.iter().position(|binding| binding.is_none()) {
+        Some(index) => {
+            let name = SessionGlobals::with_interner(|i| i.get(&generic_params[index]).unwrap());
+            Err(format!("could not infer template param {}", name))
+        }
+        None => Ok(())
+    }
+}
+
+/// Checks that the function can be called with the args, and how well it matches them
+///
+/// Generic params that appear in the type of the receiver are inferred from it, the others are
+/// given by the template args in order, or inferred from the args when there are none.
+fn match_candidate(function_def: PoolRef<FunctionDef>, template_args: &[Ty], args: &[Arg]) -> Result<Candidate, String> {
+    let (params, generic_params) = SessionGlobals::with_function_def_pool(|pool| {
+        let function_def = pool.get(function_def);
+        (function_def.params.clone(), function_def.generic_params.clone())
+    });
+
+    if args.len() != params.len() {
+        return Err(format!("expects {} args, got {}", params.len(), args.len()));
+    }
+
+    let mut bindings = vec![None; generic_params.len()];
+    if let Some(Arg::Receiver(place)) = args.first() {
+        let (name, self_ty) = &params[0];
+        if SessionGlobals::with_interner(|i| i.get(name).unwrap()) != "self" {
+            return Err("not a method".to_owned());
+        }
+        let self_ty = match &self_ty.kind {
+            TyKind::PointerTo(ty) => ty,
+            _ => self_ty
+        };
+        if !self_ty.infer_params(&place.ty, &mut bindings) {
+            return Err(format!("self is {}", place.ty));
+        }
+    }
+
+    let inferred = bindings.iter().filter(|binding| binding.is_some()).count();
+    if template_args.is_empty() {
+        infer_template_args(&generic_params, &params, args, &mut bindings)?;
+    } else if template_args.len() != generic_params.len() - inferred {
+        return Err(format!("expects {} template args, got {}", generic_params.len() - inferred, template_args.len()));
+    }
+    let mut template_args = template_args.iter();
+    let template_args: Vec<_> = bindings.into_iter().map(|binding| {
+        binding.unwrap_or_else(|| template_args.next().unwrap().clone())
+    }).collect();
+
+    let mut rank = Rank {
+        generic: !generic_params.is_empty(),
+        pointer_conversions: 0,
+        literal_conversions: 0
+    };
+    let params: Vec<_> = params.iter().map(|(_, ty)| ty.substitute(&template_args)).collect();
+    for (i, (arg, param)) in args.iter().zip(&params).enumerate() {
+        match arg {
+            Arg::IntegerLiteral if param.is_integer() => {
+                if param.kind != TyKind::Primitive(TyPrimitive::I32) {
+                    rank.literal_conversions += 1;
+                }
+            }
+            Arg::FloatLiteral if param.is_float() => {
+                if param.kind != TyKind::Primitive(TyPrimitive::F64) {
+                    rank.literal_conversions += 1;
+                }
+            }
+            Arg::Value(value) if value.ty == *param => {}
+            Arg::Value(value) if is_void_pointer(param) && matches!(value.ty.kind, TyKind::PointerTo(_)) => {
+                rank.pointer_conversions += 1;
+            }
+            Arg::Receiver(_) => {}
+            Arg::IntegerLiteral => return Err(format!("arg {} is an integer, expected {}", i + 1, param)),
+            Arg::FloatLiteral => return Err(format!("arg {} is a float, expected {}", i + 1, param)),
+            Arg::Value(value) => return Err(format!("arg {} is {}, expected {}", i + 1, value.ty, param))
+        }
+    }
+
+    Ok(Candidate {
+        function_def,
+        template_args,
+        params,
+        rank
+    })
+}
+
+fn is_void_pointer(ty: &Ty) -> bool {
+    match &ty.kind {
+        TyKind::PointerTo(ty) => ty.is_void(),
+        _ => false
+    }
+}
+
+impl Lowerer {
+    pub(in crate::solidlang::lowerer) fn lower_call(&mut self, builder: &mut FunctionBuilder, callee: &ASTExpression, args: &[ASTExpression]) -> Option<Value> {
+        let (method, template_args) = match &callee.kind {
+            ASTExpressionKind::MemberAccess(receiver, name) => (Some((receiver, *name)), vec![]),
+            ASTExpressionKind::TemplateApplication(callee, template_args) => match &callee.kind {
+                ASTExpressionKind::MemberAccess(receiver, name) => (Some((receiver, *name)), template_args.clone()),
+                _ => (None, vec![])
+            },
+            _ => (None, vec![])
+        };
+
+        let mut lowered_args = vec![];
+        let (path, function_defs, template_args) = match method {
+            Some((receiver, name)) => {
+                let mut place = self.lower_place(builder, receiver)?;
+                // Methods are reached through pointers
+                if let TyKind::PointerTo(ty) = &place.ty.kind {
+                    let ty = (**ty).clone();
+                    let ptr = builder.load(&place).ir.unwrap();
+                    place = Place { ptr, offset: 0, ty };
+                }
+
+                let mut path = place.ty.def_path().unwrap_or_else(|| panic!("ERROR {} has no methods", place.ty));
+                path.push(name);
+
+                let function_defs = self.context.find_functions(&path);
+                if function_defs.is_empty() {
+                    panic!("ERROR {} has no method {:?}", place.ty, name);
+                }
+                lowered_args.push(Arg::Receiver(place));
+
+                (path, function_defs, template_args)
+            }
+            None => {
+                let (path, template_args) = callee.collect_template_application_path().unwrap_or_else(|| {
+                    panic!("ERROR Only functions can be called")
+                });
+
+                let function_defs = self.context.resolve_function(&path);
+                if function_defs.is_empty() {
+                    if let Some(builtin) = HeapBuiltin::from_path(&path) {
+                        return self.lower_heap_call(builder, builtin, &template_args, args);
+                    }
+                    let near_misses: Vec<_> = self.context.near_miss_functions(&path).iter().map(|path| path_to_string(path)).collect();
+                    if near_misses.is_empty() {
+                        panic!("ERROR Could not find function {}", path_to_string(&path));
+                    }
+                    panic!("ERROR Could not find function {}, did you mean {}?", path_to_string(&path), near_misses.join(", "));
+                }
+
+                (path, function_defs, template_args)
+            }
+        };
+        let template_args: Vec<_> = template_args.iter().map(|ast_type| self.resolve_ast_type(ast_type)).collect();
+
+        // Literals take the type of the param they are passed to, other args are lowered first
+        for arg in args {
+            if is_integer_literal(arg) {
+                lowered_args.push(Arg::IntegerLiteral);
+            } else if is_float_literal(arg) {
+                lowered_args.push(Arg::FloatLiteral);
+            } else {
+                lowered_args.push(Arg::Value(self.lower_expression(builder, arg, None)?));
+            }
+        }
+
+        let candidate = self.pick_candidate(&path, &function_defs, &template_args, &lowered_args);
+
+        let mut ir_args = vec![];
+        let mut args = args.iter();
+        for (lowered_arg, param) in lowered_args.into_iter().zip(&candidate.params) {
+            let value = match lowered_arg {
+                Arg::IntegerLiteral | Arg::FloatLiteral => {
+                    let value = self.lower_expression(builder, args.next().unwrap(), Some(param))?;
+                    expect_ty(&value, param);
+                    value
+                }
+                Arg::Value(value) => {
+                    args.next();
+                    value
+                }
+                Arg::Receiver(place) => match &param.kind {
+                    TyKind::PointerTo(_) => {
+                        let ptr = builder.place_address(&place);
+                        Value { ir: Some(ptr), ty: param.clone() }
+                    }
+                    _ => builder.load(&place)
+                }
+            };
+            ir_args.push(builder.ir_value_or_void(&value));
+        }
+
+        let return_type = SessionGlobals::with_function_def_pool(|pool| pool.get(candidate.function_def).return_type.clone());
+        let return_type = return_type.substitute(&candidate.template_args);
+        let name = self.get_ir_name(candidate.function_def, candidate.template_args);
+        let call = IRCompKind::FunctionCall(IRCompFunctionCall { name, args: ir_args });
+        if return_type.is_void() {
+            builder.push_void(call);
+            Some(Value::void())
+        } else {
+            let ir = builder.push(call);
+            Some(Value { ir: Some(ir), ty: return_type })
+        }
+    }
+
+    /// Picks the function matching the args best
+    fn pick_candidate(&self, path: &[Symbol], function_defs: &[PoolRef<FunctionDef>], template_args: &[Ty], args: &[Arg]) -> Candidate {
+        let mut candidates = vec![];
+        let mut rejected = vec![];
+        for function_def in function_defs {
+            let candidate = match_candidate(*function_def, template_args, args).and_then(|candidate| {
+                let kinds = SessionGlobals::with_function_def_pool(|pool| pool.get(*function_def).generic_kinds.clone());
+                self.check_template_args(&kinds, &candidate.template_args)?;
+                Ok(candidate)
+            });
+            match candidate {
+                Ok(candidate) => candidates.push(candidate),
+                Err(reason) => rejected.push(format!("    {} ({})", signature(*function_def), reason))
+            }
+        }
+
+        let best = match candidates.iter().map(|candidate| candidate.rank).min() {
+            Some(best) => best,
+            None => panic!(
+                "ERROR No function {} can be called with {}, candidates are:\n{}",
+                path_to_string(path),
+                args_string(args),
+                rejected.join("\n")
+            )
+        };
+
+        candidates.retain(|candidate| candidate.rank == best);
+        if candidates.len() > 1 {
+            let signatures: Vec<_> = candidates.iter().map(|candidate| format!("    {}", signature(candidate.function_def))).collect();
+            panic!(
+                "ERROR Call to {} with {} is ambiguous, candidates are:\n{}",
+                path_to_string(path),
+                args_string(args),
+                signatures.join("\n")
+            );
+        }
+
+        candidates.pop().unwrap()
+    }
+}
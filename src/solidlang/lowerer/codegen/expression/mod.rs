@@ -0,0 +1,532 @@
+use crate::globals::Symbol;
+use crate::ir::IRValue;
+use crate::ir::comp::{
+    IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompConversion, IRCompConversionKind, IRCompKind,
+    IRCompUnaryOperation, IRCompUnaryOperationKind,
+};
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTOperator, ASTStatementBlock};
+use crate::solidlang::item::path_to_string;
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, LoopResult, LoopTarget, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+/// Whether the expression is an integer literal without a type suffix, possibly negated
+pub(in crate::solidlang::lowerer) fn is_integer_literal(expression: &ASTExpression) -> bool {
+    match &expression.kind {
+        ASTExpressionKind::IntegerLiteral(_, None) => true,
+        ASTExpressionKind::UnaryOperation(ASTOperator::Minus | ASTOperator::BitNot, operand) => {
+            is_integer_literal(operand)
+        }
+        _ => false
+    }
+}
+
+/// Whether the expression is a float literal without a type suffix, possibly negated
+pub(in crate::solidlang::lowerer) fn is_float_literal(expression: &ASTExpression) -> bool {
+    match &expression.kind {
+        ASTExpressionKind::FloatLiteral(_, None) => true,
+        ASTExpressionKind::UnaryOperation(ASTOperator::Minus, operand) => is_float_literal(operand),
+        _ => false
+    }
+}
+
+/// Constant of an integer literal, negated for `-literal`, which must fit in the type
+fn integer_constant(builder: &mut FunctionBuilder, literal: u64, negative: bool, ty: Ty) -> Value {
+    let (size, _) = ty.get_size_and_align();
+    let bits = size * 8;
+    // Only zero can be negated in unsigned types
+    let max = if ty.is_signed() { (1u128 << (bits - 1)) - 1 + negative as u128 } else if negative { 0 } else { (1u128 << bits) - 1 };
+    if literal as u128 > max {
+        panic!("ERROR Integer literal {}{} does not fit in {}", if negative { "-" } else { "" }, literal, ty);
+    }
+
+    let value = if negative { literal.wrapping_neg() } else { literal };
+    let ir = builder.constant(value.to_le_bytes()[..size as usize].to_vec());
+    Value { ir: Some(ir), ty }
+}
+
+/// Place of the element at the `u64` index of the elements the pointer points to
+pub(in crate::solidlang::lowerer) fn element_place(builder: &mut FunctionBuilder, ptr: IRValue, ty: Ty, index: IRValue) -> Place {
+    let (size, _) = ty.get_size_and_align();
+    let mut offset = index;
+    if size != 1 {
+        let size = builder.constant(size.to_le_bytes().to_vec());
+        offset = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+            kind: IRCompBinaryOperationKind::Mul,
+            left_operand: offset,
+            right_operand: size
+        }));
+    }
+    let ptr = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+        kind: IRCompBinaryOperationKind::Plus,
+        left_operand: ptr,
+        right_operand: offset
+    }));
+
+    Place { ptr, offset: 0, ty }
+}
+
+/// Applies an operator other than `&&` and `||` to values of the same type
+fn apply_binary_operator(builder: &mut FunctionBuilder, operator: &ASTOperator, left: Value, right: Value) -> Value {
+    if left.ty != right.ty {
+        panic!("ERROR Operator {:?} cannot be applied to {} and {}", operator, left.ty, right.ty);
+    }
+
+    let ty = &left.ty;
+    let is_pointer = matches!(ty.kind, TyKind::PointerTo(_));
+    let (kind, ty) = match operator {
+        ASTOperator::Plus if ty.is_integer() => (IRCompBinaryOperationKind::Plus, ty.clone()),
+        ASTOperator::Minus if ty.is_integer() => (IRCompBinaryOperationKind::Minus, ty.clone()),
+        ASTOperator::Mul if ty.is_integer() => (IRCompBinaryOperationKind::Mul, ty.clone()),
+        ASTOperator::Div if ty.is_signed() => (IRCompBinaryOperationKind::SignedDiv, ty.clone()),
+        ASTOperator::Mod if ty.is_signed() => (IRCompBinaryOperationKind::SignedMod, ty.clone()),
+        ASTOperator::BitRShift if ty.is_signed() => (IRCompBinaryOperationKind::SignedRShift, ty.clone()),
+        ASTOperator::Greater if ty.is_signed() => (IRCompBinaryOperationKind::SignedGreater, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Lesser if ty.is_signed() => (IRCompBinaryOperationKind::SignedLesser, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::GreaterEqual if ty.is_signed() => (IRCompBinaryOperationKind::SignedGreaterEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::LesserEqual if ty.is_signed() => (IRCompBinaryOperationKind::SignedLesserEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Div if ty.is_integer() => (IRCompBinaryOperationKind::Div, ty.clone()),
+        ASTOperator::Mod if ty.is_integer() => (IRCompBinaryOperationKind::Mod, ty.clone()),
+        ASTOperator::BitAnd if ty.is_integer() => (IRCompBinaryOperationKind::BitAnd, ty.clone()),
+        ASTOperator::BitOr if ty.is_integer() => (IRCompBinaryOperationKind::BitOr, ty.clone()),
+        ASTOperator::BitRShift if ty.is_integer() => (IRCompBinaryOperationKind::BitRShift, ty.clone()),
+        ASTOperator::BitLShift if ty.is_integer() => (IRCompBinaryOperationKind::BitLShift, ty.clone()),
+        ASTOperator::Plus if ty.is_float() => (IRCompBinaryOperationKind::FloatPlus, ty.clone()),
+        ASTOperator::Minus if ty.is_float() => (IRCompBinaryOperationKind::FloatMinus, ty.clone()),
+        ASTOperator::Mul if ty.is_float() => (IRCompBinaryOperationKind::FloatMul, ty.clone()),
+        ASTOperator::Div if ty.is_float() => (IRCompBinaryOperationKind::FloatDiv, ty.clone()),
+        ASTOperator::Equal if ty.is_float() => (IRCompBinaryOperationKind::FloatEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::NotEqual if ty.is_float() => (IRCompBinaryOperationKind::FloatNotEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Greater if ty.is_float() => (IRCompBinaryOperationKind::FloatGreater, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Lesser if ty.is_float() => (IRCompBinaryOperationKind::FloatLesser, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::GreaterEqual if ty.is_float() => (IRCompBinaryOperationKind::FloatGreaterEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::LesserEqual if ty.is_float() => (IRCompBinaryOperationKind::FloatLesserEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Equal if ty.is_integer() || ty.is_bool() || is_pointer => {
+            (IRCompBinaryOperationKind::Equal, Ty::from_primitive(TyPrimitive::Bool))
+        }
+        ASTOperator::NotEqual if ty.is_integer() || ty.is_bool() || is_pointer => {
+            (IRCompBinaryOperationKind::NotEqual, Ty::from_primitive(TyPrimitive::Bool))
+        }
+        ASTOperator::Greater if ty.is_integer() => (IRCompBinaryOperationKind::Greater, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::Lesser if ty.is_integer() => (IRCompBinaryOperationKind::Lesser, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::GreaterEqual if ty.is_integer() => (IRCompBinaryOperationKind::GreaterEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        ASTOperator::LesserEqual if ty.is_integer() => (IRCompBinaryOperationKind::LesserEqual, Ty::from_primitive(TyPrimitive::Bool)),
+        _ => panic!("ERROR Operator {:?} cannot be applied to {} and {}", operator, left.ty, right.ty)
+    };
+
+    let ir = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+        kind,
+        left_operand: left.ir.unwrap(),
+        right_operand: right.ir.unwrap()
+    }));
+    Value { ir: Some(ir), ty }
+}
+
+impl Lowerer {
+    pub(in crate::solidlang::lowerer) fn lower_expression(&mut self, builder: &mut FunctionBuilder, expression: &ASTExpression, expected: Option<&Ty>) -> Option<Value> {
+        match &expression.kind {
+            ASTExpressionKind::Ident(name) if builder.find_const(*name).is_some() => {
+                let (ty, value) = builder.find_const(*name).unwrap();
+                Some(integer_constant(builder, value, false, ty))
+            }
+            ASTExpressionKind::Ident(_) | ASTExpressionKind::MemberAccess(_, _) | ASTExpressionKind::Index(_, _) => {
+                let place = self.lower_place(builder, expression)?;
+                Some(builder.load(&place))
+            }
+            ASTExpressionKind::IntegerLiteral(literal, suffix) => {
+                let ty = self.literal_ty(*suffix, expected, false);
+                Some(integer_constant(builder, *literal, false, ty))
+            }
+            ASTExpressionKind::FloatLiteral(literal, suffix) => {
+                let ty = self.literal_ty(*suffix, expected, true);
+                let bytes = match ty.get_size_and_align() {
+                    (4, _) => (*literal as f32).to_le_bytes().to_vec(),
+                    _ => literal.to_le_bytes().to_vec()
+                };
+
+                let ir = builder.constant(bytes);
+                Some(Value { ir: Some(ir), ty })
+            }
+            ASTExpressionKind::Boolean(boolean) => {
+                let ir = builder.constant(vec![*boolean as u8]);
+                Some(Value { ir: Some(ir), ty: Ty::from_primitive(TyPrimitive::Bool) })
+            }
+            ASTExpressionKind::Tuple(expressions) => {
+                let expected: Option<&[Ty]> = match expected {
+                    Some(Ty { kind: TyKind::Tuple(tys) }) if tys.len() == expressions.len() => Some(tys),
+                    _ => None
+                };
+
+                let mut values = vec![];
+                for (i, expression) in expressions.iter().enumerate() {
+                    values.push(self.lower_expression(builder, expression, expected.map(|tys| &tys[i]))?);
+                }
+
+                let ty = Ty { kind: TyKind::Tuple(values.iter().map(|value| value.ty.clone()).collect()) };
+                let place = builder.alloc(None, ty.clone());
+                for (value, (_, _, offset)) in values.iter().zip(ty.struct_fields().unwrap()) {
+                    builder.store(&Place { ptr: place.ptr.clone(), offset, ty: value.ty.clone() }, value);
+                }
+
+                Some(builder.load(&place))
+            }
+            ASTExpressionKind::UnaryOperation(operator, operand) => {
+                // Negated literals are folded, so that `-128i8` fits and `-1u8` does not
+                if let (ASTOperator::Minus, ASTExpressionKind::IntegerLiteral(literal, suffix)) = (operator, &operand.kind) {
+                    let ty = self.literal_ty(*suffix, expected, false);
+                    if ty.is_integer() {
+                        return Some(integer_constant(builder, *literal, true, ty));
+                    }
+                }
+                let value = self.lower_expression(builder, operand, expected)?;
+                let kind = match operator {
+                    ASTOperator::BoolNot if value.ty.is_bool() => IRCompUnaryOperationKind::BoolNot,
+                    ASTOperator::BitNot if value.ty.is_integer() => IRCompUnaryOperationKind::BitNot,
+                    ASTOperator::Minus if value.ty.is_signed() => IRCompUnaryOperationKind::SignedNegation,
+                    ASTOperator::Minus if value.ty.is_float() => IRCompUnaryOperationKind::FloatNegation,
+                    _ => panic!("ERROR Operator {:?} cannot be applied to {}", operator, value.ty)
+                };
+
+                let ir = builder.push(IRCompKind::UnaryOperation(IRCompUnaryOperation {
+                    kind,
+                    operand: value.ir.unwrap()
+                }));
+                Some(Value { ir: Some(ir), ty: value.ty })
+            }
+            ASTExpressionKind::BinaryOperation(operator, target, value) if operator.is_assignment() => {
+                if !target.is_place() {
+                    panic!("ERROR Cannot assign to this expression");
+                }
+                let place = self.lower_place(builder, target)?;
+                let mut value = self.lower_expression(builder, value, Some(&place.ty))?;
+                // The target is only evaluated once by compound assignments
+                if let Some(operator) = operator.compound_assignment_operator() {
+                    let current = builder.load(&place);
+                    value = apply_binary_operator(builder, &operator, current, value);
+                }
+                expect_ty(&value, &place.ty);
+                builder.store(&place, &value);
+
+                Some(Value::void())
+            }
+            ASTExpressionKind::BinaryOperation(operator @ (ASTOperator::BoolAnd | ASTOperator::BoolOr), left, right) => {
+                self.lower_short_circuit(builder, operator, left, right)
+            }
+            ASTExpressionKind::BinaryOperation(operator, left, right) => {
+                self.lower_binary_operation(builder, operator, left, right, expected)
+            }
+            ASTExpressionKind::Cast(operand, ast_type) => {
+                let ty = self.resolve_ast_type(ast_type);
+                self.lower_cast(builder, operand, ty)
+            }
+            ASTExpressionKind::If(condition, block, else_block) => {
+                self.lower_if(builder, condition, block, else_block.as_ref(), expected)
+            }
+            ASTExpressionKind::While(label, condition, block) => {
+                let condition_label = builder.new_label("while");
+                let end_label = builder.new_label("while_end");
+
+                builder.place_label(condition_label);
+                let condition = self.lower_condition(builder, condition)?;
+                let negated = builder.push(IRCompKind::UnaryOperation(IRCompUnaryOperation {
+                    kind: IRCompUnaryOperationKind::BoolNot,
+                    operand: condition
+                }));
+                builder.push_void(IRCompKind::If(negated, end_label));
+
+                builder.loops.push(LoopTarget {
+                    label: *label,
+                    continue_label: condition_label,
+                    break_label: end_label,
+                    broken: false,
+                    result: None
+                });
+                let body = self.lower_block(builder, block, None);
+                builder.loops.pop();
+                if body.is_some() {
+                    builder.push_void(IRCompKind::Jmp(condition_label));
+                }
+                builder.place_label(end_label);
+
+                Some(Value::void())
+            }
+            ASTExpressionKind::Loop(label, block) => {
+                let start_label = builder.new_label("loop");
+                let end_label = builder.new_label("loop_end");
+
+                // Breaks store their value in a slot allocated once its type is known
+                let slot = builder.new_id(None);
+
+                builder.place_label(start_label);
+                builder.loops.push(LoopTarget {
+                    label: *label,
+                    continue_label: start_label,
+                    break_label: end_label,
+                    broken: false,
+                    result: Some(LoopResult { slot, ty: None, expected: expected.cloned() })
+                });
+                let body = self.lower_block(builder, block, None);
+                let target = builder.loops.pop().unwrap();
+                if body.is_some() {
+                    builder.push_void(IRCompKind::Jmp(start_label));
+                }
+
+                // Only a break leaves the loop
+                if !target.broken {
+                    return None;
+                }
+                builder.place_label(end_label);
+
+                match target.result.unwrap().ty {
+                    Some(ty) if !ty.is_void() => {
+                        builder.alloc_slot(slot, &ty);
+                        Some(builder.load(&Place { ptr: IRValue { id: slot }, offset: 0, ty }))
+                    }
+                    _ => Some(Value::void())
+                }
+            }
+            ASTExpressionKind::Range(_, _, _) => panic!("ERROR Ranges can only be iterated over by for loops"),
+            ASTExpressionKind::For(label, var, iter, block) => self.lower_for(builder, *label, *var, iter, block),
+            ASTExpressionKind::Block(block) => self.lower_block(builder, block, expected),
+            ASTExpressionKind::Call(callee, args) => {
+                // Enum variants are built like calls
+                if let Some((path, template_args)) = callee.collect_template_application_path() {
+                    if let Some(variant) = self.resolve_variant(&path) {
+                        return self.lower_variant(builder, variant, &template_args, args, expected);
+                    }
+                }
+                self.lower_call(builder, callee, args)
+            }
+            ASTExpressionKind::TemplateApplication(_, _) | ASTExpressionKind::StaticAccess(_, _) => {
+                match expression.collect_template_application_path() {
+                    Some((path, template_args)) => match self.resolve_variant(&path) {
+                        Some(variant) => self.lower_variant(builder, variant, &template_args, &[], expected),
+                        None => panic!("ERROR {} is not a value", path_to_string(&path))
+                    },
+                    None => panic!("ERROR Expected a path")
+                }
+            }
+            ASTExpressionKind::Match(scrutinee, arms) => self.lower_match(builder, scrutinee, arms, expected),
+        }
+    }
+
+    /// Lowers an expression to the location of its value
+    ///
+    /// Expressions that are not locations are stored in a new one.
+    pub(in crate::solidlang::lowerer) fn lower_place(&mut self, builder: &mut FunctionBuilder, expression: &ASTExpression) -> Option<Place> {
+        match &expression.kind {
+            ASTExpressionKind::Ident(name) if builder.find_local(*name).is_some() => {
+                Some(builder.find_local(*name).unwrap().clone())
+            }
+            ASTExpressionKind::Ident(name) => panic!("ERROR Could not resolve {:?}", name),
+            ASTExpressionKind::MemberAccess(base, field) => {
+                let mut place = self.lower_place(builder, base)?;
+                // Fields are reached through pointers
+                if let TyKind::PointerTo(ty) = &place.ty.kind {
+                    let ty = (**ty).clone();
+                    let ptr = builder.load(&place).ir.unwrap();
+                    place = Place { ptr, offset: 0, ty };
+                }
+
+                let fields = place.ty.struct_fields().unwrap_or_else(|| {
+                    panic!("ERROR Cannot access field {:?} of {}", field, place.ty)
+                });
+                let (_, ty, offset) = fields.into_iter().find(|(name, _, _)| name == field).unwrap_or_else(|| {
+                    panic!("ERROR {} has no field {:?}", place.ty, field)
+                });
+
+                Some(Place { ptr: place.ptr, offset: place.offset + offset, ty })
+            }
+            ASTExpressionKind::Index(base, index) => {
+                // Arrays are indexed in place, pointers through the address they hold
+                let base = self.lower_place(builder, base)?;
+                let (ptr, ty) = match &base.ty.kind {
+                    TyKind::PointerTo(ty) => (builder.load(&base).ir.unwrap(), (**ty).clone()),
+                    TyKind::Array(ty, _) => (builder.place_address(&base), (**ty).clone()),
+                    _ => panic!("ERROR Cannot index into {}", base.ty)
+                };
+
+                let u64_ty = Ty::from_primitive(TyPrimitive::U64);
+                let index = self.lower_expression(builder, index, Some(&u64_ty))?;
+                expect_ty(&index, &u64_ty);
+
+                Some(element_place(builder, ptr, ty, index.ir.unwrap()))
+            }
+            _ => {
+                let value = self.lower_expression(builder, expression, None)?;
+                let place = builder.alloc(None, value.ty.clone());
+                builder.store(&place, &value);
+                Some(place)
+            }
+        }
+    }
+
+    /// Lowers `operand as ty`, between integer and float types
+    fn lower_cast(&mut self, builder: &mut FunctionBuilder, operand: &ASTExpression, ty: Ty) -> Option<Value> {
+        let value = self.lower_expression(builder, operand, Some(&ty))?;
+        if value.ty == ty {
+            return Some(value);
+        }
+
+        let (from_size, _) = value.ty.get_size_and_align();
+        let (size, _) = ty.get_size_and_align();
+        let kind = if value.ty.is_integer() && ty.is_integer() {
+            // Integers of the same size share their bits
+            if from_size == size {
+                return Some(Value { ir: value.ir, ty });
+            }
+            if from_size > size {
+                IRCompConversionKind::Truncate
+            } else if value.ty.is_signed() {
+                IRCompConversionKind::SignExtend
+            } else {
+                IRCompConversionKind::ZeroExtend
+            }
+        } else if value.ty.is_integer() && ty.is_float() {
+            if value.ty.is_signed() { IRCompConversionKind::SignedToFloat } else { IRCompConversionKind::UnsignedToFloat }
+        } else if value.ty.is_float() && ty.is_integer() {
+            if ty.is_signed() { IRCompConversionKind::FloatToSigned } else { IRCompConversionKind::FloatToUnsigned }
+        } else if value.ty.is_float() && ty.is_float() {
+            IRCompConversionKind::FloatToFloat
+        } else {
+            panic!("ERROR Cannot cast {} to {}", value.ty, ty);
+        };
+
+        let ir = builder.push(IRCompKind::Conversion(IRCompConversion {
+            kind,
+            operand: value.ir.unwrap(),
+            size
+        }));
+        Some(Value { ir: Some(ir), ty })
+    }
+
+    /// Type of a literal, given by its suffix or else taken from the expected type when it is of the same kind
+    pub(in crate::solidlang::lowerer) fn literal_ty(&self, suffix: Option<Symbol>, expected: Option<&Ty>, is_float: bool) -> Ty {
+        if let Some(suffix) = suffix {
+            return self.context.resolve_ty(&[suffix]).cloned().unwrap_or_else(|| panic!("ERROR Could not resolve {:?}", suffix));
+        }
+        match expected {
+            Some(ty) if is_float && ty.is_float() => ty.clone(),
+            Some(ty) if !is_float && ty.is_integer() => ty.clone(),
+            _ if is_float => Ty::from_primitive(TyPrimitive::F64),
+            _ => Ty::from_primitive(TyPrimitive::I32)
+        }
+    }
+
+    /// Lowers a condition, yields a bool
+    fn lower_condition(&mut self, builder: &mut FunctionBuilder, condition: &ASTExpression) -> Option<IRValue> {
+        let bool_ty = Ty::from_primitive(TyPrimitive::Bool);
+        let condition = self.lower_expression(builder, condition, Some(&bool_ty))?;
+        expect_ty(&condition, &bool_ty);
+        condition.ir
+    }
+
+    fn lower_binary_operation(&mut self, builder: &mut FunctionBuilder, operator: &ASTOperator, left: &ASTExpression, right: &ASTExpression, expected: Option<&Ty>) -> Option<Value> {
+        let is_comparison = matches!(
+            operator,
+            ASTOperator::Equal | ASTOperator::NotEqual | ASTOperator::Greater | ASTOperator::Lesser
+            | ASTOperator::GreaterEqual | ASTOperator::LesserEqual
+        );
+        let operand_expected = if is_comparison { None } else { expected };
+
+        // Literals take the type of the other operand
+        let is_literal = |expression| is_integer_literal(expression) || is_float_literal(expression);
+        let (left, right) = if is_literal(left) && !is_literal(right) {
+            let right = self.lower_expression(builder, right, operand_expected)?;
+            let left = self.lower_expression(builder, left, Some(&right.ty))?;
+            (left, right)
+        } else {
+            let left = self.lower_expression(builder, left, operand_expected)?;
+            let right = self.lower_expression(builder, right, Some(&left.ty))?;
+            (left, right)
+        };
+
+        Some(apply_binary_operator(builder, operator, left, right))
+    }
+
+    /// Lowers `&&` and `||`, the right operand is only evaluated when the left one does not decide
+    /// the result
+    fn lower_short_circuit(&mut self, builder: &mut FunctionBuilder, operator: &ASTOperator, left: &ASTExpression, right: &ASTExpression) -> Option<Value> {
+        let bool_ty = Ty::from_primitive(TyPrimitive::Bool);
+        let left = self.lower_expression(builder, left, Some(&bool_ty))?;
+        if !left.ty.is_bool() {
+            panic!("ERROR Operator {:?} cannot be applied to {}", operator, left.ty);
+        }
+        let end_label = builder.new_label(if matches!(operator, ASTOperator::BoolAnd) { "and_end" } else { "or_end" });
+
+        // The result is the left operand when it decides it, false for `&&` and true for `||`
+        let result = builder.alloc(None, bool_ty.clone());
+        builder.store(&result, &left);
+        let decided = match operator {
+            ASTOperator::BoolAnd => builder.push(IRCompKind::UnaryOperation(IRCompUnaryOperation {
+                kind: IRCompUnaryOperationKind::BoolNot,
+                operand: left.ir.clone().unwrap()
+            })),
+            _ => left.ir.clone().unwrap()
+        };
+        builder.push_void(IRCompKind::If(decided, end_label));
+
+        if let Some(right) = self.lower_expression(builder, right, Some(&bool_ty)) {
+            if !right.ty.is_bool() {
+                panic!("ERROR Operator {:?} cannot be applied to {} and {}", operator, left.ty, right.ty);
+            }
+            builder.store(&result, &right);
+        }
+        builder.place_label(end_label);
+
+        Some(builder.load(&result))
+    }
+
+    fn lower_if(&mut self, builder: &mut FunctionBuilder, condition: &ASTExpression, block: &ASTStatementBlock, else_block: Option<&ASTStatementBlock>, expected: Option<&Ty>) -> Option<Value> {
+        let condition = self.lower_condition(builder, condition)?;
+        let else_label = builder.new_label("else");
+        let end_label = builder.new_label("if_end");
+
+        // Both branches store their value in a slot allocated once its type is known
+        let slot = builder.new_id(None);
+
+        let negated = builder.push(IRCompKind::UnaryOperation(IRCompUnaryOperation {
+            kind: IRCompUnaryOperationKind::BoolNot,
+            operand: condition
+        }));
+        builder.push_void(IRCompKind::If(negated, else_label));
+
+        let else_block = match else_block {
+            Some(else_block) => else_block,
+            None => {
+                self.lower_block(builder, block, None);
+                builder.place_label(else_label);
+                return Some(Value::void());
+            }
+        };
+
+        let mut result_ty: Option<Ty> = None;
+        let mut store_result = |builder: &mut FunctionBuilder, value: Value| {
+            if let Some(ty) = &result_ty {
+                expect_ty(&value, ty);
+            }
+            if !value.ty.is_void() {
+                builder.store(&Place { ptr: IRValue { id: slot }, offset: 0, ty: value.ty.clone() }, &value);
+            }
+            result_ty = Some(value.ty);
+        };
+
+        if let Some(value) = self.lower_block(builder, block, expected) {
+            store_result(builder, value);
+            builder.push_void(IRCompKind::Jmp(end_label));
+        }
+        builder.place_label(else_label);
+        if let Some(value) = self.lower_block(builder, else_block, expected) {
+            store_result(builder, value);
+        }
+        builder.place_label(end_label);
+
+        let ty = result_ty?;
+        if ty.is_void() {
+            return Some(Value::void());
+        }
+
+        builder.alloc_slot(slot, &ty);
+        Some(builder.load(&Place { ptr: IRValue { id: slot }, offset: 0, ty }))
+    }
+}
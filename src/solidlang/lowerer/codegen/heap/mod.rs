@@ -0,0 +1,91 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompFunctionCall, IRCompKind};
+use crate::solidlang::ast::{ASTExpression, ASTType};
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+/// Functions of the C library the heap builtins call, the interpreter serves them as well
+pub(in crate::solidlang::lowerer) const HEAP_FUNCTIONS: [&str; 2] = ["malloc", "free"];
+
+/// Builtin function named by the path, builtins are only called when no function has their name
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(in crate::solidlang::lowerer) enum HeapBuiltin {
+    /// `alloc::<T>(n)` yields a `*T` to room for n values of type T
+    Alloc,
+    /// `free(p)` releases the memory yielded by alloc
+    Free
+}
+
+impl HeapBuiltin {
+    pub(in crate::solidlang::lowerer) fn from_path(path: &[Symbol]) -> Option<Self> {
+        match path {
+            [name] => match SessionGlobals::with_interner(|i| i.get(name).unwrap()) {
+                "alloc" => Some(HeapBuiltin::Alloc),
+                "free" => Some(HeapBuiltin::Free),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            HeapBuiltin::Alloc => "alloc",
+            HeapBuiltin::Free => "free"
+        }
+    }
+
+    /// Type of the value yielded by the builtin with the template args
+    pub(in crate::solidlang::lowerer) fn return_type(&self, template_args: &[Ty]) -> Ty {
+        match (self, template_args) {
+            (HeapBuiltin::Alloc, [ty]) => Ty { kind: TyKind::PointerTo(Box::new(ty.clone())) },
+            (HeapBuiltin::Alloc, _) => panic!("ERROR alloc expects 1 template arg, got {}", template_args.len()),
+            (HeapBuiltin::Free, []) => Ty::from_primitive(TyPrimitive::Void),
+            (HeapBuiltin::Free, _) => panic!("ERROR free expects no template args, got {}", template_args.len())
+        }
+    }
+}
+
+impl Lowerer {
+    pub(in crate::solidlang::lowerer) fn lower_heap_call(&mut self, builder: &mut FunctionBuilder, builtin: HeapBuiltin, template_args: &[ASTType], args: &[ASTExpression]) -> Option<Value> {
+        let template_args: Vec<_> = template_args.iter().map(|ast_type| self.resolve_ast_type(ast_type)).collect();
+        let return_type = builtin.return_type(&template_args);
+        if args.len() != 1 {
+            panic!("ERROR {} expects 1 arg, got {}", builtin.name(), args.len());
+        }
+
+        match builtin {
+            HeapBuiltin::Alloc => {
+                let u64_ty = Ty::from_primitive(TyPrimitive::U64);
+                let count = self.lower_expression(builder, &args[0], Some(&u64_ty))?;
+                expect_ty(&count, &u64_ty);
+
+                let (size, _) = template_args[0].get_size_and_align();
+                let mut bytes = count.ir.unwrap();
+                if size != 1 {
+                    let size = builder.constant(size.to_le_bytes().to_vec());
+                    bytes = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+                        kind: IRCompBinaryOperationKind::Mul,
+                        left_operand: bytes,
+                        right_operand: size
+                    }));
+                }
+
+                let name = self.codegen.declare_extern("malloc", vec![u64_ty.ir_type()], return_type.ir_type());
+                let ir = builder.push(IRCompKind::FunctionCall(IRCompFunctionCall { name, args: vec![bytes] }));
+                Some(Value { ir: Some(ir), ty: return_type })
+            }
+            HeapBuiltin::Free => {
+                let ptr = self.lower_expression(builder, &args[0], None)?;
+                if !matches!(ptr.ty.kind, TyKind::PointerTo(_)) {
+                    panic!("ERROR free expects a pointer, got {}", ptr.ty);
+                }
+
+                let name = self.codegen.declare_extern("free", vec![ptr.ty.ir_type()], return_type.ir_type());
+                builder.push_void(IRCompKind::FunctionCall(IRCompFunctionCall { name, args: vec![ptr.ir.unwrap()] }));
+                Some(Value::void())
+            }
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::HashMap;
+
+use crate::globals::{SessionGlobals, Symbol};
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTOperator, ASTPattern, ASTPatternKind, ASTStatement, ASTStatementBlock, ASTStatementKind, ASTType};
+use crate::solidlang::defs::FunctionDef;
+use crate::solidlang::lowerer::codegen::heap::HeapBuiltin;
+use crate::solidlang::lowerer::codegen::FunctionBuilder;
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::pool::PoolRef;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+/// Types a type variable can be bound to
+#[derive(Clone, Copy, PartialEq)]
+enum VarKind {
+    Any,
+    /// Type of an unsuffixed integer literal, `i32` unless something else is inferred
+    Integer,
+    /// Type of an unsuffixed float literal, `f64` unless something else is inferred
+    Float
+}
+
+/// Type being inferred, tuples and pointers are split so that their parts can be variables
+#[derive(Clone)]
+enum InferTy {
+    Var(usize),
+    Known(Ty),
+    Tuple(Vec<InferTy>),
+    PointerTo(Box<InferTy>)
+}
+
+impl InferTy {
+    fn from_ty(ty: &Ty) -> Self {
+        match &ty.kind {
+            TyKind::Tuple(tys) => InferTy::Tuple(tys.iter().map(InferTy::from_ty).collect()),
+            TyKind::PointerTo(ty) => InferTy::PointerTo(Box::new(InferTy::from_ty(ty))),
+            _ => InferTy::Known(ty.clone())
+        }
+    }
+
+    fn primitive(primitive: TyPrimitive) -> Self {
+        InferTy::Known(Ty::from_primitive(primitive))
+    }
+}
+
+struct Var {
+    kind: VarKind,
+    bound: Option<InferTy>
+}
+
+/// Loop enclosing the statements being inferred
+struct InferLoop {
+    label: Option<Symbol>,
+    /// Type of the values `loop` breaks with, None for other loops
+    result: Option<InferTy>,
+    broken: bool
+}
+
+/// Unifies the types of the expressions of a block, without lowering them
+///
+/// Conflicts are ignored, lowering reports them once the types are picked.
+struct Inference<'a> {
+    lowerer: &'a Lowerer,
+    builder: &'a FunctionBuilder,
+    vars: Vec<Var>,
+    /// Locals bound since the start of the inference, innermost scope last
+    scopes: Vec<HashMap<Symbol, InferTy>>,
+    loops: Vec<InferLoop>,
+    /// Number of blocks being inferred whose items are not registered yet, types and functions
+    /// are not resolved inside them
+    unregistered_items: usize,
+    /// Number of nested blocks being inferred, they infer their own types once they are lowered
+    depth: usize,
+    /// Types of the unannotated bindings and of the for loop vars outside of nested blocks, by the
+    /// start of the span of their pattern or iterator
+    recorded: Vec<(usize, InferTy)>
+}
+
+impl<'a> Inference<'a> {
+    fn fresh(&mut self, kind: VarKind) -> InferTy {
+        self.vars.push(Var { kind, bound: None });
+        InferTy::Var(self.vars.len() - 1)
+    }
+
+    /// Follows the bindings of the variable the type is, if any
+    fn shallow_resolve(&self, mut ty: InferTy) -> InferTy {
+        while let InferTy::Var(var) = ty {
+            match &self.vars[var].bound {
+                Some(bound) => ty = bound.clone(),
+                None => break
+            }
+        }
+        ty
+    }
+
+    fn occurs(&self, var: usize, ty: &InferTy) -> bool {
+        match self.shallow_resolve(ty.clone()) {
+            InferTy::Var(other) => other == var,
+            InferTy::Known(_) => false,
+            InferTy::Tuple(tys) => tys.iter().any(|ty| self.occurs(var, ty)),
+            InferTy::PointerTo(ty) => self.occurs(var, &ty)
+        }
+    }
+
+    fn bind(&mut self, var: usize, ty: InferTy) -> bool {
+        let accepted = match (self.vars[var].kind, &ty) {
+            (VarKind::Any, _) => true,
+            (VarKind::Integer, InferTy::Known(ty)) => ty.is_integer(),
+            (VarKind::Float, InferTy::Known(ty)) => ty.is_float(),
+            _ => false
+        };
+        if !accepted || self.occurs(var, &ty) {
+            return false;
+        }
+        self.vars[var].bound = Some(ty);
+        true
+    }
+
+    fn unify(&mut self, a: InferTy, b: InferTy) -> bool {
+        match (self.shallow_resolve(a), self.shallow_resolve(b)) {
+            (InferTy::Var(a), InferTy::Var(b)) if a == b => true,
+            (InferTy::Var(a), InferTy::Var(b)) => {
+                let kind = match (self.vars[a].kind, self.vars[b].kind) {
+                    (VarKind::Any, kind) | (kind, VarKind::Any) => kind,
+                    (a, b) if a == b => a,
+                    _ => return false
+                };
+                self.vars[b].kind = kind;
+                self.vars[a].bound = Some(InferTy::Var(b));
+                true
+            }
+            (InferTy::Var(var), ty) | (ty, InferTy::Var(var)) => self.bind(var, ty),
+            (InferTy::Known(a), InferTy::Known(b)) => a == b,
+            (InferTy::Tuple(a), InferTy::Tuple(b)) => {
+                a.len() == b.len() && a.into_iter().zip(b).all(|(a, b)| self.unify(a, b))
+            }
+            (InferTy::PointerTo(a), InferTy::PointerTo(b)) => self.unify(*a, *b),
+            _ => false
+        }
+    }
+
+    /// Type the inferred type stands for, literals with no other constraint take their default type
+    fn known_ty(&self, ty: &InferTy) -> Option<Ty> {
+        match self.shallow_resolve(ty.clone()) {
+            InferTy::Var(var) => match self.vars[var].kind {
+                VarKind::Any => None,
+                VarKind::Integer => Some(Ty::from_primitive(TyPrimitive::I32)),
+                VarKind::Float => Some(Ty::from_primitive(TyPrimitive::F64))
+            },
+            InferTy::Known(ty) => Some(ty),
+            InferTy::Tuple(tys) => {
+                let tys = tys.iter().map(|ty| self.known_ty(ty)).collect::<Option<_>>()?;
+                Some(Ty { kind: TyKind::Tuple(tys) })
+            }
+            InferTy::PointerTo(ty) => Some(Ty { kind: TyKind::PointerTo(Box::new(self.known_ty(&ty)?)) })
+        }
+    }
+
+    fn ast_ty(&mut self, ast_type: &ASTType) -> InferTy {
+        if self.unregistered_items > 0 {
+            return self.fresh(VarKind::Any);
+        }
+        InferTy::from_ty(&self.lowerer.resolve_ast_type(ast_type))
+    }
+
+    fn local(&mut self, name: Symbol) -> InferTy {
+        if let Some(ty) = self.scopes.iter().rev().find_map(|scope| scope.get(&name)) {
+            return ty.clone();
+        }
+        if let Some((ty, _)) = self.builder.find_const(name) {
+            return InferTy::from_ty(&ty);
+        }
+        match self.builder.find_local(name) {
+            Some(place) => InferTy::from_ty(&place.ty),
+            None => self.fresh(VarKind::Any)
+        }
+    }
+
+    fn bind_local(&mut self, name: Symbol, ty: InferTy) {
+        self.scopes.last_mut().unwrap().insert(name, ty);
+    }
+
+    /// Binds the names of the pattern, only tuples known to be tuples are split
+    fn bind_pattern(&mut self, pattern: &ASTPattern, ty: InferTy) {
+        match (&pattern.kind, self.shallow_resolve(ty)) {
+            (ASTPatternKind::Binding(name), ty) => self.bind_local(*name, ty),
+            (ASTPatternKind::Tuple(patterns), InferTy::Tuple(tys)) if patterns.len() == tys.len() => {
+                for (pattern, ty) in patterns.iter().zip(tys) {
+                    self.bind_pattern(pattern, ty);
+                }
+            }
+            (ASTPatternKind::Array(patterns), InferTy::Known(ty)) if ty.array_element().is_some_and(|(_, len)| len == patterns.len() as u64) => {
+                let element = InferTy::from_ty(ty.array_element().unwrap().0);
+                for pattern in patterns {
+                    self.bind_pattern(pattern, element.clone());
+                }
+            }
+            _ => self.bind_unknown_pattern(pattern)
+        }
+    }
+
+    fn bind_unknown_pattern(&mut self, pattern: &ASTPattern) {
+        match &pattern.kind {
+            ASTPatternKind::Binding(name) => {
+                let ty = self.fresh(VarKind::Any);
+                self.bind_local(*name, ty);
+            }
+            ASTPatternKind::Tuple(patterns) | ASTPatternKind::Array(patterns) | ASTPatternKind::Variant(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_unknown_pattern(pattern);
+                }
+            }
+            ASTPatternKind::Struct(_, fields, _) => {
+                for (_, pattern) in fields {
+                    self.bind_unknown_pattern(pattern);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Infers the statements, yields the type of the last one or None when its end is never reached
+    fn infer_statements(&mut self, statements: &[ASTStatement]) -> Option<InferTy> {
+        let mut result = InferTy::primitive(TyPrimitive::Void);
+        for statement in statements {
+            result = self.infer_statement(statement)?;
+        }
+        Some(result)
+    }
+
+    fn infer_block(&mut self, block: &ASTStatementBlock) -> Option<InferTy> {
+        let has_items = block.statements.iter().any(|statement| matches!(statement.kind, ASTStatementKind::Item(_)));
+        if has_items {
+            self.unregistered_items += 1;
+        }
+        self.scopes.push(HashMap::new());
+        self.depth += 1;
+        let result = self.infer_statements(&block.statements);
+        self.depth -= 1;
+        self.scopes.pop();
+        if has_items {
+            self.unregistered_items -= 1;
+        }
+        result
+    }
+
+    fn infer_statement(&mut self, statement: &ASTStatement) -> Option<InferTy> {
+        let void = InferTy::primitive(TyPrimitive::Void);
+        match &statement.kind {
+            ASTStatementKind::LocalBinding(pattern, ast_type, expression) => {
+                let annotated = ast_type.as_ref().map(|ast_type| self.ast_ty(ast_type));
+                let value = match expression {
+                    Some(expression) => Some(self.infer_expression(expression)?),
+                    None => None
+                };
+                let ty = match (annotated, value) {
+                    (Some(ty), Some(value)) => {
+                        self.unify(ty.clone(), value);
+                        ty
+                    }
+                    (Some(ty), None) | (None, Some(ty)) => ty,
+                    (None, None) => self.fresh(VarKind::Any)
+                };
+                if self.depth == 0 && ast_type.is_none() {
+                    self.recorded.push((pattern.span.start, ty.clone()));
+                }
+                self.bind_pattern(pattern, ty);
+                Some(void)
+            }
+            ASTStatementKind::Expression(expression) => self.infer_expression(expression),
+            ASTStatementKind::Return(expression) => {
+                let value = self.infer_expression(expression)?;
+                if !self.builder.return_type.is_void() {
+                    self.unify(value, InferTy::from_ty(&self.builder.return_type));
+                }
+                None
+            }
+            ASTStatementKind::Break(label, value) => {
+                let value = match value {
+                    Some(value) => self.infer_expression(value)?,
+                    None => void
+                };
+                let index = match label {
+                    Some(label) => self.loops.iter().rposition(|target| target.label == Some(*label)),
+                    None => self.loops.len().checked_sub(1)
+                };
+                // Loops enclosing the binding are not known, nor used
+                if let Some(index) = index {
+                    self.loops[index].broken = true;
+                    if let Some(result) = self.loops[index].result.clone() {
+                        self.unify(result, value);
+                    }
+                }
+                None
+            }
+            ASTStatementKind::Continue(_) => None,
+            ASTStatementKind::Item(_) | ASTStatementKind::Semicolon => Some(void)
+        }
+    }
+
+    /// Infers the body of a loop, yields the type of its breaks when the loop is a `loop`
+    fn infer_loop(&mut self, label: Option<Symbol>, result: Option<InferTy>, block: &ASTStatementBlock) -> InferLoop {
+        self.loops.push(InferLoop { label, result, broken: false });
+        self.infer_block(block);
+        self.loops.pop().unwrap()
+    }
+
+    fn infer_expression(&mut self, expression: &ASTExpression) -> Option<InferTy> {
+        let bool_ty = InferTy::primitive(TyPrimitive::Bool);
+        let void = InferTy::primitive(TyPrimitive::Void);
+        match &expression.kind {
+            ASTExpressionKind::Ident(name) => Some(self.local(*name)),
+            ASTExpressionKind::IntegerLiteral(_, Some(suffix)) | ASTExpressionKind::FloatLiteral(_, Some(suffix)) => {
+                let is_float = matches!(expression.kind, ASTExpressionKind::FloatLiteral(_, _));
+                Some(InferTy::from_ty(&self.lowerer.literal_ty(Some(*suffix), None, is_float)))
+            }
+            ASTExpressionKind::IntegerLiteral(_, None) => Some(self.fresh(VarKind::Integer)),
+            ASTExpressionKind::FloatLiteral(_, None) => Some(self.fresh(VarKind::Float)),
+            ASTExpressionKind::Boolean(_) => Some(bool_ty),
+            ASTExpressionKind::Tuple(expressions) => {
+                let mut tys = vec![];
+                for expression in expressions {
+                    tys.push(self.infer_expression(expression)?);
+                }
+                Some(InferTy::Tuple(tys))
+            }
+            ASTExpressionKind::UnaryOperation(operator, operand) => {
+                let operand = self.infer_expression(operand)?;
+                if let ASTOperator::BoolNot = operator {
+                    self.unify(operand, bool_ty.clone());
+                    return Some(bool_ty);
+                }
+                Some(operand)
+            }
+            ASTExpressionKind::BinaryOperation(operator, left, right) => {
+                let left = self.infer_expression(left)?;
+                let right = self.infer_expression(right)?;
+                match operator {
+                    operator if operator.is_assignment() => {
+                        self.unify(left, right);
+                        Some(void)
+                    }
+                    ASTOperator::BoolAnd | ASTOperator::BoolOr => {
+                        self.unify(left, bool_ty.clone());
+                        self.unify(right, bool_ty.clone());
+                        Some(bool_ty)
+                    }
+                    ASTOperator::Equal | ASTOperator::NotEqual | ASTOperator::Greater | ASTOperator::Lesser
+                    | ASTOperator::GreaterEqual | ASTOperator::LesserEqual => {
+                        self.unify(left, right);
+                        Some(bool_ty)
+                    }
+                    _ => {
+                        self.unify(left.clone(), right);
+                        Some(left)
+                    }
+                }
+            }
+            ASTExpressionKind::Cast(operand, ast_type) => {
+                self.infer_expression(operand)?;
+                Some(self.ast_ty(ast_type))
+            }
+            ASTExpressionKind::Range(start, end, _) => {
+                let start = self.infer_expression(start)?;
+                let end = self.infer_expression(end)?;
+                self.unify(start.clone(), end);
+                Some(start)
+            }
+            ASTExpressionKind::If(condition, block, else_block) => {
+                let condition = self.infer_expression(condition)?;
+                self.unify(condition, bool_ty);
+                let result = self.infer_block(block);
+                let else_block = match else_block {
+                    Some(else_block) => else_block,
+                    None => return Some(void)
+                };
+                match (result, self.infer_block(else_block)) {
+                    (Some(result), Some(else_result)) => {
+                        self.unify(result.clone(), else_result);
+                        Some(result)
+                    }
+                    (Some(result), None) | (None, Some(result)) => Some(result),
+                    (None, None) => None
+                }
+            }
+            ASTExpressionKind::While(label, condition, block) => {
+                let condition = self.infer_expression(condition)?;
+                self.unify(condition, bool_ty);
+                self.infer_loop(*label, None, block);
+                Some(void)
+            }
+            ASTExpressionKind::Loop(label, block) => {
+                let result = self.fresh(VarKind::Any);
+                let target = self.infer_loop(*label, Some(result), block);
+                if target.broken { target.result } else { None }
+            }
+            ASTExpressionKind::For(label, var, iter, block) => {
+                // Ranges yield their bounds and arrays their elements, the elements of other iterators
+                // are not inferred
+                let var_ty = match &iter.kind {
+                    ASTExpressionKind::Range(_, _, _) => self.infer_expression(iter)?,
+                    _ => {
+                        let iter = self.infer_expression(iter)?;
+                        match self.shallow_resolve(iter) {
+                            InferTy::Known(Ty { kind: TyKind::Array(element, _) }) => InferTy::from_ty(&element),
+                            _ => self.fresh(VarKind::Any)
+                        }
+                    }
+                };
+                if self.depth == 0 {
+                    self.recorded.push((iter.span.start, var_ty.clone()));
+                }
+                self.scopes.push(HashMap::from([(*var, var_ty)]));
+                self.infer_loop(*label, None, block);
+                self.scopes.pop();
+                Some(void)
+            }
+            ASTExpressionKind::Block(block) => self.infer_block(block),
+            ASTExpressionKind::Call(callee, args) => self.infer_call(callee, args),
+            ASTExpressionKind::TemplateApplication(_, _) | ASTExpressionKind::StaticAccess(_, _) => Some(self.fresh(VarKind::Any)),
+            ASTExpressionKind::Index(base, index) => {
+                let base = self.infer_expression(base)?;
+                let index = self.infer_expression(index)?;
+                self.unify(index, InferTy::primitive(TyPrimitive::U64));
+                match self.shallow_resolve(base) {
+                    InferTy::PointerTo(element) => Some(*element),
+                    InferTy::Known(Ty { kind: TyKind::Array(element, _) }) => Some(InferTy::from_ty(&element)),
+                    _ => Some(self.fresh(VarKind::Any))
+                }
+            }
+            ASTExpressionKind::MemberAccess(base, field) => {
+                let base = self.infer_expression(base)?;
+                Some(self.field(base, *field).unwrap_or_else(|| self.fresh(VarKind::Any)))
+            }
+            ASTExpressionKind::Match(scrutinee, arms) => {
+                self.infer_expression(scrutinee)?;
+                let mut result: Option<InferTy> = None;
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    self.bind_unknown_pattern(&arm.pattern);
+                    let value = self.infer_expression(&arm.expression);
+                    self.scopes.pop();
+                    match (&result, value) {
+                        (Some(result), Some(value)) => {
+                            self.unify(result.clone(), value);
+                        }
+                        (None, value) => result = value,
+                        (Some(_), None) => {}
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Type of a field of a struct or tuple, reached through a pointer if needed
+    fn field(&self, base: InferTy, field: Symbol) -> Option<InferTy> {
+        let base = match self.shallow_resolve(base) {
+            InferTy::PointerTo(ty) => self.shallow_resolve(*ty),
+            base => base
+        };
+        match base {
+            InferTy::Tuple(tys) => {
+                let index: usize = SessionGlobals::with_interner(|i| i.get(&field).unwrap().parse().ok())?;
+                tys.get(index).cloned()
+            }
+            InferTy::Known(ty) => {
+                let (_, ty, _) = ty.struct_fields()?.into_iter().find(|(name, _, _)| *name == field)?;
+                Some(InferTy::from_ty(&ty))
+            }
+            _ => None
+        }
+    }
+
+    /// Type of a call to a heap builtin, None when the callee is not one
+    fn infer_heap_call(&mut self, callee: &ASTExpression, args: &[ASTExpression]) -> Option<Option<InferTy>> {
+        let (path, template_args) = callee.collect_template_application_path()?;
+        let builtin = HeapBuiltin::from_path(&path)?;
+        if self.unregistered_items > 0 || !self.lowerer.context.resolve_function(&path).is_empty() {
+            return None;
+        }
+
+        for arg in args {
+            let arg = match self.infer_expression(arg) {
+                Some(arg) => arg,
+                None => return Some(None)
+            };
+            // The count of values to allocate
+            if builtin == HeapBuiltin::Alloc {
+                self.unify(arg, InferTy::primitive(TyPrimitive::U64));
+            }
+        }
+        let template_args: Vec<_> = template_args.iter().map(|ast_type| self.lowerer.resolve_ast_type(ast_type)).collect();
+        Some(Some(InferTy::from_ty(&builtin.return_type(&template_args))))
+    }
+
+    /// Function a call resolves to, when it is a single non-generic function
+    fn callee_def(&mut self, callee: &ASTExpression) -> Option<(PoolRef<FunctionDef>, bool)> {
+        let (function_defs, is_method) = match &callee.kind {
+            ASTExpressionKind::MemberAccess(receiver, name) => {
+                let receiver = self.infer_expression(receiver)?;
+                let receiver = match self.shallow_resolve(receiver) {
+                    InferTy::PointerTo(ty) => self.shallow_resolve(*ty),
+                    receiver => receiver
+                };
+                let mut path = self.known_ty(&receiver)?.def_path()?;
+                path.push(*name);
+                (self.lowerer.context.find_functions(&path), true)
+            }
+            _ => {
+                let (path, template_args) = callee.collect_template_application_path()?;
+                if !template_args.is_empty() {
+                    return None;
+                }
+                (self.lowerer.context.resolve_function(&path), false)
+            }
+        };
+        match function_defs[..] {
+            [function_def] if SessionGlobals::with_function_def_pool(|pool| pool.get(function_def).generic_params.is_empty()) => {
+                Some((function_def, is_method))
+            }
+            _ => None
+        }
+    }
+
+    fn infer_call(&mut self, callee: &ASTExpression, args: &[ASTExpression]) -> Option<InferTy> {
+        if let Some(ty) = self.infer_heap_call(callee, args) {
+            return ty;
+        }
+        let function_def = if self.unregistered_items > 0 { None } else { self.callee_def(callee) };
+
+        let mut arg_tys = vec![];
+        for arg in args {
+            arg_tys.push(self.infer_expression(arg)?);
+        }
+
+        let (function_def, is_method) = match function_def {
+            Some(function_def) => function_def,
+            None => return Some(self.fresh(VarKind::Any))
+        };
+        let (params, return_type) = SessionGlobals::with_function_def_pool(|pool| {
+            let function_def = pool.get(function_def);
+            let params: Vec<_> = function_def.params.iter().map(|(_, ty)| ty.clone()).collect();
+            (params, function_def.return_type.clone())
+        });
+
+        // The receiver is not one of the args
+        let params = if is_method { &params[1.min(params.len())..] } else { &params[..] };
+        if params.len() == arg_tys.len() {
+            for (param, arg) in params.iter().zip(arg_tys) {
+                // `*void` params take any pointer
+                if matches!(&param.kind, TyKind::PointerTo(ty) if ty.is_void()) {
+                    continue;
+                }
+                self.unify(InferTy::from_ty(param), arg);
+            }
+        }
+        Some(InferTy::from_ty(&return_type))
+    }
+}
+
+impl Lowerer {
+    /// Infers the types of the unannotated bindings and of the for loop vars of a block, from their
+    /// values and their uses in the block, the last statement of which gives the value of the block
+    ///
+    /// Yields them by the start of the span of their pattern or iterator, types that are not
+    /// constrained enough are left out.
+    pub(in crate::solidlang::lowerer) fn infer_block_tys(&self, builder: &FunctionBuilder, statements: &[ASTStatement], expected: Option<&Ty>) -> HashMap<usize, Ty> {
+        let mut inference = Inference {
+            lowerer: self,
+            builder,
+            vars: vec![],
+            scopes: vec![HashMap::new()],
+            loops: vec![],
+            unregistered_items: 0,
+            depth: 0,
+            recorded: vec![]
+        };
+
+        // Weakest constraint, the value of a block does not always have to be of the expected type
+        if let (Some(result), Some(expected)) = (inference.infer_statements(statements), expected) {
+            inference.unify(result, InferTy::from_ty(expected));
+        }
+
+        inference.recorded.iter().filter_map(|(start, ty)| Some((*start, inference.known_ty(ty)?))).collect()
+    }
+}
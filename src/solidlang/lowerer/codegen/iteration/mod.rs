@@ -0,0 +1,217 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompKind};
+use crate::ir::IRValue;
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTStatementBlock};
+use crate::solidlang::lowerer::codegen::expression::{element_place, is_integer_literal};
+use crate::solidlang::lowerer::codegen::pattern::jump_unless_equal;
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, LoopTarget, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::span::Span;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+/// Type of the elements of a slice, a pointer to its first element and their count
+fn slice_element_ty(ty: &Ty) -> Option<Ty> {
+    match &ty.kind {
+        TyKind::Tuple(tys) if tys.len() == 2 && tys[1].kind == TyKind::Primitive(TyPrimitive::U64) => match &tys[0].kind {
+            TyKind::PointerTo(ty) if !ty.is_void() => Some((**ty).clone()),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+impl Lowerer {
+    /// Lowers a for loop over a range, an array, a slice or an iterator
+    ///
+    /// Slices are `(*T, u64)` tuples, iterators are values with a `next` method returning a
+    /// variant `Some` holding the next element, or any other variant once they are exhausted.
+    pub(in crate::solidlang::lowerer) fn lower_for(&mut self, builder: &mut FunctionBuilder, label: Option<Symbol>, var: Symbol, iter: &ASTExpression, block: &ASTStatementBlock) -> Option<Value> {
+        if let ASTExpressionKind::Range(start, end, inclusive) = &iter.kind {
+            // Literals take the type inferred for the var, or else the type of the other bound
+            let inferred = builder.inferred.get(&iter.span.start).filter(|ty| ty.is_integer()).cloned();
+            let (start, end) = if let Some(ty) = inferred {
+                let start = self.lower_expression(builder, start, Some(&ty))?;
+                let end = self.lower_expression(builder, end, Some(&ty))?;
+                (start, end)
+            } else if is_integer_literal(start) && !is_integer_literal(end) {
+                let end = self.lower_expression(builder, end, None)?;
+                let start = self.lower_expression(builder, start, Some(&end.ty))?;
+                (start, end)
+            } else {
+                let start = self.lower_expression(builder, start, None)?;
+                let end = self.lower_expression(builder, end, Some(&start.ty))?;
+                (start, end)
+            };
+            if !start.ty.is_integer() {
+                panic!("ERROR Cannot iterate over a range of {}", start.ty);
+            }
+            expect_ty(&end, &start.ty);
+
+            return self.lower_counter_loop(builder, (label, var), (start, end), *inclusive, block, |_, counter| counter);
+        }
+
+        let value = self.lower_expression(builder, iter, None)?;
+        if let Some(ty) = slice_element_ty(&value.ty) {
+            let place = builder.alloc(None, value.ty.clone());
+            builder.store(&place, &value);
+            let fields = value.ty.struct_fields().unwrap();
+            let ptr = builder.load(&Place { ptr: place.ptr.clone(), offset: fields[0].2, ty: fields[0].1.clone() }).ir.unwrap();
+            let len = builder.load(&Place { ptr: place.ptr, offset: fields[1].2, ty: fields[1].1.clone() });
+
+            let u64_ty = Ty::from_primitive(TyPrimitive::U64);
+            let start = Value { ir: Some(builder.constant(0u64.to_le_bytes().to_vec())), ty: u64_ty };
+            return self.lower_counter_loop(builder, (label, var), (start, len), false, block, |builder, counter| {
+                let element = element_place(builder, ptr.clone(), ty.clone(), counter.ir.unwrap());
+                builder.load(&element)
+            });
+        }
+        if let Some((ty, len)) = value.ty.array_element() {
+            let ty = ty.clone();
+            let place = builder.alloc(None, value.ty.clone());
+            builder.store(&place, &value);
+
+            let u64_ty = Ty::from_primitive(TyPrimitive::U64);
+            let start = Value { ir: Some(builder.constant(0u64.to_le_bytes().to_vec())), ty: u64_ty.clone() };
+            let len = Value { ir: Some(builder.constant(len.to_le_bytes().to_vec())), ty: u64_ty };
+            return self.lower_counter_loop(builder, (label, var), (start, len), false, block, |builder, counter| {
+                let element = element_place(builder, place.ptr.clone(), ty.clone(), counter.ir.unwrap());
+                builder.load(&element)
+            });
+        }
+
+        self.lower_iterator_loop(builder, (label, var), value, &iter.span, block)
+    }
+
+    /// Loops with a counter going from the start to the end, the var is bound to the element built
+    /// from the counter
+    fn lower_counter_loop(&mut self, builder: &mut FunctionBuilder, (label, var): (Option<Symbol>, Symbol), (start, end): (Value, Value), inclusive: bool, block: &ASTStatementBlock, element: impl Fn(&mut FunctionBuilder, Value) -> Value) -> Option<Value> {
+        let loop_label = builder.new_label("for");
+        let next_label = builder.new_label("for_next");
+        let end_label = builder.new_label("for_end");
+
+        let ty = start.ty.clone();
+        let counter = builder.alloc(None, ty.clone());
+        builder.store(&counter, &start);
+        let end = end.ir.unwrap();
+
+        // Inclusive ranges are checked before the first iteration and stop once the counter reaches
+        // the end, as it could be the greatest value of the type
+        let exit_kind = match (inclusive, ty.is_signed()) {
+            (true, false) => IRCompBinaryOperationKind::Greater,
+            (false, false) => IRCompBinaryOperationKind::GreaterEqual,
+            (true, true) => IRCompBinaryOperationKind::SignedGreater,
+            (false, true) => IRCompBinaryOperationKind::SignedGreaterEqual
+        };
+        if inclusive {
+            jump_if_counter(builder, &counter, end.clone(), exit_kind.clone(), end_label);
+        }
+        builder.place_label(loop_label);
+        if !inclusive {
+            jump_if_counter(builder, &counter, end.clone(), exit_kind, end_label);
+        }
+
+        let value = builder.load(&counter);
+        let value = element(builder, value);
+        self.lower_loop_body(builder, (label, var), value, block, next_label, end_label);
+
+        builder.place_label(next_label);
+        if inclusive {
+            jump_if_counter(builder, &counter, end, IRCompBinaryOperationKind::Equal, end_label);
+        }
+        let (size, _) = ty.get_size_and_align();
+        let one = builder.constant(1u64.to_le_bytes()[..size as usize].to_vec());
+        let value = builder.load(&counter).ir.unwrap();
+        let incremented = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+            kind: IRCompBinaryOperationKind::Plus,
+            left_operand: value,
+            right_operand: one
+        }));
+        builder.store(&counter, &Value { ir: Some(incremented), ty });
+        builder.push_void(IRCompKind::Jmp(loop_label));
+        builder.place_label(end_label);
+
+        Some(Value::void())
+    }
+
+    /// Loops until the `next` method of the iterator returns another variant than `Some`
+    fn lower_iterator_loop(&mut self, builder: &mut FunctionBuilder, (label, var): (Option<Symbol>, Symbol), iterator: Value, span: &Span, block: &ASTStatementBlock) -> Option<Value> {
+        let ty = match &iterator.ty.kind {
+            TyKind::PointerTo(ty) => ty,
+            _ => &iterator.ty
+        };
+        if ty.def_path().is_none() {
+            panic!("ERROR Cannot iterate over {}", iterator.ty);
+        }
+
+        // The iterator is held by a local the `next` call is built on, whose name cannot clash with
+        // the locals of the body
+        let place = builder.alloc(None, iterator.ty.clone());
+        builder.store(&place, &iterator);
+        let name = SessionGlobals::with_interner_mut(|i| i.intern("{iterator}"));
+        builder.start_scope();
+        builder.bind_local(name, place);
+        let next = SessionGlobals::with_interner_mut(|i| i.intern("next"));
+        let receiver = ASTExpression { kind: ASTExpressionKind::Ident(name), span: span.clone() };
+        let callee = ASTExpression { kind: ASTExpressionKind::MemberAccess(Box::new(receiver), next), span: span.clone() };
+
+        let loop_label = builder.new_label("for");
+        let end_label = builder.new_label("for_end");
+
+        builder.place_label(loop_label);
+        let result = self.lower_call(builder, &callee, &[])?;
+        builder.close_scope();
+
+        let some = SessionGlobals::with_interner_mut(|i| i.intern("Some"));
+        let variant = result.ty.enum_variants().and_then(|(tag_ty, variants)| {
+            let index = variants.iter().position(|(name, _)| *name == some)?;
+            match variants[index].1.as_slice() {
+                [(element_ty, offset)] => Some((tag_ty, index, element_ty.clone(), *offset)),
+                _ => None
+            }
+        });
+        let (tag_ty, index, element_ty, offset) = variant.unwrap_or_else(|| {
+            panic!("ERROR next of {} returns {}, expected an enum with a variant Some holding one value", ty, result.ty)
+        });
+
+        let place = builder.alloc(None, result.ty.clone());
+        builder.store(&place, &result);
+        let (tag_size, _) = tag_ty.get_size_and_align();
+        let tag = builder.load(&Place { ty: tag_ty, ..place.clone() });
+        let expected_tag = builder.constant(index.to_le_bytes()[..tag_size as usize].to_vec());
+        jump_unless_equal(builder, tag.ir.unwrap(), expected_tag, end_label);
+
+        let element = builder.load(&Place { ptr: place.ptr, offset, ty: element_ty });
+        self.lower_loop_body(builder, (label, var), element, block, loop_label, end_label);
+        builder.place_label(end_label);
+
+        Some(Value::void())
+    }
+
+    /// Lowers the body of a for loop with the var bound to a copy of the element, jumps to the
+    /// continue label at its end
+    fn lower_loop_body(&mut self, builder: &mut FunctionBuilder, (label, var): (Option<Symbol>, Symbol), element: Value, block: &ASTStatementBlock, continue_label: Symbol, break_label: Symbol) {
+        builder.start_scope();
+        let place = builder.alloc(Some(var), element.ty.clone());
+        builder.store(&place, &element);
+        builder.bind_local(var, place);
+
+        builder.loops.push(LoopTarget { label, continue_label, break_label, broken: false, result: None });
+        let body = self.lower_block(builder, block, None);
+        builder.loops.pop();
+        builder.close_scope();
+        if body.is_some() {
+            builder.push_void(IRCompKind::Jmp(continue_label));
+        }
+    }
+}
+
+/// Jumps to the label if the comparison of the counter with the value holds
+fn jump_if_counter(builder: &mut FunctionBuilder, counter: &Place, value: IRValue, kind: IRCompBinaryOperationKind, label: Symbol) {
+    let counter = builder.load(counter).ir.unwrap();
+    let condition = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+        kind,
+        left_operand: counter,
+        right_operand: value
+    }));
+    builder.push_void(IRCompKind::If(condition, label));
+}
@@ -1,25 +1,583 @@
-use std::collections::HashMap;
-use crate::globals::Symbol;
-use crate::solidlang::defs::FunctionDef;
+use std::collections::{HashMap, HashSet};
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::{IRComp, IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompConstant, IRCompKind, IRDebugLocation};
+use crate::ir::{IRInlineHint, IRItem, IRItemExternDecl, IRItemFunctionDef, IRItemKind, IRType, IRValue, IRVisibility};
+use crate::solidlang::ast::ASTStatementBlock;
+use crate::solidlang::defs::{FunctionDef, GenericParamKind, StructDef};
+use crate::solidlang::item::{path_to_string, SavedScopes};
+use crate::solidlang::lowerer::codegen::heap::HEAP_FUNCTIONS;
 use crate::solidlang::lowerer::Lowerer;
 use crate::solidlang::pool::PoolRef;
-use crate::solidlang::ty::Ty;
+use crate::solidlang::span::Span;
+use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
+
+pub mod call;
+pub mod expression;
+pub mod heap;
+pub mod inference;
+pub mod iteration;
+pub mod pattern;
+pub mod statement;
+pub mod variant;
 
 pub struct Codegen {
     // Maps function defs and generic args to the ir name
-    compiled: HashMap<(PoolRef<FunctionDef>, Vec<Ty>), Symbol>
+    compiled: HashMap<(PoolRef<FunctionDef>, Vec<Ty>), Symbol>,
+    used_names: HashSet<Symbol>,
+    /// External functions declared in the items
+    externs: HashSet<Symbol>,
+    items: Vec<IRItem>,
+    /// Set while a generic body is checked against its bounds, calls then compile nothing
+    checking: bool
 }
 
 impl Codegen {
     pub fn new() -> Self {
+        // Functions are renamed rather than clash with the functions the heap builtins call
+        let used_names = HEAP_FUNCTIONS.iter().map(|name| SessionGlobals::with_interner_mut(|i| i.intern(name))).collect();
+        Self {
+            compiled: HashMap::new(),
+            used_names,
+            externs: HashSet::new(),
+            items: vec![],
+            checking: false
+        }
+    }
+
+    pub fn into_items(self) -> Vec<IRItem> {
+        self.items
+    }
+
+    /// Declares the external function once, yields its name
+    fn declare_extern(&mut self, name: &str, params: Vec<IRType>, return_type: IRType) -> Symbol {
+        let name = SessionGlobals::with_interner_mut(|i| i.intern(name));
+        if self.externs.insert(name) {
+            self.items.push(IRItem {
+                kind: IRItemKind::ExternDecl(IRItemExternDecl { name, params, return_type })
+            });
+        }
+        name
+    }
+
+    /// Picks an IR name for a function that no other function uses
+    fn new_ir_name(&mut self, mangled: String) -> Symbol {
+        let mut name = mangled.clone();
+        let mut attempt = 0;
+        loop {
+            let symbol = SessionGlobals::with_interner_mut(|i| i.intern(&name));
+            if self.used_names.insert(symbol) {
+                return symbol;
+            }
+            attempt += 1;
+            name = format!("{}_{}", mangled, attempt);
+        }
+    }
+}
+
+/// IR name of a function def instantiated with the generic args
+///
+/// Functions of the root module that are neither overloaded nor generic keep their name. Others are
+/// named `_S`, followed by the length-prefixed segments of their path, then `_` and the codes of
+/// their param types when overloaded, then `_G` and the codes of the generic args.
+fn mangle(path: &[Symbol], params: &[Ty], overloaded: bool, args: &[Ty]) -> String {
+    if path.len() == 1 && !overloaded && args.is_empty() {
+        return SessionGlobals::with_interner(|i| i.get(&path[0]).unwrap().to_owned());
+    }
+
+    let mut mangled = String::from("_S");
+    mangle_path(&mut mangled, path);
+    if overloaded {
+        mangled += "_";
+        for param in params {
+            mangle_ty(&mut mangled, param);
+        }
+    }
+    if !args.is_empty() {
+        mangled += "_G";
+        for arg in args {
+            mangle_ty(&mut mangled, arg);
+        }
+    }
+
+    mangled
+}
+
+fn mangle_path(mangled: &mut String, path: &[Symbol]) {
+    for segment in path {
+        let segment = SessionGlobals::with_interner(|i| i.get(segment).unwrap());
+        *mangled += &segment.len().to_string();
+        *mangled += segment;
+    }
+}
+
+fn mangle_ty(mangled: &mut String, ty: &Ty) {
+    match &ty.kind {
+        TyKind::Primitive(primitive) => *mangled += match primitive {
+            TyPrimitive::U8 => "h",
+            TyPrimitive::I8 => "a",
+            TyPrimitive::U16 => "t",
+            TyPrimitive::I16 => "s",
+            TyPrimitive::U32 => "j",
+            TyPrimitive::I32 => "i",
+            TyPrimitive::U64 => "m",
+            TyPrimitive::I64 => "l",
+            TyPrimitive::F32 => "f",
+            TyPrimitive::F64 => "d",
+            TyPrimitive::Bool => "b",
+            TyPrimitive::Char => "c",
+            TyPrimitive::Void => "v",
+        },
+        TyKind::PointerTo(ty) => {
+            *mangled += "P";
+            mangle_ty(mangled, ty);
+        }
+        TyKind::Struct(_) | TyKind::StructWithArgs(_, _) | TyKind::Enum(_) | TyKind::EnumWithArgs(_, _) => {
+            *mangled += "N";
+            mangle_path(mangled, &ty.def_path().unwrap());
+            if let TyKind::StructWithArgs(_, args) | TyKind::EnumWithArgs(_, args) = &ty.kind {
+                *mangled += "I";
+                for arg in args.iter() {
+                    mangle_ty(mangled, arg);
+                }
+                *mangled += "E";
+            }
+            *mangled += "E";
+        }
+        TyKind::Tuple(tys) => {
+            *mangled += "X";
+            for ty in tys.iter() {
+                mangle_ty(mangled, ty);
+            }
+            *mangled += "E";
+        }
+        TyKind::Array(ty, len) => {
+            *mangled += "A";
+            mangle_ty(mangled, ty);
+            mangle_ty(mangled, len);
+            *mangled += "E";
+        }
+        TyKind::Param(index) => *mangled += &format!("T{}_", index),
+        TyKind::Const(value) => *mangled += &format!("C{}_", value),
+    }
+}
+
+/// A value yielded by an expression, without IR value when its type is void
+pub(in crate::solidlang::lowerer) struct Value {
+    pub ir: Option<IRValue>,
+    pub ty: Ty
+}
+
+impl Value {
+    pub fn void() -> Self {
+        Self {
+            ir: None,
+            ty: Ty::from_primitive(TyPrimitive::Void)
+        }
+    }
+}
+
+/// A location in memory holding a value of the type
+#[derive(Clone)]
+pub(in crate::solidlang::lowerer) struct Place {
+    pub ptr: IRValue,
+    pub offset: u64,
+    pub ty: Ty
+}
+
+/// A loop breaks and continues can jump out of
+struct LoopTarget {
+    /// Label naming the loop in breaks and continues
+    label: Option<Symbol>,
+    continue_label: Symbol,
+    break_label: Symbol,
+    /// Whether a break jumps to the end of the loop
+    broken: bool,
+    /// Where the value of a `loop` is stored by its breaks, other loops yield no value
+    result: Option<LoopResult>
+}
+
+struct LoopResult {
+    slot: Symbol,
+    /// Type of the values of the breaks so far
+    ty: Option<Ty>,
+    expected: Option<Ty>
+}
+
+/// Builds the body of the IR function a function def is lowered to
+pub(in crate::solidlang::lowerer) struct FunctionBuilder {
+    comps: Vec<IRComp>,
+    label_defs: HashMap<Symbol, u64>,
+    next_id: usize,
+    /// Local variables, innermost scope last
+    locals: Vec<HashMap<Symbol, Place>>,
+    /// Loops around the code being lowered, innermost last
+    loops: Vec<LoopTarget>,
+    /// Values bound to the const params of the function, with their types
+    consts: HashMap<Symbol, (Ty, u64)>,
+    return_type: Ty,
+    /// Path of the function, as written in the source
+    function: Symbol,
+    /// Source of the comps being pushed
+    location: Option<IRDebugLocation>,
+    /// Types inferred for the unannotated bindings and the for loop vars of the blocks being
+    /// lowered, by the start of the span of their pattern or iterator
+    inferred: HashMap<usize, Ty>
+}
+
+impl FunctionBuilder {
+    fn new(return_type: Ty, function: Symbol) -> Self {
         Self {
-            compiled: HashMap::new()
+            comps: vec![],
+            label_defs: HashMap::new(),
+            next_id: 0,
+            locals: vec![HashMap::new()],
+            loops: vec![],
+            consts: HashMap::new(),
+            return_type,
+            function,
+            location: None,
+            inferred: HashMap::new()
+        }
+    }
+
+    /// Attributes the comps pushed from now on to the span, yields the previous location
+    fn set_location(&mut self, span: &Span) -> Option<IRDebugLocation> {
+        let position = SessionGlobals::with_source_map(|source_map| {
+            source_map.lookup(span).map(|(file, span)| (file.path.display().to_string(), span.line_and_column(&file.src)))
+        });
+        let location = position.map(|(file, (line, column))| IRDebugLocation {
+            file: SessionGlobals::with_interner_mut(|i| i.intern(&file)),
+            line: line as u64,
+            column: column as u64,
+            function: self.function
+        });
+        std::mem::replace(&mut self.location, location)
+    }
+
+    /// Unique IR value name, temporaries have no name
+    fn new_id(&mut self, name: Option<Symbol>) -> Symbol {
+        let id = self.next_id;
+        self.next_id += 1;
+        let id = match name {
+            Some(name) => format!("{}_{}", SessionGlobals::with_interner(|i| i.get(&name).unwrap()), id),
+            None => format!("t{}", id)
+        };
+        SessionGlobals::with_interner_mut(|i| i.intern(&id))
+    }
+
+    fn new_label(&mut self, name: &str) -> Symbol {
+        let id = self.next_id;
+        self.next_id += 1;
+        SessionGlobals::with_interner_mut(|i| i.intern(&format!("{}_{}", name, id)))
+    }
+
+    fn place_label(&mut self, label: Symbol) {
+        self.label_defs.insert(label, self.comps.len() as u64);
+    }
+
+    fn push(&mut self, kind: IRCompKind) -> IRValue {
+        let id = self.new_id(None);
+        self.comps.push(IRComp { kind, id: Some(id), location: self.location.clone() });
+        IRValue { id }
+    }
+
+    fn push_void(&mut self, kind: IRCompKind) {
+        self.comps.push(IRComp { kind, id: None, location: self.location.clone() });
+    }
+
+    /// Allocates a slot whose type is only known once its stores are built
+    ///
+    /// The slot goes at the start of the function, where no jump can skip it.
+    fn alloc_slot(&mut self, slot: Symbol, ty: &Ty) {
+        self.comps.insert(0, IRComp {
+            kind: IRCompKind::Alloc(ty.ir_type()),
+            id: Some(slot),
+            location: self.location.clone()
+        });
+        for index in self.label_defs.values_mut() {
+            *index += 1;
+        }
+    }
+
+    fn constant(&mut self, bytes: Vec<u8>) -> IRValue {
+        self.push(IRCompKind::Constant(IRCompConstant { bytes }))
+    }
+
+    /// Value to return from the function, void values still need one
+    fn ir_value_or_void(&mut self, value: &Value) -> IRValue {
+        match &value.ir {
+            Some(ir) => ir.clone(),
+            None => self.constant(vec![])
+        }
+    }
+
+    fn load(&mut self, place: &Place) -> Value {
+        if place.ty.is_void() {
+            return Value::void();
+        }
+
+        let ir_type = place.ty.ir_type();
+        let ir = if place.offset == 0 {
+            self.push(IRCompKind::Load(ir_type, place.ptr.clone()))
+        } else {
+            self.push(IRCompKind::OffsetLoad(ir_type, place.ptr.clone(), place.offset))
+        };
+
+        Value {
+            ir: Some(ir),
+            ty: place.ty.clone()
+        }
+    }
+
+    fn store(&mut self, place: &Place, value: &Value) {
+        let ir = match &value.ir {
+            Some(ir) => ir.clone(),
+            None => return
+        };
+
+        let ir_type = place.ty.ir_type();
+        if place.offset == 0 {
+            self.push_void(IRCompKind::Store(ir_type, place.ptr.clone(), ir));
+        } else {
+            self.push_void(IRCompKind::OffsetStore(ir_type, place.ptr.clone(), ir, place.offset));
+        }
+    }
+
+    /// Pointer to the start of the place
+    fn place_address(&mut self, place: &Place) -> IRValue {
+        if place.offset == 0 {
+            return place.ptr.clone();
+        }
+
+        let offset = self.constant(place.offset.to_le_bytes().to_vec());
+        self.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+            kind: IRCompBinaryOperationKind::Plus,
+            left_operand: place.ptr.clone(),
+            right_operand: offset
+        }))
+    }
+
+    /// Stack location for a value of the type
+    fn alloc(&mut self, name: Option<Symbol>, ty: Ty) -> Place {
+        let id = self.new_id(name);
+        self.comps.push(IRComp {
+            kind: IRCompKind::Alloc(ty.ir_type()),
+            id: Some(id),
+            location: self.location.clone()
+        });
+
+        Place {
+            ptr: IRValue { id },
+            offset: 0,
+            ty
+        }
+    }
+
+    /// Index of the loop named by the label, or of the innermost one without label
+    fn find_loop(&self, label: Option<Symbol>) -> Option<usize> {
+        match label {
+            Some(label) => self.loops.iter().rposition(|target| target.label == Some(label)),
+            None => self.loops.len().checked_sub(1)
+        }
+    }
+
+    fn start_scope(&mut self) {
+        self.locals.push(HashMap::new());
+    }
+
+    fn close_scope(&mut self) {
+        self.locals.pop();
+    }
+
+    fn bind_local(&mut self, name: Symbol, place: Place) {
+        self.locals.last_mut().unwrap().insert(name, place);
+    }
+
+    fn find_local(&self, name: Symbol) -> Option<&Place> {
+        self.locals.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    /// Type and value of the const param, unless a local shadows it
+    fn find_const(&self, name: Symbol) -> Option<(Ty, u64)> {
+        if self.find_local(name).is_some() {
+            return None;
         }
+        self.consts.get(&name).cloned()
     }
 }
 
 impl Lowerer {
     pub(in crate::solidlang::lowerer) fn get_ir_name(&mut self, function_def: PoolRef<FunctionDef>, args: Vec<Ty>) -> Symbol {
-        todo!()
+        if self.codegen.checking {
+            return SessionGlobals::with_function_def_pool(|pool| *pool.get(function_def).path.last().unwrap());
+        }
+
+        let key = (function_def, args);
+        if let Some(name) = self.codegen.compiled.get(&key) {
+            return *name;
+        }
+
+        let mangled = SessionGlobals::with_function_def_pool(|pool| {
+            let function_def = pool.get(function_def);
+            let params: Vec<_> = function_def.params.iter().map(|(_, ty)| ty.clone()).collect();
+            let overloaded = function_def.scopes.count_functions(&function_def.path) > 1;
+            mangle(&function_def.path, &params, overloaded, &key.1)
+        });
+        let name = self.codegen.new_ir_name(mangled);
+        // Registered before lowering so recursive calls find it
+        self.codegen.compiled.insert(key.clone(), name);
+        self.lower_function(function_def, &key.1, name);
+
+        name
+    }
+
+    /// Checks the body of a generic function against the bounds of its params, before it is
+    /// instantiated
+    ///
+    /// Each param is bound to a struct with no fields, whose only methods are the functions its bounds
+    /// require. The body is lowered with them and the result thrown away.
+    pub(in crate::solidlang::lowerer) fn check_generic_function(&mut self, function_def: PoolRef<FunctionDef>) {
+        let (path, generic_params, kinds, code, scopes) = SessionGlobals::with_function_def_pool(|pool| {
+            let function_def = pool.get(function_def);
+            (
+                function_def.path.clone(),
+                function_def.generic_params.clone(),
+                function_def.generic_kinds.clone(),
+                function_def.code.clone(),
+                function_def.scopes.clone()
+            )
+        });
+
+        let mut methods = vec![];
+        let mut args = vec![];
+        for (param, kind) in generic_params.iter().zip(&kinds) {
+            // Const params are checked with any value of their type
+            let bounds = match kind {
+                GenericParamKind::Type(bounds) => bounds,
+                GenericParamKind::Const(_) => {
+                    args.push(Ty { kind: TyKind::Const(0) });
+                    continue;
+                }
+            };
+            let mut archetype_path = path.clone();
+            archetype_path.push(*param);
+            let struct_def = StructDef { path: archetype_path.clone(), fields: vec![], generic_params: 0, generic_kinds: vec![] };
+            let archetype = Ty::from_struct_def(SessionGlobals::with_struct_def_pool_mut(|pool| pool.add(struct_def)));
+
+            let self_args = [archetype.clone()];
+            for interface_def in bounds {
+                let functions: Vec<_> = SessionGlobals::with_interface_def_pool(|pool| {
+                    pool.get(*interface_def).functions.iter().map(|function| {
+                        let params: Vec<_> = function.params.iter().map(|(name, ty)| (*name, ty.substitute(&self_args))).collect();
+                        (function.name, params, function.return_type.substitute(&self_args))
+                    }).collect()
+                });
+                for (name, params, return_type) in functions {
+                    let mut method_path = archetype_path.clone();
+                    method_path.push(name);
+                    let method = FunctionDef {
+                        path: method_path.clone(),
+                        params,
+                        generic_params: vec![],
+                        generic_kinds: vec![],
+                        return_type,
+                        code: ASTStatementBlock { statements: vec![], span: code.span.clone() },
+                        scopes: SavedScopes::empty()
+                    };
+                    methods.push((method_path, SessionGlobals::with_function_def_pool_mut(|pool| pool.add(method))));
+                }
+            }
+            args.push(archetype);
+        }
+
+        let checked_scopes = scopes.with_functions(&methods);
+        SessionGlobals::with_function_def_pool_mut(|pool| pool.get_mut(function_def).scopes = checked_scopes);
+        let checking = std::mem::replace(&mut self.codegen.checking, true);
+        self.lower_function(function_def, &args, *path.last().unwrap());
+        self.codegen.items.pop();
+        self.codegen.checking = checking;
+        SessionGlobals::with_function_def_pool_mut(|pool| pool.get_mut(function_def).scopes = scopes);
+    }
+
+    /// Lowers the function def with the generic args to an IR function
+    fn lower_function(&mut self, function_def: PoolRef<FunctionDef>, args: &[Ty], name: Symbol) {
+        let (path, params, generic_params, kinds, return_type, code, scopes) = SessionGlobals::with_function_def_pool(|pool| {
+            let function_def = pool.get(function_def);
+            (
+                function_def.path.clone(),
+                function_def.params.clone(),
+                function_def.generic_params.clone(),
+                function_def.generic_kinds.clone(),
+                function_def.return_type.clone(),
+                function_def.code.clone(),
+                function_def.scopes.clone()
+            )
+        });
+
+        // The body sees the items of the function def, with its generic params bound to the args
+        let saved_scopes = self.context.swap_scopes(scopes);
+        let saved_module_path = self.context.swap_module_path(path[..path.len() - 1].to_vec());
+        self.context.start_scope();
+        for (param, arg) in generic_params.iter().zip(args) {
+            self.context.register_ty(&[*param], arg.clone());
+        }
+
+        let return_type = return_type.substitute(args);
+        let function_name = path_to_string(&path);
+        let function = SessionGlobals::with_interner_mut(|i| i.intern(&function_name));
+        let mut builder = FunctionBuilder::new(return_type.clone(), function);
+        // Comps outside of the statements, like the stores of the params, belong to the whole body
+        builder.set_location(&code.span);
+        for ((param, kind), arg) in generic_params.iter().zip(&kinds).zip(args) {
+            if let (GenericParamKind::Const(ty), Some(value)) = (kind, arg.const_value()) {
+                builder.consts.insert(*param, (ty.clone(), value));
+            }
+        }
+
+        let mut ir_params = vec![];
+        for (param, ty) in &params {
+            let ty = ty.substitute(args);
+            let id = builder.new_id(Some(*param));
+            ir_params.push((Some(id), ty.ir_type()));
+
+            let place = builder.alloc(Some(*param), ty.clone());
+            builder.store(&place, &Value {
+                ir: if ty.is_void() { None } else { Some(IRValue { id }) },
+                ty
+            });
+            builder.bind_local(*param, place);
+        }
+
+        if let Some(result) = self.lower_block(&mut builder, &code, Some(&return_type)) {
+            if !return_type.is_void() {
+                expect_ty(&result, &return_type);
+            }
+            let result = if return_type.is_void() { Value::void() } else { result };
+            let ir = builder.ir_value_or_void(&result);
+            builder.push_void(IRCompKind::Return(ir));
+        }
+
+        self.context.close_scope();
+        self.context.swap_module_path(saved_module_path);
+        self.context.swap_scopes(saved_scopes);
+
+        self.codegen.items.push(IRItem {
+            kind: IRItemKind::FunctionDef(IRItemFunctionDef {
+                name,
+                // Instances of templates are compiled again by every module using them
+                visibility: if args.is_empty() { IRVisibility::Exported } else { IRVisibility::Internal },
+                inline_hint: IRInlineHint::Auto,
+                return_type: return_type.ir_type(),
+                params: ir_params,
+                comps: builder.comps,
+                label_defs: builder.label_defs
+            })
+        });
     }
-}
\ No newline at end of file
+}
+
+pub(in crate::solidlang::lowerer) fn expect_ty(value: &Value, ty: &Ty) {
+    if value.ty != *ty {
+        panic!("ERROR Expected a value of type {}, got {}", ty, value.ty);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::globals::Symbol;
+use crate::ir::IRValue;
+use crate::ir::comp::{IRCompBinaryOperation, IRCompBinaryOperationKind, IRCompKind};
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTMatchArm, ASTPattern, ASTPatternKind};
+use crate::solidlang::item::path_to_string;
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::ty::{Ty, TyKind};
+
+/// What a pattern checks the head of a value against
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    /// Structs, tuples and arrays only have one constructor, holding their fields or elements
+    Single,
+    Variant(usize),
+    Bool(bool),
+    /// Integers have too many values to be listed, only a wildcard covers them all
+    Integer
+}
+
+/// A pattern reduced to what matters for exhaustiveness
+#[derive(Debug, Clone)]
+enum Pat {
+    Wildcard,
+    Constructor(Constructor, Vec<Pat>)
+}
+
+/// Every constructor of the type, None when they cannot be listed
+fn all_constructors(ty: &Ty) -> Option<Vec<Constructor>> {
+    if ty.struct_fields().is_some() || ty.array_element().is_some() {
+        return Some(vec![Constructor::Single]);
+    }
+    if ty.is_bool() {
+        return Some(vec![Constructor::Bool(false), Constructor::Bool(true)]);
+    }
+
+    let (_, variants) = ty.enum_variants()?;
+    Some((0..variants.len()).map(Constructor::Variant).collect())
+}
+
+/// Types of the values held by a constructor of the type
+fn constructor_fields(ty: &Ty, constructor: &Constructor) -> Vec<Ty> {
+    match constructor {
+        Constructor::Single => match ty.array_element() {
+            Some((element, len)) => vec![element.clone(); len as usize],
+            None => ty.struct_fields().unwrap().into_iter().map(|(_, ty, _)| ty).collect()
+        },
+        Constructor::Variant(index) => {
+            let (_, mut variants) = ty.enum_variants().unwrap();
+            variants.swap_remove(*index).1.into_iter().map(|(ty, _)| ty).collect()
+        }
+        Constructor::Bool(_) | Constructor::Integer => vec![]
+    }
+}
+
+/// Rows whose head matches the constructor, with the head replaced by the patterns of its fields
+fn specialize(rows: &[Vec<Pat>], constructor: &Constructor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter().filter_map(|row| {
+        let mut specialized = match &row[0] {
+            Pat::Wildcard => vec![Pat::Wildcard; arity],
+            Pat::Constructor(head, fields) if head == constructor => fields.clone(),
+            Pat::Constructor(_, _) => return None
+        };
+        specialized.extend_from_slice(&row[1..]);
+        Some(specialized)
+    }).collect()
+}
+
+/// Values of the types matched by none of the rows, if there are any
+///
+/// When the heads of the rows use every constructor of the first type, each constructor is
+/// checked against the rows starting with it. Otherwise a missing constructor is only missing if
+/// the rows starting with a wildcard leave the remaining values uncovered.
+fn missing_values(rows: &[Vec<Pat>], tys: &[Ty]) -> Option<Vec<Pat>> {
+    let (ty, rest) = match tys.split_first() {
+        Some(split) => split,
+        None => return if rows.is_empty() { Some(vec![]) } else { None }
+    };
+
+    let used: Vec<_> = rows.iter().filter_map(|row| match &row[0] {
+        Pat::Constructor(constructor, _) => Some(constructor.clone()),
+        Pat::Wildcard => None
+    }).collect();
+    let all = all_constructors(ty);
+
+    match all {
+        Some(all) if all.iter().all(|constructor| used.contains(constructor)) => {
+            for constructor in all {
+                let fields = constructor_fields(ty, &constructor);
+                let rows = specialize(rows, &constructor, fields.len());
+                let mut tys = fields.clone();
+                tys.extend_from_slice(rest);
+
+                if let Some(mut missing) = missing_values(&rows, &tys) {
+                    let rest = missing.split_off(fields.len());
+                    let mut result = vec![Pat::Constructor(constructor, missing)];
+                    result.extend(rest);
+                    return Some(result);
+                }
+            }
+
+            None
+        }
+        all => {
+            let rows: Vec<_> = rows.iter().filter(|row| matches!(row[0], Pat::Wildcard)).map(|row| row[1..].to_vec()).collect();
+            let mut missing = missing_values(&rows, rest)?;
+
+            let head = match all.and_then(|all| all.into_iter().find(|constructor| !used.contains(constructor))) {
+                Some(constructor) => {
+                    let arity = constructor_fields(ty, &constructor).len();
+                    Pat::Constructor(constructor, vec![Pat::Wildcard; arity])
+                }
+                None => Pat::Wildcard
+            };
+            missing.insert(0, head);
+            Some(missing)
+        }
+    }
+}
+
+/// Writes a pattern matching values of the type as source
+fn pat_to_string(pat: &Pat, ty: &Ty) -> String {
+    match pat {
+        Pat::Wildcard | Pat::Constructor(Constructor::Integer, _) => "_".to_owned(),
+        Pat::Constructor(Constructor::Bool(boolean), _) => boolean.to_string(),
+        Pat::Constructor(Constructor::Single, fields) if ty.array_element().is_some() => {
+            let (element, _) = ty.array_element().unwrap();
+            let elements: Vec<_> = fields.iter().map(|field| pat_to_string(field, element)).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Pat::Constructor(Constructor::Single, fields) => {
+            let struct_fields = ty.struct_fields().unwrap();
+            let fields = fields.iter().zip(&struct_fields).map(|(field, (_, ty, _))| pat_to_string(field, ty));
+            match &ty.kind {
+                TyKind::Tuple(_) => format!("({})", fields.collect::<Vec<_>>().join(", ")),
+                _ => {
+                    let fields: Vec<_> = fields.zip(&struct_fields).map(|(field, (name, _, _))| format!("{:?}: {}", name, field)).collect();
+                    format!("{} {{ {} }}", path_to_string(&ty.def_path().unwrap()), fields.join(", "))
+                }
+            }
+        }
+        Pat::Constructor(Constructor::Variant(index), fields) => {
+            let (_, variants) = ty.enum_variants().unwrap();
+            let (name, payload) = &variants[*index];
+            let mut path = ty.def_path().unwrap();
+            path.push(*name);
+
+            let mut result = path_to_string(&path);
+            if !fields.is_empty() {
+                let fields: Vec<_> = fields.iter().zip(payload).map(|(field, (ty, _))| pat_to_string(field, ty)).collect();
+                result += &format!("({})", fields.join(", "));
+            }
+            result
+        }
+    }
+}
+
+impl Lowerer {
+    /// Lowers a match by testing the arms in order, yields the value of the arm taken
+    pub(in crate::solidlang::lowerer) fn lower_match(&mut self, builder: &mut FunctionBuilder, scrutinee: &ASTExpression, arms: &[ASTMatchArm], expected: Option<&Ty>) -> Option<Value> {
+        let place = self.lower_place(builder, scrutinee)?;
+        let place = deref_place(builder, place);
+
+        let rows: Vec<_> = arms.iter().map(|arm| vec![self.check_pattern(&arm.pattern, &place.ty, &mut vec![])]).collect();
+        if let Some(missing) = missing_values(&rows, std::slice::from_ref(&place.ty)) {
+            panic!("ERROR Match on {} is not exhaustive, {} is not covered", place.ty, pat_to_string(&missing[0], &place.ty));
+        }
+
+        let end_label = builder.new_label("match_end");
+
+        // Every arm stores its value in a slot allocated once its type is known
+        let slot = builder.new_id(None);
+
+        let mut result_ty: Option<Ty> = None;
+        for arm in arms {
+            let next_label = builder.new_label("match_arm");
+
+            builder.start_scope();
+            self.lower_pattern(builder, &arm.pattern, &place, Some(next_label));
+            let value = self.lower_expression(builder, &arm.expression, expected);
+            builder.close_scope();
+
+            if let Some(value) = value {
+                if let Some(ty) = &result_ty {
+                    expect_ty(&value, ty);
+                }
+                if !value.ty.is_void() {
+                    builder.store(&Place { ptr: IRValue { id: slot }, offset: 0, ty: value.ty.clone() }, &value);
+                }
+                result_ty = Some(value.ty);
+                builder.push_void(IRCompKind::Jmp(end_label));
+            }
+            builder.place_label(next_label);
+        }
+        builder.place_label(end_label);
+
+        let ty = result_ty?;
+        if ty.is_void() {
+            return Some(Value::void());
+        }
+
+        builder.alloc_slot(slot, &ty);
+        Some(builder.load(&Place { ptr: IRValue { id: slot }, offset: 0, ty }))
+    }
+
+    /// Lowers a `let` with a pattern other than a name, which has to match every value
+    pub(in crate::solidlang::lowerer) fn lower_destructuring(&mut self, builder: &mut FunctionBuilder, pattern: &ASTPattern, ty: Option<Ty>, expression: Option<&ASTExpression>) -> Option<Value> {
+        let expression = expression.unwrap_or_else(|| panic!("ERROR Destructuring let needs a value"));
+        let place = match ty {
+            Some(ty) => {
+                let value = self.lower_expression(builder, expression, Some(&ty))?;
+                expect_ty(&value, &ty);
+                let place = builder.alloc(None, ty);
+                builder.store(&place, &value);
+                place
+            }
+            None => self.lower_place(builder, expression)?
+        };
+        let place = if matches!(pattern.kind, ASTPatternKind::Wildcard) { place } else { deref_place(builder, place) };
+
+        let row = vec![self.check_pattern(pattern, &place.ty, &mut vec![])];
+        if let Some(missing) = missing_values(&[row], std::slice::from_ref(&place.ty)) {
+            panic!("ERROR Refutable pattern in let, {} is not covered", pat_to_string(&missing[0], &place.ty));
+        }
+        self.lower_pattern(builder, pattern, &place, None);
+
+        Some(Value::void())
+    }
+
+    /// Checks that the pattern can match values of the type
+    fn check_pattern(&self, pattern: &ASTPattern, ty: &Ty, bound: &mut Vec<Symbol>) -> Pat {
+        match &pattern.kind {
+            ASTPatternKind::Wildcard => Pat::Wildcard,
+            ASTPatternKind::Binding(name) => {
+                if bound.contains(name) {
+                    panic!("ERROR {:?} is bound more than once in the pattern", name);
+                }
+                bound.push(*name);
+                Pat::Wildcard
+            }
+            ASTPatternKind::Literal(literal) => match &literal.kind {
+                ASTExpressionKind::Boolean(boolean) if ty.is_bool() => Pat::Constructor(Constructor::Bool(*boolean), vec![]),
+                // The parser only allows integer literals to be negated
+                ASTExpressionKind::IntegerLiteral(..) | ASTExpressionKind::UnaryOperation(..) if ty.is_integer() => {
+                    Pat::Constructor(Constructor::Integer, vec![])
+                }
+                _ => panic!("ERROR Literal pattern cannot match values of type {}", ty)
+            },
+            ASTPatternKind::Struct(path, patterns, rest) => {
+                let fields = self.pattern_struct_fields(path, ty);
+
+                let mut checked = vec![Pat::Wildcard; fields.len()];
+                let mut mentioned = vec![false; fields.len()];
+                for (name, pattern) in patterns {
+                    let index = fields.iter().position(|(field, _, _)| field == name).unwrap_or_else(|| {
+                        panic!("ERROR {} has no field {:?}", ty, name)
+                    });
+                    if mentioned[index] {
+                        panic!("ERROR Field {:?} appears more than once in the pattern", name);
+                    }
+                    mentioned[index] = true;
+                    checked[index] = self.check_pattern(pattern, &fields[index].1, bound);
+                }
+                if !rest {
+                    if let Some(index) = mentioned.iter().position(|mentioned| !mentioned) {
+                        panic!("ERROR Pattern of {} does not mention field {:?}, use .. to ignore the others", ty, fields[index].0);
+                    }
+                }
+
+                Pat::Constructor(Constructor::Single, checked)
+            }
+            ASTPatternKind::Tuple(patterns) => {
+                let fields = match &ty.kind {
+                    TyKind::Tuple(_) => ty.struct_fields().unwrap(),
+                    _ => panic!("ERROR Tuple pattern cannot match values of type {}", ty)
+                };
+                if patterns.len() != fields.len() {
+                    panic!("ERROR Tuple pattern with {} elements cannot match values of type {}", patterns.len(), ty);
+                }
+
+                let fields = patterns.iter().zip(&fields).map(|(pattern, (_, ty, _))| self.check_pattern(pattern, ty, bound)).collect();
+                Pat::Constructor(Constructor::Single, fields)
+            }
+            ASTPatternKind::Array(patterns) => {
+                let (element, len) = ty.array_element().unwrap_or_else(|| panic!("ERROR Array pattern cannot match values of type {}", ty));
+                if patterns.len() as u64 != len {
+                    panic!("ERROR Array pattern with {} elements cannot match values of type {}", patterns.len(), ty);
+                }
+
+                let elements = patterns.iter().map(|pattern| self.check_pattern(pattern, element, bound)).collect();
+                Pat::Constructor(Constructor::Single, elements)
+            }
+            ASTPatternKind::Variant(path, patterns) => {
+                let index = self.resolve_pattern_variant(path, ty);
+                let fields = constructor_fields(ty, &Constructor::Variant(index));
+                if patterns.len() != fields.len() {
+                    panic!("ERROR {} holds {} values, got {} patterns", path_to_string(path), fields.len(), patterns.len());
+                }
+
+                let fields = patterns.iter().zip(&fields).map(|(pattern, ty)| self.check_pattern(pattern, ty, bound)).collect();
+                Pat::Constructor(Constructor::Variant(index), fields)
+            }
+        }
+    }
+
+    /// Fields of the struct type named by the path
+    fn pattern_struct_fields(&self, path: &[Symbol], ty: &Ty) -> Vec<(Symbol, Ty, u64)> {
+        let named = self.context.resolve_ty(path).unwrap_or_else(|| {
+            panic!("ERROR Could not resolve struct {}", path_to_string(path))
+        });
+        match (&named.kind, &ty.kind) {
+            (TyKind::Struct(named), TyKind::Struct(struct_def) | TyKind::StructWithArgs(struct_def, _)) if named == struct_def => {
+                ty.struct_fields().unwrap()
+            }
+            _ => panic!("ERROR Pattern of {} cannot match values of type {}", path_to_string(path), ty)
+        }
+    }
+
+    /// Index of the variant of the enum type named by the path
+    fn resolve_pattern_variant(&self, path: &[Symbol], ty: &Ty) -> usize {
+        let variant = self.resolve_variant(path).unwrap_or_else(|| {
+            panic!("ERROR Could not resolve variant {}", path_to_string(path))
+        });
+        match ty.kind {
+            TyKind::Enum(enum_def) | TyKind::EnumWithArgs(enum_def, _) if enum_def == variant.enum_def => variant.index,
+            _ => panic!("ERROR Variant {} cannot match values of type {}", path_to_string(path), ty)
+        }
+    }
+
+    /// Jumps to the label unless the value in the place matches the pattern, binds its names otherwise
+    ///
+    /// Without label, the pattern is known to match and nothing is tested.
+    fn lower_pattern(&mut self, builder: &mut FunctionBuilder, pattern: &ASTPattern, place: &Place, fail_label: Option<Symbol>) {
+        match &pattern.kind {
+            ASTPatternKind::Wildcard => {}
+            ASTPatternKind::Binding(name) => {
+                let value = builder.load(place);
+                let binding = builder.alloc(Some(*name), place.ty.clone());
+                builder.store(&binding, &value);
+                builder.bind_local(*name, binding);
+            }
+            ASTPatternKind::Literal(literal) => {
+                let fail_label = match fail_label {
+                    Some(fail_label) => fail_label,
+                    None => return
+                };
+                let literal = self.lower_expression(builder, literal, Some(&place.ty)).unwrap();
+                expect_ty(&literal, &place.ty);
+                let value = builder.load(place);
+                jump_unless_equal(builder, value.ir.unwrap(), literal.ir.unwrap(), fail_label);
+            }
+            ASTPatternKind::Struct(_, patterns, _) => {
+                let fields = place.ty.struct_fields().unwrap();
+                for (name, pattern) in patterns {
+                    let (_, ty, offset) = fields.iter().find(|(field, _, _)| field == name).unwrap();
+                    let field = Place { ptr: place.ptr.clone(), offset: place.offset + offset, ty: ty.clone() };
+                    self.lower_pattern(builder, pattern, &field, fail_label);
+                }
+            }
+            ASTPatternKind::Tuple(patterns) => {
+                for (pattern, (_, ty, offset)) in patterns.iter().zip(place.ty.struct_fields().unwrap()) {
+                    let field = Place { ptr: place.ptr.clone(), offset: place.offset + offset, ty };
+                    self.lower_pattern(builder, pattern, &field, fail_label);
+                }
+            }
+            ASTPatternKind::Array(patterns) => {
+                let (element, _) = place.ty.array_element().unwrap();
+                let (size, _) = element.get_size_and_align();
+                for (index, pattern) in patterns.iter().enumerate() {
+                    let element = Place { ptr: place.ptr.clone(), offset: place.offset + index as u64 * size, ty: element.clone() };
+                    self.lower_pattern(builder, pattern, &element, fail_label);
+                }
+            }
+            ASTPatternKind::Variant(path, patterns) => {
+                let index = self.resolve_pattern_variant(path, &place.ty);
+                let (tag_ty, variants) = place.ty.enum_variants().unwrap();
+
+                if let Some(fail_label) = fail_label {
+                    let (tag_size, _) = tag_ty.get_size_and_align();
+                    let tag = builder.load(&Place { ty: tag_ty, ..place.clone() });
+                    let expected_tag = builder.constant(index.to_le_bytes()[..tag_size as usize].to_vec());
+                    jump_unless_equal(builder, tag.ir.unwrap(), expected_tag, fail_label);
+                }
+
+                for (pattern, (ty, offset)) in patterns.iter().zip(&variants[index].1) {
+                    let field = Place { ptr: place.ptr.clone(), offset: place.offset + offset, ty: ty.clone() };
+                    self.lower_pattern(builder, pattern, &field, fail_label);
+                }
+            }
+        }
+    }
+
+}
+
+/// Place of the value a pointer points to, values are matched through pointers
+fn deref_place(builder: &mut FunctionBuilder, place: Place) -> Place {
+    match &place.ty.kind {
+        TyKind::PointerTo(ty) => {
+            let ty = (**ty).clone();
+            let ptr = builder.load(&place).ir.unwrap();
+            Place { ptr, offset: 0, ty }
+        }
+        _ => place
+    }
+}
+
+pub(in crate::solidlang::lowerer) fn jump_unless_equal(builder: &mut FunctionBuilder, value: IRValue, expected: IRValue, label: Symbol) {
+    let different = builder.push(IRCompKind::BinaryOperation(IRCompBinaryOperation {
+        kind: IRCompBinaryOperationKind::NotEqual,
+        left_operand: value,
+        right_operand: expected
+    }));
+    builder.push_void(IRCompKind::If(different, label));
+}
@@ -0,0 +1,154 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::comp::IRCompKind;
+use crate::ir::IRValue;
+use crate::solidlang::ast::{ASTItem, ASTPatternKind, ASTStatement, ASTStatementBlock, ASTStatementKind};
+use crate::solidlang::lowerer::codegen::{expect_ty, FunctionBuilder, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::ty::Ty;
+
+/// Index of the loop a break or continue jumps out of
+fn find_loop(builder: &FunctionBuilder, label: Option<Symbol>, keyword: &str) -> usize {
+    builder.find_loop(label).unwrap_or_else(|| match label {
+        Some(label) => {
+            let label = SessionGlobals::with_interner(|i| i.get(&label).unwrap());
+            panic!("ERROR {} outside of a loop labeled {}", keyword, label)
+        }
+        None => panic!("ERROR {} outside of a loop", keyword)
+    })
+}
+
+impl Lowerer {
+    /// Lowers the statements of the block, yields the value of its last statement
+    ///
+    /// Yields None when the end of the block is never reached.
+    pub(in crate::solidlang::lowerer) fn lower_block(&mut self, builder: &mut FunctionBuilder, block: &ASTStatementBlock, expected: Option<&Ty>) -> Option<Value> {
+        let items: Box<[&ASTItem]> = block.statements.iter().filter_map(|statement| match &statement.kind {
+            ASTStatementKind::Item(item) => Some(item),
+            _ => None
+        }).collect();
+        if !items.is_empty() {
+            self.context.start_scope();
+            self.process_items(&items);
+        }
+        builder.start_scope();
+        let inferred = self.infer_block_tys(builder, &block.statements, expected);
+        builder.inferred.extend(inferred);
+
+        let mut result = Some(Value::void());
+        for (i, statement) in block.statements.iter().enumerate() {
+            let location = builder.set_location(&statement.span);
+            result = self.lower_statement(builder, statement, &block.statements[i + 1..], expected);
+            builder.location = location;
+            if result.is_none() {
+                break;
+            }
+        }
+
+        builder.close_scope();
+        if !items.is_empty() {
+            self.context.close_scope();
+        }
+
+        result
+    }
+
+    /// Lowers a statement followed by `rest` in a block of the expected type
+    fn lower_statement(&mut self, builder: &mut FunctionBuilder, statement: &ASTStatement, rest: &[ASTStatement], expected: Option<&Ty>) -> Option<Value> {
+        match &statement.kind {
+            ASTStatementKind::LocalBinding(pattern, ast_type, expression) => {
+                let ty = ast_type.as_ref().map(|ast_type| self.resolve_ast_type(ast_type));
+                let name = match &pattern.kind {
+                    ASTPatternKind::Binding(name) => name,
+                    _ => return self.lower_destructuring(builder, pattern, ty, expression.as_ref())
+                };
+                // Unannotated bindings take the type their uses give them
+                let inferred = match ty {
+                    Some(_) => None,
+                    None => builder.inferred.get(&pattern.span.start).cloned()
+                };
+                let value = match expression {
+                    Some(expression) => Some(self.lower_expression(builder, expression, ty.as_ref().or(inferred.as_ref()))?),
+                    None => None
+                };
+
+                let ty = match (ty, &value) {
+                    (Some(ty), Some(value)) => {
+                        expect_ty(value, &ty);
+                        ty
+                    }
+                    (Some(ty), None) => ty,
+                    (None, Some(value)) => value.ty.clone(),
+                    (None, None) => inferred.unwrap_or_else(|| {
+                        let name = SessionGlobals::with_interner(|i| i.get(name).unwrap());
+                        let message = format!("type annotations needed for {}", name);
+                        panic!("ERROR {}", SessionGlobals::with_source_map(|source_map| source_map.report(&pattern.span, &message)))
+                    })
+                };
+
+                let place = builder.alloc(Some(*name), ty);
+                if let Some(value) = value {
+                    builder.store(&place, &value);
+                }
+                builder.bind_local(*name, place);
+
+                Some(Value::void())
+            }
+            ASTStatementKind::Expression(expression) => {
+                let expected = if rest.is_empty() { expected } else { None };
+                self.lower_expression(builder, expression, expected)
+            }
+            ASTStatementKind::Return(expression) => {
+                let return_type = builder.return_type.clone();
+                let value = self.lower_expression(builder, expression, Some(&return_type))?;
+                if !return_type.is_void() {
+                    expect_ty(&value, &return_type);
+                }
+                let value = if return_type.is_void() { Value::void() } else { value };
+                let ir = builder.ir_value_or_void(&value);
+                builder.push_void(IRCompKind::Return(ir));
+
+                None
+            }
+            ASTStatementKind::Break(label, value) => {
+                let index = find_loop(builder, *label, "Break");
+
+                let value = match value {
+                    Some(value) => {
+                        let expected = match &builder.loops[index].result {
+                            Some(result) => result.ty.clone().or_else(|| result.expected.clone()),
+                            None => panic!("ERROR Only loop can break with a value")
+                        };
+                        self.lower_expression(builder, value, expected.as_ref())?
+                    }
+                    None => Value::void()
+                };
+
+                // All the breaks of a loop give it values of the same type
+                let target = &mut builder.loops[index];
+                target.broken = true;
+                let break_label = target.break_label;
+                if let Some(result) = &mut target.result {
+                    if let Some(ty) = &result.ty {
+                        expect_ty(&value, ty);
+                    }
+                    result.ty = Some(value.ty.clone());
+                    let slot = result.slot;
+                    builder.store(&Place { ptr: IRValue { id: slot }, offset: 0, ty: value.ty.clone() }, &value);
+                }
+                builder.push_void(IRCompKind::Jmp(break_label));
+
+                None
+            }
+            ASTStatementKind::Continue(label) => {
+                let index = find_loop(builder, *label, "Continue");
+                let continue_label = builder.loops[index].continue_label;
+                builder.push_void(IRCompKind::Jmp(continue_label));
+
+                None
+            }
+            // Registered with the other items of the block
+            ASTStatementKind::Item(_) => Some(Value::void()),
+            ASTStatementKind::Semicolon => Some(Value::void())
+        }
+    }
+}
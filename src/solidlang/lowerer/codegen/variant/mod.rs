@@ -0,0 +1,98 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::solidlang::ast::{ASTExpression, ASTType};
+use crate::solidlang::defs::EnumDef;
+use crate::solidlang::item::path_to_string;
+use crate::solidlang::lowerer::codegen::{FunctionBuilder, Place, Value};
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::pool::PoolRef;
+use crate::solidlang::ty::{Ty, TyKind};
+
+/// A variant of an enum named by a path like `Option::Some`
+pub(in crate::solidlang::lowerer) struct VariantRef {
+    pub enum_def: PoolRef<EnumDef>,
+    pub index: usize,
+    pub path: Vec<Symbol>
+}
+
+impl Lowerer {
+    /// Resolves a path to the variant of an enum, the last segment naming the variant
+    pub(in crate::solidlang::lowerer) fn resolve_variant(&self, path: &[Symbol]) -> Option<VariantRef> {
+        let (name, enum_path) = path.split_last()?;
+        if enum_path.is_empty() {
+            return None;
+        }
+
+        let enum_def = match self.context.resolve_ty(enum_path)?.kind {
+            TyKind::Enum(enum_def) => enum_def,
+            _ => return None
+        };
+        let index = SessionGlobals::with_enum_def_pool(|pool| {
+            pool.get(enum_def).variants.iter().position(|variant| variant.name == *name)
+        })?;
+
+        Some(VariantRef { enum_def, index, path: path.to_vec() })
+    }
+
+    /// Builds a value of an enum holding the variant with the args as payload
+    ///
+    /// The template args of a generic enum are given explicitly, taken from the expected type, or
+    /// inferred from the args.
+    pub(in crate::solidlang::lowerer) fn lower_variant(&mut self, builder: &mut FunctionBuilder, variant: VariantRef, template_args: &[ASTType], args: &[ASTExpression], expected: Option<&Ty>) -> Option<Value> {
+        let (generic_params, kinds, payload) = SessionGlobals::with_enum_def_pool(|pool| {
+            let enum_def = pool.get(variant.enum_def);
+            (enum_def.generic_params, enum_def.generic_kinds.clone(), enum_def.variants[variant.index].payload.clone())
+        });
+
+        let mut bindings = vec![None; generic_params];
+        if !template_args.is_empty() {
+            if template_args.len() != generic_params {
+                panic!("ERROR {} expects {} template args, got {}", path_to_string(&variant.path), generic_params, template_args.len());
+            }
+            bindings = template_args.iter().map(|ast_type| Some(self.resolve_ast_type(ast_type))).collect();
+        } else if let Some(Ty { kind: TyKind::EnumWithArgs(enum_def, args) }) = expected {
+            if *enum_def == variant.enum_def {
+                bindings = args.iter().cloned().map(Some).collect();
+            }
+        }
+
+        if args.len() != payload.len() {
+            panic!("ERROR {} holds {} values, got {}", path_to_string(&variant.path), payload.len(), args.len());
+        }
+
+        let mut values = vec![];
+        for (arg, ty) in args.iter().zip(&payload) {
+            let expected = ty.try_substitute(&bindings);
+            let value = self.lower_expression(builder, arg, expected.as_ref())?;
+            if !ty.infer_params(&value.ty, &mut bindings) {
+                let ty = ty.try_substitute(&bindings).unwrap();
+                panic!("ERROR Expected a value of type {}, got {}", ty, value.ty);
+            }
+            values.push(value);
+        }
+
+        let ty = if generic_params == 0 {
+            Ty::from_enum_def(variant.enum_def)
+        } else {
+            let args: Box<[Ty]> = bindings.into_iter().map(|binding| binding.unwrap_or_else(|| {
+                panic!("ERROR Cannot infer the template args of {}", path_to_string(&variant.path))
+            })).collect();
+            let ty = Ty { kind: TyKind::EnumWithArgs(variant.enum_def, args.clone()) };
+            if let Err(reason) = self.check_template_args(&kinds, &args) {
+                panic!("ERROR Cannot instantiate {}: {}", ty, reason);
+            }
+            ty
+        };
+
+        let (tag_ty, variants) = ty.enum_variants().unwrap();
+        let place = builder.alloc(None, ty.clone());
+
+        let (tag_size, _) = tag_ty.get_size_and_align();
+        let tag = builder.constant(variant.index.to_le_bytes()[..tag_size as usize].to_vec());
+        builder.store(&Place { ty: tag_ty.clone(), ..place.clone() }, &Value { ir: Some(tag), ty: tag_ty });
+        for (value, (field_ty, offset)) in values.iter().zip(&variants[variant.index].1) {
+            builder.store(&Place { ptr: place.ptr.clone(), offset: *offset, ty: field_ty.clone() }, value);
+        }
+
+        Some(builder.load(&place))
+    }
+}
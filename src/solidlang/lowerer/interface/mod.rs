@@ -0,0 +1,135 @@
+use crate::globals::SessionGlobals;
+use crate::solidlang::ast::ASTTemplateParam;
+use crate::solidlang::defs::{FunctionDef, GenericParamKind, InterfaceDef, InterfaceDefFunction};
+use crate::solidlang::item::path_to_string;
+use crate::solidlang::lowerer::Lowerer;
+use crate::solidlang::pool::PoolRef;
+use crate::solidlang::ty::{Ty, TyKind};
+
+/// Type behind a pointer, methods take their receiver either way
+fn pointee(ty: &Ty) -> &Ty {
+    match &ty.kind {
+        TyKind::PointerTo(ty) => ty,
+        _ => ty
+    }
+}
+
+fn signature(function: &InterfaceDefFunction) -> String {
+    let self_name = SessionGlobals::with_interner_mut(|i| i.intern("Self"));
+    let names = [self_name];
+    let params: Vec<_> = function.params.iter().map(|(name, ty)| {
+        let name = SessionGlobals::with_interner(|i| i.get(name).unwrap());
+        format!("{}: {}", name, ty.to_string_with_params(&names))
+    }).collect();
+
+    let name = SessionGlobals::with_interner(|i| i.get(&function.name).unwrap());
+    let mut signature = format!("fn {}({})", name, params.join(", "));
+    if !function.return_type.is_void() {
+        signature += &format!(" -> {}", function.return_type.to_string_with_params(&names));
+    }
+    signature
+}
+
+/// Checks that the method has the params and return type the interface requires of the type
+///
+/// Generic params of the method must all appear in the type of its receiver.
+fn method_matches(method: PoolRef<FunctionDef>, ty: &Ty, params: &[Ty], return_type: &Ty) -> bool {
+    let (method_params, generic_params, method_return_type) = SessionGlobals::with_function_def_pool(|pool| {
+        let method = pool.get(method);
+        (method.params.clone(), method.generic_params.len(), method.return_type.clone())
+    });
+    if method_params.len() != params.len() || method_params.is_empty() {
+        return false;
+    }
+
+    let mut bindings = vec![None; generic_params];
+    if !pointee(&method_params[0].1).infer_params(ty, &mut bindings) {
+        return false;
+    }
+    let method_params: Option<Vec<_>> = method_params.iter().map(|(_, ty)| ty.try_substitute(&bindings)).collect();
+    let (method_params, method_return_type) = match (method_params, method_return_type.try_substitute(&bindings)) {
+        (Some(method_params), Some(method_return_type)) => (method_params, method_return_type),
+        _ => return false
+    };
+
+    pointee(&method_params[0]) == pointee(&params[0])
+        && method_params[1..] == params[1..]
+        && method_return_type == *return_type
+}
+
+impl Lowerer {
+    /// Resolves the interfaces bounding a type param, or the type of the values of a const param
+    pub(in crate::solidlang::lowerer) fn resolve_generic_kind(&self, param: &ASTTemplateParam) -> GenericParamKind {
+        if let Some(ast_type) = &param.ast_type {
+            let ty = self.resolve_ast_type(ast_type);
+            // Const args are integer literals, which are never negative
+            if !ty.is_integer() || ty.is_signed() {
+                panic!("ERROR Const params must have an unsigned integer type, got {}", ty);
+            }
+            return GenericParamKind::Const(ty);
+        }
+
+        GenericParamKind::Type(param.bounds.iter().map(|path| {
+            self.context.resolve_interface(path).unwrap_or_else(|| {
+                panic!("ERROR Could not resolve interface {}", path_to_string(path))
+            })
+        }).collect())
+    }
+
+    /// Checks that the type has a method for each function of the interface, `Self` standing for the type
+    fn implements(&self, ty: &Ty, interface_def: PoolRef<InterfaceDef>) -> Result<(), String> {
+        let self_args = [ty.clone()];
+        let (path, functions) = SessionGlobals::with_interface_def_pool(|pool| {
+            let interface_def = pool.get(interface_def);
+            let functions: Vec<_> = interface_def.functions.iter().map(|function| {
+                let params: Vec<_> = function.params.iter().map(|(_, param)| param.substitute(&self_args)).collect();
+                (signature(function), function.name, params, function.return_type.substitute(&self_args))
+            }).collect();
+            (interface_def.path.clone(), functions)
+        });
+
+        for (signature, name, params, return_type) in functions {
+            let methods = match ty.def_path() {
+                Some(mut method_path) => {
+                    method_path.push(name);
+                    self.context.find_functions(&method_path)
+                }
+                None => vec![]
+            };
+            if !methods.into_iter().any(|method| method_matches(method, ty, &params, &return_type)) {
+                return Err(format!("{} does not implement {}, which requires {}", ty, path_to_string(&path), signature));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that each arg is a type implementing the interfaces bounding its generic param, or a
+    /// value fitting the type of its const param
+    pub(in crate::solidlang::lowerer) fn check_template_args(&self, kinds: &[GenericParamKind], args: &[Ty]) -> Result<(), String> {
+        for (kind, arg) in kinds.iter().zip(args) {
+            match (kind, arg.const_value()) {
+                (GenericParamKind::Type(bounds), None) => {
+                    for interface_def in bounds {
+                        self.implements(arg, *interface_def)?;
+                    }
+                }
+                (GenericParamKind::Type(_), Some(value)) => {
+                    return Err(format!("expected a type, got the value {}", value));
+                }
+                (GenericParamKind::Const(ty), Some(value)) => {
+                    let (size, _) = ty.get_size_and_align();
+                    let max = (1u128 << (size * 8)) - 1;
+                    if value as u128 > max {
+                        return Err(format!("the value {} does not fit in {}", value, ty));
+                    }
+                }
+                (GenericParamKind::Const(ty), None) => {
+                    return Err(format!("expected a value of type {}, got the type {}", ty, arg));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
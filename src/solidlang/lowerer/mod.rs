@@ -1,12 +1,14 @@
 use crate::globals::{SessionGlobals, Symbol};
-use crate::solidlang::ast::{ASTItem, ASTItemKind, ASTModule, ASTType, ASTTypeKind};
-use crate::solidlang::item::{ItemContext};
+use crate::ir::IRModule;
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTItem, ASTItemKind, ASTModule, ASTModuleDef, ASTPatternKind, ASTStatement, ASTStatementKind, ASTType, ASTTypeKind};
+use crate::solidlang::item::{path_to_string, ItemContext, SavedScopes};
 use crate::solidlang::pool::PoolRef;
-use crate::solidlang::defs::{FunctionDef, StructDef, StructDefField};
+use crate::solidlang::defs::{EnumDef, EnumDefVariant, FunctionDef, GenericParamKind, InterfaceDef, InterfaceDefFunction, StructDef, StructDefField};
 use crate::solidlang::lowerer::codegen::Codegen;
 use crate::solidlang::ty::{Ty, TyKind, TyPrimitive};
 
 pub mod codegen;
+pub mod interface;
 
 pub struct Lowerer {
     context: ItemContext,
@@ -27,6 +29,7 @@ impl Lowerer {
                 if let Some(resolved) = self.context.resolve_ty(symbols) {
                     let expected_args = match resolved.kind {
                         TyKind::Struct(struct_def) => SessionGlobals::with_struct_def_pool(|pool| pool.get(struct_def).generic_params),
+                        TyKind::Enum(enum_def) => SessionGlobals::with_enum_def_pool(|pool| pool.get(enum_def).generic_params),
                         _ => 0
                     };
 
@@ -35,14 +38,25 @@ impl Lowerer {
                     }
 
                     if expected_args > 0 {
-                        let struct_def = match resolved.kind {
-                            TyKind::Struct(struct_def) => struct_def,
+                        let args: Box<[Ty]> = generic_args.iter().map(|t| self.resolve_ast_type(t)).collect();
+                        let (ty, kinds) = match resolved.kind {
+                            TyKind::Struct(struct_def) => {
+                                let kinds = SessionGlobals::with_struct_def_pool(|pool| pool.get(struct_def).generic_kinds.clone());
+                                (Ty { kind: TyKind::StructWithArgs(struct_def, args.clone()) }, kinds)
+                            }
+                            TyKind::Enum(enum_def) => {
+                                let kinds = SessionGlobals::with_enum_def_pool(|pool| pool.get(enum_def).generic_kinds.clone());
+                                (Ty { kind: TyKind::EnumWithArgs(enum_def, args.clone()) }, kinds)
+                            }
                             _ => unreachable!()
                         };
-
-                        Ty {
-                            kind: TyKind::StructWithArgs(struct_def, generic_args.iter().map(|t| self.resolve_ast_type(t)).collect())
+                        // Args naming generic params are checked once the params are bound
+                        if !args.iter().any(|arg| arg.has_params()) {
+                            if let Err(reason) = self.check_template_args(&kinds, &args) {
+                                panic!("ERROR Cannot instantiate {}: {}", ty, reason);
+                            }
                         }
+                        ty
                     }
                     else {
                         resolved.clone()
@@ -57,39 +71,97 @@ impl Lowerer {
                     kind: TyKind::PointerTo(Box::new(self.resolve_ast_type(ast_type)))
                 }
             }
+            ASTTypeKind::Tuple(ast_types) => {
+                Ty {
+                    kind: TyKind::Tuple(ast_types.iter().map(|ast_type| self.resolve_ast_type(ast_type)).collect())
+                }
+            }
+            ASTTypeKind::Array(ast_type, len) => {
+                let len = self.resolve_ast_type(len);
+                if !matches!(len.kind, TyKind::Const(_) | TyKind::Param(_)) {
+                    panic!("ERROR Expected the length of the array, got the type {}", len);
+                }
+                Ty {
+                    kind: TyKind::Array(Box::new(self.resolve_ast_type(ast_type)), Box::new(len))
+                }
+            }
+            ASTTypeKind::IntegerLiteral(value) => {
+                Ty {
+                    kind: TyKind::Const(*value)
+                }
+            }
+        }
+    }
+
+    fn module_items(ast_module_def: &ASTModuleDef) -> Box<[&ASTItem]> {
+        match &ast_module_def.items {
+            Some(items) => items.iter().collect(),
+            None => panic!("ERROR Module file not loaded")
         }
     }
 
     fn register_type_items(&mut self, items: &[&ASTItem], generic_params_height: usize) {
         for item in items {
             match &item.kind {
+                ASTItemKind::ModuleDef(ast_module_def) => {
+                    let items = Self::module_items(ast_module_def);
+                    self.context.enter_module(ast_module_def.name);
+                    self.register_type_items(&items, generic_params_height);
+                    self.context.exit_module();
+                }
+                ASTItemKind::Use(ast_use) => {
+                    self.context.register_alias(&ast_use.path);
+                }
                 ASTItemKind::Template(ast_template) => {
                     let items: Box<[_]> = ast_template.items.iter().collect();
                     self.register_type_items(&items, generic_params_height + ast_template.params.len());
                 }
                 ASTItemKind::StructDef(ast_struct_def) => {
-                    let struct_def = StructDef { fields: vec![], generic_params: generic_params_height };
+                    let struct_def = StructDef { path: self.context.item_path(ast_struct_def.name), fields: vec![], generic_params: generic_params_height, generic_kinds: vec![] };
                     let struct_def = SessionGlobals::with_struct_def_pool_mut(|pool| pool.add(struct_def));
                     self.context.register_ty(&[ast_struct_def.name], Ty::from_struct_def(struct_def));
                 }
+                ASTItemKind::EnumDef(ast_enum_def) => {
+                    let enum_def = EnumDef { path: self.context.item_path(ast_enum_def.name), variants: vec![], generic_params: generic_params_height, generic_kinds: vec![] };
+                    let enum_def = SessionGlobals::with_enum_def_pool_mut(|pool| pool.add(enum_def));
+                    self.context.register_ty(&[ast_enum_def.name], Ty::from_enum_def(enum_def));
+                }
+                ASTItemKind::InterfaceDef(ast_interface_def) => {
+                    if generic_params_height > 0 {
+                        panic!("ERROR Interfaces cannot be declared in templates");
+                    }
+                    let interface_def = InterfaceDef { path: self.context.item_path(ast_interface_def.name), functions: vec![] };
+                    let interface_def = SessionGlobals::with_interface_def_pool_mut(|pool| pool.add(interface_def));
+                    self.context.register_interface(ast_interface_def.name, interface_def);
+                }
                 _ => {}
             }
         }
     }
 
-    fn process_type_items(&mut self, items: &[&ASTItem], mut generic_params_height: usize) {
+    /// Resolves the types of the items, `kinds` holding what each generic param of the enclosing
+    /// templates takes
+    fn process_type_items(&mut self, items: &[&ASTItem], kinds: &[GenericParamKind]) {
         for item in items {
             match &item.kind {
+                ASTItemKind::ModuleDef(ast_module_def) => {
+                    let items = Self::module_items(ast_module_def);
+                    self.context.enter_module(ast_module_def.name);
+                    self.process_type_items(&items, kinds);
+                    self.context.exit_module();
+                }
                 ASTItemKind::Template(ast_template) => {
                     let items: Box<[_]> = ast_template.items.iter().collect();
                     self.context.start_scope();
 
+                    let mut kinds = kinds.to_vec();
                     for param in &ast_template.params {
-                        self.context.register_ty(&[*param], Ty { kind: TyKind::Param(generic_params_height) });
-                        generic_params_height += 1;
+                        let kind = self.resolve_generic_kind(param);
+                        self.context.register_ty(&[param.name], Ty { kind: TyKind::Param(kinds.len()) });
+                        kinds.push(kind);
                     }
 
-                    self.process_type_items(&items, generic_params_height);
+                    self.process_type_items(&items, &kinds);
 
                     self.context.close_scope();
                 }
@@ -106,43 +178,170 @@ impl Lowerer {
                             SessionGlobals::with_struct_def_pool_mut(|pool| {
                                 let struct_def = pool.get_mut(struct_def);
                                 struct_def.fields = fields;
+                                struct_def.generic_kinds = kinds.to_vec();
+                            });
+                        }
+                        _ => unreachable!()
+                    }
+                }
+                ASTItemKind::EnumDef(ast_enum_def) => {
+                    let mut variants: Vec<EnumDefVariant> = vec![];
+                    for variant in &ast_enum_def.variants {
+                        if variants.iter().any(|other| other.name == variant.name) {
+                            panic!("ERROR Variant {:?} is already defined", variant.name);
+                        }
+                        variants.push(EnumDefVariant {
+                            name: variant.name,
+                            payload: variant.payload.iter().map(|ast_type| self.resolve_ast_type(ast_type)).collect()
+                        });
+                    }
+                    let ty = self.context.resolve_ty(&[ast_enum_def.name]).unwrap();
+                    match ty.kind {
+                        TyKind::Enum(enum_def) => {
+                            SessionGlobals::with_enum_def_pool_mut(|pool| {
+                                let enum_def = pool.get_mut(enum_def);
+                                enum_def.variants = variants;
+                                enum_def.generic_kinds = kinds.to_vec();
                             });
                         }
                         _ => unreachable!()
                     }
                 }
+                ASTItemKind::InterfaceDef(ast_interface_def) => {
+                    // `Self` is the type implementing the interface
+                    self.context.start_scope();
+                    let self_name = SessionGlobals::with_interner_mut(|i| i.intern("Self"));
+                    self.context.register_ty(&[self_name], Ty { kind: TyKind::Param(0) });
+
+                    let mut functions: Vec<InterfaceDefFunction> = vec![];
+                    for function in &ast_interface_def.functions {
+                        if functions.iter().any(|other| other.name == function.name) {
+                            panic!("ERROR Function {:?} is already required by the interface", function.name);
+                        }
+                        let params: Vec<_> = function.params.iter().map(|param| match &param.pattern.kind {
+                            ASTPatternKind::Binding(name) => (*name, self.resolve_ast_type(&param.ast_type)),
+                            _ => panic!("ERROR Params of interface functions must be names")
+                        }).collect();
+                        let takes_self = params.first().is_some_and(|(name, _)| SessionGlobals::with_interner(|i| i.get(name).unwrap()) == "self");
+                        if !takes_self {
+                            panic!("ERROR Functions of interfaces must be methods, taking self as first param");
+                        }
+                        functions.push(InterfaceDefFunction {
+                            name: function.name,
+                            params,
+                            return_type: match &function.return_type {
+                                Some(return_type) => self.resolve_ast_type(return_type),
+                                None => Ty::from_primitive(TyPrimitive::Void)
+                            }
+                        });
+                    }
+                    self.context.close_scope();
+
+                    let interface_def = self.context.resolve_interface(&[ast_interface_def.name]).unwrap();
+                    SessionGlobals::with_interface_def_pool_mut(|pool| pool.get_mut(interface_def).functions = functions);
+                }
                 _ => {}
             }
         }
     }
 
-    fn register_function_items(&mut self, items: &[&ASTItem], generic_params: Vec<Symbol>, functions_with_no_generics: &mut Vec<(Vec<Symbol>, PoolRef<FunctionDef>)>) {
+    fn register_function_items(&mut self, items: &[&ASTItem], generic_params: Vec<(Symbol, GenericParamKind)>, functions: &mut Vec<PoolRef<FunctionDef>>) {
         for item in items {
             match &item.kind {
+                ASTItemKind::ModuleDef(ast_module_def) => {
+                    let items = Self::module_items(ast_module_def);
+                    self.context.enter_module(ast_module_def.name);
+                    self.register_function_items(&items, generic_params.clone(), functions);
+                    self.context.exit_module();
+                }
                 ASTItemKind::Template(ast_template) => {
                     let items: Box<[_]> = ast_template.items.iter().collect();
                     let mut generic_params = generic_params.clone();
-                    generic_params.extend(ast_template.params.iter());
-                    self.register_function_items(&items, generic_params, functions_with_no_generics);
+                    generic_params.extend(ast_template.params.iter().map(|param| (param.name, self.resolve_generic_kind(param))));
+                    self.register_function_items(&items, generic_params, functions);
+                }
+                ASTItemKind::Impl(ast_impl) => {
+                    self.context.start_scope();
+                    for (i, (param, _)) in generic_params.iter().enumerate() {
+                        self.context.register_ty(&[*param], Ty { kind: TyKind::Param(i) });
+                    }
+                    let ty = self.resolve_ast_type(&ast_impl.ast_type);
+                    self.context.close_scope();
+
+                    let path = ty.def_path().unwrap_or_else(|| {
+                        panic!("ERROR Impl blocks can only be declared for structs, enums and primitives, got {}", ty)
+                    });
+                    for item in &ast_impl.items {
+                        if !matches!(item.kind, ASTItemKind::FunctionDef(_) | ASTItemKind::Template(_)) {
+                            panic!("ERROR Impl blocks can only contain functions");
+                        }
+                    }
+
+                    // Functions of the impl are registered under the path of the type
+                    let items: Box<[_]> = ast_impl.items.iter().collect();
+                    let module_path = self.context.swap_module_path(path);
+                    self.register_function_items(&items, generic_params.clone(), functions);
+                    self.context.swap_module_path(module_path);
                 }
                 ASTItemKind::FunctionDef(ast_function_def) => {
+                    // Generic params are only visible while resolving the signature
+                    self.context.start_scope();
+                    for (i, (param, _)) in generic_params.iter().enumerate() {
+                        self.context.register_ty(&[*param], Ty { kind: TyKind::Param(i) });
+                    }
+                    // Params with patterns other than a name are destructured at the start of the body
+                    let mut code = ast_function_def.statement_block.clone();
+                    let mut params = vec![];
+                    let mut destructured = vec![];
+                    for (i, param) in ast_function_def.params.iter().enumerate() {
+                        let name = match &param.pattern.kind {
+                            ASTPatternKind::Binding(name) => *name,
+                            kind => {
+                                let name = SessionGlobals::with_interner_mut(|interner| interner.intern(&format!("_{}", i)));
+                                if !matches!(kind, ASTPatternKind::Wildcard) {
+                                    let value = ASTExpression { kind: ASTExpressionKind::Ident(name), span: param.span.clone() };
+                                    destructured.push(ASTStatement {
+                                        kind: ASTStatementKind::LocalBinding(param.pattern.clone(), None, Some(value)),
+                                        span: param.span.clone()
+                                    });
+                                }
+                                name
+                            }
+                        };
+                        params.push((name, self.resolve_ast_type(&param.ast_type)));
+                    }
+                    code.statements.splice(0..0, destructured);
+
                     let function_def = FunctionDef {
-                        params: ast_function_def.params.iter().map(|param| {
-                            (param.name, self.resolve_ast_type(&param.ast_type))
-                        }).collect(),
-                        generic_params: generic_params.clone(),
+                        path: self.context.item_path(ast_function_def.name),
+                        params,
+                        generic_params: generic_params.iter().map(|(name, _)| *name).collect(),
+                        generic_kinds: generic_params.iter().map(|(_, kind)| kind.clone()).collect(),
                         return_type: if let Some(return_type) = &ast_function_def.return_type {
                             self.resolve_ast_type(return_type)
                         }
                         else {
                             Ty::from_primitive(TyPrimitive::Void)
                         },
-                        code: ast_function_def.statement_block.clone()
+                        code,
+                        scopes: SavedScopes::empty()
                     };
-                    let function_def = SessionGlobals::with_function_def_pool_mut(|pool| pool.add(function_def));
-                    if generic_params.is_empty() {
-                        functions_with_no_generics.push((vec![ast_function_def.name], function_def));
+                    self.context.close_scope();
+
+                    let path = self.context.item_path(ast_function_def.name);
+                    for other in self.context.find_functions(&path) {
+                        let same_params = SessionGlobals::with_function_def_pool(|pool| {
+                            let other = pool.get(other);
+                            other.generic_params.len() == function_def.generic_params.len()
+                                && other.params.iter().map(|p| &p.1).eq(function_def.params.iter().map(|p| &p.1))
+                        });
+                        if same_params {
+                            panic!("ERROR Function {} is already defined with the same params", path_to_string(&path));
+                        }
                     }
+
+                    let function_def = SessionGlobals::with_function_def_pool_mut(|pool| pool.add(function_def));
+                    functions.push(function_def);
                     self.context.register_function(&[ast_function_def.name], function_def);
                 }
                 _ => {}
@@ -150,24 +349,56 @@ impl Lowerer {
         }
     }
 
-    pub fn process_function_items(&mut self, functions_with_no_generics: Vec<(Vec<Symbol>, PoolRef<FunctionDef>)>) {
-        for function in &functions_with_no_generics {
-            todo!()
+    /// Compiles the functions with no generics, and checks the generic ones with bounds, once every
+    /// item they can see is registered
+    fn process_function_items(&mut self, functions: Vec<PoolRef<FunctionDef>>) {
+        let scopes = self.context.save_scopes();
+        SessionGlobals::with_function_def_pool_mut(|pool| {
+            for function in &functions {
+                pool.get_mut(*function).scopes = scopes.clone();
+            }
+        });
+
+        for function in functions {
+            let (has_generics, has_bounds) = SessionGlobals::with_function_def_pool(|pool| {
+                let function = pool.get(function);
+                let has_bounds = function.generic_kinds.iter().any(|kind| matches!(kind, GenericParamKind::Type(bounds) if !bounds.is_empty()));
+                (!function.generic_params.is_empty(), has_bounds)
+            });
+            if !has_generics {
+                self.get_ir_name(function, vec![]);
+            } else if has_bounds {
+                self.check_generic_function(function);
+            }
         }
     }
 
-    pub fn process_module(mut self, module: ASTModule) {
+    /// Registers and compiles items declared inside a function body
+    pub(in crate::solidlang::lowerer) fn process_items(&mut self, items: &[&ASTItem]) {
+        self.register_type_items(items, 0);
+        self.process_type_items(items, &[]);
+        let mut functions = vec![];
+        self.register_function_items(items, vec![], &mut functions);
+        self.process_function_items(functions);
+    }
+
+    pub fn process_module(mut self, module: ASTModule) -> IRModule {
         self.context.start_scope();
 
         self.context.register_default_tys();
 
         let items: Box<[_]> = module.items.iter().collect();
         self.register_type_items(&items, 0);
-        self.process_type_items(&items, 0);
-        let mut functions_with_no_generics = vec![];
-        self.register_function_items(&items, vec![], &mut functions_with_no_generics);
-        self.process_function_items(functions_with_no_generics);
+        self.process_type_items(&items, &[]);
+        let mut functions = vec![];
+        self.register_function_items(&items, vec![], &mut functions);
+        self.context.check_aliases();
+        self.process_function_items(functions);
 
         self.context.close_scope();
+
+        IRModule {
+            items: self.codegen.into_items()
+        }
     }
 }
@@ -1,6 +1,7 @@
 pub mod ast;
 pub mod defs;
 pub mod lexer;
+pub mod loader;
 pub mod lowerer;
 pub mod parser;
 pub mod span;
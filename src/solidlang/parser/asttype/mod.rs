@@ -1,4 +1,4 @@
-use crate::solidlang::ast::{ASTNameAndType, ASTType, ASTTypeKind};
+use crate::solidlang::ast::{ASTExpressionKind, ASTNameAndType, ASTParam, ASTType, ASTTypeKind};
 use crate::solidlang::lexer::{Token, TokenKind};
 use crate::solidlang::parser::{Parser, ParserResult};
 
@@ -19,6 +19,20 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         })
     }
 
+    pub(in crate::solidlang::parser) fn parse_param(&mut self) -> ParserResult<ASTParam> {
+        self.start_span();
+
+        let pattern = self.parse_pattern()?;
+        self.expect(TokenKind::Colon)?;
+        let ast_type = self.parse_type()?;
+
+        Ok(ASTParam {
+            pattern,
+            ast_type,
+            span: self.close_span(),
+        })
+    }
+
     pub(in crate::solidlang::parser) fn parse_type(&mut self) -> ParserResult<ASTType> {
         self.start_span();
 
@@ -49,6 +63,31 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
+        if self.check(TokenKind::LParen) {
+            // Tuples, a single type in parentheses needs a trailing comma to be a tuple
+            self.advance();
+            let mut tys = vec![self.parse_type()?];
+            let mut is_tuple = false;
+            while self.check(TokenKind::Comma) {
+                self.advance();
+                is_tuple = true;
+                if self.check(TokenKind::RParen) {
+                    break;
+                }
+                tys.push(self.parse_type()?);
+            }
+            self.expect(TokenKind::RParen)?;
+
+            if !is_tuple {
+                self.close_span();
+                return Ok(tys.pop().unwrap());
+            }
+            return Ok(ASTType {
+                kind: ASTTypeKind::Tuple(tys),
+                span: self.close_span(),
+            });
+        }
+
         if self.check(TokenKind::Mul) {
             self.advance();
             return Ok(ASTType {
@@ -57,6 +96,31 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
+        if self.check(TokenKind::LSBracket) {
+            self.advance();
+            let element = self.parse_type()?;
+            self.expect(TokenKind::Semicolon)?;
+            let len = self.parse_type()?;
+            self.expect(TokenKind::RSBracket)?;
+            return Ok(ASTType {
+                kind: ASTTypeKind::Array(Box::new(element), Box::new(len)),
+                span: self.close_span(),
+            });
+        }
+
+        if self.check(TokenKind::IntegerLiteral) {
+            // Values of const params have the type of the param, they take no suffix
+            let token = self.advance();
+            let value = match self.parse_number_literal(&token)? {
+                ASTExpressionKind::IntegerLiteral(value, None) => value,
+                _ => return Err(self.error_invalid_literal(self.token_span(&token))),
+            };
+            return Ok(ASTType {
+                kind: ASTTypeKind::IntegerLiteral(value),
+                span: self.close_span(),
+            });
+        }
+
         Err(self.error_unexpected_current())
     }
 }
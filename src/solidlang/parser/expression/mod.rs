@@ -1,9 +1,21 @@
-use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTOperator};
+use crate::globals::{SessionGlobals, Symbol};
+use crate::solidlang::ast::{ASTExpression, ASTExpressionKind, ASTMatchArm, ASTOperator};
 use crate::solidlang::lexer::{Token, TokenKind};
 use crate::solidlang::parser::{Parser, ParserResult};
 
+/// Ranges bind looser than every operator but assignment
+const RANGE_PRECEDENCE: u8 = 1;
+const INTEGER_SUFFIXES: [&str; 8] = ["u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64"];
+/// Decimal integer literals with these suffixes are floats, like `1f32`
+const FLOAT_SUFFIXES: [&str; 2] = ["f32", "f64"];
+
+/// Casts bind tighter than every binary operator
+const CAST_PRECEDENCE: u8 = 10;
+
 impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
-    fn parse_primary_expression(&mut self) -> ParserResult<ASTExpression> {
+    pub(in crate::solidlang::parser) fn parse_primary_expression(
+        &mut self,
+    ) -> ParserResult<ASTExpression> {
         self.start_span();
 
         if self.check(TokenKind::Ident) {
@@ -14,19 +26,13 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
-        if self.check(TokenKind::IntegerLiteral) {
-            // Integer literals
+        if self.check(TokenKind::IntegerLiteral) || self.check(TokenKind::FloatLiteral) {
+            // Number literals
             let token = self.advance();
-            let start = token.start;
-            let end = token.start + token.len;
-            let literal = &self.src[start..end];
-            // FIXME : replace with more appropriate error
-            let literal = literal
-                .parse()
-                .map_err(|_| self.error_unexpected_current())?;
+            let kind = self.parse_number_literal(&token)?;
 
             return Ok(ASTExpression {
-                kind: ASTExpressionKind::IntegerLiteral(literal),
+                kind,
                 span: self.close_span(),
             });
         }
@@ -50,13 +56,30 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         }
 
         if self.check(TokenKind::LParen) {
-            // Parenthesised expressions
+            // Parenthesised expressions and tuples
             self.advance();
             let expression = self.parse_expression()?;
+            if !self.check(TokenKind::Comma) {
+                self.expect(TokenKind::RParen)?;
+
+                self.close_span();
+                return Ok(expression);
+            }
+
+            let mut expressions = vec![expression];
+            while self.check(TokenKind::Comma) {
+                self.advance();
+                if self.check(TokenKind::RParen) {
+                    break;
+                }
+                expressions.push(self.parse_expression()?);
+            }
             self.expect(TokenKind::RParen)?;
 
-            self.close_span();
-            return Ok(expression);
+            return Ok(ASTExpression {
+                kind: ASTExpressionKind::Tuple(expressions),
+                span: self.close_span(),
+            });
         }
 
         if self.check(TokenKind::LCBracket) {
@@ -87,6 +110,114 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
+        if self.check(TokenKind::Label) {
+            // Labeled loops
+            let label = self.advance_symbol();
+            self.expect(TokenKind::Colon)?;
+
+            return self.parse_loop(Some(label));
+        }
+
+        if self.check(TokenKind::KwWhile)
+            || self.check(TokenKind::KwLoop)
+            || self.check(TokenKind::KwFor)
+        {
+            return self.parse_loop(None);
+        }
+
+        if self.check(TokenKind::KwMatch) {
+            // Match expressions
+            self.advance();
+            let scrutinee = self.parse_expression()?;
+
+            let mut arms = vec![];
+            self.expect(TokenKind::LCBracket)?;
+            while !self.check(TokenKind::RCBracket) {
+                let arm = self.parse_match_arm()?;
+                // Arms ending with a block do not need a comma
+                let requires_comma = !matches!(
+                    arm.expression.kind,
+                    ASTExpressionKind::Block(_)
+                        | ASTExpressionKind::If(_, _, _)
+                        | ASTExpressionKind::While(_, _, _)
+                        | ASTExpressionKind::Loop(_, _)
+                        | ASTExpressionKind::For(_, _, _, _)
+                        | ASTExpressionKind::Match(_, _)
+                );
+                arms.push(arm);
+
+                if self.check(TokenKind::Comma) {
+                    self.advance();
+                } else if requires_comma {
+                    break;
+                }
+            }
+            self.expect(TokenKind::RCBracket)?;
+
+            return Ok(ASTExpression {
+                kind: ASTExpressionKind::Match(Box::new(scrutinee), arms),
+                span: self.close_span(),
+            });
+        }
+
+        Err(self.error_unexpected_current())
+    }
+
+    /// Parses the digits, radix prefix, separators and type suffix of a number literal
+    pub(super) fn parse_number_literal(
+        &mut self,
+        token: &Token,
+    ) -> ParserResult<ASTExpressionKind> {
+        let literal = &self.src[token.start..(token.start + token.len)];
+        let error = || self.error_invalid_literal(self.token_span(token));
+
+        let (radix, body) = match literal.get(..2) {
+            Some("0x") => (16, &literal[2..]),
+            Some("0o") => (8, &literal[2..]),
+            Some("0b") => (2, &literal[2..]),
+            _ => (10, literal),
+        };
+        let is_float = token.kind == TokenKind::FloatLiteral;
+        // Exponents are the only letters of float literals before their suffix
+        let suffix_start = body
+            .find(|c: char| {
+                if is_float {
+                    c.is_ascii_alphabetic() && !matches!(c, 'e' | 'E')
+                } else {
+                    !c.is_digit(radix) && c != '_'
+                }
+            })
+            .unwrap_or(body.len());
+        let (digits, suffix) = body.split_at(suffix_start);
+        let digits: String = digits.chars().filter(|&c| c != '_').collect();
+        if digits.is_empty() {
+            return Err(error());
+        }
+        let suffix_symbol = if suffix.is_empty() {
+            None
+        } else {
+            Some(SessionGlobals::with_interner_mut(|interner| {
+                interner.intern(suffix)
+            }))
+        };
+
+        if is_float || (radix == 10 && FLOAT_SUFFIXES.contains(&suffix)) {
+            if !suffix.is_empty() && !FLOAT_SUFFIXES.contains(&suffix) {
+                return Err(error());
+            }
+            let value = digits.parse().map_err(|_| error())?;
+            return Ok(ASTExpressionKind::FloatLiteral(value, suffix_symbol));
+        }
+
+        if !suffix.is_empty() && !INTEGER_SUFFIXES.contains(&suffix) {
+            return Err(error());
+        }
+        let value = u64::from_str_radix(&digits, radix).map_err(|_| error())?;
+        Ok(ASTExpressionKind::IntegerLiteral(value, suffix_symbol))
+    }
+
+    /// Parses a loop whose span was started by the caller
+    fn parse_loop(&mut self, label: Option<Symbol>) -> ParserResult<ASTExpression> {
         if self.check(TokenKind::KwWhile) {
             // While loops
             self.advance();
@@ -94,7 +225,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             let block = self.parse_statement_block()?;
 
             return Ok(ASTExpression {
-                kind: ASTExpressionKind::While(Box::new(condition), block),
+                kind: ASTExpressionKind::While(label, Box::new(condition), block),
                 span: self.close_span(),
             });
         }
@@ -105,7 +236,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             let block = self.parse_statement_block()?;
 
             return Ok(ASTExpression {
-                kind: ASTExpressionKind::Loop(block),
+                kind: ASTExpressionKind::Loop(label, block),
                 span: self.close_span(),
             });
         }
@@ -119,7 +250,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             let block = self.parse_statement_block()?;
 
             return Ok(ASTExpression {
-                kind: ASTExpressionKind::For(var, Box::new(iter), block),
+                kind: ASTExpressionKind::For(label, var, Box::new(iter), block),
                 span: self.close_span(),
             });
         }
@@ -127,16 +258,57 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         Err(self.error_unexpected_current())
     }
 
+    fn parse_match_arm(&mut self) -> ParserResult<ASTMatchArm> {
+        self.start_span();
+
+        let pattern = self.parse_pattern()?;
+        self.expect(TokenKind::FatArrow)?;
+        let expression = self.parse_expression()?;
+
+        Ok(ASTMatchArm {
+            pattern,
+            expression,
+            span: self.close_span(),
+        })
+    }
+
     fn parse_application_and_access(&mut self) -> ParserResult<ASTExpression> {
         self.start_span();
         let mut expression = self.parse_primary_expression()?;
 
         loop {
             if self.check(TokenKind::Dot) {
-                // Member access
+                // Member access, fields of tuples are named by their index
                 self.clone_span();
                 self.advance();
-                let sym = self.expect_ident()?;
+                if self.check_tuple_index_pair() {
+                    // `t.0.1` lexes its two indices as a single float literal
+                    let token = self.advance();
+                    let literal = &self.src[token.start..(token.start + token.len)];
+                    let (first, second) = literal.split_once('.').unwrap();
+                    let (first, second) = SessionGlobals::with_interner_mut(|interner| {
+                        (interner.intern(first), interner.intern(second))
+                    });
+                    let span = self.close_span();
+                    self.clone_span();
+                    expression = ASTExpression {
+                        kind: ASTExpressionKind::MemberAccess(
+                            Box::new(ASTExpression {
+                                kind: ASTExpressionKind::MemberAccess(Box::new(expression), first),
+                                span,
+                            }),
+                            second,
+                        ),
+                        span: self.close_span(),
+                    };
+
+                    continue;
+                }
+                let sym = if self.check(TokenKind::IntegerLiteral) {
+                    self.advance_symbol()
+                } else {
+                    self.expect_ident()?
+                };
                 expression = ASTExpression {
                     kind: ASTExpressionKind::MemberAccess(Box::new(expression), sym),
                     span: self.close_span(),
@@ -225,6 +397,16 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         Ok(expression)
     }
 
+    fn check_tuple_index_pair(&mut self) -> bool {
+        if !self.check(TokenKind::FloatLiteral) {
+            return false;
+        }
+        let (start, len) = (self.peek().start, self.peek().len);
+        self.src[start..(start + len)]
+            .bytes()
+            .all(|c| c.is_ascii_digit() || c == b'.')
+    }
+
     fn parse_unary_expression(&mut self) -> ParserResult<ASTExpression> {
         // TODO : tidy this up
         if self.check(TokenKind::BitNot) {
@@ -271,14 +453,56 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         self.start_span();
         let mut lhs = self.parse_unary_expression()?;
 
-        while let Some((operator, p)) = self.check_operator() {
-            if p < precedence {
-                break;
+        loop {
+            if precedence <= RANGE_PRECEDENCE
+                && (self.check(TokenKind::DotDot) || self.check(TokenKind::DotDotEq))
+            {
+                // Ranges
+                self.clone_span();
+                let inclusive = self.advance().kind == TokenKind::DotDotEq;
+
+                let end = self.parse_binary_operation_with_precedence(RANGE_PRECEDENCE + 1)?;
+                lhs = ASTExpression {
+                    kind: ASTExpressionKind::Range(Box::new(lhs), Box::new(end), inclusive),
+                    span: self.close_span(),
+                };
+
+                continue;
+            }
+
+            if precedence <= CAST_PRECEDENCE && self.check(TokenKind::KwAs) {
+                // Casts
+                self.clone_span();
+                self.advance();
+
+                let ty = self.parse_type()?;
+                lhs = ASTExpression {
+                    kind: ASTExpressionKind::Cast(Box::new(lhs), ty),
+                    span: self.close_span(),
+                };
+
+                continue;
+            }
+
+            let (operator, p) = match self.check_operator() {
+                Some((operator, p)) if p >= precedence => (operator, p),
+                _ => break,
+            };
+            if operator.is_assignment() {
+                if let ASTExpressionKind::BinaryOperation(previous, ..) = &lhs.kind {
+                    if previous.is_assignment() {
+                        return Err(self.error_chained_assignment(lhs.span));
+                    }
+                }
+                if !lhs.is_place() {
+                    return Err(self.error_invalid_assignment_target(lhs.span));
+                }
             }
             self.clone_span();
             self.advance();
 
-            let rhs = self.parse_binary_operation_with_precedence(p)?;
+            // Operators are left associative, so that chained assignments end up in the target
+            let rhs = self.parse_binary_operation_with_precedence(p + 1)?;
             lhs = ASTExpression {
                 kind: ASTExpressionKind::BinaryOperation(operator, Box::new(lhs), Box::new(rhs)),
                 span: self.close_span(),
@@ -295,40 +519,58 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
 
         if self.check(TokenKind::Assign) {
             Some((ASTOperator::Assign, 0))
+        } else if self.check(TokenKind::PlusAssign) {
+            Some((ASTOperator::PlusAssign, 0))
+        } else if self.check(TokenKind::MinusAssign) {
+            Some((ASTOperator::MinusAssign, 0))
+        } else if self.check(TokenKind::MulAssign) {
+            Some((ASTOperator::MulAssign, 0))
+        } else if self.check(TokenKind::DivAssign) {
+            Some((ASTOperator::DivAssign, 0))
+        } else if self.check(TokenKind::ModAssign) {
+            Some((ASTOperator::ModAssign, 0))
+        } else if self.check(TokenKind::BitAndAssign) {
+            Some((ASTOperator::BitAndAssign, 0))
+        } else if self.check(TokenKind::BitOrAssign) {
+            Some((ASTOperator::BitOrAssign, 0))
+        } else if self.check(TokenKind::BitRShiftAssign) {
+            Some((ASTOperator::BitRShiftAssign, 0))
+        } else if self.check(TokenKind::BitLShiftAssign) {
+            Some((ASTOperator::BitLShiftAssign, 0))
+        } else if self.check(TokenKind::BoolOr) {
+            Some((ASTOperator::BoolOr, 2))
+        } else if self.check(TokenKind::BoolAnd) {
+            Some((ASTOperator::BoolAnd, 3))
         } else if self.check(TokenKind::BitRShift) {
-            Some((ASTOperator::BitRShift, 1))
+            Some((ASTOperator::BitRShift, 4))
         } else if self.check(TokenKind::BitLShift) {
-            Some((ASTOperator::BitLShift, 1))
+            Some((ASTOperator::BitLShift, 4))
         } else if self.check(TokenKind::Equal) {
-            Some((ASTOperator::Equal, 2))
+            Some((ASTOperator::Equal, 5))
         } else if self.check(TokenKind::NotEqual) {
-            Some((ASTOperator::NotEqual, 2))
+            Some((ASTOperator::NotEqual, 5))
         } else if self.check(TokenKind::RABracket) {
-            Some((ASTOperator::Greater, 2))
+            Some((ASTOperator::Greater, 5))
         } else if self.check(TokenKind::LABracket) {
-            Some((ASTOperator::Lesser, 2))
+            Some((ASTOperator::Lesser, 5))
         } else if self.check(TokenKind::GreaterEqual) {
-            Some((ASTOperator::GreaterEqual, 2))
+            Some((ASTOperator::GreaterEqual, 5))
         } else if self.check(TokenKind::LesserEqual) {
-            Some((ASTOperator::LesserEqual, 2))
-        } else if self.check(TokenKind::BoolOr) {
-            Some((ASTOperator::BoolOr, 3))
-        } else if self.check(TokenKind::BoolAnd) {
-            Some((ASTOperator::BoolAnd, 4))
+            Some((ASTOperator::LesserEqual, 5))
         } else if self.check(TokenKind::BitOr) {
-            Some((ASTOperator::BitOr, 5))
+            Some((ASTOperator::BitOr, 6))
         } else if self.check(TokenKind::BitAnd) {
-            Some((ASTOperator::BitAnd, 6))
+            Some((ASTOperator::BitAnd, 7))
         } else if self.check(TokenKind::Plus) {
-            Some((ASTOperator::Plus, 7))
+            Some((ASTOperator::Plus, 8))
         } else if self.check(TokenKind::Minus) {
-            Some((ASTOperator::Minus, 7))
+            Some((ASTOperator::Minus, 8))
         } else if self.check(TokenKind::Mul) {
-            Some((ASTOperator::Mul, 8))
+            Some((ASTOperator::Mul, 9))
         } else if self.check(TokenKind::Div) {
-            Some((ASTOperator::Div, 8))
+            Some((ASTOperator::Div, 9))
         } else if self.check(TokenKind::Mod) {
-            Some((ASTOperator::Mod, 8))
+            Some((ASTOperator::Mod, 9))
         } else {
             None
         }
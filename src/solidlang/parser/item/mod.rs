@@ -1,4 +1,9 @@
-use crate::solidlang::ast::{ASTFunctionDef, ASTItem, ASTItemKind, ASTStructDef, ASTTemplate};
+use crate::globals::Symbol;
+use crate::solidlang::ast::{
+    ASTEnumDef, ASTEnumVariant, ASTFunctionDef, ASTFunctionSignature, ASTImpl, ASTInterfaceDef,
+    ASTItem, ASTItemKind, ASTModuleDef, ASTParam, ASTStructDef, ASTTemplate, ASTTemplateParam,
+    ASTType, ASTUse,
+};
 use crate::solidlang::lexer::{Token, TokenKind};
 use crate::solidlang::parser::{Parser, ParserResult};
 
@@ -8,29 +13,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
 
         if self.check(TokenKind::KwFn) {
             // Function def
-            self.advance();
-            let name = self.expect_ident()?;
-
-            let mut params = vec![];
-            self.expect(TokenKind::LParen)?;
-            if !self.check(TokenKind::RParen) {
-                params.push(self.parse_name_and_type()?);
-                while self.check(TokenKind::Comma) {
-                    self.advance();
-                    params.push(self.parse_name_and_type()?);
-                }
-                self.expect(TokenKind::RParen)?;
-            } else {
-                self.advance();
-            }
-
-            let return_type = if self.check(TokenKind::Arrow) {
-                self.advance();
-                Some(self.parse_type()?)
-            } else {
-                None
-            };
-
+            let (name, params, return_type) = self.parse_signature()?;
             let statement_block = self.parse_statement_block()?;
 
             return Ok(ASTItem {
@@ -72,14 +55,40 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
+        if self.check(TokenKind::KwEnum) {
+            // Enum def
+            self.advance();
+
+            let name = self.expect_ident()?;
+
+            let mut variants = vec![];
+            self.expect(TokenKind::LCBracket)?;
+            while !self.check(TokenKind::RCBracket) {
+                variants.push(self.parse_enum_variant()?);
+                if !self.check(TokenKind::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+            self.expect(TokenKind::RCBracket)?;
+
+            return Ok(ASTItem {
+                kind: ASTItemKind::EnumDef(ASTEnumDef {
+                    name,
+                    variants,
+                    span: self.close_span(),
+                }),
+            });
+        }
+
         if self.check(TokenKind::KwTemplate) {
             // Template
             self.advance();
             self.expect(TokenKind::LABracket)?;
-            let mut params = vec![self.expect_ident()?];
+            let mut params = vec![self.parse_template_param()?];
             while self.check(TokenKind::Comma) {
                 self.advance();
-                params.push(self.expect_ident()?);
+                params.push(self.parse_template_param()?);
             }
             self.expect(TokenKind::RABracket)?;
 
@@ -101,9 +110,195 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             });
         }
 
+        if self.check(TokenKind::KwMod) {
+            // Module def
+            self.advance();
+
+            let name = self.expect_ident()?;
+
+            let items;
+            if self.check(TokenKind::Semicolon) {
+                self.advance();
+                items = None;
+            } else {
+                self.expect(TokenKind::LCBracket)?;
+                items = Some(self.parse_items(TokenKind::RCBracket)?);
+                self.advance();
+            }
+
+            return Ok(ASTItem {
+                kind: ASTItemKind::ModuleDef(ASTModuleDef {
+                    name,
+                    items,
+                    span: self.close_span(),
+                }),
+            });
+        }
+
+        if self.check(TokenKind::KwUse) {
+            // Use
+            self.advance();
+
+            let path = self.parse_path()?;
+            self.expect(TokenKind::Semicolon)?;
+
+            return Ok(ASTItem {
+                kind: ASTItemKind::Use(ASTUse {
+                    path,
+                    span: self.close_span(),
+                }),
+            });
+        }
+
+        if self.check(TokenKind::KwImpl) {
+            // Impl
+            self.advance();
+
+            let ast_type = self.parse_type()?;
+            self.expect(TokenKind::LCBracket)?;
+            let items = self.parse_items(TokenKind::RCBracket)?;
+            self.advance();
+
+            return Ok(ASTItem {
+                kind: ASTItemKind::Impl(ASTImpl {
+                    ast_type,
+                    items,
+                    span: self.close_span(),
+                }),
+            });
+        }
+
+        if self.check(TokenKind::KwInterface) {
+            // Interface def
+            self.advance();
+
+            let name = self.expect_ident()?;
+
+            let mut functions = vec![];
+            self.expect(TokenKind::LCBracket)?;
+            while !self.check(TokenKind::RCBracket) {
+                self.start_span();
+                let (name, params, return_type) = self.parse_signature()?;
+                self.expect(TokenKind::Semicolon)?;
+                functions.push(ASTFunctionSignature {
+                    name,
+                    return_type,
+                    params,
+                    span: self.close_span(),
+                });
+            }
+            self.advance();
+
+            return Ok(ASTItem {
+                kind: ASTItemKind::InterfaceDef(ASTInterfaceDef {
+                    name,
+                    functions,
+                    span: self.close_span(),
+                }),
+            });
+        }
+
         Err(self.error_unexpected_current())
     }
 
+    /// Parses `fn name(params) -> return_type`, without the body
+    fn parse_signature(&mut self) -> ParserResult<(Symbol, Vec<ASTParam>, Option<ASTType>)> {
+        self.expect(TokenKind::KwFn)?;
+        let name = self.expect_ident()?;
+
+        let mut params = vec![];
+        self.expect(TokenKind::LParen)?;
+        if !self.check(TokenKind::RParen) {
+            params.push(self.parse_param()?);
+            while self.check(TokenKind::Comma) {
+                self.advance();
+                params.push(self.parse_param()?);
+            }
+            self.expect(TokenKind::RParen)?;
+        } else {
+            self.advance();
+        }
+
+        let return_type = if self.check(TokenKind::Arrow) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        Ok((name, params, return_type))
+    }
+
+    fn parse_template_param(&mut self) -> ParserResult<ASTTemplateParam> {
+        self.start_span();
+
+        if self.check(TokenKind::KwConst) {
+            self.advance();
+            let name = self.expect_ident()?;
+            self.expect(TokenKind::Colon)?;
+            let ast_type = self.parse_type()?;
+
+            return Ok(ASTTemplateParam {
+                name,
+                bounds: vec![],
+                ast_type: Some(ast_type),
+                span: self.close_span(),
+            });
+        }
+
+        let name = self.expect_ident()?;
+
+        let mut bounds = vec![];
+        if self.check(TokenKind::Colon) {
+            self.advance();
+            bounds.push(self.parse_path()?);
+            while self.check(TokenKind::Plus) {
+                self.advance();
+                bounds.push(self.parse_path()?);
+            }
+        }
+
+        Ok(ASTTemplateParam {
+            name,
+            bounds,
+            ast_type: None,
+            span: self.close_span(),
+        })
+    }
+
+    fn parse_path(&mut self) -> ParserResult<Vec<Symbol>> {
+        let mut path = vec![self.expect_ident()?];
+        while self.check(TokenKind::ColonColon) {
+            self.advance();
+            path.push(self.expect_ident()?);
+        }
+
+        Ok(path)
+    }
+
+    fn parse_enum_variant(&mut self) -> ParserResult<ASTEnumVariant> {
+        self.start_span();
+
+        let name = self.expect_ident()?;
+
+        let mut payload = vec![];
+        if self.check(TokenKind::LParen) {
+            self.advance();
+            payload.push(self.parse_type()?);
+            while self.check(TokenKind::Comma) {
+                self.advance();
+                payload.push(self.parse_type()?);
+            }
+            self.expect(TokenKind::RParen)?;
+        }
+
+        Ok(ASTEnumVariant {
+            name,
+            payload,
+            span: self.close_span(),
+        })
+    }
+
     pub(in crate::solidlang::parser) fn parse_items(
         &mut self,
         closing_delim: TokenKind,
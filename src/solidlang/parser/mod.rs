@@ -8,6 +8,7 @@ use std::iter::Peekable;
 pub mod asttype;
 pub mod expression;
 pub mod item;
+pub mod pattern;
 pub mod statement;
 
 #[derive(Debug)]
@@ -16,6 +17,12 @@ pub enum ParserErrorKind {
         expected: Vec<TokenKind>,
         got: TokenKind,
     },
+    /// Assignment to an expression that is not a place, like a literal or a call
+    InvalidAssignmentTarget { span: Span },
+    /// Assignment whose target is itself an assignment, like `a = b = c`
+    ChainedAssignment { span: Span },
+    /// Number literal with a digit invalid for its radix, an unknown suffix or a value too large
+    InvalidLiteral { span: Span },
 }
 
 #[derive(Debug)]
@@ -32,16 +39,19 @@ pub struct Parser<'a, T: Iterator<Item = Token>> {
     src: &'a str,
     span_starts: Vec<usize>,
     ending_span: usize,
+    /// Offset of the file in the source map, added to the positions of spans
+    offset: usize,
 }
 
 impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
-    pub fn new(iter: T, src: &'a str) -> Self {
+    pub fn new(iter: T, src: &'a str, offset: usize) -> Self {
         Self {
             tokens: iter.peekable(),
             expected_tokens: vec![],
             src,
             span_starts: vec![],
-            ending_span: 0,
+            ending_span: offset,
+            offset,
         }
     }
 
@@ -58,7 +68,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
     fn advance(&mut self) -> Token {
         self.expected_tokens.clear();
         let next = self.tokens.next().unwrap();
-        self.ending_span = next.start + next.len;
+        self.ending_span = self.offset + next.start + next.len;
         next
     }
 
@@ -72,6 +82,27 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         }
     }
 
+    fn error_invalid_assignment_target(&self, span: Span) -> ParserError {
+        ParserError {
+            kind: ParserErrorKind::InvalidAssignmentTarget { span },
+            backtrace: Backtrace::new(),
+        }
+    }
+
+    fn error_chained_assignment(&self, span: Span) -> ParserError {
+        ParserError {
+            kind: ParserErrorKind::ChainedAssignment { span },
+            backtrace: Backtrace::new(),
+        }
+    }
+
+    fn error_invalid_literal(&self, span: Span) -> ParserError {
+        ParserError {
+            kind: ParserErrorKind::InvalidLiteral { span },
+            backtrace: Backtrace::new(),
+        }
+    }
+
     fn error_unexpected_current(&mut self) -> ParserError {
         let kind = self.peek().kind;
         self.error_unexpected(kind)
@@ -109,8 +140,15 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         self.expect_symbol(TokenKind::Ident)
     }
 
+    fn token_span(&self, token: &Token) -> Span {
+        Span {
+            start: self.offset + token.start,
+            len: token.len,
+        }
+    }
+
     fn start_span(&mut self) {
-        let start = self.peek().start;
+        let start = self.offset + self.peek().start;
         self.span_starts.push(start);
     }
 
@@ -134,7 +172,10 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         if items.len() == 0 {
             return Ok(ASTModule {
                 items,
-                span: Span { start: 0, len: 0 },
+                span: Span {
+                    start: self.offset,
+                    len: 0,
+                },
             });
         }
 
@@ -0,0 +1,157 @@
+use crate::globals::SessionGlobals;
+use crate::solidlang::ast::{
+    ASTExpression, ASTExpressionKind, ASTOperator, ASTPattern, ASTPatternKind,
+};
+use crate::solidlang::lexer::{Token, TokenKind};
+use crate::solidlang::parser::{Parser, ParserResult};
+
+impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
+    pub(in crate::solidlang::parser) fn parse_pattern(&mut self) -> ParserResult<ASTPattern> {
+        self.start_span();
+
+        if self.check(TokenKind::Ident) {
+            let name = self.advance_symbol();
+            let is_path = self.check(TokenKind::ColonColon)
+                || self.check(TokenKind::LParen)
+                || self.check(TokenKind::LCBracket);
+            if !is_path {
+                let wildcard = SessionGlobals::with_interner(|i| i.get(&name).unwrap() == "_");
+                return Ok(ASTPattern {
+                    kind: if wildcard {
+                        ASTPatternKind::Wildcard
+                    } else {
+                        ASTPatternKind::Binding(name)
+                    },
+                    span: self.close_span(),
+                });
+            }
+
+            let mut path = vec![name];
+            while self.check(TokenKind::ColonColon) {
+                self.advance();
+                path.push(self.expect_ident()?);
+            }
+
+            if self.check(TokenKind::LCBracket) {
+                // Structs
+                self.advance();
+                let mut fields = vec![];
+                let mut rest = false;
+                while !self.check(TokenKind::RCBracket) {
+                    if self.check(TokenKind::DotDot) {
+                        self.advance();
+                        rest = true;
+                        break;
+                    }
+
+                    self.start_span();
+                    let field = self.expect_ident()?;
+                    let pattern = if self.check(TokenKind::Colon) {
+                        self.advance();
+                        self.close_span();
+                        self.parse_pattern()?
+                    } else {
+                        ASTPattern {
+                            kind: ASTPatternKind::Binding(field),
+                            span: self.close_span(),
+                        }
+                    };
+                    fields.push((field, pattern));
+
+                    if !self.check(TokenKind::Comma) {
+                        break;
+                    }
+                    self.advance();
+                }
+                self.expect(TokenKind::RCBracket)?;
+
+                return Ok(ASTPattern {
+                    kind: ASTPatternKind::Struct(path, fields, rest),
+                    span: self.close_span(),
+                });
+            }
+
+            // Enum variants
+            let mut patterns = vec![];
+            if self.check(TokenKind::LParen) {
+                self.advance();
+                patterns = self.parse_pattern_list(TokenKind::RParen)?;
+            }
+
+            return Ok(ASTPattern {
+                kind: ASTPatternKind::Variant(path, patterns),
+                span: self.close_span(),
+            });
+        }
+
+        if self.check(TokenKind::LParen) {
+            // Tuples
+            self.advance();
+            let patterns = self.parse_pattern_list(TokenKind::RParen)?;
+
+            return Ok(ASTPattern {
+                kind: ASTPatternKind::Tuple(patterns),
+                span: self.close_span(),
+            });
+        }
+
+        if self.check(TokenKind::LSBracket) {
+            // Arrays
+            self.advance();
+            let patterns = self.parse_pattern_list(TokenKind::RSBracket)?;
+
+            return Ok(ASTPattern {
+                kind: ASTPatternKind::Array(patterns),
+                span: self.close_span(),
+            });
+        }
+
+        if self.check(TokenKind::Minus) {
+            // Negative integer literals
+            self.advance();
+            let literal = self.parse_primary_expression()?;
+            if !matches!(literal.kind, ASTExpressionKind::IntegerLiteral(..)) {
+                return Err(self.error_unexpected(TokenKind::Minus));
+            }
+            let span = self.close_span();
+
+            return Ok(ASTPattern {
+                kind: ASTPatternKind::Literal(ASTExpression {
+                    kind: ASTExpressionKind::UnaryOperation(ASTOperator::Minus, Box::new(literal)),
+                    span: span.clone(),
+                }),
+                span,
+            });
+        }
+
+        if self.check(TokenKind::IntegerLiteral)
+            || self.check(TokenKind::BooleanTrue)
+            || self.check(TokenKind::BooleanFalse)
+        {
+            // Literals
+            let literal = self.parse_primary_expression()?;
+
+            return Ok(ASTPattern {
+                kind: ASTPatternKind::Literal(literal),
+                span: self.close_span(),
+            });
+        }
+
+        Err(self.error_unexpected_current())
+    }
+
+    /// Comma separated patterns up to the closing delimiter, which is consumed
+    fn parse_pattern_list(&mut self, closing_delim: TokenKind) -> ParserResult<Vec<ASTPattern>> {
+        let mut patterns = vec![];
+        while !self.check(closing_delim) {
+            patterns.push(self.parse_pattern()?);
+            if !self.check(TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(closing_delim)?;
+
+        Ok(patterns)
+    }
+}
@@ -19,7 +19,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             // Local binding
             self.advance();
 
-            let name = self.expect_ident()?;
+            let pattern = self.parse_pattern()?;
 
             // Type hint
             let type_hint = if self.check(TokenKind::Colon) {
@@ -38,7 +38,7 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
             };
 
             return Ok(ASTStatement {
-                kind: ASTStatementKind::LocalBinding(name, type_hint, expression),
+                kind: ASTStatementKind::LocalBinding(pattern, type_hint, expression),
                 span: self.close_span(),
             });
         }
@@ -58,19 +58,34 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         if self.check(TokenKind::KwBreak) {
             // Break
             self.advance();
+            let label = if self.check(TokenKind::Label) {
+                Some(self.advance_symbol())
+            } else {
+                None
+            };
+            let value = if self.check(TokenKind::Semicolon) || self.check(TokenKind::RCBracket) {
+                None
+            } else {
+                Some(self.parse_expression()?)
+            };
 
             return Ok(ASTStatement {
-                kind: ASTStatementKind::Break,
+                kind: ASTStatementKind::Break(label, value),
                 span: self.close_span(),
             });
         }
 
         if self.check(TokenKind::KwContinue) {
-            // Break
+            // Continue
             self.advance();
+            let label = if self.check(TokenKind::Label) {
+                Some(self.advance_symbol())
+            } else {
+                None
+            };
 
             return Ok(ASTStatement {
-                kind: ASTStatementKind::Continue,
+                kind: ASTStatementKind::Continue(label),
                 span: self.close_span(),
             });
         }
@@ -78,6 +93,8 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         if self.check(TokenKind::KwStruct)
             || self.check(TokenKind::KwFn)
             || self.check(TokenKind::KwTemplate)
+            || self.check(TokenKind::KwImpl)
+            || self.check(TokenKind::KwEnum)
         {
             return Ok(ASTStatement {
                 kind: ASTStatementKind::Item(self.parse_item()?),
@@ -104,9 +121,10 @@ impl<'a, T: Iterator<Item = Token>> Parser<'a, T> {
         while !self.check(TokenKind::RCBracket) {
             let semi_present = self.check(TokenKind::Semicolon);
             let semi_span = {
+                let offset = self.offset;
                 let peeked = self.peek();
                 Span {
-                    start: peeked.start,
+                    start: offset + peeked.start,
                     len: peeked.len,
                 }
             };
@@ -1,14 +1,26 @@
 use crate::globals::SessionGlobals;
 use std::fmt::{Debug, Formatter};
-use std::hash::Hash;
-use crate::solidlang::defs::StructDef;
+use std::hash::{Hash, Hasher};
+use crate::solidlang::defs::{EnumDef, InterfaceDef, StructDef};
 
-#[derive(Hash)]
 pub struct PoolRef<T> {
     index: usize,
     _marker: std::marker::PhantomData<T>,
 }
 
+impl<T> Hash for PoolRef<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> PartialEq for PoolRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+impl<T> Eq for PoolRef<T> {}
+
 impl<T> Clone for PoolRef<T> {
     fn clone(&self) -> Self {
         *self
@@ -22,6 +34,18 @@ impl Debug for PoolRef<StructDef> {
     }
 }
 
+impl Debug for PoolRef<EnumDef> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        SessionGlobals::with_enum_def_pool(|pool| pool.get(*self).fmt(f))
+    }
+}
+
+impl Debug for PoolRef<InterfaceDef> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        SessionGlobals::with_interface_def_pool(|pool| pool.get(*self).fmt(f))
+    }
+}
+
 pub struct Pool<T> {
     values: Vec<T>,
     current_index: usize,
@@ -1,5 +1,86 @@
+use std::path::PathBuf;
+
 #[derive(Debug, Clone)]
 pub struct Span {
     pub start: usize,
     pub len: usize,
 }
+
+impl Span {
+    /// Line and column of the start of the span in the source, both starting at 1
+    pub fn line_and_column(&self, src: &str) -> (usize, usize) {
+        let before = &src[..self.start.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        (line, column)
+    }
+
+    /// The line the span starts on, with the span underlined on the next line
+    pub fn excerpt(&self, src: &str) -> String {
+        let (line, column) = self.line_and_column(src);
+        let text = src.lines().nth(line - 1).unwrap_or("");
+        let len = self
+            .len
+            .clamp(1, (text.len() + 1).saturating_sub(column).max(1));
+        format!("{}\n{}{}", text, " ".repeat(column - 1), "^".repeat(len))
+    }
+}
+
+/// A file loaded in the session, the spans in it start at its offset
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub src: String,
+    pub offset: usize,
+}
+
+/// Files loaded in the session, laid out one after the other so that a span tells its file
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Registers a file, yields the offset to add to the positions in it
+    pub fn add_file(&mut self, path: PathBuf, src: String) -> usize {
+        // Past the end of the previous file, so that the spans of an empty file are still its own
+        let offset = self
+            .files
+            .last()
+            .map_or(0, |file| file.offset + file.src.len() + 1);
+        self.files.push(SourceFile { path, src, offset });
+        offset
+    }
+
+    /// File the span starts in, with the span relative to the start of the file
+    pub fn lookup(&self, span: &Span) -> Option<(&SourceFile, Span)> {
+        let file = self
+            .files
+            .iter()
+            .rev()
+            .find(|file| file.offset <= span.start)?;
+        let span = Span {
+            start: span.start - file.offset,
+            len: span.len,
+        };
+        Some((file, span))
+    }
+
+    /// `path:line:column: message` followed by the excerpt of the span
+    pub fn report(&self, span: &Span, message: &str) -> String {
+        match self.lookup(span) {
+            Some((file, span)) => {
+                let (line, column) = span.line_and_column(&file.src);
+                format!(
+                    "{}:{}:{}: {}\n{}",
+                    file.path.display(),
+                    line,
+                    column,
+                    message,
+                    span.excerpt(&file.src)
+                )
+            }
+            None => message.to_owned(),
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::globals::{SessionGlobals, Symbol};
+use crate::ir::IRType;
+use crate::solidlang::item::path_to_string;
 use crate::solidlang::pool::{PoolRef};
-use crate::solidlang::defs::StructDef;
+use crate::solidlang::defs::{EnumDef, StructDef};
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Hash, Clone, Eq, PartialEq)]
 pub enum TyPrimitive {
@@ -11,83 +15,162 @@ pub enum TyPrimitive {
     I32,
     U64,
     I64,
+    F32,
+    F64,
     Bool,
     Char,
     Void,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum TyKind {
     Primitive(TyPrimitive),
     PointerTo(Box<Ty>),
     Struct(PoolRef<StructDef>),
     StructWithArgs(PoolRef<StructDef>, Box<[Ty]>),
-    Param(usize)
+    Enum(PoolRef<EnumDef>),
+    EnumWithArgs(PoolRef<EnumDef>, Box<[Ty]>),
+    Tuple(Box<[Ty]>),
+    /// Elements laid out one after the other, the length being a `Const` or a `Param`
+    Array(Box<Ty>, Box<Ty>),
+    Param(usize),
+    /// Value given to a const param
+    Const(u64)
 }
 
-#[derive(Debug, Clone)]
+/// Names of the variants of an enum type, with the types and offsets of their payloads
+pub type EnumVariants = Vec<(Symbol, Vec<(Ty, u64)>)>;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Ty {
     pub kind: TyKind,
 }
 
 impl Ty {
-    /*
-    // TODO Optimize this whole thing
-    pub fn struct_recompute_offsets_size_and_align_recursive(pool_ref: PoolRef<Ty>) {
-        let to_recompute = SessionGlobals::with_ty_pool(|pool| {
-            let self_ty = pool.get(pool_ref);
-            match &self_ty.kind {
-                TyKind::Struct(s) => Some(s.fields.clone()),
-                _ => None,
-            }
-        });
+    /// Replaces the generic params of the type with the given args
+    pub fn substitute(&self, args: &[Ty]) -> Ty {
+        match &self.kind {
+            TyKind::Param(index) => args[*index].clone(),
+            TyKind::PointerTo(ty) => Ty {
+                kind: TyKind::PointerTo(Box::new(ty.substitute(args)))
+            },
+            TyKind::StructWithArgs(struct_def, struct_args) => Ty {
+                kind: TyKind::StructWithArgs(*struct_def, struct_args.iter().map(|t| t.substitute(args)).collect())
+            },
+            TyKind::EnumWithArgs(enum_def, enum_args) => Ty {
+                kind: TyKind::EnumWithArgs(*enum_def, enum_args.iter().map(|t| t.substitute(args)).collect())
+            },
+            TyKind::Tuple(tys) => Ty {
+                kind: TyKind::Tuple(tys.iter().map(|t| t.substitute(args)).collect())
+            },
+            TyKind::Array(ty, len) => Ty {
+                kind: TyKind::Array(Box::new(ty.substitute(args)), Box::new(len.substitute(args)))
+            },
+            _ => self.clone()
+        }
+    }
+
+    /// Replaces the generic params of the type with the bound args, fails if one of them is unbound
+    pub fn try_substitute(&self, bindings: &[Option<Ty>]) -> Option<Ty> {
+        match &self.kind {
+            TyKind::Param(index) => bindings[*index].clone(),
+            TyKind::PointerTo(ty) => Some(Ty {
+                kind: TyKind::PointerTo(Box::new(ty.try_substitute(bindings)?))
+            }),
+            TyKind::StructWithArgs(struct_def, args) => Some(Ty {
+                kind: TyKind::StructWithArgs(*struct_def, args.iter().map(|t| t.try_substitute(bindings)).collect::<Option<_>>()?)
+            }),
+            TyKind::EnumWithArgs(enum_def, args) => Some(Ty {
+                kind: TyKind::EnumWithArgs(*enum_def, args.iter().map(|t| t.try_substitute(bindings)).collect::<Option<_>>()?)
+            }),
+            TyKind::Tuple(tys) => Some(Ty {
+                kind: TyKind::Tuple(tys.iter().map(|t| t.try_substitute(bindings)).collect::<Option<_>>()?)
+            }),
+            TyKind::Array(ty, len) => Some(Ty {
+                kind: TyKind::Array(Box::new(ty.try_substitute(bindings)?), Box::new(len.try_substitute(bindings)?))
+            }),
+            _ => Some(self.clone())
+        }
+    }
 
-        if let Some(to_recompute) = to_recompute {
-            for to_recompute in &to_recompute {
-                Self::struct_recompute_offsets_size_and_align_recursive(to_recompute.ty);
+    /// Fields of a struct or tuple type with their types and offsets
+    ///
+    /// Fields of tuples are named by their index.
+    pub fn struct_fields(&self) -> Option<Vec<(Symbol, Ty, u64)>> {
+        let (struct_def, args): (_, &[Ty]) = match &self.kind {
+            TyKind::Struct(struct_def) => (*struct_def, &[]),
+            TyKind::StructWithArgs(struct_def, args) => (*struct_def, args),
+            TyKind::Tuple(tys) => {
+                let fields = tys.iter().enumerate().map(|(i, ty)| {
+                    (SessionGlobals::with_interner_mut(|interner| interner.intern(&i.to_string())), ty.clone())
+                }).collect();
+                return Some(Self::lay_out_fields(fields));
             }
+            _ => return None
+        };
 
-            SessionGlobals::with_ty_pool_mut(|pool| {
-                let mut fields = match &pool.get(pool_ref).kind {
-                    TyKind::Struct(s) => s.fields.clone(),
-                    _ => unreachable!(),
-                };
+        let fields: Vec<_> = SessionGlobals::with_struct_def_pool(|pool| {
+            pool.get(struct_def).fields.iter().map(|field| (field.name, field.ty.substitute(args))).collect()
+        });
+        Some(Self::lay_out_fields(fields))
+    }
 
-                let mut current_offset = 0;
-                let mut max_align = 1;
-                for field in &mut fields {
-                    let (size, align) = pool.get(field.ty).get_size_and_align();
+    /// Places fields one after the other, each at the next offset matching its alignment
+    fn lay_out_fields(fields: Vec<(Symbol, Ty)>) -> Vec<(Symbol, Ty, u64)> {
 
-                    if align > max_align {
-                        max_align = align;
-                    }
+        let mut current_offset = 0;
+        fields.into_iter().map(|(name, ty)| {
+            let (size, align) = ty.get_size_and_align();
+            if current_offset % align != 0 {
+                current_offset += align - current_offset % align;
+            }
+            let offset = current_offset;
+            current_offset += size;
+            (name, ty, offset)
+        }).collect()
+    }
 
-                    if current_offset % align != 0 {
-                        current_offset += align - current_offset % align;
-                    }
+    /// Tag type of an enum type, along with its variants and the types and offsets of their payloads
+    ///
+    /// The tag is the index of the variant, each payload is laid out after it like the fields of a struct.
+    pub fn enum_variants(&self) -> Option<(Ty, EnumVariants)> {
+        let (enum_def, args): (_, &[Ty]) = match &self.kind {
+            TyKind::Enum(enum_def) => (*enum_def, &[]),
+            TyKind::EnumWithArgs(enum_def, args) => (*enum_def, args),
+            _ => return None
+        };
 
-                    field.offset = current_offset;
+        let variants: Vec<_> = SessionGlobals::with_enum_def_pool(|pool| {
+            pool.get(enum_def).variants.iter().map(|variant| {
+                (variant.name, variant.payload.iter().map(|ty| ty.substitute(args)).collect::<Vec<_>>())
+            }).collect()
+        });
 
-                    current_offset += size;
-                }
+        let tag = Ty::from_primitive(match variants.len() {
+            0..=0x100 => TyPrimitive::U8,
+            0x101..=0x10000 => TyPrimitive::U16,
+            _ => TyPrimitive::U32
+        });
+        let (tag_size, _) = tag.get_size_and_align();
 
-                if current_offset % max_align != 0 {
-                    current_offset += max_align - current_offset % max_align;
+        let variants = variants.into_iter().map(|(name, payload)| {
+            let mut current_offset = tag_size;
+            let payload = payload.into_iter().map(|ty| {
+                let (size, align) = ty.get_size_and_align();
+                if current_offset % align != 0 {
+                    current_offset += align - current_offset % align;
                 }
+                let offset = current_offset;
+                current_offset += size;
+                (ty, offset)
+            }).collect();
+            (name, payload)
+        }).collect();
 
-                match &mut pool.get_mut(pool_ref).kind {
-                    TyKind::Struct(s) => {
-                        s.size = current_offset;
-                        s.fields = fields;
-                        s.align = max_align;
-                    }
-                    _ => unreachable!(),
-                }
-            })
-        }
+        Some((tag, variants))
     }
 
-    pub fn get_size_and_align(&self) -> (usize, usize) {
+    pub fn get_size_and_align(&self) -> (u64, u64) {
         match &self.kind {
             TyKind::Primitive(primitive) => match primitive {
                 TyPrimitive::U8 => (1, 1),
@@ -98,19 +181,173 @@ impl Ty {
                 TyPrimitive::I32 => (4, 4),
                 TyPrimitive::U64 => (8, 8),
                 TyPrimitive::I64 => (8, 8),
+                TyPrimitive::F32 => (4, 4),
+                TyPrimitive::F64 => (8, 8),
                 TyPrimitive::Bool => (1, 1),
                 TyPrimitive::Char => (4, 4),
                 TyPrimitive::Void => (0, 1),
             },
             TyKind::PointerTo(_) => (8, 8),
-            TyKind::Struct(s) => (s.size, s.align),
-            TyKind::PlaceholderUnknown => {
-                panic!("ERROR Cannot compute size and alignment of unknown type")
+            TyKind::Struct(_) | TyKind::StructWithArgs(_, _) | TyKind::Tuple(_) => {
+                let mut size = 0;
+                let mut max_align = 1;
+                for (_, ty, offset) in self.struct_fields().unwrap() {
+                    let (field_size, field_align) = ty.get_size_and_align();
+                    size = offset + field_size;
+                    max_align = max_align.max(field_align);
+                }
+
+                if size % max_align != 0 {
+                    size += max_align - size % max_align;
+                }
+
+                (size, max_align)
+            }
+            TyKind::Enum(_) | TyKind::EnumWithArgs(_, _) => {
+                let (tag, variants) = self.enum_variants().unwrap();
+                let (mut size, mut max_align) = tag.get_size_and_align();
+                for (_, payload) in variants {
+                    for (ty, offset) in payload {
+                        let (field_size, field_align) = ty.get_size_and_align();
+                        size = size.max(offset + field_size);
+                        max_align = max_align.max(field_align);
+                    }
+                }
+
+                if size % max_align != 0 {
+                    size += max_align - size % max_align;
+                }
+
+                (size, max_align)
+            }
+            TyKind::Array(ty, len) => {
+                let (size, align) = ty.get_size_and_align();
+                (size * len.const_value().unwrap(), align)
+            }
+            TyKind::Param(_) => {
+                panic!("ERROR Cannot compute size and alignment of generic param")
+            }
+            TyKind::Const(value) => {
+                panic!("ERROR Expected a type, got the value {}", value)
+            }
+        }
+    }
+
+    /// Binds the generic params in the type so that it matches the other type
+    ///
+    /// Fails when the types do not match, or a param would be bound to two different types.
+    pub fn infer_params(&self, other: &Ty, bindings: &mut [Option<Ty>]) -> bool {
+        match (&self.kind, &other.kind) {
+            (TyKind::Param(index), _) => match &bindings[*index] {
+                Some(bound) => bound == other,
+                None => {
+                    bindings[*index] = Some(other.clone());
+                    true
+                }
+            },
+            (TyKind::PointerTo(ty), TyKind::PointerTo(other)) => ty.infer_params(other, bindings),
+            (TyKind::StructWithArgs(struct_def, args), TyKind::StructWithArgs(other_struct_def, other_args)) => {
+                struct_def == other_struct_def
+                    && args.len() == other_args.len()
+                    && args.iter().zip(other_args.iter()).all(|(arg, other)| arg.infer_params(other, bindings))
+            }
+            (TyKind::Tuple(tys), TyKind::Tuple(other_tys)) => {
+                tys.len() == other_tys.len()
+                    && tys.iter().zip(other_tys.iter()).all(|(ty, other)| ty.infer_params(other, bindings))
+            }
+            (TyKind::Array(ty, len), TyKind::Array(other, other_len)) => {
+                ty.infer_params(other, bindings) && len.infer_params(other_len, bindings)
+            }
+            (TyKind::EnumWithArgs(enum_def, args), TyKind::EnumWithArgs(other_enum_def, other_args)) => {
+                enum_def == other_enum_def
+                    && args.len() == other_args.len()
+                    && args.iter().zip(other_args.iter()).all(|(arg, other)| arg.infer_params(other, bindings))
             }
+            _ => self == other
         }
     }
 
-     */
+    /// Whether generic params appear in the type
+    pub fn has_params(&self) -> bool {
+        match &self.kind {
+            TyKind::Param(_) => true,
+            TyKind::PointerTo(ty) => ty.has_params(),
+            TyKind::StructWithArgs(_, tys) | TyKind::EnumWithArgs(_, tys) | TyKind::Tuple(tys) => {
+                tys.iter().any(|ty| ty.has_params())
+            }
+            TyKind::Array(ty, len) => ty.has_params() || len.has_params(),
+            _ => false
+        }
+    }
+
+    /// Full path of the struct or enum def of the type, or the name of the primitive, under which
+    /// its methods are registered
+    pub fn def_path(&self) -> Option<Vec<Symbol>> {
+        match &self.kind {
+            TyKind::Primitive(_) => {
+                let name = self.to_string();
+                Some(vec![SessionGlobals::with_interner_mut(|i| i.intern(&name))])
+            }
+            TyKind::Struct(struct_def) | TyKind::StructWithArgs(struct_def, _) => {
+                Some(SessionGlobals::with_struct_def_pool(|pool| pool.get(*struct_def).path.clone()))
+            }
+            TyKind::Enum(enum_def) | TyKind::EnumWithArgs(enum_def, _) => {
+                Some(SessionGlobals::with_enum_def_pool(|pool| pool.get(*enum_def).path.clone()))
+            }
+            _ => None
+        }
+    }
+
+    /// Value of a `Const` type
+    pub fn const_value(&self) -> Option<u64> {
+        match self.kind {
+            TyKind::Const(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// Element type and length of an array type
+    pub fn array_element(&self) -> Option<(&Ty, u64)> {
+        match &self.kind {
+            TyKind::Array(ty, len) => Some((ty, len.const_value()?)),
+            _ => None
+        }
+    }
+
+    pub fn ir_type(&self) -> IRType {
+        let (size, align) = self.get_size_and_align();
+        IRType { size, align, float: self.is_float() }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.kind,
+            TyKind::Primitive(
+                TyPrimitive::U8 | TyPrimitive::I8 | TyPrimitive::U16 | TyPrimitive::I16
+                | TyPrimitive::U32 | TyPrimitive::I32 | TyPrimitive::U64 | TyPrimitive::I64
+            )
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self.kind,
+            TyKind::Primitive(TyPrimitive::I8 | TyPrimitive::I16 | TyPrimitive::I32 | TyPrimitive::I64)
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self.kind, TyKind::Primitive(TyPrimitive::F32 | TyPrimitive::F64))
+    }
+
+    pub fn is_bool(&self) -> bool {
+        self.kind == TyKind::Primitive(TyPrimitive::Bool)
+    }
+
+    pub fn is_void(&self) -> bool {
+        self.kind == TyKind::Primitive(TyPrimitive::Void)
+    }
+
 
     pub fn from_primitive(primitive: TyPrimitive) -> Self {
         Self {
@@ -123,4 +360,61 @@ impl Ty {
             kind: TyKind::Struct(struct_def)
         }
     }
-}
\ No newline at end of file
+
+    pub fn from_enum_def(enum_def: PoolRef<EnumDef>) -> Self {
+        Self {
+            kind: TyKind::Enum(enum_def)
+        }
+    }
+}
+
+impl Ty {
+    /// Writes the type, naming generic params with the given names
+    pub fn to_string_with_params(&self, params: &[Symbol]) -> String {
+        match &self.kind {
+            TyKind::Primitive(primitive) => match primitive {
+                TyPrimitive::U8 => "u8",
+                TyPrimitive::I8 => "i8",
+                TyPrimitive::U16 => "u16",
+                TyPrimitive::I16 => "i16",
+                TyPrimitive::U32 => "u32",
+                TyPrimitive::I32 => "i32",
+                TyPrimitive::U64 => "u64",
+                TyPrimitive::I64 => "i64",
+                TyPrimitive::F32 => "f32",
+                TyPrimitive::F64 => "f64",
+                TyPrimitive::Bool => "bool",
+                TyPrimitive::Char => "char",
+                TyPrimitive::Void => "void",
+            }.to_owned(),
+            TyKind::PointerTo(ty) => format!("*{}", ty.to_string_with_params(params)),
+            TyKind::Struct(_) | TyKind::Enum(_) => path_to_string(&self.def_path().unwrap()),
+            TyKind::StructWithArgs(_, args) | TyKind::EnumWithArgs(_, args) => {
+                let args: Vec<_> = args.iter().map(|arg| arg.to_string_with_params(params)).collect();
+                format!("{}<{}>", path_to_string(&self.def_path().unwrap()), args.join(", "))
+            }
+            TyKind::Tuple(tys) => {
+                let tys: Vec<_> = tys.iter().map(|ty| ty.to_string_with_params(params)).collect();
+                if tys.len() == 1 {
+                    format!("({},)", tys[0])
+                } else {
+                    format!("({})", tys.join(", "))
+                }
+            }
+            TyKind::Array(ty, len) => {
+                format!("[{}; {}]", ty.to_string_with_params(params), len.to_string_with_params(params))
+            }
+            TyKind::Param(index) => match params.get(*index) {
+                Some(name) => SessionGlobals::with_interner(|i| i.get(name).unwrap().to_owned()),
+                None => format!("<param {}>", index)
+            },
+            TyKind::Const(value) => value.to_string(),
+        }
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_with_params(&[]))
+    }
+}
@@ -0,0 +1,460 @@
+//! Programs run by the interpreter, mostly SolidLang programs lowered to IR
+
+use solidc::globals::SessionGlobals;
+use solidc::ir::assembly::assembler::assemble_ir_module;
+use solidc::ir::interpreter::IRInterpreter;
+use solidc::ir::IRModule;
+use solidc::solidlang::loader::load_module;
+use solidc::solidlang::lowerer::Lowerer;
+use std::cell::RefCell;
+
+/// Bytes of the result of a run or the trap it stopped with, and the sizes and allocation sites
+/// of the heap blocks it leaked
+type Run = (Result<Vec<u8>, String>, Vec<(u64, String)>);
+
+/// Runs the `main` of the module, in the session of the caller
+fn run_module(module: IRModule) -> Run {
+    let mut interpreter = IRInterpreter::new();
+    interpreter.load_module(module);
+    let main = SessionGlobals::with_interner_mut(|i| i.intern("main"));
+    let value = unsafe { interpreter.call_function(main, &[]) };
+    let leaks = interpreter
+        .leaks()
+        .into_iter()
+        .map(|(size, site)| (size, site.describe()))
+        .collect();
+    (
+        value
+            .map(|value| value.bytes)
+            .map_err(|trap| trap.to_string()),
+        leaks,
+    )
+}
+
+/// Runs the `main` of the program
+fn run_with_leaks(name: &str, src: &str) -> Run {
+    let path = std::env::temp_dir().join(format!("solidc_{}_{}.solid", name, std::process::id()));
+    std::fs::write(&path, src).unwrap();
+
+    let result = RefCell::new((Ok(vec![]), vec![]));
+    SessionGlobals::create(|| {
+        let loaded = load_module(&path);
+        // Removed before the errors of parsing and lowering panic
+        std::fs::remove_file(&path).unwrap();
+        let module = Lowerer::new().process_module(loaded.unwrap());
+        *result.borrow_mut() = run_module(module);
+    });
+    result.into_inner()
+}
+
+/// Runs the `main` of the program, yielding the bytes of its result or the trap it stopped with
+fn run(name: &str, src: &str) -> Result<Vec<u8>, String> {
+    run_with_leaks(name, src).0
+}
+
+/// Runs the `main` of a module in the IR assembly syntax, for what SolidLang can't express
+fn run_ir(src: &str) -> Run {
+    let result = RefCell::new((Ok(vec![]), vec![]));
+    SessionGlobals::create(|| {
+        *result.borrow_mut() = run_module(assemble_ir_module(src).unwrap());
+    });
+    result.into_inner()
+}
+
+fn run_i32(name: &str, src: &str) -> i32 {
+    i32::from_le_bytes(run(name, src).unwrap().try_into().unwrap())
+}
+
+#[test]
+fn functions_with_the_same_control_flow() {
+    let src = "
+enum Color { Red, Green, Blue }
+
+fn a(x: i32) -> i32 {
+    if x == 1 { 100 } else { 200 }
+}
+
+fn b(x: i32) -> i32 {
+    if x == 3 { return 3; }
+    if x == 1 { 1 } else { 2 }
+}
+
+fn pick(color: Color) -> i32 {
+    match color {
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Blue => 3
+    }
+}
+
+fn shade(color: Color) -> i32 {
+    match color {
+        Color::Red => 10,
+        Color::Green => 20,
+        _ => 30
+    }
+}
+
+fn main() -> i32 {
+    if a(1) != 100 { return 1; }
+    if b(1) != 1 { return 2; }
+    if a(2) != 200 { return 3; }
+    if pick(Color::Blue) != 3 { return 4; }
+    if shade(Color::Green) != 20 { return 5; }
+    if b(2) != 2 { return 6; }
+    0
+}
+";
+    assert_eq!(run_i32("same_control_flow", src), 0);
+}
+
+#[test]
+fn integer_casts() {
+    let src = "
+fn widen(x: i8) -> i64 {
+    x as i64
+}
+
+fn main() -> i32 {
+    let a: i8 = -3;
+    if widen(a) != -3 { return 1; }
+    let b: u8 = 200;
+    if b as i16 != 200 { return 2; }
+    let c: u32 = 300;
+    if c as u8 != 44 { return 3; }
+    let d: i32 = -1;
+    if d as u16 != 65535 { return 4; }
+    if d as u32 != 4294967295 { return 5; }
+    let e: i16 = -129;
+    if e as i8 != 127 { return 6; }
+    0
+}
+";
+    assert_eq!(run_i32("integer_casts", src), 0);
+}
+
+#[test]
+fn signed_operations() {
+    let src = "
+fn main() -> i32 {
+    let a = -5i64;
+    if a / 2 != -2 { return 1; }
+    if a % 2 != -1 { return 2; }
+    if (a ~> 1) != -3 { return 3; }
+    let b = -1i8;
+    let lt = b < 1;
+    if lt == false { return 4; }
+    if b > 1 { return 5; }
+    let c = -100i8;
+    if c / 7 != -14 { return 6; }
+    if (c ~> 2) != -25 { return 7; }
+    let total = 0i32;
+    for i in -3..=3 {
+        total = total + i;
+    }
+    if total != 0 { return 8; }
+    let u = 200u8;
+    if u / 3 != 66 { return 9; }
+    if u <= 100 { return 10; }
+    0
+}
+";
+    assert_eq!(run_i32("signed_operations", src), 0);
+}
+
+#[test]
+#[should_panic(expected = "ChainedAssignment")]
+fn assignments_cannot_be_chained() {
+    let src = "
+fn main() -> i32 {
+    let a = 1;
+    let b = 2;
+    a = b = 3;
+    a
+}
+";
+    run("chained_assignment", src).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Integer literal -1 does not fit in u8")]
+fn negated_unsigned_literals_do_not_fit() {
+    let src = "
+fn main() -> i32 {
+    let x: u8 = -1;
+    0
+}
+";
+    run("negated_unsigned_literal", src).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Operator Minus cannot be applied to u64")]
+fn unsigned_values_cannot_be_negated() {
+    let src = "
+fn main() -> i32 {
+    let x: u64 = 1;
+    let y = -x;
+    0
+}
+";
+    run("negated_unsigned_value", src).unwrap();
+}
+
+#[test]
+fn bindings_take_the_type_of_their_uses() {
+    let src = "
+fn total() -> u64 {
+    let s = 0;
+    for i in 0..10 {
+        s += i;
+    }
+    s
+}
+
+fn main() -> i32 {
+    if total() != 45 { return 1; }
+    let small = 250;
+    let step = 10u8;
+    small = small + step;
+    if small != 4 { return 2; }
+    0
+}
+";
+    assert_eq!(run_i32("binding_inference", src), 0);
+}
+
+#[test]
+#[should_panic(expected = "smallest::T has no method \"greater\"")]
+fn bounded_bodies_only_call_methods_of_the_bounds() {
+    let src = "
+interface Ord {
+    fn less(self: Self, other: Self) -> bool;
+}
+
+template<T: Ord> fn smallest(a: T, b: T) -> T {
+    if a.greater(b) { b } else { a }
+}
+
+fn main() -> i32 {
+    0
+}
+";
+    run("missing_bound_method", src).unwrap();
+}
+
+#[test]
+#[should_panic(
+    expected = "Point does not implement Ord, which requires fn less(self: Self, other: Self) -> bool"
+)]
+fn instantiations_implement_the_bounds() {
+    let src = "
+interface Ord {
+    fn less(self: Self, other: Self) -> bool;
+}
+
+struct Point { x: i32, y: i32 }
+
+template<T: Ord> fn smallest(a: T, b: T) -> T {
+    if a.less(b) { a } else { b }
+}
+
+fn main() -> i32 {
+    let p: Point;
+    let q = smallest(p, p);
+    0
+}
+";
+    run("unimplemented_bound", src).unwrap();
+}
+
+#[test]
+fn array_patterns_and_loops() {
+    let src = "
+fn sum(values: [i32; 4]) -> i32 {
+    let total = 0;
+    for value in values {
+        total += value;
+    }
+    total
+}
+
+fn corners(values: [i32; 3]) -> i32 {
+    let [first, _, last] = values;
+    first * 10 + last
+}
+
+fn classify(values: [u8; 2]) -> i32 {
+    match values {
+        [0, 0] => 1,
+        [0, _] => 2,
+        [x, 7] => x as i32,
+        _ => 4
+    }
+}
+
+fn pair(a: u8, b: u8) -> [u8; 2] {
+    let values: [u8; 2];
+    values[0] = a;
+    values[1] = b;
+    values
+}
+
+fn main() -> i32 {
+    let values: [i32; 4];
+    for i in 0..4 {
+        values[i] = i as i32 + 1;
+    }
+    if sum(values) != 10 { return 1; }
+    let three: [i32; 3];
+    three[0] = 5;
+    three[2] = 7;
+    if corners(three) != 57 { return 2; }
+    if classify(pair(0, 0)) != 1 { return 3; }
+    if classify(pair(0, 9)) != 2 { return 4; }
+    if classify(pair(9, 7)) != 9 { return 5; }
+    if classify(pair(9, 8)) != 4 { return 6; }
+    let grid: [[u64; 2]; 2];
+    grid[0][0] = 1;
+    grid[0][1] = 2;
+    grid[1][0] = 3;
+    grid[1][1] = 4;
+    let count = 0;
+    for row in grid {
+        let [a, b] = row;
+        count += a * b;
+    }
+    if count != 14 { return 7; }
+    0
+}
+";
+    assert_eq!(run_i32("arrays", src), 0);
+}
+
+#[test]
+fn division_by_zero_traps() {
+    let src = "
+fn divide(a: u64, b: u64) -> u64 {
+    a / b
+}
+
+fn main() -> u64 {
+    let a: u64 = 0;
+    divide(5, a)
+}
+";
+    let trap = run("division_by_zero", src).unwrap_err();
+    let lines: Vec<_> = trap.lines().collect();
+    assert_eq!(lines[0], "trap: division by zero");
+    assert!(lines[1].starts_with("    at divide (") && lines[1].ends_with(":3:5)"));
+    assert!(lines[2].starts_with("    at main (") && lines[2].ends_with(":8:5)"));
+}
+
+#[test]
+fn double_frees_trap() {
+    let src = "
+fn main() -> i32 {
+    let p = alloc::<u64>(1);
+    free(p);
+    free(p);
+    0
+}
+";
+    let trap = run("double_free", src).unwrap_err();
+    let lines: Vec<_> = trap.lines().collect();
+    assert!(lines[0].starts_with("trap: double free of 0x") && lines[0].ends_with(":3:5)"));
+    assert!(lines[1].starts_with("    at main (") && lines[1].ends_with(":5:5)"));
+}
+
+#[test]
+fn uses_after_free_trap() {
+    let src = "
+fn main() -> u64 {
+    let p = alloc::<u64>(1);
+    free(p);
+    p[0]
+}
+";
+    let trap = run("use_after_free", src).unwrap_err();
+    let lines: Vec<_> = trap.lines().collect();
+    assert!(lines[0].starts_with("trap: use after free of 0x") && lines[0].ends_with(":3:5)"));
+    assert!(lines[1].starts_with("    at main (") && lines[1].ends_with(":5:5)"));
+}
+
+#[test]
+fn frees_of_pointers_malloc_did_not_yield_trap() {
+    let src = "\
+fn main: -> (8 8)
+    %size := const 8 16 0 0 0 0 0 0 0
+    %ptr := call malloc 1 %size
+    %eight := const 8 8 0 0 0 0 0 0 0
+    %middle := binop + %ptr %eight
+    call free 1 %middle
+    return %size
+endfn
+";
+    let trap = run_ir(src).0.unwrap_err();
+    let lines: Vec<_> = trap.lines().collect();
+    assert!(lines[0].starts_with("trap: free of 0x"));
+    assert!(lines[0].ends_with(", which malloc did not yield"));
+}
+
+#[test]
+fn realloc_copies_the_block() {
+    let src = "\
+fn main: -> (8 8)
+    %size := const 8 16 0 0 0 0 0 0 0
+    %ptr := call malloc 1 %size
+    %a := const 8 42 0 0 0 0 0 0 0
+    %b := const 8 7 0 0 0 0 0 0 0
+    store (8 8) %ptr %a
+    offsetstore (8 8) %ptr %b 8
+    %larger := const 8 32 0 0 0 0 0 0 0
+    %ptr := call realloc 2 %ptr %larger
+    %b := offsetload (8 8) %ptr 8
+    %smaller := const 8 8 0 0 0 0 0 0 0
+    %ptr := call realloc 2 %ptr %smaller
+    %a := load (8 8) %ptr
+    call free 1 %ptr
+    %sum := binop + %a %b
+    return %sum
+endfn
+";
+    let (result, leaks) = run_ir(src);
+    assert_eq!(result.unwrap(), 49u64.to_le_bytes());
+    assert!(leaks.is_empty());
+}
+
+#[test]
+fn leaks_are_reported() {
+    let src = "
+fn main() -> i32 {
+    let p = alloc::<u64>(2);
+    let q = alloc::<u8>(3);
+    free(p);
+    0
+}
+";
+    let (result, leaks) = run_with_leaks("leaks", src);
+    assert_eq!(result.unwrap(), 0i32.to_le_bytes());
+    assert_eq!(leaks.len(), 1);
+    assert_eq!(leaks[0].0, 3);
+    assert!(leaks[0].1.starts_with("main#") && leaks[0].1.ends_with(":4:5)"));
+}
+
+#[test]
+fn heap_overflows_trap() {
+    let src = "
+fn main() -> i32 {
+    let p = alloc::<u64>(2);
+    p[5] = 1;
+    free(p);
+    0
+}
+";
+    let trap = run("heap_overflow", src).unwrap_err();
+    let lines: Vec<_> = trap.lines().collect();
+    assert!(lines[0].starts_with("trap: out of bounds access of 8 bytes at "));
+    assert!(lines[0].contains(", outside of the 16 bytes of "));
+    assert!(lines[1].starts_with("    at main (") && lines[1].ends_with(":4:5)"));
+}